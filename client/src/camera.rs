@@ -0,0 +1,194 @@
+use rust_us_core::*;
+
+// Which part of the map is on screen, and how big it's drawn. Everything
+// drawn in map coordinates goes through offset to find where it lands on the
+// canvas.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Camera {
+  pub(crate) zoom: f64,
+  left: f64,
+  right: f64,
+  top: f64,
+  bottom: f64,
+}
+
+impl Camera {
+  // How far past the edge of the map the camera's allowed to look, so that
+  // players can tell they're at the edge.
+  const OOB_LIMIT: f64 = 20.0;
+
+  // Map coordinates to canvas coordinates.
+  pub(crate) fn offset(self, x: f64, y: f64) -> (f64, f64) {
+    let x = (x - self.left) * self.zoom;
+    let y = (y - self.top) * self.zoom;
+    (x, y)
+  }
+
+  // Whether any of the given box, in map coordinates, would be on screen.
+  pub(crate) fn can_see(self, (min, max): (Position, Position)) -> bool {
+    min.x <= self.right && max.x >= self.left && min.y <= self.bottom && max.y >= self.top
+  }
+
+  // Draws in canvas coordinates as they are. For the screens that aren't
+  // about the map, like voting.
+  pub(crate) fn get_global_camera(dimensions: (f64, f64)) -> Self {
+    let (width, height) = dimensions;
+    Self {
+      zoom: 1.0,
+      left: 0.0,
+      top: 0.0,
+      right: width,
+      bottom: height,
+    }
+  }
+
+  // Fits the whole map on screen, for those who get to see everything.
+  pub(crate) fn whole_map(dimensions: (f64, f64), map: &Map) -> Self {
+    let (width, height) = dimensions;
+    let zoom = (width / map.width()).min(height / map.height());
+    let mut camera = Camera::at_zoom(
+      dimensions,
+      zoom,
+      Position {
+        x: map.width() / 2.0,
+        y: map.height() / 2.0,
+      },
+    );
+    camera.snap_to_edge(map);
+    camera
+  }
+
+  // The whole map shrunk down to fit in a box on screen, with its top left
+  // corner at the given spot on the canvas.
+  pub(crate) fn inset((x, y): (f64, f64), (width, height): (f64, f64), map: &Map) -> Self {
+    let zoom = (width / map.width()).min(height / map.height());
+    Camera {
+      zoom,
+      left: -x / zoom,
+      right: width / zoom,
+      top: -y / zoom,
+      bottom: height / zoom,
+    }
+  }
+
+  fn centered_on_point(dimensions: (f64, f64), center: Position) -> Self {
+    // This zoom shouldn't be constant
+    Camera::at_zoom(dimensions, 2.0, center)
+  }
+
+  fn at_zoom((width, height): (f64, f64), zoom: f64, center: Position) -> Self {
+    let map_width = width / zoom;
+    let map_height = height / zoom;
+    // Players see the area around them
+    Camera {
+      zoom,
+      left: center.x - (map_width / 2.0),
+      right: center.x + (map_width / 2.0),
+      top: center.y - (map_height / 2.0),
+      bottom: center.y + (map_height / 2.0),
+    }
+  }
+
+  pub(crate) fn roughly_track_object(
+    self,
+    (width, height): (f64, f64),
+    map: &Map,
+    tracked: Position,
+  ) -> Camera {
+    // This is what this article calls the 'camera-window' system
+    // https://www.gamasutra.com/blogs/ItayKeren/20150511/243083/Scroll_Back_The_Theory_and_Practice_of_Cameras_in_SideScrollers.php
+
+    // This zoom shouldn't be constant
+    let zoom = 2.0;
+    // Keeping the same corner, since the last camera might not have been
+    // following anyone.
+    let mut result = Camera {
+      zoom,
+      left: self.left,
+      right: self.left + width / zoom,
+      top: self.top,
+      bottom: self.top + height / zoom,
+    };
+
+    // Imagine a smallish rectangle in the center of the screen.
+    // If the tracked object stays within that rectangle, the camera stays
+    // fixed. When it leaves the rectangle, the camera moves the minimal amount
+    // to keep it in there.
+    let (dx, dy) = {
+      let x_center = width / 2.0;
+      let y_center = height / 2.0;
+      let bounding_left = x_center - (width * 0.075);
+      let bounding_right = x_center + (width * 0.075);
+      let bounding_top = y_center - (height * 0.075);
+      let bounding_bottom = y_center + (height * 0.075);
+
+      let (x, y) = result.offset(tracked.x, tracked.y);
+      let mut dx = 0.0;
+
+      if x < bounding_left {
+        dx = x - (bounding_left);
+      } else if x > bounding_right {
+        dx = x - (bounding_right - 1.0);
+      }
+      let mut dy = 0.0;
+      if y < bounding_top {
+        dy = y - bounding_top;
+      } else if y > bounding_bottom {
+        dy = y - bounding_bottom;
+      }
+      (dx / zoom, dy / zoom)
+    };
+
+    if dx != 0.0 {
+      result.left += dx;
+      result.right += dx;
+    }
+    if dy != 0.0 {
+      result.top += dy;
+      result.bottom += dy;
+    }
+
+    result.snap_to_edge(map);
+
+    // The camera jerked abruptly? Maybe the player teleported. To help anchor them, try to center
+    // the player in the camera.
+    if (result.left - self.left).abs() > 30.0 || (result.top - self.top).abs() > 30.0 {
+      let mut centered = Camera::centered_on_point((width, height), tracked);
+      centered.snap_to_edge(map);
+      return centered;
+    }
+    result
+  }
+
+  fn snap_to_edge(&mut self, map: &Map) {
+    // See edge-snapping from
+    // https://www.gamasutra.com/blogs/ItayKeren/20150511/243083/Scroll_Back_The_Theory_and_Practice_of_Cameras_in_SideScrollers.php
+    //
+    // When the map's narrower than the screen there's no edge to snap to, so
+    // the map sits in the middle instead.
+    let (left, right) = clamp_span(self.left, self.right, map.width());
+    self.left = left;
+    self.right = right;
+    let (top, bottom) = clamp_span(self.top, self.bottom, map.height());
+    self.top = top;
+    self.bottom = bottom;
+  }
+}
+
+// Slides the span from start to end so it stays over 0..size, give or take
+// the out of bounds limit.
+fn clamp_span(start: f64, end: f64, size: f64) -> (f64, f64) {
+  let oob_limit = Camera::OOB_LIMIT;
+  let span = end - start;
+  if span >= size + 2.0 * oob_limit {
+    let start = (size - span) / 2.0;
+    return (start, start + span);
+  }
+  if start < -oob_limit {
+    (-oob_limit, -oob_limit + span)
+  } else if end > size + oob_limit {
+    (size + oob_limit - span, size + oob_limit)
+  } else {
+    (start, end)
+  }
+}
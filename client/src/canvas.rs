@@ -1,6 +1,8 @@
+use crate::camera::Camera;
 use crate::*;
 use core::time::Duration;
 use rust_us_core::*;
+use std::borrow::Cow;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::{collections::BTreeMap, error::Error};
@@ -42,152 +44,10 @@ pub struct Canvas {
   camera: Camera,
   context: web_sys::CanvasRenderingContext2d,
   canvas_element: web_sys::HtmlCanvasElement,
-}
-
-#[derive(Clone, Copy, Debug)]
-struct Camera {
-  zoom: f64,
-  left: f64,
-  right: f64,
-  top: f64,
-  bottom: f64,
-}
-impl Camera {
-  fn offset(self, x: f64, y: f64) -> (f64, f64) {
-    let x = (x - self.left) * self.zoom;
-    let y = (y - self.top) * self.zoom;
-    (x, y)
-  }
-
-  fn get_global_camera(dimensions: (f64, f64)) -> Self {
-    let (width, height) = dimensions;
-    Self {
-      // this isn't the right zoom, it should be relative to
-      // canvas width and height
-      zoom: 1.0,
-      left: 0.0,
-      top: 0.0,
-      right: width,
-      bottom: height,
-    }
-  }
-
-  fn centered_on_point(dimensions: (f64, f64), center: Position) -> Self {
-    // Likewise, this zoom shouldn't be constant
-    let zoom = 2.0;
-    let (width, height) = dimensions;
-    let map_width = width / zoom;
-    let map_height = height / zoom;
-    // Players see the area around them
-    Camera {
-      zoom,
-      left: center.x - (map_width / 2.0),
-      right: center.x + (map_width / 2.0),
-      top: center.y - (map_height / 2.0),
-      bottom: center.y + (map_height / 2.0),
-    }
-  }
-
-  fn roughly_track_object(
-    self,
-    (width, height): (f64, f64),
-    map: &Map,
-    tracked: Position,
-  ) -> Camera {
-    // This is what this article calls the 'camera-window' system
-    // https://www.gamasutra.com/blogs/ItayKeren/20150511/243083/Scroll_Back_The_Theory_and_Practice_of_Cameras_in_SideScrollers.php
-
-    let mut result = Camera {
-      // This zoom shouldn't be constant
-      zoom: 2.0,
-      left: self.left,
-      right: self.right,
-      top: self.top,
-      bottom: self.bottom,
-    };
-
-    // Imagine a smallish rectangle in the center of the screen.
-    // If the tracked object stays within that rectangle, the camera stays
-    // fixed. When it leaves the rectangle, the camera moves the minimal amount
-    // to keep it in there.
-    let (dx, dy) = {
-      let x_center = width / 2.0;
-      let y_center = height / 2.0;
-      let bounding_left = x_center - (width * 0.075);
-      let bounding_right = x_center + (width * 0.075);
-      let bounding_top = y_center - (height * 0.075);
-      let bounding_bottom = y_center + (height * 0.075);
-
-      let (x, y) = result.offset(tracked.x, tracked.y);
-      let mut dx = 0.0;
-
-      if x < bounding_left {
-        dx = x - (bounding_left);
-      } else if x > bounding_right {
-        dx = x - (bounding_right - 1.0);
-      }
-      let mut dy = 0.0;
-      if y < bounding_top {
-        dy = y - bounding_top;
-      } else if y > bounding_bottom {
-        dy = y - bounding_bottom;
-      }
-      (dx / self.zoom, dy / self.zoom)
-    };
-
-    if dx != 0.0 {
-      result.left += dx;
-      result.right += dx;
-    }
-    if dy != 0.0 {
-      result.top += dy;
-      result.bottom += dy;
-    }
-
-    let oob_limit = 20.0;
-    result.snap_to_edge(map, oob_limit);
-
-    // The camera jerked abruptly? Maybe the player teleported. To help anchor them, try to center
-    // the player in the camera.
-    if (result.left - self.left).abs() > 30.0 || (result.top - self.top).abs() > 30.0 {
-      let mut centered = Camera::centered_on_point((width, height), tracked);
-      centered.snap_to_edge(map, oob_limit);
-      return centered;
-    }
-    result
-  }
-
-  fn snap_to_edge(&mut self, map: &Map, oob_limit: f64) {
-    // See edge-snapping from
-    // https://www.gamasutra.com/blogs/ItayKeren/20150511/243083/Scroll_Back_The_Theory_and_Practice_of_Cameras_in_SideScrollers.php
-    //
-    // Still need to resolve what happens with viewports that are almost the size
-    // of the map, or even larger.
-    // as the player moves around within them, the camera could mostly stay still,
-    // but this causes them to snap to the edge sometimes. Hm.
-    if self.left <= -oob_limit {
-      console_log!("Snapped to left edge");
-      let correction = -self.left - oob_limit;
-      self.left += correction;
-      self.right += correction;
-    } else if self.right >= (map.width() + oob_limit) {
-      console_log!("Snapped to right edge");
-      let correction = self.right - (map.width() + oob_limit);
-      self.left -= correction;
-      self.right -= correction;
-    }
-    if self.top <= -oob_limit {
-      console_log!("Snapped to top edge");
-      let correction = -self.top - oob_limit;
-      self.top += correction;
-      self.bottom += correction;
-    } else if self.bottom >= (map.height() + oob_limit) {
-      console_log!("Snapped to bottom edge");
-      let correction = self.bottom - (map.height() + oob_limit);
-      self.top -= correction;
-      self.bottom -= correction;
-    }
-  }
+  // Where everyone's pets have got to. They only exist on screen, so each
+  // client keeps track of its own.
+  pets: BTreeMap<UUID, Position>,
+  last_drawn: Option<instant::Instant>,
 }
 
 struct WindowDimensions {
@@ -225,6 +85,8 @@ impl Canvas {
       camera: Camera::get_global_camera((width, height)),
       width,
       height,
+      pets: BTreeMap::new(),
+      last_drawn: None,
     })
   }
 
@@ -267,14 +129,33 @@ impl Canvas {
       GameStatus::Disconnected => {
         self.draw_big_centered_text("Disconnected! D:")?;
       }
-      GameStatus::Won(team) => {
-        let message = match game.has_won(team) {
-          Some(true) => "You win!".to_string(),
-          Some(false) => "You lose!".to_string(),
-          None => format!("{:?} win!", team),
+      GameStatus::Paused { resumes_in, .. } => {
+        let message = match resumes_in {
+          Some(remaining) => format!("Resuming in {}s", remaining.as_secs() + 1),
+          None if game.state.is_host(game.my_uuid) => {
+            "Paused, waiting for players (press P to carry on anyway)".to_string()
+          }
+          None => "Paused, waiting for players".to_string(),
         };
         self.draw_big_centered_text(&message)?;
       }
+      GameStatus::Won(_) | GameStatus::Draw => {
+        let message = match &game.state.status {
+          GameStatus::Won(team) => match game.has_won(team) {
+            Some(true) => "You win!".to_string(),
+            Some(false) => "You lose!".to_string(),
+            None => format!("{:?} win!", team),
+          },
+          _ => "Time's up! It's a draw.".to_string(),
+        };
+        self.draw_big_centered_text(&message)?;
+        if let Some(summary) = &game.summary {
+          self.draw_summary(summary)?;
+        }
+        if game.state.is_host(game.my_uuid) {
+          self.draw_footer_text("Press B to go back to the lobby")?;
+        }
+      }
       GameStatus::Lobby | GameStatus::Playing(PlayState::Night) => {
         self.draw_night(&game)?;
       }
@@ -300,7 +181,11 @@ impl Canvas {
           voting_ui_state.map(|s| s.highlighted_player).flatten(),
           &votes,
           vote_state.time_remaining,
-          " remaining to vote",
+          match (vote_state.phase, &vote_state.candidates) {
+            (DayPhase::Discussion, _) => " until voting begins",
+            (DayPhase::Voting, Some(_)) => " remaining in the runoff",
+            (DayPhase::Voting, None) => " remaining to vote",
+          },
         )?
       }
       GameStatus::Playing(PlayState::TallyingVotes(tally_state)) => {
@@ -309,7 +194,7 @@ impl Canvas {
           &game,
           &BTreeSet::new(),
           None,
-          &tally_state.votes_against,
+          tally_state.tally.votes_against(),
           tally_state.time_remaining,
           " until judgment",
         )?
@@ -320,6 +205,13 @@ impl Canvas {
       }
     };
 
+    if let (Some(progress), GameStatus::Playing(_)) = (game.task_bar, &game.state.status) {
+      self.draw_task_bar(progress)?;
+    }
+    if let GameStatus::Playing(PlayState::Night) = game.state.status {
+      self.draw_minimap(game)?;
+    }
+
     let font_height = 24.0;
     self
       .context
@@ -336,10 +228,93 @@ impl Canvas {
       .collect();
     if game.state.status == GameStatus::Lobby && game.local_player().is_some() {
       messages.push(Message::PlainString(format!(
-        "In the lobby. {}/10 players",
+        "In the lobby. {}/{} players",
+        game.state.players.len(),
+        game.state.settings.max_players
+      )));
+      if let Some(code) = &game.room_code {
+        messages.push(Message::PlainString(format!("Room code: {}", code)));
+      }
+      if game.state.settings.game_mode != GameMode::Classic {
+        messages.push(Message::PlainString(format!(
+          "Playing {}",
+          game.state.settings.game_mode.name()
+        )));
+      }
+      let ready = game.state.players.values().filter(|p| p.ready).count();
+      messages.push(Message::PlainString(format!(
+        "{}/{} ready",
+        ready,
         game.state.players.len()
       )));
-      messages.push(Message::PlainString(format!("Press P to start")));
+      for player in game.state.players.values() {
+        if let Some(latency) = player.latency {
+          messages.push(Message::FormattingString(vec![
+            FormattedText {
+              color: Some(player.color),
+              text: player.name.clone(),
+            },
+            FormattedText {
+              color: None,
+              text: format!(" {}ms", latency.as_millis()),
+            },
+          ]));
+        }
+      }
+      if game.state.is_host(game.my_uuid) {
+        messages.push(Message::PlainString("Press P to start".to_string()));
+      } else {
+        messages.push(Message::PlainString("Press P to get ready".to_string()));
+      }
+    }
+    if let (Some(player), GameStatus::Playing(PlayState::Night)) =
+      (game.local_player(), &game.state.status)
+    {
+      if let Some(region) = game.state.map.region_at(player.position) {
+        messages.push(Message::PlainString(format!("In {}", region.name)));
+      }
+    }
+    if let (Some(remaining), GameStatus::Playing(_)) =
+      (game.state.game_time_remaining, &game.state.status)
+    {
+      let seconds = remaining.as_secs();
+      messages.push(Message::PlainString(format!(
+        "Time left: {}:{:02}",
+        seconds / 60,
+        seconds % 60
+      )));
+    }
+    if let (Some(remaining), GameStatus::Playing(_)) =
+      (game.state.sudden_death_remaining, &game.state.status)
+    {
+      messages.push(Message::PlainString(format!(
+        "Sudden death! {}s left to finish the tasks",
+        remaining.as_secs()
+      )));
+    }
+    if let Some(player) = game.local_player() {
+      if player.dead && player.in_kill_animation() {
+        messages.push(Message::PlainString("You were killed!".to_string()));
+      }
+      let disguise = player
+        .disguise
+        .and_then(|d| Some((game.state.players.get(&d.target)?, d.time_remaining)));
+      if let Some((target, remaining)) = disguise {
+        messages.push(Message::PlainString(format!(
+          "Disguised as {} for {}s",
+          target.name,
+          remaining.as_secs()
+        )));
+      }
+    }
+    if let Some(active) = game.state.sabotage.active {
+      let text = match (active.kind, active.time_remaining) {
+        (Sabotage::Reactor, Some(remaining)) => {
+          format!("Reactor meltdown in {}s!", remaining.as_secs())
+        }
+        (kind, _) => format!("{} sabotaged!", kind.name()),
+      };
+      messages.push(Message::PlainString(text));
     }
     for (i, message) in messages.into_iter().enumerate() {
       self.context.begin_path();
@@ -384,6 +359,113 @@ impl Canvas {
     Ok(())
   }
 
+  fn draw_task_bar(&self, progress: f64) -> Result<(), JsValue> {
+    let (x, y, width, height) = (30.0, 30.0, 300.0, 24.0);
+    self.context.begin_path();
+    self.context.set_fill_style(&JsValue::from("#444a"));
+    self.context.fill_rect(x, y, width, height);
+    self.context.set_fill_style(&JsValue::from("#2c5"));
+    self.context.fill_rect(x, y, width * progress, height);
+    self.context.set_stroke_style(&JsValue::from("#000"));
+    self.context.set_line_width(2.0);
+    self.context.stroke_rect(x, y, width, height);
+    Ok(())
+  }
+
+  // The whole map in the top right corner, with the markers from
+  // GameState::minimap on it.
+  fn draw_minimap(&mut self, game: &GameAsPlayer) -> Result<(), JsValue> {
+    let map = &game.state.map;
+    let minimap = game.state.minimap(game.my_uuid);
+    let width = (self.width / 4.0).min(240.0);
+    let height = (width * map.height() / map.width()).min(160.0);
+    let top_left = (self.width - 30.0 - width, 30.0);
+    let camera = self.camera;
+    self.camera = Camera::inset(top_left, (width, height), map);
+    // Markers stay the same size on screen, however big the map is.
+    let zoom = self.camera.zoom;
+    let marker = |center: Position, radius: f64, color: &'static str| Shape::Circle {
+      radius: radius / zoom,
+      center,
+      outline_width: 1.0 / zoom,
+      outline_color: Cow::Borrowed("#000"),
+      fill_color: Cow::Borrowed(color),
+    };
+
+    let mut shapes = vec![Shape::Rect {
+      top_left: Position { x: 0.0, y: 0.0 },
+      width: map.width(),
+      height: map.height(),
+      fill_color: Cow::Borrowed("#f3f3f3c0"),
+      outline_width: 2.0 / zoom,
+      outline_color: Cow::Borrowed("#000"),
+    }];
+    shapes.extend(map.static_geometry.iter().cloned());
+    shapes.extend(map.closed_doors(&game.state.doors).cloned());
+    shapes.extend(
+      minimap
+        .sabotage_targets
+        .iter()
+        .map(|(_, position)| marker(*position, 4.0, "#a22")),
+    );
+    shapes.extend(
+      minimap
+        .fix_stations
+        .iter()
+        .map(|position| marker(*position, 4.0, "#ee2")),
+    );
+    shapes.extend(
+      minimap
+        .tasks
+        .iter()
+        .map(|position| marker(*position, 3.0, "#fd0")),
+    );
+    shapes.extend(minimap.you.map(|position| marker(position, 5.0, "#fff")));
+    // Whatever the console we're standing at can see.
+    match &game.surveillance {
+      Some(Surveillance::Admin { headcounts }) => {
+        for (region, count) in map.regions.iter().zip(headcounts.iter().copied()) {
+          let points = region.points.len().max(1) as f64;
+          let middle = Position {
+            x: region.points.iter().map(|p| p.x).sum::<f64>() / points,
+            y: region.points.iter().map(|p| p.y).sum::<f64>() / points,
+          };
+          // A dot for each person, in a row.
+          for i in 0..count {
+            let x = middle.x + (i as f64 - (count as f64 - 1.0) / 2.0) * 10.0 / zoom;
+            shapes.push(marker(Position { x, y: middle.y }, 4.0, "#fff"));
+          }
+        }
+      }
+      Some(Surveillance::Security { spotted }) => {
+        for (uuid, position) in spotted.iter().copied() {
+          if let Some(player) = game.state.players.get(&uuid) {
+            shapes.push(marker(position, 4.0, player.color.to_str()));
+          }
+        }
+      }
+      None => (),
+    }
+    let result = shapes.iter().try_for_each(|shape| self.draw_shape(shape));
+    self.camera = camera;
+    result
+  }
+
+  // A hint along the bottom of the screen.
+  fn draw_footer_text(&self, message: &str) -> Result<(), JsValue> {
+    self.context.set_text_align("center");
+    self.context.set_text_baseline("bottom");
+    self.context.set_font(&format!(
+      "{}px Arial Black",
+      (16.0 * self.camera.zoom).floor()
+    ));
+    self.context.set_fill_style(&JsValue::from("#000"));
+    self
+      .context
+      .fill_text(message, self.width / 2.0, self.height - 16.0)?;
+    Ok(())
+  }
+
   fn draw_big_centered_text(&self, message: &str) -> Result<(), JsValue> {
     self.context.begin_path();
     self.context.set_text_align("center");
@@ -401,6 +483,75 @@ impl Canvas {
     Ok(())
   }
 
+  // Who everyone really was and how they did, under the big "You win!".
+  fn draw_summary(&self, summary: &GameSummary) -> Result<(), JsValue> {
+    let font_height = 16.0 * self.camera.zoom;
+    let line_height = font_height * 1.5;
+    let middle = self.width / 2.0;
+    let mut y = self.height / 2.0 + 48.0 * self.camera.zoom;
+    self.context.set_text_baseline("middle");
+    self
+      .context
+      .set_font(&format!("{}px Arial Black", font_height.floor()));
+    self.context.set_line_width(self.camera.zoom * 2.0);
+    let minutes = summary.duration.as_secs() / 60;
+    let seconds = summary.duration.as_secs() % 60;
+    self.context.set_text_align("center");
+    self.context.set_fill_style(&JsValue::from("#000"));
+    self.context.fill_text(
+      &format!("The game took {}:{:02}", minutes, seconds),
+      middle,
+      y,
+    )?;
+    for player in summary.players.iter() {
+      y += line_height;
+      let ejected = summary.ejections.iter().any(|e| e.uuid == player.uuid);
+      let fate = match (player.survived, ejected) {
+        (true, _) => "",
+        (false, true) => ", ejected",
+        (false, false) => ", killed",
+      };
+      let role = if player.impostor {
+        "Impostor"
+      } else {
+        player.role.name()
+      };
+      self.context.begin_path();
+      self.context.arc(
+        middle - 300.0 * self.camera.zoom,
+        y,
+        font_height / 2.0,
+        0.0,
+        PI * 2.0,
+      )?;
+      self
+        .context
+        .set_fill_style(&JsValue::from_str(player.color.to_str()));
+      self.context.set_stroke_style(&JsValue::from("#000"));
+      self.context.fill();
+      self.context.stroke();
+      self
+        .context
+        .set_fill_style(&JsValue::from(if player.won { "#000" } else { "#666" }));
+      self.context.set_text_align("left");
+      self.context.fill_text(
+        &format!("{} ({}{})", player.name, role, fate),
+        middle - 280.0 * self.camera.zoom,
+        y,
+      )?;
+      self.context.set_text_align("right");
+      self.context.fill_text(
+        &format!(
+          "{} kills, {}/{} tasks, {} votes",
+          player.kills, player.tasks_done, player.tasks_total, player.votes_cast
+        ),
+        middle + 300.0 * self.camera.zoom,
+        y,
+      )?;
+    }
+    Ok(())
+  }
+
   fn draw_night(&mut self, game: &GameAsPlayer) -> Result<(), JsValue> {
     self.context.begin_path();
     self.context.rect(0.0, 0.0, self.width, self.height);
@@ -410,7 +561,7 @@ impl Canvas {
     self.camera = match local_player {
       None => {
         // the spectator sees all
-        Camera::get_global_camera((self.width, self.height))
+        Camera::whole_map((self.width, self.height), &game.state.map)
       }
       Some(p) => {
         // Center the camera on the player
@@ -458,43 +609,87 @@ impl Canvas {
 
     self.context.set_line_width(self.camera.zoom);
 
+    for decoration in game.state.map.decorations_in(Layer::Floor) {
+      self.draw_shape(&decoration.shape)?;
+    }
+    for interactable in game.state.map.interactables.iter() {
+      self.draw_shape(&interactable.zone)?;
+    }
     for shape in game.state.map.static_geometry.iter() {
       self.draw_shape(shape)?;
     }
-
-    let show_dead_people = match game.local_player() {
-      None => true,
-      Some(p) => p.dead || p.impostor,
-    };
-
-    let can_see = |other: &Position| match local_player {
-      Some(p) => p.can_see(&game.state.settings, &game.state.status, other),
-      None => {
-        return true;
+    for door in game.state.map.closed_doors(&game.state.doors) {
+      self.draw_shape(door)?;
+    }
+    for passage in game.state.map.passages.iter() {
+      self.draw_shape(&passage.shape)?;
+    }
+    self.draw_emergency_button(&game.state.map.emergency_button)?;
+    let map = &game.state.map;
+    let consoles = map
+      .admin_consoles
+      .iter()
+      .map(|c| (c.position, "#4a8"))
+      .chain(map.security_consoles.iter().map(|c| (c.position, "#48a")));
+    for (position, color) in consoles {
+      self.draw_shape(&Shape::Rect {
+        top_left: Position {
+          x: position.x - 12.0,
+          y: position.y - 12.0,
+        },
+        width: 24.0,
+        height: 24.0,
+        fill_color: Cow::Borrowed(color),
+        outline_width: 2.0,
+        outline_color: Cow::Borrowed("#000"),
+      })?;
+    }
+    for vent in game.state.map.vents.iter() {
+      self.draw_vent(vent)?;
+    }
+    if let Some(active) = game.state.sabotage.active {
+      for station in game.state.map.fix_stations.iter() {
+        if station.sabotage == active.kind {
+          self.draw_fix_station(station)?;
+        }
       }
-    };
+    }
 
     // Draw tasks, then bodies, then players on top, so tasks are behind everything, then
     // bodies, then imps. That way imps can stand on top of bodies.
     // However maybe we should instead draw items from highest to lowest, vertically?
+    // Your own tasks stay on the map even when they're out of sight, so you
+    // know where to go next.
     if let Some(local_player) = game.local_player() {
       for task in local_player.tasks.iter() {
         if task.finished {
           continue;
         }
-        self.draw_task(*task, local_player.impostor)?;
+        self.draw_task(task)?;
       }
     }
-    for body in game.state.bodies.iter() {
-      if can_see(&body.position) {
-        self.draw_body(*body)?;
-      }
+    let visible = game.state.visible_entities(game.my_uuid);
+    for body in visible.bodies.iter() {
+      self.draw_body(**body)?;
     }
-    for (_, player) in game.state.players.iter() {
-      if (show_dead_people || !player.dead) && can_see(&player.position) {
-        self.draw_player(player)?
+    self.move_pets(game, &visible.players);
+    for player in visible.players.iter() {
+      if let (Some(pet), Some(position)) = (player.cosmetics.pet, self.pets.get(&player.uuid)) {
+        self.draw_pet(pet, *position, player.color)?;
       }
     }
+    for player in visible.players.iter() {
+      self.draw_player(player)?
+    }
+    if let Some(player) = local_player {
+      self.draw_interaction(player, &game.state.settings)?;
+    }
+    for decoration in game.state.map.decorations_in(Layer::Overhead) {
+      self.draw_shape(&decoration.shape)?;
+    }
+    if DebugOverlay::enabled() {
+      self.draw_debug_overlay(&DebugOverlay::take())?;
+    }
 
     // Draw a semitransparant overlay for fog of war.
     let vision = game.vision();
@@ -521,33 +716,114 @@ impl Canvas {
   }
 
   fn draw_shape(&self, shape: &Shape) -> Result<(), JsValue> {
-    match shape {
+    // Big maps have a lot that's nowhere near the screen.
+    if !self.camera.can_see(shape.bounds()) {
+      return Ok(());
+    }
+    // Walls are just a thick line, everything else gets filled in.
+    let (fill_color, line_width, line_color) = match shape {
       Shape::Circle {
-        radius,
-        center,
         fill_color,
         outline_width,
         outline_color,
-      } => {
-        self.context.begin_path();
-        self.context.set_fill_style(&JsValue::from(*fill_color));
-        self
-          .context
-          .set_stroke_style(&JsValue::from(*outline_color));
-        self
-          .context
-          .set_line_width(outline_width * self.camera.zoom);
+        ..
+      }
+      | Shape::Rect {
+        fill_color,
+        outline_width,
+        outline_color,
+        ..
+      }
+      | Shape::Polygon {
+        fill_color,
+        outline_width,
+        outline_color,
+        ..
+      } => (Some(fill_color), outline_width, outline_color),
+      Shape::Wall {
+        thickness, color, ..
+      } => (None, thickness, color),
+    };
+    self.context.begin_path();
+    if let Some(fill_color) = fill_color {
+      self
+        .context
+        .set_fill_style(&JsValue::from(fill_color.as_ref()));
+    }
+    self
+      .context
+      .set_stroke_style(&JsValue::from(line_color.as_ref()));
+    self.context.set_line_width(line_width * self.camera.zoom);
+    match shape {
+      Shape::Circle { radius, center, .. } => {
         let (x, y) = self.camera.offset(center.x, center.y);
         self
           .context
           .arc(x, y, radius * self.camera.zoom, 0.0, 2.0 * PI)?;
-        self.context.stroke();
-        self.context.fill();
       }
+      Shape::Rect { .. } | Shape::Polygon { .. } | Shape::Wall { .. } => {
+        for (i, corner) in shape.corners().iter().enumerate() {
+          let (x, y) = self.camera.offset(corner.x, corner.y);
+          if i == 0 {
+            self.context.move_to(x, y);
+          } else {
+            self.context.line_to(x, y);
+          }
+        }
+        if fill_color.is_some() {
+          self.context.close_path();
+        }
+      }
+    }
+    self.context.set_line_cap("round");
+    self.context.stroke();
+    self.context.set_line_cap("butt");
+    if fill_color.is_some() {
+      self.context.fill();
     }
     Ok(())
   }
 
+  fn draw_emergency_button(&self, button: &EmergencyButton) -> Result<(), JsValue> {
+    self.context.begin_path();
+    self.arc(button.position.x, button.position.y, 12.0, 0.0, 2.0 * PI)?;
+    self.context.set_fill_style(&JsValue::from("#d22"));
+    self.context.set_stroke_style(&JsValue::from("#000"));
+    self.context.set_line_width(2.0 * self.camera.zoom);
+    self.context.fill();
+    self.context.stroke();
+    Ok(())
+  }
+
+  fn draw_vent(&self, vent: &Vent) -> Result<(), JsValue> {
+    self.context.begin_path();
+    let pos = vent.position;
+    self.context.set_fill_style(&JsValue::from("#555"));
+    self.context.set_stroke_style(&JsValue::from("#000"));
+    self.context.set_line_width(2.0 * self.camera.zoom);
+    let (x, y) = self.camera.offset(pos.x - 12.0, pos.y - 8.0);
+    self
+      .context
+      .rect(x, y, 24.0 * self.camera.zoom, 16.0 * self.camera.zoom);
+    self.context.fill();
+    self.context.stroke();
+    Ok(())
+  }
+
+  fn draw_fix_station(&self, station: &FixStation) -> Result<(), JsValue> {
+    self.context.begin_path();
+    let pos = station.position;
+    self.context.set_fill_style(&JsValue::from("#ee2"));
+    self.context.set_stroke_style(&JsValue::from("#000"));
+    self.context.set_line_width(2.0 * self.camera.zoom);
+    let (x, y) = self.camera.offset(pos.x - 8.0, pos.y - 8.0);
+    let size = 16.0 * self.camera.zoom;
+    self.context.rect(x, y, size, size);
+    self.context.fill();
+    self.context.stroke();
+    Ok(())
+  }
+
   fn draw_player(&self, player: &Player) -> Result<(), &'static str> {
     // draw circle
     self.context.begin_path();
@@ -588,13 +864,191 @@ impl Canvas {
       self.context.set_fill_style(&JsValue::from("#000"));
       self.context.set_stroke_style(&JsValue::from("#fff"));
       self.context.set_line_width(self.camera.zoom);
-      self.stroke_text(&player.name, player.position.x, player.position.y - 14.0)?;
-      self.fill_text(&player.name, player.position.x, player.position.y - 14.0)?;
+      // Leave room for their hat.
+      let name_y = match player.cosmetics.hat {
+        Some(_) => player.position.y - 22.0,
+        None => player.position.y - 14.0,
+      };
+      self.stroke_text(&player.name, player.position.x, name_y)?;
+      self.fill_text(&player.name, player.position.x, name_y)?;
+    }
+    if let Some(hat) = player.cosmetics.hat {
+      self
+        .draw_hat(hat, player)
+        .map_err(|_| "Failed to draw a hat.")?;
     }
 
     Ok(())
   }
 
+  // Pets follow along after their owners each frame, at the same pace as the
+  // players move. Anyone who's gone out of sight takes their pet with them.
+  fn move_pets(&mut self, game: &GameAsPlayer, visible: &[&Player]) {
+    let now = instant::Instant::now();
+    let elapsed = self.last_drawn.map(|last| now - last).unwrap_or_default();
+    self.last_drawn = Some(now);
+    let mut pets = BTreeMap::new();
+    for player in visible.iter() {
+      if player.cosmetics.pet.is_none() || player.in_vent.is_some() {
+        continue;
+      }
+      let position = match self.pets.get(&player.uuid) {
+        Some(pet) => PetId::follow(*pet, player, &game.state.settings, elapsed),
+        None => PetId::follow(player.position, player, &game.state.settings, Duration::MAX),
+      };
+      pets.insert(player.uuid, position);
+    }
+    self.pets = pets;
+  }
+
+  fn draw_hat(&self, hat: HatId, player: &Player) -> Result<(), JsValue> {
+    let radius = Player::radius();
+    let (x, top) = (player.position.x, player.position.y - radius);
+    let rect = |x: f64, y: f64, width: f64, height: f64, fill: &'static str| Shape::Rect {
+      top_left: Position { x, y },
+      width,
+      height,
+      fill_color: Cow::Borrowed(fill),
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+    };
+    let alpha = if player.dead { "88" } else { "" };
+    match hat {
+      HatId::TopHat => {
+        self.draw_shape(&rect(x - 8.0, top - 2.0, 16.0, 3.0, "#222"))?;
+        self.draw_shape(&rect(x - 5.0, top - 11.0, 10.0, 9.0, "#222"))?;
+      }
+      HatId::Crown => {
+        self.context.begin_path();
+        self.move_to(x - 7.0, top + 1.0);
+        self.line_to(x - 7.0, top - 7.0);
+        self.line_to(x - 3.5, top - 3.0);
+        self.line_to(x, top - 8.0);
+        self.line_to(x + 3.5, top - 3.0);
+        self.line_to(x + 7.0, top - 7.0);
+        self.line_to(x + 7.0, top + 1.0);
+        self.context.close_path();
+        self
+          .context
+          .set_fill_style(&JsValue::from(format!("#fc3{}", alpha)));
+        self.context.set_stroke_style(&JsValue::from("#000"));
+        self.context.fill();
+        self.context.stroke();
+      }
+      HatId::Beanie => {
+        self.context.begin_path();
+        self.move_to(x + 8.0, top + 2.0);
+        self.arc(x, top + 2.0, 8.0, PI, PI * 2.0)?;
+        self
+          .context
+          .set_fill_style(&JsValue::from(format!("#c33{}", alpha)));
+        self.context.set_stroke_style(&JsValue::from("#000"));
+        self.context.fill();
+        self.context.stroke();
+      }
+      HatId::Cap => {
+        self.context.begin_path();
+        self.move_to(x + 7.0, top + 1.0);
+        self.arc(x, top + 1.0, 7.0, PI, PI * 2.0)?;
+        self
+          .context
+          .set_fill_style(&JsValue::from(format!("#36c{}", alpha)));
+        self.context.set_stroke_style(&JsValue::from("#000"));
+        self.context.fill();
+        self.context.stroke();
+        self.draw_shape(&rect(x, top - 1.0, 11.0, 2.0, "#36c"))?;
+      }
+    }
+    Ok(())
+  }
+
+  // A little something in the owner's color, smaller than a player so it
+  // can't be mistaken for one.
+  fn draw_pet(&self, pet: PetId, position: Position, color: Color) -> Result<(), JsValue> {
+    let radius = 5.0;
+    self.context.begin_path();
+    match pet {
+      PetId::Dog | PetId::Cat => {
+        self.move_to(position.x + radius, position.y);
+        self.arc(position.x, position.y, radius, 0.0, PI * 2.0)?;
+        // Ears: floppy for dogs, pointy for cats.
+        let ear = if pet == PetId::Dog { 1.0 } else { -4.0 };
+        for side in [-1.0, 1.0].iter() {
+          let x = position.x + side * radius * 0.7;
+          self.move_to(x - 2.0, position.y - radius * 0.6);
+          self.line_to(x, position.y - radius + ear);
+          self.line_to(x + 2.0, position.y - radius * 0.6);
+        }
+      }
+      PetId::Robot => {
+        let (x, y) = self.camera.offset(position.x - radius, position.y - radius);
+        let size = radius * 2.0 * self.camera.zoom;
+        self.context.rect(x, y, size, size);
+        self.move_to(position.x, position.y - radius);
+        self.line_to(position.x, position.y - radius - 3.0);
+      }
+    }
+    self
+      .context
+      .set_fill_style(&JsValue::from_str(color.to_str()));
+    self.context.set_stroke_style(&JsValue::from("#000"));
+    self.context.fill();
+    self.context.stroke();
+    Ok(())
+  }
+
+  // How far along we are with the task we're working on, under our feet.
+  fn draw_interaction(&self, player: &Player, settings: &Settings) -> Result<(), JsValue> {
+    let interaction = match player.interaction {
+      Some(interaction) => interaction,
+      None => return Ok(()),
+    };
+    let work_time = match player.tasks.get(interaction.task) {
+      Some(task) => task.work_time(settings),
+      None => return Ok(()),
+    };
+    let done = 1.0 - interaction.time_remaining.as_secs_f64() / work_time.as_secs_f64().max(0.001);
+    let (width, height) = (30.0, 4.0);
+    let top_left = Position {
+      x: player.position.x - width / 2.0,
+      y: player.position.y + Player::radius() + 4.0,
+    };
+    let bar = |width: f64, color: &'static str| Shape::Rect {
+      top_left,
+      width,
+      height,
+      fill_color: Cow::Borrowed(color),
+      outline_width: 0.5,
+      outline_color: Cow::Borrowed("#000"),
+    };
+    self.draw_shape(&bar(width, "#444a"))?;
+    self.draw_shape(&bar(width * done.clamp(0.0, 1.0), "#2c5"))
+  }
+
+  // Where players bumped into things since the last frame, and which way
+  // they got pushed back. Only with the debug_collision feature.
+  fn draw_debug_overlay(&self, overlay: &DebugOverlay) -> Result<(), JsValue> {
+    for contact in overlay.contacts.iter() {
+      self.draw_shape(&Shape::Circle {
+        radius: 2.0,
+        center: contact.at,
+        fill_color: Cow::Borrowed("#f0f"),
+        outline_width: 0.0,
+        outline_color: Cow::Borrowed("#f0f"),
+      })?;
+      self.draw_shape(&Shape::Wall {
+        start: contact.at,
+        end: contact.at.move_by(Velocity {
+          dx: contact.normal.dx * 12.0,
+          dy: contact.normal.dy * 12.0,
+        }),
+        thickness: 1.0,
+        color: Cow::Borrowed("#f0f"),
+      })?;
+    }
+    Ok(())
+  }
+
   fn draw_body(&self, body: DeadBody) -> Result<(), &'static str> {
     self.context.begin_path();
     let radius = 10.0;
@@ -611,7 +1065,7 @@ impl Canvas {
     Ok(())
   }
 
-  fn draw_task(&self, task: Task, fake: bool) -> Result<(), &'static str> {
+  fn draw_task(&self, task: &Task) -> Result<(), &'static str> {
     self.context.begin_path();
     let len: f64 = 15.0;
     let pos = task.position;
@@ -621,7 +1075,8 @@ impl Canvas {
     self.line_to(pos.x, pos.y + height);
     self.line_to(pos.x + len, pos.y + height);
     self.line_to(pos.x + (len / 2.0), pos.y);
-    if fake {
+    // Timed tasks that are still going get drawn faded out too.
+    if task.fake || task.is_waiting() {
       self.context.set_fill_style(&JsValue::from("#ffa50244"));
       self.context.set_stroke_style(&JsValue::from("#00000044"));
     } else {
@@ -774,13 +1229,14 @@ impl Canvas {
         top_left.0 + avatar_radius + (3.5 * line_width),
         top_left.1 + (1.5 * line_width),
       );
-      self
-        .context
-        .stroke_text(&player.name, text_pos.0, text_pos.1)?;
+      let name = if player.afk {
+        format!("{} (AFK)", player.name)
+      } else {
+        player.name.clone()
+      };
+      self.context.stroke_text(&name, text_pos.0, text_pos.1)?;
       self.context.set_fill_style(&JsValue::from("#fff"));
-      self
-        .context
-        .fill_text(&player.name, text_pos.0, text_pos.1)?;
+      self.context.fill_text(&name, text_pos.0, text_pos.1)?;
 
       // Draw icons for those who voted for this player
       if let Some(voters) = votes.get(&this_target) {
@@ -1,7 +1,7 @@
 use crate::canvas::*;
 use crate::network::create_websocket_and_listen;
-use instant::Instant;
 use rust_us_core::*;
+use serde::Deserialize;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
@@ -17,7 +17,7 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 #[wasm_bindgen]
 pub struct GameWrapper {
   canvas: Canvas,
-  previous_frame_time: Instant,
+  frames: Ticker,
   game: Arc<Mutex<Option<GameAsPlayer>>>,
   playback_server: Option<PlaybackServer>,
 }
@@ -38,6 +38,12 @@ impl GameWrapper {
     skip_back: bool,
     skip_forward: bool,
     pause_playback: bool,
+    sabotage_lights: bool,
+    sabotage_reactor: bool,
+    sabotage_doors: bool,
+    sabotage_comms: bool,
+    drag: bool,
+    decoy: bool,
   ) -> Result<(), JsValue> {
     let mut game = self
       .game
@@ -60,6 +66,12 @@ impl GameWrapper {
       skip_back,
       skip_forward,
       pause_playback,
+      sabotage_lights,
+      sabotage_reactor,
+      sabotage_doors,
+      sabotage_comms,
+      drag,
+      decoy,
     };
     if let Some(playback_server) = &mut self.playback_server {
       if input.skip_back && !prev_input.skip_back {
@@ -72,7 +84,7 @@ impl GameWrapper {
             game,
           )
           .map_err(|e| JsValue::from(format!("{}", e)))?;
-        console_log!("Skipped back");
+        tracing::info!("Skipped back");
       } else if input.skip_forward && !prev_input.skip_forward {
         let time = playback_server.current_time();
         playback_server
@@ -86,7 +98,7 @@ impl GameWrapper {
       } else if input.pause_playback && !prev_input.pause_playback {
         playback_server.toggle_pause();
         if !playback_server.paused() {
-          self.previous_frame_time = Instant::now();
+          self.frames.restart(&SystemClock);
         }
       }
     }
@@ -105,9 +117,7 @@ impl GameWrapper {
       return Ok(false);
     }
     let game = game.as_mut().unwrap();
-    let now = Instant::now();
-    let elapsed = now - self.previous_frame_time;
-    self.previous_frame_time = now;
+    let elapsed = self.frames.tick(&SystemClock);
     if let Some(playback_server) = &mut self.playback_server {
       if playback_server.paused() {
         // Skip all simulation and drawing while paused until we
@@ -122,7 +132,13 @@ impl GameWrapper {
     if game.state.status == GameStatus::Connecting {
       return Ok(false);
     }
-    Ok(game.simulate(elapsed))
+    let finished = game.simulate(elapsed);
+    // A live game that's been won can still go back to the lobby, so we
+    // keep going until the server's done with us.
+    match self.playback_server {
+      Some(_) => Ok(finished),
+      None => Ok(game.state.status == GameStatus::Disconnected),
+    }
   }
 
   fn write_time_offset_into_url(&self) {
@@ -159,61 +175,550 @@ impl GameWrapper {
     Some(Duration::from_secs_f64(time))
   }
 
+  pub fn is_playback(&self) -> bool {
+    self.playback_server.is_some()
+  }
+
+  // Jumps to somewhere in the recording being played back, given as a
+  // fraction of the way through it.
+  pub fn scrub_playback(&mut self, fraction: f64) -> Result<(), JsValue> {
+    let playback_server = match &mut self.playback_server {
+      None => return Ok(()),
+      Some(p) => p,
+    };
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let game = match game.as_mut() {
+      None => return Ok(()),
+      Some(g) => g,
+    };
+    playback_server
+      .scrub_to(fraction, game)
+      .map_err(|e| JsValue::from(format!("{}", e)))?;
+    self.write_time_offset_into_url();
+    Ok(())
+  }
+
+  pub fn send_chat(&mut self, text: String) -> Result<(), JsValue> {
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g.send_chat(text).map_err(JsValue::from),
+    }
+  }
+
+  pub fn add_bots(&mut self, count: usize) -> Result<(), JsValue> {
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g.add_bots(count).map_err(JsValue::from),
+    }
+  }
+
+  // For the host. Banned players can't come back.
+  pub fn kick_player(&mut self, uuid: String, ban: bool) -> Result<(), JsValue> {
+    let uuid: UUID = serde_json::from_value(serde_json::Value::String(uuid))
+      .map_err(|e| JsValue::from(format!("{}", e)))?;
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g.kick_player(uuid, ban).map_err(JsValue::from),
+    }
+  }
+
+  // Takes a color's name, like "Red". See available_colors.
+  pub fn request_color(&mut self, color: String) -> Result<(), JsValue> {
+    let color: Color = serde_json::from_value(serde_json::Value::String(color))
+      .map_err(|e| JsValue::from(format!("{}", e)))?;
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g.request_color(color).map_err(JsValue::from),
+    }
+  }
+
+  // Switches to the next free color, if there is one.
+  pub fn cycle_color(&mut self) -> Result<(), JsValue> {
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let game = match game.as_mut() {
+      None => return Ok(()),
+      Some(g) => g,
+    };
+    match game.next_color() {
+      None => Ok(()),
+      Some(color) => game.request_color(color).map_err(JsValue::from),
+    }
+  }
+
+  // For the host, to play again once the game's over.
+  pub fn return_to_lobby(&mut self) -> Result<(), JsValue> {
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g.return_to_lobby().map_err(JsValue::from),
+    }
+  }
+
+  // For the host, in the lobby. Takes a JSON object of just the settings to
+  // change, e.g. {"speed": 3.0}.
+  pub fn update_settings(&mut self, patch: String) -> Result<(), JsValue> {
+    let patch: SettingsPatch =
+      serde_json::from_str(&patch).map_err(|e| JsValue::from(format!("{}", e)))?;
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g.update_settings(patch).map_err(JsValue::from),
+    }
+  }
+
+  // For the host, in the lobby. See settings_presets for the names.
+  pub fn use_settings_preset(&mut self, name: String) -> Result<(), JsValue> {
+    let settings = SettingsPreset::from_name(&name)
+      .ok_or("no such preset")?
+      .settings();
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g
+        .update_settings(SettingsPatch::from(&settings))
+        .map_err(JsValue::from),
+    }
+  }
+
+  // For the host, in the lobby, with a code from settings_code.
+  pub fn use_settings_code(&mut self, code: String) -> Result<(), JsValue> {
+    let settings = Settings::from_code(&code).map_err(|e| JsValue::from(format!("{}", e)))?;
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g
+        .update_settings(SettingsPatch::from(&settings))
+        .map_err(JsValue::from),
+    }
+  }
+
+  // The room's rules, for sharing with another room.
+  pub fn settings_code(&self) -> Option<String> {
+    let game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    game.as_ref().map(|g| g.state.settings.to_code())
+  }
+
+  // In seconds, for a clock, if the game has a time limit.
+  pub fn game_time_remaining(&self) -> Option<f64> {
+    let game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    game
+      .as_ref()
+      .and_then(|g| g.state.game_time_remaining)
+      .map(|remaining| remaining.as_secs_f64())
+  }
+
+  // How the room's set up, as JSON, for showing in the lobby.
+  pub fn settings(&self) -> Result<String, JsValue> {
+    let game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let settings = game.as_ref().map(|g| &g.state.settings);
+    serde_json::to_string(&settings).map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
+  // Asks the server how we've done, for the profile screen. See profile_stats.
+  pub fn request_stats(&mut self) -> Result<(), JsValue> {
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g.request_stats().map_err(JsValue::from),
+    }
+  }
+
+  // Games played, wins, tasks and kills as JSON, or null if the server
+  // hasn't told us yet.
+  pub fn profile_stats(&self) -> Result<String, JsValue> {
+    let game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let stats = game.as_ref().and_then(|g| g.stats);
+    serde_json::to_string(&stats).map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
+  // Everyone's roles and how they did, as JSON, or null until the game's over.
+  pub fn game_summary(&self) -> Result<String, JsValue> {
+    let game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let summary = game.as_ref().and_then(|g| g.summary.as_ref());
+    serde_json::to_string(&summary).map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
+  // The colors no one in the lobby is wearing, as a JSON array of names.
+  pub fn available_colors(&self) -> Result<String, JsValue> {
+    let game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let colors = match game.as_ref() {
+      None => Color::all().to_vec(),
+      Some(g) => g.state.available_colors(),
+    };
+    serde_json::to_string(&colors).map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
+  // For impostors, takes the uuid of the player to look like.
+  pub fn shapeshift(&mut self, uuid: String) -> Result<(), JsValue> {
+    let uuid: UUID = serde_json::from_value(serde_json::Value::String(uuid))
+      .map_err(|e| JsValue::from(format!("{}", e)))?;
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g.shapeshift(uuid).map_err(JsValue::from),
+    }
+  }
+
+  pub fn vote_kick(&mut self, uuid: String) -> Result<(), JsValue> {
+    let uuid: UUID = serde_json::from_value(serde_json::Value::String(uuid))
+      .map_err(|e| JsValue::from(format!("{}", e)))?;
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g.vote_kick(uuid).map_err(JsValue::from),
+    }
+  }
+
+  // Passes on a voice signal, given as JSON like
+  // {"to": "<uuid>", "signal": {"Offer": {"sdp": "..."}}}.
+  pub fn send_signal(&mut self, json: String) -> Result<(), JsValue> {
+    let outgoing: OutgoingSignal =
+      serde_json::from_str(&json).map_err(|e| JsValue::from(format!("{}", e)))?;
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g
+        .send_signal(outgoing.to, outgoing.signal)
+        .map_err(JsValue::from),
+    }
+  }
+
+  // Voice signals from other players since the last call, as a JSON array of
+  // [from, signal] pairs.
+  pub fn take_signals(&mut self) -> Result<String, JsValue> {
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let signals = match game.as_mut() {
+      None => vec![],
+      Some(g) => g.take_signals(),
+    };
+    serde_json::to_string(&signals).map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
+  // The players we should be able to hear, as a JSON array of UUIDs.
+  pub fn audible_peers(&self, max_distance: f64) -> Result<String, JsValue> {
+    let game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let peers = match game.as_ref() {
+      None => vec![],
+      Some(g) => g.audible_peers(max_distance),
+    };
+    serde_json::to_string(&peers).map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
+  // How loud each of the players we can hear should be, as a JSON array of
+  // [uuid, volume] pairs, with volumes from 0 to 1. Quieter the further away
+  // they are.
+  pub fn peer_volumes(&self, max_distance: f64) -> Result<String, JsValue> {
+    let game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let volumes = match game.as_ref() {
+      None => vec![],
+      Some(g) => g.peer_volumes(max_distance),
+    };
+    serde_json::to_string(&volumes).map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
+  // The puzzle for the task we're working on, as JSON, or null if we're not
+  // working on one.
+  pub fn current_minigame(&self) -> Result<String, JsValue> {
+    let game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let minigame = game.as_ref().and_then(|g| g.current_minigame());
+    serde_json::to_string(&minigame).map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
+  // Hands in an answer to the current puzzle, given as JSON like
+  // {"Keypad": {"code": [1, 2, 3, 4, 5]}}.
+  pub fn solve_minigame(&mut self, json: String) -> Result<(), JsValue> {
+    let solution: MinigameSolution =
+      serde_json::from_str(&json).map_err(|e| JsValue::from(format!("{}", e)))?;
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    match game.as_mut() {
+      None => Ok(()),
+      Some(g) => g.solve_minigame(solution).map_err(JsValue::from),
+    }
+  }
+
+  // What's happened in the game since the last call, as a JSON array.
+  pub fn take_events(&mut self) -> Result<String, JsValue> {
+    let mut game = self
+      .game
+      .lock()
+      .expect("Internal Error: could not get a lock on the game");
+    let events = match game.as_mut() {
+      None => vec![],
+      Some(g) => g.take_events(),
+    };
+    serde_json::to_string(&events).map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
   pub fn draw(&mut self) -> Result<(), JsValue> {
     self.canvas.draw(self.game.clone())
   }
 }
 
+// Exposes a map that's being edited, so the editor in the browser can use
+// the same rules as the game.
+#[wasm_bindgen]
+pub struct MapEditor {
+  draft: MapDraft,
+}
+
+#[wasm_bindgen]
+impl MapEditor {
+  // Makes a change, given as JSON like {"PlaceTaskSpot": {"position": {"x":
+  // 100, "y": 200}}}.
+  pub fn apply(&mut self, json: String) -> Result<(), JsValue> {
+    let edit: MapEdit = serde_json::from_str(&json).map_err(|e| JsValue::from(format!("{}", e)))?;
+    self
+      .draft
+      .apply(edit)
+      .map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
+  // What's stopping the map from being played on, if anything.
+  pub fn problem(&self) -> Option<String> {
+    self.draft.validate().err().map(|e| format!("{}", e))
+  }
+
+  // The map as it is now, as JSON that can be loaded as a custom map.
+  pub fn to_json(&self) -> Result<String, JsValue> {
+    serde_json::to_string(self.draft.map()).map_err(|e| JsValue::from(format!("{}", e)))
+  }
+
+  // Keeps the map around for hosting a game on, with ?custom_map.
+  pub fn save(&self) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("no window")?;
+    let local_storage = window.local_storage()?.ok_or("no window.localStorage")?;
+    local_storage.set(CUSTOM_MAP_KEY, &self.to_json()?)
+  }
+}
+
+const CUSTOM_MAP_KEY: &str = "custom map";
+
+fn get_custom_map() -> Result<Option<String>, JsValue> {
+  let window = web_sys::window().ok_or("no window")?;
+  let local_storage = window.local_storage()?.ok_or("no window.localStorage")?;
+  local_storage.get(CUSTOM_MAP_KEY)
+}
+
+const COSMETICS_KEY: &str = "cosmetics";
+
+// The hat and pet picked out for next time we join a game, e.g.
+// set_cosmetics("Crown", "Cat"). Anything else (like null) means none.
+#[wasm_bindgen]
+pub fn set_cosmetics(hat: Option<String>, pet: Option<String>) -> Result<(), JsValue> {
+  let cosmetics = Cosmetics {
+    hat: hat.and_then(|hat| serde_json::from_value(serde_json::Value::String(hat)).ok()),
+    pet: pet.and_then(|pet| serde_json::from_value(serde_json::Value::String(pet)).ok()),
+  };
+  let window = web_sys::window().ok_or("no window")?;
+  let local_storage = window.local_storage()?.ok_or("no window.localStorage")?;
+  let json = serde_json::to_string(&cosmetics).map_err(|e| JsValue::from(format!("{}", e)))?;
+  local_storage.set(COSMETICS_KEY, &json)
+}
+
+// The choices there are, as JSON, for showing in a picker.
+#[wasm_bindgen]
+pub fn available_cosmetics() -> Result<String, JsValue> {
+  let all = serde_json::json!({
+    "hats": HatId::all(),
+    "pets": PetId::all(),
+  });
+  Ok(all.to_string())
+}
+
+// The names of the ready-made rule sets, as JSON, for use_settings_preset.
+#[wasm_bindgen]
+pub fn settings_presets() -> Result<String, JsValue> {
+  let names: Vec<&str> = SettingsPreset::all().iter().map(|p| p.name()).collect();
+  serde_json::to_string(&names).map_err(|e| JsValue::from(format!("{}", e)))
+}
+
+const ACCOUNT_ID_KEY: &str = "account id";
+
+// Who we are to the server from one game to the next. Made up the first time
+// we're asked, and remembered from then on.
+fn get_account_id() -> Result<PlayerAccountId, JsValue> {
+  let window = web_sys::window().ok_or("no window")?;
+  let local_storage = window.local_storage()?.ok_or("no window.localStorage")?;
+  let saved = local_storage
+    .get(ACCOUNT_ID_KEY)?
+    .and_then(|id| serde_json::from_value(serde_json::Value::String(id)).ok());
+  if let Some(account) = saved {
+    return Ok(account);
+  }
+  let account = PlayerAccountId::random();
+  local_storage.set(ACCOUNT_ID_KEY, &account.to_string())?;
+  Ok(account)
+}
+
+fn get_cosmetics() -> Result<Cosmetics, JsValue> {
+  let window = web_sys::window().ok_or("no window")?;
+  let local_storage = window.local_storage()?.ok_or("no window.localStorage")?;
+  // Anything we can't make sense of, maybe from an older version, is
+  // forgotten rather than stopping us from joining.
+  Ok(
+    local_storage
+      .get(COSMETICS_KEY)?
+      .and_then(|json| serde_json::from_str(&json).ok())
+      .unwrap_or_default(),
+  )
+}
+
+// Starts editing a copy of one of the built-in maps, by name, e.g. "Ship".
+#[wasm_bindgen]
+pub fn make_map_editor(starting_map: String) -> Result<MapEditor, JsValue> {
+  let id: MapId = serde_json::from_str(&format!("{:?}", starting_map))
+    .map_err(|e| JsValue::from(format!("{}", e)))?;
+  let map = MapRegistry::get(id).ok_or("Can't start from a custom map")?;
+  Ok(MapEditor {
+    draft: MapDraft::new(map),
+  })
+}
+
+#[derive(Deserialize)]
+struct OutgoingSignal {
+  to: UUID,
+  signal: VoiceSignal,
+}
+
+// localStorage only holds strings, so recordings get stored base64 encoded.
 fn get_recorded_game() -> Result<Option<RecordedGame>, JsValue> {
-  let local_storage = web_sys::window()
-    .ok_or("no window")?
-    .local_storage()?
-    .ok_or("no window.localStorage")?;
+  let window = web_sys::window().ok_or("no window")?;
+  let local_storage = window.local_storage()?.ok_or("no window.localStorage")?;
   let value = local_storage.get("latest game")?;
   let encoded_game = match value {
     None => return Ok(None),
     Some(g) => g,
   };
-  let game = serde_json::from_str(&encoded_game).map_err(|e| {
+  let bytes: Vec<u8> = window
+    .atob(&encoded_game)?
+    .chars()
+    .map(|c| c as u8)
+    .collect();
+  let game = RecordedGame::from_bytes(&bytes).map_err(|e| {
     format!(
       "Unable to decode game recording from localStorage {:?} – {:?}",
       encoded_game, e
     )
   })?;
-  let game = match game {
-    ServerToClientMessage::Replay(game) => game,
-    _ => {
-      return Err(
-        format!(
-          "Could not decode recorded game from local storage. Expected a Replay but found a {}",
-          game.kind()
-        )
-        .into(),
-      )
-    }
-  };
   Ok(Some(game))
 }
 
-pub fn save_recorded_game(encoded_game: &str) -> Result<(), JsValue> {
-  let local_storage = web_sys::window()
-    .ok_or("no window")?
-    .local_storage()?
-    .ok_or("no window.localStorage")?;
-  local_storage.set("latest game", encoded_game)?;
+pub fn save_recorded_game(recording: &[u8]) -> Result<(), JsValue> {
+  let window = web_sys::window().ok_or("no window")?;
+  let local_storage = window.local_storage()?.ok_or("no window.localStorage")?;
+  let binary_string: String = recording.iter().map(|b| *b as char).collect();
+  local_storage.set("latest game", &window.btoa(&binary_string)?)?;
   Ok(())
 }
 
 #[wasm_bindgen]
 pub fn make_game(name: String) -> Result<GameWrapper, JsValue> {
   crate::utils::set_panic_hook();
+  crate::utils::set_up_logging();
   let location = web_sys::window().ok_or("no window")?.location();
   let should_playback = location.search()?.contains("recording");
   let spectate = location.search()?.contains("spectate");
+  // Binary is smaller, but JSON is much easier to read in the network tab.
+  let encoding = if location.search()?.contains("json") {
+    Encoding::Json
+  } else {
+    Encoding::Binary
+  };
+  let search = location.search()?;
+  let room_code = search
+    .trim_start_matches('?')
+    .split('&')
+    .find_map(|param| param.strip_prefix("room="))
+    .map(|code| code.to_string());
+  // Host a game on the map saved from the editor.
+  let custom_map = if room_code.is_none() && search.contains("custom_map") {
+    get_custom_map()?
+  } else {
+    None
+  };
   let mut wrapper;
   if !should_playback {
     wrapper = GameWrapper {
-      previous_frame_time: Instant::now(),
+      frames: Ticker::new(TICK, &SystemClock),
       canvas: Canvas::find_in_document()?,
       game: Arc::new(Mutex::new(None)),
       playback_server: None,
@@ -223,16 +728,26 @@ pub fn make_game(name: String) -> Result<GameWrapper, JsValue> {
     } else {
       JoinRequest::JoinAsPlayer {
         name,
-        preferred_color: Color::random(),
+        preferred_color: Color::random(&mut rand::thread_rng()),
+        cosmetics: get_cosmetics()?,
       }
     };
-    create_websocket_and_listen(wrapper.game.clone(), join)?;
+    // Without somewhere to keep it, we just don't have stats.
+    let account = get_account_id().ok();
+    create_websocket_and_listen(
+      wrapper.game.clone(),
+      join,
+      room_code,
+      custom_map,
+      encoding,
+      account,
+    )?;
   } else {
     let recording = match get_recorded_game()? {
       None => return Err(JsValue::from("No saved game found")),
       Some(recording) => recording,
     };
-    console_log!(
+    tracing::info!(
       "Starting replay of version {} inside game with version {}",
       recording.version,
       get_version_sha()
@@ -241,7 +756,7 @@ pub fn make_game(name: String) -> Result<GameWrapper, JsValue> {
     let mut game_as_player = GameAsPlayer::new(UUID::random(), connection);
     game_as_player.state.status = GameStatus::Lobby;
     wrapper = GameWrapper {
-      previous_frame_time: Instant::now(),
+      frames: Ticker::new(TICK, &SystemClock),
       canvas: Canvas::find_in_document()?,
       playback_server: Some(PlaybackServer::new(recording)),
       game: Arc::new(Mutex::new(Some(game_as_player))),
@@ -1,5 +1,6 @@
 #![warn(rust_2018_idioms)]
 
+mod camera;
 mod canvas;
 mod js_api;
 mod network;
@@ -9,8 +10,7 @@ use std::f64;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
-// Set up a console.log macro. This has to live in the main file of the crate
-// it seems, for macro-related reasons.
+// Where the logs go, in the browser.
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 extern "C" {
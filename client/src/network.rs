@@ -1,38 +1,50 @@
 use crate::js_api::save_recorded_game;
-use rust_us_core::console_log;
 use rust_us_core::get_version_sha;
 use rust_us_core::ClientToServerMessage;
+use rust_us_core::EncodedMessage;
+use rust_us_core::Encoding;
 use rust_us_core::GameAsPlayer;
 use rust_us_core::GameTx;
+use rust_us_core::JoinError;
 use rust_us_core::JoinRequest;
+use rust_us_core::MapId;
+use rust_us_core::PlayerAccountId;
+use rust_us_core::RoomVisibility;
 use rust_us_core::ServerToClientMessage;
+use rust_us_core::PROTOCOL_VERSION;
 use std::sync::Arc;
 use std::sync::Mutex;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::JsValue;
-use web_sys::{ErrorEvent, MessageEvent, WebSocket};
+use web_sys::{BinaryType, ErrorEvent, MessageEvent, WebSocket};
 
 #[derive(Clone)]
 pub struct WebSocketTx {
   socket: WebSocket,
+  encoding: Encoding,
 }
 
 impl WebSocketTx {
-  pub fn new(ws: WebSocket) -> WebSocketTx {
-    WebSocketTx { socket: ws }
+  pub fn new(ws: WebSocket, encoding: Encoding) -> WebSocketTx {
+    WebSocketTx {
+      socket: ws,
+      encoding,
+    }
   }
 }
 
 impl GameTx for WebSocketTx {
   fn send(&self, message: &ClientToServerMessage) -> Result<(), String> {
-    let encoded = serde_json::to_string(&message)
-      .map_err(|_| JsValue::from_str("Unable to encode Message to json"))
-      .map_err(|e| format!("{:?}", e))?;
-    self
-      .socket
-      .send_with_str(&encoded)
-      .map_err(|e| format!("{:?}", e))?;
+    let encoded = self
+      .encoding
+      .encode(message)
+      .map_err(|e| format!("Unable to encode {:?}: {}", message, e))?;
+    match encoded {
+      EncodedMessage::Text(text) => self.socket.send_with_str(&text),
+      EncodedMessage::Binary(bytes) => self.socket.send_with_u8_array(&bytes),
+    }
+    .map_err(|e| format!("{:?}", e))?;
     Ok(())
   }
 }
@@ -57,47 +69,133 @@ fn get_websocket_url() -> Result<String, JsValue> {
   Ok(format!("ws://{}/", hostname))
 }
 
+// Asks to join the game, in the given room if there is one. Given a custom
+// map, we make a room of our own to play it in instead.
+fn send_join(
+  tx: &WebSocketTx,
+  room_code: &Option<String>,
+  custom_map: &Option<String>,
+  password: Option<String>,
+  join: &JoinRequest,
+) -> Result<(), String> {
+  if let Some(map) = custom_map {
+    tx.send(&ClientToServerMessage::CreateRoom {
+      visibility: RoomVisibility::Private,
+      password: password.clone(),
+      quick_chat_only: false,
+      map_id: MapId::Custom,
+      custom_map: Some(map.clone()),
+    })?;
+  } else if let Some(code) = room_code {
+    tx.send(&ClientToServerMessage::JoinRoom {
+      code: code.clone(),
+      password,
+    })?;
+  }
+  tx.send(&ClientToServerMessage::Join {
+    version: get_version_sha().to_string(),
+    details: join.clone(),
+  })
+}
+
 // Creates a websocket and hooks it up to the callbacks on the given GameAsPlayer.
+// If room_code is given, we join that room, otherwise the server picks one for us.
 pub fn create_websocket_and_listen(
   game_as_player: Arc<Mutex<Option<GameAsPlayer>>>,
   join: JoinRequest,
+  room_code: Option<String>,
+  custom_map: Option<String>,
+  encoding: Encoding,
+  account: Option<PlayerAccountId>,
 ) -> Result<(), JsValue> {
   let ws = WebSocket::new(&get_websocket_url()?)?;
+  // Get binary messages as bytes we can read right away, rather than as blobs.
+  ws.set_binary_type(BinaryType::Arraybuffer);
 
   let game_as_player_clone = game_as_player.clone();
   let ws_clone = ws.clone();
+  // Messages about rooms show up before we're welcomed into the game.
+  let mut before_welcome: Vec<ServerToClientMessage> = vec![];
+  let join_clone = join.clone();
+  let room_code_clone = room_code.clone();
   let onmessage_callback = Closure::wrap(Box::new(move |e: MessageEvent| {
-    // Starting with assuming text messages. Can make efficient later (bson?).
-    let message: ServerToClientMessage;
-    if let Ok(txt) = e.data().dyn_into::<js_sys::JsString>() {
-      let strng: String = txt.into();
-      message = match serde_json::from_str(&strng) {
-        Ok(m) => m,
-        Err(e) => {
-          console_log!("Unable to deserialize {:?} – {:?}", strng, e);
-          return;
-        }
+    let encoded = if let Ok(txt) = e.data().dyn_into::<js_sys::JsString>() {
+      EncodedMessage::Text(txt.into())
+    } else if let Ok(buffer) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
+      EncodedMessage::Binary(js_sys::Uint8Array::new(&buffer).to_vec())
+    } else {
+      tracing::warn!("Unable to handle message: {:?}", e.data());
+      return;
+    };
+    let message: ServerToClientMessage = match encoded.decode() {
+      Ok(m) => m,
+      Err(e) => {
+        tracing::warn!("Unable to deserialize {:?} – {:?}", encoded, e);
+        return;
+      }
+    };
+    if let ServerToClientMessage::Replay(recording) = &message {
+      let saved = recording
+        .to_bytes()
+        .map_err(|e| JsValue::from(format!("{}", e)))
+        .and_then(|bytes| save_recorded_game(&bytes));
+      match saved {
+        Ok(()) => tracing::info!("Saved replay!"),
+        Err(e) => tracing::warn!("Error saving game recording to localStorage: {:?}", e),
+      }
+    }
+    let about_rooms = matches!(
+      message,
+      ServerToClientMessage::JoinedRoom { .. }
+        | ServerToClientMessage::RoomList { .. }
+        | ServerToClientMessage::Stats(_)
+    );
+    if about_rooms && game_as_player_clone.lock().unwrap().is_none() {
+      before_welcome.push(message);
+      return;
+    }
+    if let ServerToClientMessage::ServerHello { server_build, .. } = &message {
+      tracing::info!("Connected to server build {}", server_build);
+      return;
+    }
+    if let ServerToClientMessage::IncompatibleVersion(e) = &message {
+      // There's no game to show this in yet, and there never will be.
+      if let Some(window) = web_sys::window() {
+        window.alert_with_message(&e.to_string()).ok();
+      }
+      ws_clone.close().ok();
+      return;
+    }
+    if let ServerToClientMessage::JoinFailed(JoinError::WrongPassword) = message {
+      let window = match web_sys::window() {
+        Some(window) => window,
+        None => return,
+      };
+      let password = match window.prompt_with_message("This room needs a password") {
+        Ok(Some(password)) => password,
+        _ => return,
       };
-      if let ServerToClientMessage::Replay(_) = &message {
-        match save_recorded_game(&strng) {
-          Ok(()) => console_log!("Saved replay!"),
-          Err(e) => console_log!("Error saving game recording to localStorage: {:?}", e),
-        }
+      let tx = WebSocketTx::new(ws_clone.clone(), encoding);
+      if let Err(e) = send_join(&tx, &room_code_clone, &None, Some(password), &join_clone) {
+        tracing::warn!("Unable to rejoin with a password: {}", e);
       }
-    } else {
-      console_log!("Unable to handle binary encoded message.");
       return;
     }
+    if let ServerToClientMessage::JoinFailed(e) = &message {
+      tracing::warn!("Unable to join: {}", e);
+    }
     if let ServerToClientMessage::Welcome {
       connection_id: uuid,
     } = message
     {
       let clone = &game_as_player_clone.clone();
       let mut wrapped = clone.lock().unwrap();
-      *wrapped = Some(GameAsPlayer::new(
-        uuid,
-        Box::new(WebSocketTx::new(ws_clone.clone())),
-      ));
+      let mut game =
+        GameAsPlayer::new(uuid, Box::new(WebSocketTx::new(ws_clone.clone(), encoding)));
+      for message in before_welcome.drain(..) {
+        game.handle_msg(message).unwrap();
+      }
+      *wrapped = Some(game);
     }
     let clone = &game_as_player_clone.clone();
     let mut option_wrapped = clone.lock().unwrap();
@@ -109,7 +207,7 @@ pub fn create_websocket_and_listen(
     match game_as_player.handle_msg(message) {
       Ok(()) => (),
       Err(e) => {
-        console_log!("Player failed to handle message: {:?}", e);
+        tracing::warn!("Player failed to handle message: {:?}", e);
       }
     }
   }) as Box<dyn FnMut(MessageEvent)>);
@@ -119,13 +217,13 @@ pub fn create_websocket_and_listen(
   onmessage_callback.forget();
 
   let onerror_callback = Closure::wrap(Box::new(move |e: ErrorEvent| {
-    console_log!("error event on websocket: {:?}", e);
+    tracing::warn!("error event on websocket: {:?}", e);
   }) as Box<dyn FnMut(ErrorEvent)>);
   ws.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
   onerror_callback.forget();
 
   let onclose_callback = Closure::wrap(Box::new(move |_| {
-    console_log!("websocket closed");
+    tracing::info!("websocket closed");
     let mut option_wrapped = game_as_player.lock().unwrap();
     let game_as_player = match option_wrapped.as_mut() {
       Some(v) => v,
@@ -140,13 +238,18 @@ pub fn create_websocket_and_listen(
 
   let ws_clone = ws.clone();
   let onopen_callback = Closure::wrap(Box::new(move |_| {
-    console_log!("socket opened");
-    let tx = Box::new(WebSocketTx::new(ws_clone.clone()));
-    tx.send(&ClientToServerMessage::Join {
-      version: get_version_sha().to_string(),
-      details: join.clone(),
-    })
-    .unwrap();
+    tracing::info!("socket opened");
+    // Everyone starts out speaking JSON, so that's how we say hello.
+    WebSocketTx::new(ws_clone.clone(), Encoding::Json)
+      .send(&ClientToServerMessage::ClientHello {
+        protocol_version: PROTOCOL_VERSION,
+        client_build: get_version_sha().to_string(),
+        encoding,
+        account,
+      })
+      .unwrap();
+    let tx = WebSocketTx::new(ws_clone.clone(), encoding);
+    send_join(&tx, &room_code, &custom_map, None, &join).unwrap();
   }) as Box<dyn FnMut(JsValue)>);
   ws.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
   onopen_callback.forget();
@@ -9,3 +9,13 @@ pub fn set_panic_hook() {
   #[cfg(feature = "console_error_panic_hook")]
   console_error_panic_hook::set_once();
 }
+
+// Everything that's logged, here or in the game itself, goes to the
+// browser's console.
+pub fn set_up_logging() {
+  #[cfg(target_arch = "wasm32")]
+  let write = crate::log;
+  #[cfg(not(target_arch = "wasm32"))]
+  let write = |line: &str| println!("{}", line);
+  rust_us_core::init_logging(rust_us_core::LogFormat::Plain, tracing::Level::INFO, write);
+}
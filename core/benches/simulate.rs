@@ -0,0 +1,75 @@
+use core::time::Duration;
+use rust_us_core::*;
+use std::hint::black_box;
+use std::time::Instant;
+
+// Rough numbers for how long the server spends on each tick with a crowd
+// of players, for noticing when collisions or serialization get a lot
+// slower.
+
+const PLAYER_COUNTS: &[usize] = &[10, 50, 100];
+const TICKS: u32 = 300;
+
+// Everyone spread around the spawn circle, and heading off in every
+// direction, so that plenty of them end up bumping into the furniture.
+fn crowded_game(count: usize) -> GameState {
+  let mut state = GameState::new();
+  state.set_map(MapRegistry::get(MapId::Ship).unwrap());
+  state.status = GameStatus::Playing(PlayState::Night);
+  let speed = state.settings.speed;
+  for index in 0..count {
+    let uuid = UUID::random();
+    let position = state.map.spawn_area.spot(index, count);
+    let color = Color::all()[index % Color::all().len()];
+    let mut player = Player::new(uuid, format!("Player {}", index), color, position);
+    let angle = index as f64 * 2.4;
+    player.velocity = Velocity {
+      dx: angle.cos() * speed,
+      dy: angle.sin() * speed,
+    };
+    // Someone has to be the impostor, or the crew win straight away.
+    player.impostor = index == 0;
+    state.players.insert(uuid, player);
+  }
+  state
+}
+
+fn time_per(iterations: u32, mut f: impl FnMut()) -> Duration {
+  let start = Instant::now();
+  for _ in 0..iterations {
+    f();
+  }
+  start.elapsed() / iterations
+}
+
+fn main() {
+  for &count in PLAYER_COUNTS {
+    let mut state = crowded_game(count);
+    let simulate = time_per(TICKS, || {
+      state.simulate(TICK);
+    });
+    let snapshot = ServerToClientMessage::Snapshot(Snapshot::new(&state, state.task_bar()));
+    let json = time_per(TICKS, || {
+      black_box(Encoding::Json.encode(&snapshot).unwrap());
+    });
+    let binary = time_per(TICKS, || {
+      black_box(Encoding::Binary.encode(&snapshot).unwrap());
+    });
+    // Every time something changes, everyone gets told what they can see.
+    let uuids: Vec<UUID> = state.players.keys().copied().collect();
+    let views = time_per(TICKS / 10, || {
+      for uuid in uuids.iter() {
+        let view = state.view_for(*uuid);
+        black_box(
+          Encoding::Binary
+            .encode(&Snapshot::new(&view, None))
+            .unwrap(),
+        );
+      }
+    });
+    println!(
+      "{:>3} players: simulate {:?}/tick, json {:?}/snapshot, bincode {:?}/snapshot, everyone's views {:?}",
+      count, simulate, json, binary, views
+    );
+  }
+}
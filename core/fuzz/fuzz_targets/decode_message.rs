@@ -0,0 +1,16 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rust_us_core::*;
+
+// Whatever shows up on a websocket, in either direction, should decode or
+// fail to, but never take anything down.
+fuzz_target!(|data: &[u8]| {
+  let binary = EncodedMessage::Binary(data.to_vec());
+  let _ = binary.decode::<ClientToServerMessage>();
+  let _ = binary.decode::<ServerToClientMessage>();
+  if let Ok(text) = std::str::from_utf8(data) {
+    let text = EncodedMessage::Text(text.to_string());
+    let _ = text.decode::<ClientToServerMessage>();
+    let _ = text.decode::<ServerToClientMessage>();
+  }
+});
@@ -0,0 +1,195 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+// When everyone last did something themselves, for spotting who's gone to
+// sleep at the keyboard. Only the server keeps track.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ActivityLog {
+  // How many meetings there have been this game.
+  pub day: u32,
+  pub last_active: BTreeMap<UUID, LastActive>,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct LastActive {
+  pub tick: u64,
+  pub day: u32,
+}
+
+impl ClientToServerMessage {
+  // Whether it took someone at the keyboard to send it, rather than their
+  // client keeping the connection going on its own.
+  pub fn shows_activity(&self) -> bool {
+    !matches!(
+      self,
+      ClientToServerMessage::ClientHello { .. }
+        | ClientToServerMessage::Checksum { .. }
+        | ClientToServerMessage::Pong { .. }
+        | ClientToServerMessage::Signal { .. }
+    )
+  }
+}
+
+impl GameState {
+  // Returns whether they'd been marked AFK, and are back now.
+  pub(crate) fn note_activity(&mut self, uuid: UUID) -> bool {
+    let last_active = LastActive {
+      tick: self.position_history.tick,
+      day: self.activity.day,
+    };
+    self.activity.last_active.insert(uuid, last_active);
+    match self.players.get_mut(&uuid) {
+      Some(player) if player.afk => {
+        player.afk = false;
+        true
+      }
+      _ => false,
+    }
+  }
+
+  // Everyone starts off with a clean slate, at the start of a game and back
+  // in the lobby.
+  pub(crate) fn reset_activity(&mut self) {
+    if self.partial_view {
+      return;
+    }
+    let last_active = LastActive {
+      tick: self.position_history.tick,
+      day: 0,
+    };
+    self.activity = ActivityLog {
+      day: 0,
+      last_active: self
+        .players
+        .keys()
+        .map(|uuid| (*uuid, last_active))
+        .collect(),
+    };
+    for player in self.players.values_mut() {
+      player.afk = false;
+    }
+  }
+
+  // A new day has started. Anyone who hasn't done a thing since the start of
+  // the last one has sat through a whole day and night, and isn't waited on
+  // to vote any more, so one sleeping player can't hold up every meeting.
+  pub(crate) fn mark_afk_players(&mut self) {
+    if self.partial_view {
+      return;
+    }
+    self.activity.day += 1;
+    let day = self.activity.day;
+    for (uuid, player) in self.players.iter_mut() {
+      let last_day = self.activity.last_active.get(uuid).map_or(0, |a| a.day);
+      if !player.dead && last_day + 1 < day {
+        player.afk = true;
+      }
+    }
+  }
+
+  // The players who've been sitting in the lobby without doing anything for
+  // longer than the room allows.
+  pub fn idle_in_lobby(&self) -> Vec<UUID> {
+    let kick_time = match self.settings.afk_kick_time {
+      Some(time) if self.status == GameStatus::Lobby => time,
+      _ => return vec![],
+    };
+    let kick_ticks = (kick_time.as_nanos() / TICK.as_nanos()) as u64;
+    let now = self.position_history.tick;
+    self
+      .players
+      .keys()
+      .filter(|uuid| {
+        self
+          .activity
+          .last_active
+          .get(uuid)
+          .is_some_and(|a| now.saturating_sub(a.tick) > kick_ticks)
+      })
+      .copied()
+      .collect()
+  }
+}
+
+impl GameServer {
+  // Shows the door to anyone who's fallen asleep in the lobby, so they're not
+  // holding up the start of the game.
+  pub(crate) fn kick_idle_players(&mut self) -> Result<(), Box<dyn Error>> {
+    for uuid in self.state.idle_in_lobby() {
+      // Bots never get bored of waiting.
+      if self.bots.contains(uuid) {
+        continue;
+      }
+      tracing::info!(%uuid, "Idle in the lobby for too long");
+      self.rejoin_tokens.remove(&uuid);
+      let connection = self.connection_for_player(uuid);
+      self.broadcaster.send_to_player(
+        &connection,
+        &ServerToClientMessage::Kicked {
+          reason: KickReason::Idle,
+        },
+      )?;
+      self.state.activity.last_active.remove(&uuid);
+      self.disconnected(connection)?;
+      self.broadcaster.disconnect(&connection);
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_sleeping_players_dont_hold_up_the_vote() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate; 3]);
+    state.reset_activity();
+    let sleeper = uuids[2];
+
+    // The first day they might just be slow.
+    state.note_activity(uuids[0]);
+    state.start_meeting(MeetingReason::EmergencyButton { caller: uuids[0] });
+    assert!(!state.players[&sleeper].afk);
+    state.status = GameStatus::Playing(PlayState::Night);
+    state.note_activity(uuids[0]);
+    state.note_activity(uuids[1]);
+    state.start_meeting(MeetingReason::EmergencyButton { caller: uuids[0] });
+    assert!(state.players[&sleeper].afk);
+    assert!(!state.players[&uuids[1]].afk);
+    assert!(!state.players[&sleeper].eligable_to_vote());
+
+    // So the day ends once everyone else has voted.
+    state.simulate(state.settings.discussion_time + TICK);
+    state.note_vote(uuids[0], VoteTarget::Skip).unwrap();
+    state.note_vote(uuids[1], VoteTarget::Skip).unwrap();
+    state.simulate(TICK);
+    assert!(matches!(
+      state.status,
+      GameStatus::Playing(PlayState::TallyingVotes(_))
+    ));
+
+    // And they're back as soon as they do anything.
+    assert!(state.note_activity(sleeper));
+    assert!(!state.players[&sleeper].afk);
+  }
+
+  #[test]
+  fn test_idle_players_are_kicked_from_the_lobby() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate]);
+    state.status = GameStatus::Lobby;
+    let uuid = uuids[0];
+    state.reset_activity();
+    state.simulate(Duration::from_secs(600));
+    // Unless the room asks for it, they can stay as long as they like.
+    assert_eq!(state.idle_in_lobby(), vec![]);
+    state.settings.afk_kick_time = Some(Duration::from_secs(300));
+    assert_eq!(state.idle_in_lobby(), vec![uuid]);
+    state.note_activity(uuid);
+    assert_eq!(state.idle_in_lobby(), vec![]);
+  }
+}
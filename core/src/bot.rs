@@ -0,0 +1,548 @@
+use crate::*;
+use core::time::Duration;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+
+// Something a bot does. Bots just say which way they want to go, and get
+// their inputs numbered for them.
+#[derive(Clone, Debug)]
+pub enum BotAction {
+  Move(MovementInput),
+  Send(ClientToServerMessage),
+}
+
+// What a bot gets to know about the game, which is no more than a player in
+// its shoes would.
+#[derive(Clone, Debug)]
+pub struct PlayerView {
+  pub uuid: UUID,
+  pub state: GameState,
+}
+
+impl PlayerView {
+  pub fn me(&self) -> Option<&Player> {
+    self.state.players.get(&self.uuid)
+  }
+}
+
+// Decides what a bot does. The built-in bots use SimpleBrain, but anything
+// that can play the game from a PlayerView can be plugged in with
+// GameServer::add_bot.
+pub trait BotBrain: Send {
+  // Called every so often, with how long it's been since the last time.
+  fn decide(&mut self, view: &PlayerView, dt: Duration) -> Vec<BotAction>;
+}
+
+struct Bot {
+  // Only missing while it's busy thinking.
+  brain: Option<Box<dyn BotBrain>>,
+  // The last input we sent, so we only send a new one when changing course.
+  movement: MovementInput,
+  sequence: u64,
+}
+
+// Players that the server plays itself, to make up the numbers. They send
+// the same messages as anyone else, so the rest of the game can't tell them
+// apart, and recordings of games with bots in play back like any other.
+#[derive(Default)]
+pub struct Bots {
+  bots: BTreeMap<UUID, Bot>,
+  since_thought: Duration,
+}
+
+impl Bots {
+  // Bots don't need to make up their minds every tick.
+  const THINK_INTERVAL: Duration = Duration::from_millis(200);
+  // Close enough to where we're going to stop heading there.
+  const ARRIVED: f64 = 5.0;
+
+  pub fn contains(&self, uuid: UUID) -> bool {
+    self.bots.contains_key(&uuid)
+  }
+
+  pub fn len(&self) -> usize {
+    self.bots.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.bots.is_empty()
+  }
+}
+
+impl GameServer {
+  // Adds up to count of the built-in bots to the lobby, as many as there's
+  // room for. Returns how many were added.
+  pub fn add_bots(&mut self, requester: UUID, count: usize) -> Result<usize, Box<dyn Error>> {
+    if !self.state.is_host(requester) {
+      return Err(
+        GameError::NotHost {
+          action: Action::AddBots,
+        }
+        .into(),
+      );
+    }
+    let mut added = 0;
+    while added < count {
+      let name = format!("Bot {}", self.bots.len() + 1);
+      if self
+        .add_bot(name, Box::new(SimpleBrain::default()))?
+        .is_none()
+      {
+        break;
+      }
+      added += 1;
+    }
+    Ok(added)
+  }
+
+  // Adds a bot to the lobby that's played by the given brain, if there's
+  // room for one.
+  pub fn add_bot(
+    &mut self,
+    name: String,
+    brain: Box<dyn BotBrain>,
+  ) -> Result<Option<UUID>, Box<dyn Error>> {
+    if self.state.status != GameStatus::Lobby {
+      return Err(
+        GameError::WrongStatus {
+          action: Action::AddBots,
+        }
+        .into(),
+      );
+    }
+    if self.state.check_room_for_player().is_err() {
+      return Ok(None);
+    }
+    let uuid = UUID::random();
+    self.bots.bots.insert(
+      uuid,
+      Bot {
+        brain: Some(brain),
+        movement: MovementInput::default(),
+        sequence: 0,
+      },
+    );
+    self.handle_message(
+      uuid,
+      ClientToServerMessage::Join {
+        version: self.version.clone(),
+        details: JoinRequest::JoinAsPlayer {
+          name,
+          preferred_color: Color::random(&mut rand::thread_rng()),
+          cosmetics: Cosmetics::default(),
+        },
+      },
+    )?;
+    if !self.state.players.contains_key(&uuid) {
+      // Out of colors, most likely.
+      self.bots.bots.remove(&uuid);
+      return Ok(None);
+    }
+    self.handle_message(uuid, ClientToServerMessage::Ready { ready: true })?;
+    Ok(Some(uuid))
+  }
+
+  // Has each of the bots do whatever they're going to do next.
+  pub(crate) fn play_bots(&mut self, elapsed: Duration) {
+    self.bots.since_thought += elapsed;
+    if self.bots.is_empty() || self.bots.since_thought < Bots::THINK_INTERVAL {
+      return;
+    }
+    let dt = self.bots.since_thought;
+    self.bots.since_thought = Duration::from_secs(0);
+    let uuids: Vec<UUID> = self.bots.bots.keys().copied().collect();
+    for uuid in uuids {
+      let mut brain = match self.bots.bots.get_mut(&uuid).and_then(|b| b.brain.take()) {
+        Some(brain) => brain,
+        None => continue,
+      };
+      let view = PlayerView {
+        uuid,
+        state: self.state.view_for(uuid),
+      };
+      for action in brain.decide(&view, dt) {
+        let message = match action {
+          BotAction::Send(message) => message,
+          BotAction::Move(movement) => {
+            let bot = match self.bots.bots.get_mut(&uuid) {
+              Some(bot) if bot.movement != movement => bot,
+              _ => continue,
+            };
+            bot.movement = movement;
+            bot.sequence += 1;
+            ClientToServerMessage::Input {
+              sequence: bot.sequence,
+              movement,
+            }
+          }
+        };
+        // Bots get things wrong sometimes, same as everyone else.
+        let _ = self.handle_message(uuid, message);
+      }
+      if let Some(bot) = self.bots.bots.get_mut(&uuid) {
+        bot.brain = Some(brain);
+      }
+    }
+  }
+}
+
+// The built-in bots. Crew bots head for their nearest unfinished task and do
+// it, impostors wander around and kill whoever they come across, and
+// everyone votes at random.
+#[derive(Clone, Debug, Default)]
+pub struct SimpleBrain {
+  moving: MovementInput,
+  // Where an impostor is wandering off to.
+  heading_for: Option<Position>,
+  // Where we were last time we thought about it, to notice being stuck.
+  last_position: Option<Position>,
+  // How many more thoughts to spend going some random way, to get unstuck
+  // from whatever wall we walked into.
+  unsticking: u32,
+}
+
+impl BotBrain for SimpleBrain {
+  fn decide(&mut self, view: &PlayerView, _dt: Duration) -> Vec<BotAction> {
+    let mut rng = rand::thread_rng();
+    let (messages, movement) = self.decisions(view, &mut rng);
+    self.moving = movement;
+    std::iter::once(BotAction::Move(movement))
+      .chain(messages.into_iter().map(BotAction::Send))
+      .collect()
+  }
+}
+
+impl SimpleBrain {
+  fn decisions(
+    &mut self,
+    view: &PlayerView,
+    rng: &mut impl Rng,
+  ) -> (Vec<ClientToServerMessage>, MovementInput) {
+    let state = &view.state;
+    let me = match view.me() {
+      Some(me) if !me.dead => me,
+      _ => return (vec![], MovementInput::default()),
+    };
+    match &state.status {
+      GameStatus::Playing(PlayState::Night) => {}
+      GameStatus::Playing(PlayState::Voting(voting)) => {
+        return (
+          bot_vote(state, me.uuid, voting, rng),
+          MovementInput::default(),
+        );
+      }
+      _ => return (vec![], MovementInput::default()),
+    }
+    let stuck = self.moving != MovementInput::default()
+      && self
+        .last_position
+        .is_some_and(|last| last.distance(&me.position) < 1.0);
+    self.last_position = Some(me.position);
+    if stuck {
+      self.unsticking = 5;
+      self.heading_for = None;
+    }
+    if self.unsticking > 0 {
+      self.unsticking -= 1;
+      return (vec![], random_direction(rng));
+    }
+    if me.impostor {
+      self.impostor_decisions(state, me, rng)
+    } else {
+      crewmate_decisions(state, me)
+    }
+  }
+
+  fn impostor_decisions(
+    &mut self,
+    state: &GameState,
+    me: &Player,
+    rng: &mut impl Rng,
+  ) -> (Vec<ClientToServerMessage>, MovementInput) {
+    if me.kill_cooldown == Duration::from_secs(0) {
+      let victim = state
+        .players
+        .values()
+        .filter(|p| !p.impostor && !p.dead && p.in_vent.is_none())
+        .find(|p| p.position.distance(&me.position) < state.settings.kill_distance);
+      if let Some(victim) = victim {
+        return (
+          vec![ClientToServerMessage::Kill {
+            victim: victim.uuid,
+            seen_at: state.position_history.tick,
+          }],
+          MovementInput::default(),
+        );
+      }
+    }
+    let target = match self.heading_for {
+      Some(target) if target.distance(&me.position) > Bots::ARRIVED => target,
+      _ => {
+        let map = &state.map;
+        let target = map
+          .task_spots
+          .choose(rng)
+          .copied()
+          .unwrap_or_else(|| Position {
+            x: rng.gen_range(0.0, map.width()),
+            y: rng.gen_range(0.0, map.height()),
+          });
+        self.heading_for = Some(target);
+        target
+      }
+    };
+    (vec![], along_path(state, me.position, target))
+  }
+}
+
+fn crewmate_decisions(
+  state: &GameState,
+  me: &Player,
+) -> (Vec<ClientToServerMessage>, MovementInput) {
+  let stop = MovementInput::default();
+  if let Some(interaction) = &me.interaction {
+    let minigame = me
+      .tasks
+      .get(interaction.task)
+      .and_then(|t| t.minigame.as_ref());
+    return match minigame {
+      Some(minigame) if interaction.time_remaining == Duration::from_secs(0) => (
+        vec![ClientToServerMessage::SolveMinigame {
+          solution: minigame.solution(),
+        }],
+        stop,
+      ),
+      _ => (vec![], stop),
+    };
+  }
+  let task_distance = state.settings.task_distance;
+  let nearest = me
+    .tasks
+    .iter()
+    .enumerate()
+    .filter(|(_, t)| t.check_workable().is_ok())
+    .min_by(|(_, a), (_, b)| {
+      let a = a.position.distance(&me.position);
+      let b = b.position.distance(&me.position);
+      a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+  let (index, task) = match nearest {
+    Some(nearest) => nearest,
+    None => return (vec![], stop),
+  };
+  if state
+    .map
+    .can_interact(me.position, task.position, task_distance)
+  {
+    // Work only starts once we've actually stopped.
+    if me.velocity == Velocity::default() {
+      return (
+        vec![ClientToServerMessage::BeginInteraction { task: index }],
+        stop,
+      );
+    }
+    return (vec![], stop);
+  }
+  (vec![], along_path(state, me.position, task.position))
+}
+
+// Does whatever a real player did in a recorded game, at the same times, for
+// checking that a game still plays out the same way.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayBrain {
+  // What to do, and how long after the bot joins to do it.
+  trace: VecDeque<(Duration, BotAction)>,
+  elapsed: Duration,
+}
+
+impl ReplayBrain {
+  pub fn new(trace: Vec<(Duration, BotAction)>) -> Self {
+    ReplayBrain {
+      trace: trace.into(),
+      elapsed: Duration::from_secs(0),
+    }
+  }
+
+  // Everything the given player did in the recording, timed from when they
+  // joined. Joining and keeping the connection going are left out, since the
+  // server handles that for bots.
+  pub fn from_recording(recording: &RecordedGame, player: UUID) -> Self {
+    let mut joined_at = None;
+    let mut trace = vec![];
+    for entry in recording.entries.iter() {
+      let message = match &entry.event {
+        RecordingEvent::Message(m) if m.sender == player => &m.message,
+        _ => continue,
+      };
+      let at = tick_time(entry.tick);
+      let joined_at = match (joined_at, message) {
+        (None, ClientToServerMessage::Join { .. }) => {
+          joined_at = Some(at);
+          continue;
+        }
+        (None, _) => continue,
+        (Some(joined_at), _) => joined_at,
+      };
+      let action = match message {
+        ClientToServerMessage::Input { movement, .. } => BotAction::Move(*movement),
+        ClientToServerMessage::Join { .. }
+        | ClientToServerMessage::Rejoin { .. }
+        | ClientToServerMessage::ClientHello { .. }
+        | ClientToServerMessage::CreateRoom { .. }
+        | ClientToServerMessage::JoinRoom { .. }
+        | ClientToServerMessage::ListRooms()
+        | ClientToServerMessage::GetStats()
+        | ClientToServerMessage::Checksum { .. }
+        | ClientToServerMessage::Pong { .. }
+        | ClientToServerMessage::Signal { .. } => continue,
+        message => BotAction::Send(message.clone()),
+      };
+      trace.push((at - joined_at, action));
+    }
+    ReplayBrain::new(trace)
+  }
+}
+
+impl BotBrain for ReplayBrain {
+  fn decide(&mut self, _view: &PlayerView, dt: Duration) -> Vec<BotAction> {
+    self.elapsed += dt;
+    let mut due = vec![];
+    while let Some((at, _)) = self.trace.front() {
+      if *at > self.elapsed {
+        break;
+      }
+      if let Some((_, action)) = self.trace.pop_front() {
+        due.push(action);
+      }
+    }
+    due
+  }
+}
+
+// Bots vote for anyone at random, including skipping.
+fn bot_vote(
+  state: &GameState,
+  uuid: UUID,
+  voting: &VotingState,
+  rng: &mut impl Rng,
+) -> Vec<ClientToServerMessage> {
+  if !voting.accepting_votes() || voting.votes.contains_key(&uuid) {
+    return vec![];
+  }
+  let targets: Vec<VoteTarget> = state
+    .players
+    .values()
+    .filter(|p| !p.dead)
+    .map(|p| VoteTarget::Player { uuid: p.uuid })
+    .chain(std::iter::once(VoteTarget::Skip))
+    .filter(|t| voting.can_vote_for(*t))
+    .collect();
+  match targets.choose(rng) {
+    Some(target) => vec![ClientToServerMessage::Vote { target: *target }],
+    None => vec![],
+  }
+}
+
+// Heads for the next corner on the way there, or straight at it if there's
+// no way through.
+fn along_path(state: &GameState, from: Position, to: Position) -> MovementInput {
+  state
+    .map
+    .find_path(from, to, &state.doors)
+    .and_then(|path| {
+      path
+        .into_iter()
+        .map(|next| towards(from, next))
+        .find(|movement| *movement != MovementInput::default())
+    })
+    .unwrap_or_else(|| towards(from, to))
+}
+
+fn towards(from: Position, to: Position) -> MovementInput {
+  let dx = to.x - from.x;
+  let dy = to.y - from.y;
+  MovementInput {
+    up: dy < -Bots::ARRIVED,
+    down: dy > Bots::ARRIVED,
+    left: dx < -Bots::ARRIVED,
+    right: dx > Bots::ARRIVED,
+  }
+}
+
+fn random_direction(rng: &mut impl Rng) -> MovementInput {
+  // Any of the eight ways, but not standing still.
+  let horizontal = rng.gen_range(0, 3);
+  let vertical = if horizontal == 0 {
+    rng.gen_range(1, 3)
+  } else {
+    rng.gen_range(0, 3)
+  };
+  MovementInput {
+    up: vertical == 1,
+    down: vertical == 2,
+    left: horizontal == 1,
+    right: horizontal == 2,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+
+  #[test]
+  fn test_replay_brains_do_what_the_player_did() {
+    let player = UUID::random();
+    let someone_else = UUID::random();
+    let mut recording = RecordedGame::new(0);
+    let mut add = |tick, sender, message| {
+      recording.entries.push(RecordingEntry {
+        tick,
+        event: RecordingEvent::Message(PlaybackMessage {
+          sender,
+          message,
+          decision: None,
+        }),
+      });
+    };
+    let up = MovementInput {
+      up: true,
+      ..MovementInput::default()
+    };
+    add(
+      10,
+      player,
+      ClientToServerMessage::Join {
+        version: "test".to_string(),
+        details: JoinRequest::JoinAsSpectator,
+      },
+    );
+    add(20, someone_else, ClientToServerMessage::CallMeeting());
+    add(
+      30,
+      player,
+      ClientToServerMessage::Input {
+        sequence: 1,
+        movement: up,
+      },
+    );
+    add(35, player, ClientToServerMessage::Pong { id: 1 });
+    add(40, player, ClientToServerMessage::CallMeeting());
+
+    let mut brain = ReplayBrain::from_recording(&recording, player);
+    let view = PlayerView {
+      uuid: player,
+      state: GameState::new(),
+    };
+    assert!(brain.decide(&view, tick_time(19)).is_empty());
+    let actions = brain.decide(&view, tick_time(1));
+    assert!(matches!(actions.as_slice(), [BotAction::Move(m)] if *m == up));
+    let actions = brain.decide(&view, tick_time(100));
+    assert!(matches!(
+      actions.as_slice(),
+      [BotAction::Send(ClientToServerMessage::CallMeeting())]
+    ));
+    assert!(brain.decide(&view, tick_time(100)).is_empty());
+  }
+}
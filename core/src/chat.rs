@@ -0,0 +1,135 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+// Long enough to make an accusation, short enough that no one can fill
+// everyone's screen with one message.
+pub const MAX_CHAT_LENGTH: usize = 200;
+
+// Who gets to read a chat message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatChannel {
+  Everyone,
+  // The dead can keep talking amongst themselves, but the living can't hear
+  // them.
+  Ghosts,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatContent {
+  Text(String),
+  Quick(QuickChat),
+}
+
+impl ChatContent {
+  pub fn describe(&self, state: &GameState) -> String {
+    match self {
+      ChatContent::Text(text) => text.clone(),
+      ChatContent::Quick(quick) => quick.describe(state),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+  pub sender: UUID,
+  pub channel: ChatChannel,
+  pub content: ChatContent,
+}
+
+impl GameState {
+  // Which channel the sender would be talking on right now, if they're allowed
+  // to talk at all. Everyone can chat in the lobby and during meetings, but
+  // the living have to keep quiet at night. Spectators follow the same rules
+  // as the living, since they could be talking to people who are still
+  // playing.
+  pub fn chat_channel_for(&self, sender: UUID) -> Result<ChatChannel, GameError> {
+    let action = Action::Chat;
+    let dead = match (self.players.get(&sender), self.is_spectator(sender)) {
+      (Some(player), _) => player.dead,
+      (None, true) => false,
+      (None, false) => return Err(GameError::UnknownPlayer { uuid: sender }),
+    };
+    match &self.status {
+      GameStatus::Lobby | GameStatus::Won(_) | GameStatus::Draw => Ok(ChatChannel::Everyone),
+      GameStatus::Playing(_) if dead => Ok(ChatChannel::Ghosts),
+      GameStatus::Playing(PlayState::Night) => Err(GameError::WrongStatus { action }),
+      GameStatus::Playing(_) => Ok(ChatChannel::Everyone),
+      GameStatus::Connecting | GameStatus::Paused { .. } | GameStatus::Disconnected => {
+        Err(GameError::WrongStatus { action })
+      }
+    }
+  }
+
+  pub fn note_chat(&self, sender: UUID, text: &str) -> Result<ChatMessage, GameError> {
+    if self.settings.quick_chat_only {
+      return Err(GameError::QuickChatOnly);
+    }
+    let text = text.trim();
+    if text.is_empty() {
+      return Err(GameError::EmptyMessage);
+    }
+    if text.chars().count() > MAX_CHAT_LENGTH {
+      return Err(GameError::MessageTooLong {
+        max: MAX_CHAT_LENGTH,
+      });
+    }
+    Ok(ChatMessage {
+      sender,
+      channel: self.chat_channel_for(sender)?,
+      content: ChatContent::Text(text.to_string()),
+    })
+  }
+
+  // Everyone who should see a message on the given channel.
+  pub fn chat_recipients(&self, channel: ChatChannel) -> Vec<UUID> {
+    match channel {
+      ChatChannel::Everyone => self
+        .players
+        .keys()
+        .chain(self.spectators.keys())
+        .copied()
+        .collect(),
+      ChatChannel::Ghosts => self
+        .players
+        .values()
+        .filter(|p| p.dead)
+        .map(|p| p.uuid)
+        .collect(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_the_living_keep_quiet_at_night() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate; 3]);
+    state.status = GameStatus::Lobby;
+    assert_eq!(
+      state.note_chat(uuids[0], "  hi  ").unwrap().content,
+      ChatContent::Text("hi".to_string())
+    );
+    assert_eq!(state.note_chat(uuids[0], " "), Err(GameError::EmptyMessage));
+    let rant = "a".repeat(MAX_CHAT_LENGTH + 1);
+    assert_eq!(
+      state.note_chat(uuids[0], &rant),
+      Err(GameError::MessageTooLong {
+        max: MAX_CHAT_LENGTH
+      })
+    );
+
+    state.status = GameStatus::Playing(PlayState::Night);
+    state.players.get_mut(&uuids[2]).unwrap().dead = true;
+    assert_eq!(
+      state.chat_channel_for(uuids[0]),
+      Err(GameError::WrongStatus {
+        action: Action::Chat
+      })
+    );
+    assert_eq!(state.chat_channel_for(uuids[2]), Ok(ChatChannel::Ghosts));
+    assert_eq!(state.chat_recipients(ChatChannel::Ghosts), vec![uuids[2]]);
+  }
+}
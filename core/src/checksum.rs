@@ -0,0 +1,197 @@
+use crate::*;
+use core::time::Duration;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Display;
+
+// The parts of the game that players and the server should always agree on,
+// in a fixed order. Where everyone is and how fast they're going are left out,
+// since players predict their own movement and will be a bit off by design.
+#[derive(Serialize)]
+struct ChecksummedState<'a> {
+  status: &'a GameStatus,
+  players: Vec<ChecksummedPlayer<'a>>,
+  bodies: &'a [DeadBody],
+  emergency_cooldown: Duration,
+  sabotage: &'a SabotageState,
+  doors: &'a DoorState,
+  ejection: &'a Option<EjectionResult>,
+  spectators: Vec<&'a Spectator>,
+  host: Option<UUID>,
+}
+
+#[derive(Serialize)]
+struct ChecksummedPlayer<'a> {
+  uuid: UUID,
+  name: &'a str,
+  color: Color,
+  dead: bool,
+  impostor: bool,
+  tasks: &'a [Task],
+  kill_cooldown: Duration,
+  emergency_meetings_called: usize,
+  in_vent: Option<usize>,
+  role: Role,
+  shielded: bool,
+  status_effects: &'a [StatusEffect],
+  ready: bool,
+  disconnected: bool,
+  reconnect_time_remaining: Duration,
+  latency: Option<Duration>,
+}
+
+impl GameState {
+  // A hash of the state, for checking that a player hasn't drifted away from
+  // what the server told them.
+  pub fn checksum(&self) -> u64 {
+    let state = ChecksummedState {
+      status: &self.status,
+      players: self
+        .players
+        .values()
+        .map(|p| ChecksummedPlayer {
+          uuid: p.uuid,
+          name: &p.name,
+          color: p.color,
+          dead: p.dead,
+          impostor: p.impostor,
+          tasks: &p.tasks,
+          kill_cooldown: p.kill_cooldown,
+          emergency_meetings_called: p.emergency_meetings_called,
+          in_vent: p.in_vent,
+          role: p.role,
+          shielded: p.shielded,
+          status_effects: &p.status_effects,
+          ready: p.ready,
+          disconnected: p.disconnected,
+          reconnect_time_remaining: p.reconnect_time_remaining,
+          latency: p.latency,
+        })
+        .collect(),
+      bodies: &self.bodies,
+      emergency_cooldown: self.emergency_cooldown,
+      sabotage: &self.sabotage,
+      doors: &self.doors,
+      ejection: &self.ejection,
+      spectators: self.spectators.values().collect(),
+      host: self.host,
+    };
+    let bytes = bincode::serialize(&state).expect("Internal error: unable to serialize state");
+    fnv1a(&bytes)
+  }
+}
+
+// FNV-1a, which unlike the standard library's hasher is guaranteed to give
+// the same answer on every build.
+fn fnv1a(bytes: &[u8]) -> u64 {
+  let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+  for byte in bytes {
+    hash ^= u64::from(*byte);
+    hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+  }
+  hash
+}
+
+// A player's game didn't match what the server sent them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+  pub uuid: UUID,
+  pub tick: u64,
+  pub expected: u64,
+  pub actual: u64,
+}
+
+impl Display for Divergence {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    write!(
+      fmt,
+      "{} has drifted from the server as of tick {}: expected checksum {:016x}, got {:016x}",
+      self.uuid, self.tick, self.expected, self.actual
+    )
+  }
+}
+
+// The checksums of the last few snapshots the server sent each player, to
+// compare against when they tell us theirs.
+#[derive(Default, Debug, Clone)]
+pub struct ChecksumHistory {
+  sent: BTreeMap<UUID, VecDeque<(u64, u64)>>,
+}
+
+impl ChecksumHistory {
+  // Players answer within a few snapshots, anything older than this we've
+  // probably already corrected.
+  const LENGTH: usize = 16;
+
+  pub fn note_sent(&mut self, uuid: UUID, tick: u64, checksum: u64) {
+    let sent = self.sent.entry(uuid).or_default();
+    if sent.back().map(|(t, _)| *t) == Some(tick) {
+      sent.pop_back();
+    }
+    if sent.len() >= Self::LENGTH {
+      sent.pop_front();
+    }
+    sent.push_back((tick, checksum));
+  }
+
+  pub fn remove(&mut self, uuid: UUID) {
+    self.sent.remove(&uuid);
+  }
+
+  // Compares a player's checksum with the one we sent them for that tick. If
+  // we don't remember that tick there's nothing to compare it with, so we
+  // give them the benefit of the doubt.
+  pub fn check(&self, uuid: UUID, tick: u64, actual: u64) -> Result<(), Divergence> {
+    let expected = self
+      .sent
+      .get(&uuid)
+      .and_then(|sent| sent.iter().find(|(t, _)| *t == tick))
+      .map(|(_, checksum)| *checksum);
+    match expected {
+      Some(expected) if expected != actual => Err(Divergence {
+        uuid,
+        tick,
+        expected,
+        actual,
+      }),
+      _ => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_checksums_catch_players_drifting() {
+    let (state, uuids) = game_with(&[Role::Crewmate]);
+    let uuid = uuids[0];
+    let checksum = state.checksum();
+
+    // Being a little off about where someone is doesn't count.
+    let mut predicted = state.clone();
+    predicted.players.get_mut(&uuid).unwrap().position.x += 5.0;
+    assert_eq!(predicted.checksum(), checksum);
+
+    let mut drifted = state.clone();
+    drifted.players.get_mut(&uuid).unwrap().dead = true;
+    assert_ne!(drifted.checksum(), checksum);
+
+    let mut history = ChecksumHistory::default();
+    history.note_sent(uuid, 10, checksum);
+    history.check(uuid, 10, predicted.checksum()).unwrap();
+    assert_eq!(
+      history.check(uuid, 10, drifted.checksum()),
+      Err(Divergence {
+        uuid,
+        tick: 10,
+        expected: checksum,
+        actual: drifted.checksum(),
+      })
+    );
+    // Nothing to go on for ticks we didn't send.
+    history.check(uuid, 11, drifted.checksum()).unwrap();
+  }
+}
@@ -0,0 +1,140 @@
+use core::time::Duration;
+use instant::Instant;
+use std::sync::{Arc, Mutex};
+
+// Where the time comes from. The game itself only ever deals in how much
+// time has passed, but timeouts and latency need to know what time it is, and
+// tests and replays need to be able to say so themselves.
+pub trait Clock: Send {
+  fn now(&self) -> Instant;
+}
+
+// The real time. Natively that's the OS's monotonic clock, and in the
+// browser it's performance.now(), which is what the instant crate gives us
+// with its wasm-bindgen feature.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> Instant {
+    Instant::now()
+  }
+}
+
+// Time that only moves when it's told to. Clones all share the same time,
+// so one can be handed off to a server while another is kept to move it
+// along.
+#[derive(Clone, Debug)]
+pub struct VirtualClock {
+  now: Arc<Mutex<Instant>>,
+}
+
+impl VirtualClock {
+  pub fn new() -> Self {
+    VirtualClock {
+      now: Arc::new(Mutex::new(Instant::now())),
+    }
+  }
+
+  pub fn advance(&self, by: Duration) {
+    let mut now = self
+      .now
+      .lock()
+      .expect("Internal Error: virtual clock poisoned");
+    *now += by;
+  }
+}
+
+impl Default for VirtualClock {
+  fn default() -> Self {
+    VirtualClock::new()
+  }
+}
+
+impl Clock for VirtualClock {
+  fn now(&self) -> Instant {
+    *self
+      .now
+      .lock()
+      .expect("Internal Error: virtual clock poisoned")
+  }
+}
+
+// Keeps a loop running every so often, like the server's simulation loop or
+// the client's frames, and measures out how much time each tick covers.
+#[derive(Clone, Copy, Debug)]
+pub struct Ticker {
+  period: Duration,
+  last_tick: Instant,
+}
+
+impl Ticker {
+  pub fn new(period: Duration, clock: &dyn Clock) -> Self {
+    Ticker {
+      period,
+      last_tick: clock.now(),
+    }
+  }
+
+  // How long to sleep for before the next tick is due.
+  pub fn until_next(&self, clock: &dyn Clock) -> Duration {
+    let since = clock.now().duration_since(self.last_tick);
+    self
+      .period
+      .checked_sub(since)
+      .unwrap_or_else(|| Duration::from_secs(0))
+  }
+
+  // Ticks, returning how long it's been since the last one.
+  pub fn tick(&mut self, clock: &dyn Clock) -> Duration {
+    let now = clock.now();
+    let elapsed = now.duration_since(self.last_tick);
+    self.last_tick = now;
+    elapsed
+  }
+
+  // Forgets about the time since the last tick, e.g. after being paused.
+  pub fn restart(&mut self, clock: &dyn Clock) {
+    self.last_tick = clock.now();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use core::time::Duration;
+  use std::error::Error;
+
+  struct NoOne;
+  impl Broadcaster for NoOne {
+    fn broadcast(&self, _: &ServerToClientMessage) -> Result<(), Box<dyn Error>> {
+      Ok(())
+    }
+    fn send_to_player(&self, _: &UUID, _: &ServerToClientMessage) -> Result<(), Box<dyn Error>> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_virtual_time_only_moves_when_told() {
+    let clock = VirtualClock::new();
+    let mut ticker = Ticker::new(Duration::from_millis(16), &clock);
+    assert_eq!(ticker.until_next(&clock), Duration::from_millis(16));
+    clock.advance(Duration::from_millis(10));
+    assert_eq!(ticker.until_next(&clock), Duration::from_millis(6));
+    clock.clone().advance(Duration::from_millis(10));
+    assert_eq!(ticker.until_next(&clock), Duration::from_secs(0));
+    assert_eq!(ticker.tick(&clock), Duration::from_millis(20));
+    assert_eq!(ticker.tick(&clock), Duration::from_secs(0));
+
+    // A server that hasn't heard from anyone in a long time gives up on the
+    // game, without the test having to wait around.
+    let mut server = GameServer::new(Box::new(NoOne), false).with_clock(clock.clone());
+    server.state.status = GameStatus::Lobby;
+    server.simulate(Duration::from_millis(16)).unwrap();
+    assert_eq!(server.state.status, GameStatus::Lobby);
+    clock.advance(Duration::from_secs(60 * 60));
+    server.simulate(Duration::from_millis(16)).unwrap();
+    assert_eq!(server.state.status, GameStatus::Disconnected);
+  }
+}
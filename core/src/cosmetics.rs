@@ -0,0 +1,105 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HatId {
+  TopHat,
+  Crown,
+  Beanie,
+  Cap,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PetId {
+  Dog,
+  Cat,
+  Robot,
+}
+
+// Purely for looks. Picked before joining, and carried around on the player
+// so that everyone sees the same thing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cosmetics {
+  pub hat: Option<HatId>,
+  pub pet: Option<PetId>,
+}
+
+impl HatId {
+  pub fn all() -> [HatId; 4] {
+    [HatId::TopHat, HatId::Crown, HatId::Beanie, HatId::Cap]
+  }
+}
+
+impl PetId {
+  pub fn all() -> [PetId; 3] {
+    [PetId::Dog, PetId::Cat, PetId::Robot]
+  }
+
+  // How far behind its owner a pet likes to be.
+  pub const LEASH: f64 = 18.0;
+
+  // Where a pet at the given spot has got to after a bit more time following
+  // its owner around. It heads for the spot behind them (the way they came
+  // from), moving each tick as far as its owner could, so it keeps up without
+  // ever looking faster than them.
+  pub fn follow(pet: Position, owner: &Player, settings: &Settings, elapsed: Duration) -> Position {
+    let behind = if owner.velocity.magnitude() > 0.0 {
+      owner.velocity.normalize::<Velocity>()
+    } else {
+      // Standing still, they settle down off to one side.
+      Velocity { dx: -1.0, dy: 0.0 }
+    };
+    let target: Position = owner
+      .position
+      .minus(&behind.times::<Velocity>(PetId::LEASH));
+    let distance = pet.distance(&target);
+    let ticks = elapsed.as_secs_f64() / TICK.as_secs_f64();
+    let step = settings.speed * owner.speed_multiplier() * ticks;
+    if distance <= step || distance > PetId::LEASH * 10.0 {
+      // Too far to walk (e.g. their owner's just come out of a vent), so
+      // it's easier to just pop up next to them.
+      return target;
+    }
+    let direction: Velocity = target.minus::<Velocity>(&pet).normalize();
+    pet.add(&direction.times::<Velocity>(step))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+
+  #[test]
+  fn test_pets_trail_behind_their_owners() {
+    let mut owner = Player::new(
+      UUID::random(),
+      "Owner".to_string(),
+      Color::Red,
+      Position { x: 100.0, y: 100.0 },
+    );
+    owner.cosmetics.pet = Some(PetId::Dog);
+    owner.velocity = Velocity { dx: 0.0, dy: 1.0 };
+    let settings = Settings::default();
+    let mut pet = Position { x: 100.0, y: 100.0 };
+    for _ in 0..60 {
+      pet = PetId::follow(pet, &owner, &settings, TICK);
+    }
+    // Heading down the screen, so the pet ends up above.
+    assert!((pet.x - 100.0).abs() < 0.01);
+    assert!((pet.y - (100.0 - PetId::LEASH)).abs() < 0.01);
+
+    // It only gets there as fast as a player could walk.
+    let later = PetId::follow(Position { x: 100.0, y: 50.0 }, &owner, &settings, TICK * 5);
+    assert!((later.y - (50.0 + settings.speed * 5.0)).abs() < 0.01);
+
+    // Way off, it appears right by them.
+    owner.position = Position {
+      x: 2000.0,
+      y: 100.0,
+    };
+    let pet = PetId::follow(pet, &owner, &settings, TICK);
+    assert!((pet.y - (100.0 - PetId::LEASH)).abs() < 0.01);
+    assert!((pet.x - 2000.0).abs() < 0.01);
+  }
+}
@@ -0,0 +1,94 @@
+use crate::*;
+#[cfg(feature = "debug_collision")]
+use std::cell::RefCell;
+
+// Where players bumped into things, for drawing on top of the map while
+// working on the collision code. Only collected with the debug_collision
+// feature, otherwise noting things down does nothing and there's never
+// anything to draw.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DebugOverlay {
+  pub contacts: Vec<Contact>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+  // The point on the player's edge that touched the shape.
+  pub at: Position,
+  // Pointing back out of the shape.
+  pub normal: Velocity,
+}
+
+#[cfg(feature = "debug_collision")]
+thread_local! {
+  static OVERLAY: RefCell<DebugOverlay> = RefCell::new(DebugOverlay::default());
+}
+
+impl DebugOverlay {
+  // Anything more than this is from frames nobody's drawing.
+  #[cfg(feature = "debug_collision")]
+  const MAX_CONTACTS: usize = 256;
+
+  pub fn enabled() -> bool {
+    cfg!(feature = "debug_collision")
+  }
+
+  #[cfg(feature = "debug_collision")]
+  pub fn note_contact(contact: Contact) {
+    OVERLAY.with(|overlay| {
+      let contacts = &mut overlay.borrow_mut().contacts;
+      if contacts.len() < Self::MAX_CONTACTS {
+        contacts.push(contact);
+      }
+    });
+  }
+
+  #[cfg(not(feature = "debug_collision"))]
+  #[inline(always)]
+  pub fn note_contact(_contact: Contact) {}
+
+  // Everything noted since the last time, e.g. once per frame.
+  pub fn take() -> DebugOverlay {
+    #[cfg(feature = "debug_collision")]
+    {
+      OVERLAY.with(|overlay| std::mem::take(&mut *overlay.borrow_mut()))
+    }
+    #[cfg(not(feature = "debug_collision"))]
+    {
+      DebugOverlay::default()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+
+  #[test]
+  fn test_contacts_are_only_kept_when_debugging_collisions() {
+    DebugOverlay::take();
+    let wall = Shape::Wall {
+      start: Position { x: 100.0, y: 0.0 },
+      end: Position { x: 100.0, y: 200.0 },
+      thickness: 2.0,
+      color: "#000".into(),
+    };
+    let moved = wall.collide(
+      Position { x: 80.0, y: 100.0 },
+      10.0,
+      Velocity { dx: 20.0, dy: 0.0 },
+      0.0,
+    );
+    assert!(moved.dx < 10.0);
+    let overlay = DebugOverlay::take();
+    if DebugOverlay::enabled() {
+      assert_eq!(overlay.contacts.len(), 1);
+      let contact = overlay.contacts[0];
+      assert!((contact.at.x - 99.0).abs() < 0.01);
+      assert!(contact.normal.dx < 0.0);
+      assert_eq!(DebugOverlay::take(), DebugOverlay::default());
+    } else {
+      assert_eq!(overlay, DebugOverlay::default());
+    }
+  }
+}
@@ -0,0 +1,87 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+// Whether a decoration goes under the players or over them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Layer {
+  // Floor markings, rugs, and so on. Drawn before the furniture.
+  Floor,
+  // Pipes, lamps and the like that everyone walks underneath. Drawn after
+  // the players, but before the fog.
+  Overhead,
+}
+
+// Something on the map that's only there to look at. No one bumps into it,
+// and it doesn't get in the way of anyone seeing anything.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Decoration {
+  pub shape: Shape,
+  pub layer: Layer,
+  // Within a layer, higher ones get drawn on top. Ties go in map order.
+  #[serde(default)]
+  pub z: i32,
+}
+
+impl Map {
+  // The decorations in the given layer, in the order they should be drawn.
+  pub fn decorations_in(&self, layer: Layer) -> Vec<&Decoration> {
+    let mut decorations: Vec<&Decoration> = self
+      .decorations
+      .iter()
+      .filter(|d| d.layer == layer)
+      .collect();
+    decorations.sort_by_key(|d| d.z);
+    decorations
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use std::borrow::Cow;
+
+  #[test]
+  fn test_decorations_dont_get_in_the_way() {
+    let mut map = MapRegistry::get(MapId::Lobby).unwrap();
+    let rug = |z: i32, layer| Decoration {
+      shape: Shape::Rect {
+        top_left: Position { x: 0.0, y: 400.0 },
+        width: 800.0,
+        height: 40.0,
+        fill_color: Cow::Borrowed("#000"),
+        outline_width: 1.0,
+        outline_color: Cow::Borrowed("#000"),
+      },
+      layer,
+      z,
+    };
+    map.decorations = Cow::Owned(vec![
+      rug(2, Layer::Floor),
+      rug(0, Layer::Overhead),
+      rug(-1, Layer::Floor),
+    ]);
+    assert_eq!(
+      map
+        .decorations_in(Layer::Floor)
+        .iter()
+        .map(|d| d.z)
+        .collect::<Vec<i32>>(),
+      vec![-1, 2]
+    );
+    assert_eq!(map.decorations_in(Layer::Overhead).len(), 1);
+
+    // Walking straight across them.
+    let mut player = Player::new(
+      UUID::random(),
+      "Test Player".to_string(),
+      Color::Red,
+      Position { x: 100.0, y: 350.0 },
+    );
+    let doors = DoorState::default();
+    for _ in 0..40 {
+      player.position = map.move_player(&player, Velocity { dx: 0.0, dy: 4.0 }, &doors);
+    }
+    assert!(player.position.y > 480.0);
+    map.validate().unwrap();
+  }
+}
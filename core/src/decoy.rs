@@ -0,0 +1,111 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+// A likeness of an impostor, left standing somewhere to fool the crew while
+// the real one gets on with it. The crew see it as the impostor themselves,
+// the impostors don't see it at all.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Decoy {
+  pub owner: UUID,
+  pub position: Position,
+  pub time_remaining: Duration,
+}
+
+impl GameState {
+  // Leaves a decoy where the impostor's standing. They only get one at a
+  // time, so they have to wait for the last one to fade.
+  pub fn note_decoy_dropped(&mut self, uuid: UUID) -> Result<(), GameError> {
+    let action = Action::DropDecoy;
+    let time = self
+      .settings
+      .decoy_time
+      .ok_or(GameError::TurnedOff { action })?;
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return Err(GameError::WrongStatus { action });
+    }
+    let owner = match self.players.get(&uuid) {
+      None => return Err(GameError::UnknownPlayer { uuid }),
+      Some(p) if p.dead => return Err(GameError::Dead { action }),
+      Some(p) if !p.impostor => return Err(GameError::NotAllowedForRole { action }),
+      Some(p) if p.in_vent.is_some() => return Err(GameError::InVent { action }),
+      Some(p) if p.in_kill_animation() => return Err(GameError::InKillAnimation { action }),
+      Some(p) => p,
+    };
+    if let Some(decoy) = self.decoys.iter().find(|d| d.owner == uuid) {
+      return Err(GameError::OnCooldown {
+        action,
+        remaining: decoy.time_remaining,
+      });
+    }
+    let decoy = Decoy {
+      owner: uuid,
+      position: owner.position,
+      time_remaining: time,
+    };
+    self.decoys.push(decoy);
+    Ok(())
+  }
+
+  pub(crate) fn progress_decoys(&mut self, elapsed: Duration) {
+    for decoy in self.decoys.iter_mut() {
+      decoy.time_remaining = decoy.time_remaining.saturating_sub(elapsed);
+    }
+    self
+      .decoys
+      .retain(|d| d.time_remaining > Duration::from_secs(0));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_decoys_only_fool_the_crew() {
+    let position = Position { x: 200.0, y: 600.0 };
+    let (mut state, uuids) = game_at(&[
+      (Role::Impostor, position),
+      (Role::Impostor, position),
+      (Role::Crewmate, position),
+    ]);
+    state.settings.decoy_time = Some(Duration::from_secs(5));
+    let (impostor, partner, crew) = (uuids[0], uuids[1], uuids[2]);
+    assert_eq!(
+      state.note_decoy_dropped(crew),
+      Err(GameError::NotAllowedForRole {
+        action: Action::DropDecoy
+      })
+    );
+    state.note_decoy_dropped(impostor).unwrap();
+    assert!(matches!(
+      state.note_decoy_dropped(impostor),
+      Err(GameError::OnCooldown { .. })
+    ));
+    let decoy_position = state.players[&impostor].position;
+
+    // The impostor wanders off, out of sight.
+    let far_away = Position {
+      x: 2900.0,
+      y: 600.0,
+    };
+    state.players.get_mut(&impostor).unwrap().position = far_away;
+    assert_eq!(state.visible_entities(crew).decoys.len(), 1);
+    assert!(state.visible_entities(partner).decoys.is_empty());
+    // But the crew still sees them standing where they were.
+    let view = state.view_for(crew);
+    assert_eq!(view.players[&impostor].position, decoy_position);
+    assert!(view.decoys.is_empty());
+    // The other impostor knows better.
+    let view = state.view_for(partner);
+    assert_ne!(view.players[&impostor].position, decoy_position);
+
+    // Until it fades.
+    state.simulate(Duration::from_secs(6));
+    assert!(state.decoys.is_empty());
+    let view = state.view_for(crew);
+    assert_ne!(view.players[&impostor].position, decoy_position);
+  }
+}
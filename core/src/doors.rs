@@ -0,0 +1,124 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// A door on the map. Open doors can be walked and seen through, closed ones
+// are as solid as the walls.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Door {
+  pub shape: Shape,
+}
+
+// Which doors are shut right now. Indexes into Map::doors.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DoorState {
+  // How long until each closed door opens back up on its own.
+  pub closed: BTreeMap<usize, Duration>,
+}
+
+impl DoorState {
+  pub fn is_closed(&self, index: usize) -> bool {
+    self.closed.contains_key(&index)
+  }
+
+  // Ticks down the reopen timers. Returns the doors that have just opened.
+  fn progress_time(&mut self, elapsed: Duration) -> Vec<usize> {
+    for time_remaining in self.closed.values_mut() {
+      *time_remaining = time_remaining
+        .checked_sub(elapsed)
+        .unwrap_or_else(|| Duration::from_secs(0));
+    }
+    let opened: Vec<usize> = self
+      .closed
+      .iter()
+      .filter(|(_, time_remaining)| **time_remaining == Duration::from_secs(0))
+      .map(|(index, _)| *index)
+      .collect();
+    for index in opened.iter() {
+      self.closed.remove(index);
+    }
+    opened
+  }
+}
+
+impl Map {
+  // Everything the doors add to the furniture right now.
+  pub fn closed_doors<'a>(&'a self, doors: &'a DoorState) -> impl Iterator<Item = &'a Shape> {
+    self
+      .doors
+      .iter()
+      .enumerate()
+      .filter(move |(index, _)| doors.is_closed(*index))
+      .map(|(_, door)| &door.shape)
+  }
+}
+
+impl GameState {
+  // Shuts a door for the given amount of time. Closing a door that's already
+  // shut just restarts its timer.
+  pub fn close_door(&mut self, index: usize, duration: Duration) -> Result<(), GameError> {
+    if index >= self.map.doors.len() {
+      return Err(GameError::NoSuchDoor { index });
+    }
+    if self.doors.closed.insert(index, duration).is_none() {
+      self.emit(GameEvent::DoorClosed { index });
+    }
+    Ok(())
+  }
+
+  pub fn open_door(&mut self, index: usize) {
+    if self.doors.closed.remove(&index).is_some() {
+      self.emit(GameEvent::DoorOpened { index });
+    }
+  }
+
+  pub(crate) fn simulate_doors(&mut self, elapsed: Duration) {
+    for index in self.doors.progress_time(elapsed) {
+      self.emit(GameEvent::DoorOpened { index });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_doors_open_back_up_on_their_own() {
+    let map = MapRegistry::get(MapId::Station).unwrap();
+    let door = map.doors[0].shape.center();
+    // Standing right next to the door, facing it.
+    let position = Position {
+      x: door.x,
+      y: door.y + 80.0,
+    };
+    let (mut state, uuids) = game_at(&[(Role::Impostor, position)]);
+    state.set_map(map);
+    let impostor = uuids[0];
+    state.players.get_mut(&impostor).unwrap().velocity = Velocity { dx: 0.0, dy: -2.0 };
+
+    state.note_sabotage(impostor, Sabotage::Doors).unwrap();
+    assert!((0..state.map.doors.len()).all(|i| state.doors.is_closed(i)));
+    state.simulate(Duration::from_secs(1));
+    // The door held.
+    assert!(state.players[&impostor].position.y > door.y);
+
+    state.simulate(state.settings.doors_locked_time);
+    assert_eq!(state.doors, DoorState::default());
+    assert!(state
+      .drain_events()
+      .contains(&GameEvent::DoorOpened { index: 0 }));
+    state.simulate(Duration::from_secs(2));
+    assert!(state.players[&impostor].position.y < door.y);
+
+    assert_eq!(
+      state.close_door(state.map.doors.len(), Duration::from_secs(1)),
+      Err(GameError::NoSuchDoor {
+        index: state.map.doors.len()
+      })
+    );
+  }
+}
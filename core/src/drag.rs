@@ -0,0 +1,156 @@
+use crate::*;
+use core::time::Duration;
+
+impl GameState {
+  fn check_can_drag(&self, uuid: UUID) -> Result<&Player, GameError> {
+    let action = Action::DragBody;
+    if !self.settings.allow_body_drag {
+      return Err(GameError::TurnedOff { action });
+    }
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return Err(GameError::WrongStatus { action });
+    }
+    match self.players.get(&uuid) {
+      None => Err(GameError::UnknownPlayer { uuid }),
+      Some(p) if p.dead => Err(GameError::Dead { action }),
+      Some(p) if !p.impostor => Err(GameError::NotAllowedForRole { action }),
+      Some(p) if p.in_vent.is_some() => Err(GameError::InVent { action }),
+      Some(p) if p.in_kill_animation() => Err(GameError::InKillAnimation { action }),
+      Some(p) => Ok(p),
+    }
+  }
+
+  // The body the given player could pick up, if they're allowed to and
+  // there's one within reach that no one else has hold of.
+  pub fn body_to_drag_near(&self, uuid: UUID) -> Option<Color> {
+    let player = self.check_can_drag(uuid).ok()?;
+    let mut closest_distance = self.settings.kill_distance;
+    let mut closest = None;
+    for body in self.bodies.iter().filter(|b| b.dragged_by.is_none()) {
+      let distance = body.position.distance(&player.position);
+      if distance < closest_distance {
+        closest_distance = distance;
+        closest = Some(body.color);
+      }
+    }
+    closest
+  }
+
+  pub fn dragging(&self, uuid: UUID) -> bool {
+    self.bodies.iter().any(|b| b.dragged_by == Some(uuid))
+  }
+
+  // Impostors can haul a body somewhere quieter, though it slows them down.
+  pub fn note_body_dragged(&mut self, dragger: UUID, color: Color) -> Result<(), GameError> {
+    let action = Action::DragBody;
+    let position = self.check_can_drag(dragger)?.position;
+    if self.dragging(dragger) {
+      return Err(GameError::InvalidTarget { action });
+    }
+    let reach = self.settings.kill_distance;
+    let body = match self.bodies.iter_mut().find(|b| b.color == color) {
+      None => return Err(GameError::NoSuchBody { color }),
+      Some(body) if body.dragged_by.is_some() => return Err(GameError::InvalidTarget { action }),
+      Some(body) if body.position.distance(&position) > reach => {
+        return Err(GameError::OutOfRange { action })
+      }
+      Some(body) => body,
+    };
+    body.dragged_by = Some(dragger);
+    body.position = position;
+    if let Some(player) = self.players.get_mut(&dragger) {
+      player.add_status_effect(StatusEffectKind::Dragging, Duration::from_secs(0));
+    }
+    Ok(())
+  }
+
+  pub fn note_body_released(&mut self, dragger: UUID) -> Result<(), GameError> {
+    if !self.dragging(dragger) {
+      return Err(GameError::NotDragging);
+    }
+    self.release_body(dragger);
+    Ok(())
+  }
+
+  // Drops whatever they're dragging where it is, and lets them get back up
+  // to speed.
+  pub(crate) fn release_body(&mut self, dragger: UUID) {
+    for body in self.bodies.iter_mut() {
+      if body.dragged_by == Some(dragger) {
+        body.dragged_by = None;
+      }
+    }
+    if let Some(player) = self.players.get_mut(&dragger) {
+      player.remove_status_effect(StatusEffectKind::Dragging);
+    }
+  }
+
+  // Everyone lets go when the bodies are cleared away for a meeting.
+  pub(crate) fn release_all_bodies(&mut self) {
+    let draggers: Vec<UUID> = self.bodies.iter().filter_map(|b| b.dragged_by).collect();
+    for dragger in draggers {
+      self.release_body(dragger);
+    }
+  }
+
+  // Bodies stay right with whoever's dragging them.
+  pub(crate) fn move_dragged_bodies(&mut self) {
+    let players = &self.players;
+    for body in self.bodies.iter_mut() {
+      if let Some(dragger) = body.dragged_by.and_then(|uuid| players.get(&uuid)) {
+        body.position = dragger.position;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_impostors_drag_bodies_around() {
+    let (mut state, uuids) = game_with(&[Role::Impostor, Role::Crewmate]);
+    let (impostor, crew) = (uuids[0], uuids[1]);
+    let body = DeadBody::new(Color::Green, Position { x: 120.0, y: 100.0 });
+    state.bodies.push(body);
+
+    // Only if the room allows it.
+    assert_eq!(
+      state.note_body_dragged(impostor, Color::Green),
+      Err(GameError::TurnedOff {
+        action: Action::DragBody
+      })
+    );
+    state.settings.allow_body_drag = true;
+    assert_eq!(
+      state.note_body_dragged(crew, Color::Green),
+      Err(GameError::NotAllowedForRole {
+        action: Action::DragBody
+      })
+    );
+    assert_eq!(state.body_to_drag_near(impostor), Some(Color::Green));
+    state.note_body_dragged(impostor, Color::Green).unwrap();
+    assert!(state.dragging(impostor));
+    assert_eq!(state.body_to_drag_near(impostor), None);
+
+    // The body comes along, and slows them down.
+    state.players.get_mut(&impostor).unwrap().velocity = Velocity { dx: 2.0, dy: 0.0 };
+    state.simulate(Duration::from_millis(160));
+    let position = state.players[&impostor].position;
+    assert!((position.x - 100.0 - 2.0 * 10.0 * DRAG_SPEED).abs() < 0.001);
+    assert_eq!(state.bodies[0].position, position);
+
+    // Until they let go.
+    state.note_body_released(impostor).unwrap();
+    assert_eq!(
+      state.note_body_released(impostor),
+      Err(GameError::NotDragging)
+    );
+    state.simulate(Duration::from_millis(160));
+    assert_eq!(state.bodies[0].position, position);
+    assert_eq!(state.players[&impostor].speed_multiplier(), 1.0);
+  }
+}
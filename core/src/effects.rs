@@ -0,0 +1,193 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StatusEffectKind {
+  // Stumbling around while the ship falls apart.
+  Slowed,
+  // A little pep in your step, e.g. for finishing a task.
+  SpeedBoost,
+  // The seeker in Hide & Seek, picking up speed as the game goes on.
+  Hunting,
+  // Held still while a kill plays out, for the killer and the victim both.
+  KillAnimation,
+  // Hauling a body around. Lasts until they let go.
+  Dragging,
+}
+
+// How much of their usual speed someone dragging a body keeps.
+pub const DRAG_SPEED: f64 = 0.6;
+
+// How long a kill takes to play out.
+pub const KILL_ANIMATION_TIME: Duration = Duration::from_millis(1500);
+
+impl StatusEffectKind {
+  pub fn speed_multiplier(self) -> f64 {
+    match self {
+      StatusEffectKind::Slowed => 0.6,
+      StatusEffectKind::SpeedBoost => 1.5,
+      // Depends on how long they've been at it, see StatusEffect.
+      StatusEffectKind::Hunting => 1.0,
+      StatusEffectKind::KillAnimation => 0.0,
+      StatusEffectKind::Dragging => DRAG_SPEED,
+    }
+  }
+
+  // Whether it goes away on its own once its time is up.
+  pub fn wears_off(self) -> bool {
+    !matches!(self, StatusEffectKind::Hunting | StatusEffectKind::Dragging)
+  }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StatusEffect {
+  pub kind: StatusEffectKind,
+  pub time_remaining: Duration,
+}
+
+impl Player {
+  // Adds the given effect, or refreshes it if the player already has it.
+  pub fn add_status_effect(&mut self, kind: StatusEffectKind, duration: Duration) {
+    match self.status_effects.iter_mut().find(|e| e.kind == kind) {
+      Some(effect) => effect.time_remaining = effect.time_remaining.max(duration),
+      None => self.status_effects.push(StatusEffect {
+        kind,
+        time_remaining: duration,
+      }),
+    }
+  }
+
+  // How much faster (or slower) than normal this player moves right now.
+  pub fn speed_multiplier(&self) -> f64 {
+    self
+      .status_effects
+      .iter()
+      .map(|e| e.speed_multiplier())
+      .product()
+  }
+
+  pub fn in_kill_animation(&self) -> bool {
+    self
+      .status_effects
+      .iter()
+      .any(|e| e.kind == StatusEffectKind::KillAnimation)
+  }
+
+  pub(crate) fn progress_status_effects(&mut self, elapsed: Duration) {
+    for effect in self.status_effects.iter_mut() {
+      effect.time_remaining = effect
+        .time_remaining
+        .checked_sub(elapsed)
+        .unwrap_or_else(|| Duration::from_secs(0));
+    }
+    self
+      .status_effects
+      .retain(|e| e.time_remaining > Duration::from_secs(0) || !e.kind.wears_off());
+  }
+
+  pub(crate) fn remove_status_effect(&mut self, kind: StatusEffectKind) {
+    self.status_effects.retain(|e| e.kind != kind);
+  }
+}
+
+impl GameState {
+  // Whatever they were trying to do, neither of them goes anywhere until the
+  // kill's played out, so their positions don't drift out from under it.
+  pub(crate) fn freeze_for_kill(&mut self, killer: UUID, victim: UUID) {
+    for uuid in [killer, victim] {
+      if let Some(player) = self.players.get_mut(&uuid) {
+        player.add_status_effect(StatusEffectKind::KillAnimation, KILL_ANIMATION_TIME);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_speed_boost_wears_off() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate]);
+    let uuid = uuids[0];
+    let player = state.players.get_mut(&uuid).unwrap();
+    player.velocity = Velocity { dx: 2.0, dy: 0.0 };
+    player.add_status_effect(StatusEffectKind::SpeedBoost, Duration::from_millis(32));
+
+    state.simulate(Duration::from_millis(16));
+    assert_eq!(state.players[&uuid].position.x, 103.0);
+    state.simulate(Duration::from_millis(16));
+    assert_eq!(state.players[&uuid].position.x, 106.0);
+    assert!(state.players[&uuid].status_effects.is_empty());
+    state.simulate(Duration::from_millis(16));
+    assert_eq!(state.players[&uuid].position.x, 108.0);
+  }
+
+  #[test]
+  fn test_kills_hold_everyone_involved_still() {
+    // Enough of the crew have to be left, or the impostor's already won.
+    let roles = [
+      Role::Impostor,
+      Role::Crewmate,
+      Role::Crewmate,
+      Role::Crewmate,
+    ];
+    let (mut state, uuids) = game_with(&roles);
+    let (killer, victim) = (uuids[0], uuids[1]);
+    for player in state.players.values_mut() {
+      player.velocity = Velocity { dx: 2.0, dy: 0.0 };
+    }
+    state.note_kill(killer, victim, None).unwrap();
+    assert!(state.players[&killer].in_kill_animation());
+    assert!(state.players[&victim].in_kill_animation());
+
+    state.simulate(KILL_ANIMATION_TIME + TICK);
+    assert_eq!(state.players[&killer].position.x, 100.0);
+    assert_eq!(state.players[&victim].position.x, 100.0);
+    // Then they're free to go on their way.
+    assert!(!state.players[&killer].in_kill_animation());
+    state.simulate(TICK);
+    assert_eq!(state.players[&killer].position.x, 102.0);
+  }
+
+  #[test]
+  fn test_nothing_else_happens_during_a_kill() {
+    let roles = [
+      Role::Impostor,
+      Role::Crewmate,
+      Role::Crewmate,
+      Role::Crewmate,
+    ];
+    let (mut state, uuids) = game_with(&roles);
+    let (killer, victim) = (uuids[0], uuids[1]);
+    state.note_kill(killer, victim, None).unwrap();
+
+    // Not even reporting the body they're standing on.
+    assert_eq!(
+      state.note_body_reported(killer, Color::Pink, None),
+      Err(GameError::InKillAnimation {
+        action: Action::ReportBody
+      })
+    );
+    assert_eq!(
+      state.can_sabotage(killer, Sabotage::Lights),
+      Err(GameError::InKillAnimation {
+        action: Action::Sabotage
+      })
+    );
+    state.players.get_mut(&killer).unwrap().in_vent = Some(0);
+    assert_eq!(
+      state.note_exited_vent(killer),
+      Err(GameError::InKillAnimation {
+        action: Action::Vent
+      })
+    );
+    state.players.get_mut(&killer).unwrap().in_vent = None;
+
+    state.simulate(KILL_ANIMATION_TIME + TICK);
+    state.note_body_reported(killer, Color::Pink, None).unwrap();
+  }
+}
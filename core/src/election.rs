@@ -0,0 +1,180 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// The counted votes from a meeting.
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ElectionTally {
+  votes_against: BTreeMap<VoteTarget, Vec<UUID>>,
+  // Players who could have voted but didn't.
+  abstentions: Vec<UUID>,
+}
+
+impl ElectionTally {
+  // Counts the votes of everyone still alive and in the game. Votes from
+  // players who have died or left since don't count.
+  pub fn new(day_state: &VotingState, players: &BTreeMap<UUID, Player>) -> Self {
+    let mut votes_against: BTreeMap<VoteTarget, Vec<UUID>> = BTreeMap::new();
+    let mut abstentions = Vec::new();
+    for (uuid, player) in players.iter() {
+      if !player.eligable_to_vote() {
+        continue;
+      }
+      match day_state.votes.get(uuid) {
+        Some(target) => votes_against.entry(*target).or_default().push(*uuid),
+        None => abstentions.push(*uuid),
+      }
+    }
+    Self {
+      votes_against,
+      abstentions,
+    }
+  }
+
+  pub fn from_votes_against(votes_against: BTreeMap<VoteTarget, Vec<UUID>>) -> Self {
+    Self {
+      votes_against,
+      abstentions: vec![],
+    }
+  }
+
+  pub fn votes_against(&self) -> &BTreeMap<VoteTarget, Vec<UUID>> {
+    &self.votes_against
+  }
+
+  pub fn counts(&self) -> BTreeMap<VoteTarget, usize> {
+    self
+      .votes_against
+      .iter()
+      .map(|(target, voters)| (*target, voters.len()))
+      .collect()
+  }
+
+  pub fn abstentions(&self) -> &[UUID] {
+    &self.abstentions
+  }
+
+  // The targets that share the most votes, if more than one of them does.
+  pub fn tied(&self) -> Vec<VoteTarget> {
+    let counts = self.counts();
+    let most_votes = match counts.values().max() {
+      None => return vec![],
+      Some(most_votes) => *most_votes,
+    };
+    let tied: Vec<VoteTarget> = counts
+      .into_iter()
+      .filter(|(_, count)| *count == most_votes)
+      .map(|(target, _)| target)
+      .collect();
+    if tied.len() < 2 {
+      return vec![];
+    }
+    tied
+  }
+
+  // The winner is the one with the most votes! If no one voted, or the top
+  // targets are tied, then it's a tie.
+  pub fn winner(&self) -> VoteOutcome {
+    if !self.tied().is_empty() {
+      return VoteOutcome::Tie;
+    }
+    let winner = self
+      .counts()
+      .into_iter()
+      .max_by_key(|(_, count)| *count)
+      .map(|(target, _)| target);
+    match winner {
+      None => VoteOutcome::Tie,
+      Some(target) => target.into(),
+    }
+  }
+}
+
+impl From<VoteTarget> for VoteOutcome {
+  fn from(target: VoteTarget) -> Self {
+    match target {
+      VoteTarget::Skip => VoteOutcome::Skip,
+      VoteTarget::Player { uuid } => VoteOutcome::Player { uuid },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  fn tally(votes: Vec<(VoteTarget, Vec<UUID>)>) -> ElectionTally {
+    ElectionTally::from_votes_against(votes.into_iter().collect())
+  }
+
+  #[test]
+  fn test_no_votes() {
+    let tally = tally(vec![]);
+    assert_eq!(tally.winner(), VoteOutcome::Tie);
+    assert!(tally.counts().is_empty());
+  }
+
+  #[test]
+  fn test_one_vote_skip() {
+    let uuid = UUID::random();
+    let tally = tally(vec![(VoteTarget::Skip, vec![uuid])]);
+    assert_eq!(tally.winner(), VoteOutcome::Skip);
+  }
+
+  #[test]
+  fn test_one_vote_player() {
+    let uuid = UUID::random();
+    let tally = tally(vec![(VoteTarget::Player { uuid }, vec![uuid])]);
+    assert_eq!(tally.winner(), VoteOutcome::Player { uuid });
+  }
+
+  #[test]
+  fn test_majority_wins() {
+    let guilty = UUID::random();
+    let p1 = UUID::random();
+    let p2 = UUID::random();
+    let p3 = UUID::random();
+    let tally = tally(vec![
+      (VoteTarget::Player { uuid: guilty }, vec![p1, p2, p3]),
+      (VoteTarget::Player { uuid: p1 }, vec![guilty]),
+    ]);
+    assert_eq!(tally.winner(), VoteOutcome::Player { uuid: guilty });
+    assert_eq!(tally.counts()[&VoteTarget::Player { uuid: guilty }], 3);
+  }
+
+  #[test]
+  fn test_tie() {
+    let p1 = UUID::random();
+    let p2 = UUID::random();
+    let p3 = UUID::random();
+    let p4 = UUID::random();
+    let p5 = UUID::random();
+    let tally = tally(vec![
+      (VoteTarget::Player { uuid: p1 }, vec![p1, p2]),
+      (VoteTarget::Player { uuid: p2 }, vec![p3, p4]),
+      (VoteTarget::Skip, vec![p5]),
+    ]);
+    assert_eq!(tally.winner(), VoteOutcome::Tie);
+    assert_eq!(tally.tied().len(), 2);
+  }
+
+  #[test]
+  fn test_dead_voters_and_abstentions() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate; 3]);
+    state.start_meeting(MeetingReason::EmergencyButton { caller: uuids[0] });
+    state.simulate(state.settings.discussion_time + TICK);
+    state.note_vote(uuids[0], VoteTarget::Skip).unwrap();
+    state.note_vote(uuids[1], VoteTarget::Skip).unwrap();
+    // Killed before the votes were counted.
+    state.players.get_mut(&uuids[1]).unwrap().dead = true;
+
+    let day_state = match &state.status {
+      GameStatus::Playing(PlayState::Voting(day_state)) => day_state,
+      status => panic!("Expected a meeting, got {:?}", status),
+    };
+    let tally = ElectionTally::new(day_state, &state.players);
+    assert_eq!(tally.counts()[&VoteTarget::Skip], 1);
+    assert_eq!(tally.abstentions(), &[uuids[2]]);
+  }
+}
@@ -0,0 +1,196 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+// Things players try to do, for saying which one didn't work out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+  Vote,
+  LockInVote,
+  GetReady,
+  TransferHost,
+  StartGame,
+  ResumeGame,
+  Kill,
+  ReportBody,
+  CallMeeting,
+  Sabotage,
+  FixSabotage,
+  Vent,
+  Shield,
+  Shoot,
+  FinishTask,
+  Rejoin,
+  Spectate,
+  Chat,
+  Voice,
+  AddBots,
+  Kick,
+  VoteKick,
+  ChangeColor,
+  ReturnToLobby,
+  ChangeSettings,
+  DragBody,
+  DropDecoy,
+  Shapeshift,
+}
+
+impl Display for Action {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    let action = match self {
+      Action::Vote => "vote",
+      Action::LockInVote => "lock in your vote",
+      Action::GetReady => "get ready",
+      Action::TransferHost => "hand off the lobby",
+      Action::StartGame => "start the game",
+      Action::ResumeGame => "resume the game",
+      Action::Kill => "kill",
+      Action::ReportBody => "report a body",
+      Action::CallMeeting => "call an emergency meeting",
+      Action::Sabotage => "sabotage",
+      Action::FixSabotage => "fix the sabotage",
+      Action::Vent => "use vents",
+      Action::Shield => "give a shield",
+      Action::Shoot => "shoot",
+      Action::FinishTask => "finish that task",
+      Action::Rejoin => "rejoin",
+      Action::Spectate => "spectate",
+      Action::Chat => "chat",
+      Action::Voice => "talk to",
+      Action::AddBots => "add bots",
+      Action::Kick => "kick players out",
+      Action::VoteKick => "vote to kick them",
+      Action::ChangeColor => "change color",
+      Action::ReturnToLobby => "go back to the lobby",
+      Action::ChangeSettings => "change the settings",
+      Action::DragBody => "drag bodies",
+      Action::DropDecoy => "leave a decoy",
+      Action::Shapeshift => "shapeshift",
+    };
+    write!(fmt, "{}", action)
+  }
+}
+
+// Why the game wouldn't let someone do what they asked. These get sent back
+// to whoever asked, so that the client can explain it in its own words.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameError {
+  // It can't be done at this point in the game.
+  WrongStatus { action: Action },
+  UnknownPlayer { uuid: UUID },
+  // Too far away from whatever they were reaching for.
+  OutOfRange { action: Action },
+  NotAllowedForRole { action: Action },
+  Dead { action: Action },
+  InVent { action: Action },
+  // Held still while a kill plays out.
+  InKillAnimation { action: Action },
+  OnCooldown { action: Action, remaining: Duration },
+  NotHost { action: Action },
+  InvalidTarget { action: Action },
+  BlockedBySabotage { action: Action, sabotage: Sabotage },
+  StillDiscussing,
+  AlreadyVoted,
+  VoteLockedIn,
+  NoVoteToLockIn,
+  NoSuchBody { color: Color },
+  NoMeetingsLeft,
+  NoSuchTask { index: usize },
+  NoSuchDoor { index: usize },
+  FakeTask,
+  TaskAlreadyFinished,
+  WrongSolution,
+  NotInVent,
+  NotEnoughPlayers { needed: usize, have: usize },
+  NotEveryoneReady,
+  NeverLeft,
+  AlreadyPlaying,
+  EmptyMessage,
+  MessageTooLong { max: usize },
+  QuickChatOnly,
+  NoSuchLandmark,
+  ColorTaken { color: Color },
+  BadSetting { setting: Setting },
+  NotInGameMode { action: Action, mode: GameMode },
+  InSuddenDeath { action: Action },
+  // The room's settings don't allow it.
+  TurnedOff { action: Action },
+  NotDragging,
+}
+
+impl Display for GameError {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    match self {
+      GameError::WrongStatus { action } => write!(fmt, "You can't {} right now", action),
+      GameError::UnknownPlayer { uuid } => write!(fmt, "{} isn't in the game", uuid),
+      GameError::OutOfRange { action } => write!(fmt, "You're too far away to {}", action),
+      GameError::NotAllowedForRole { action } => write!(fmt, "Your role can't {}", action),
+      GameError::Dead { action } => write!(fmt, "The dead can't {}", action),
+      GameError::InVent { action } => write!(fmt, "You can't {} from inside a vent", action),
+      GameError::InKillAnimation { action } => {
+        write!(fmt, "You can't {} in the middle of a kill", action)
+      }
+      GameError::OnCooldown { action, remaining } => write!(
+        fmt,
+        "You can't {} for another {}s",
+        action,
+        remaining.as_secs_f64().ceil()
+      ),
+      GameError::NotHost { action } => write!(fmt, "Only the host can {}", action),
+      GameError::InvalidTarget { action } => write!(fmt, "You can't {} them", action),
+      GameError::BlockedBySabotage { action, sabotage } => write!(
+        fmt,
+        "You can't {} while the {:?} is sabotaged",
+        action, sabotage
+      ),
+      GameError::StillDiscussing => write!(fmt, "Still discussing, no voting yet"),
+      GameError::AlreadyVoted => write!(fmt, "You've already voted"),
+      GameError::VoteLockedIn => write!(fmt, "You've already locked in your vote"),
+      GameError::NoVoteToLockIn => write!(fmt, "You can't lock in a vote you haven't cast"),
+      GameError::NoSuchBody { color } => write!(fmt, "There's no {:?} body to report", color),
+      GameError::NoMeetingsLeft => write!(fmt, "You've used up all your emergency meetings"),
+      GameError::NoSuchTask { index } => write!(fmt, "There's no task #{}", index),
+      GameError::NoSuchDoor { index } => write!(fmt, "There's no door #{}", index),
+      GameError::FakeTask => write!(fmt, "That task is fake"),
+      GameError::TaskAlreadyFinished => write!(fmt, "That task is already finished"),
+      GameError::WrongSolution => write!(fmt, "That's not how that task's done"),
+      GameError::NotInVent => write!(fmt, "You aren't in a vent"),
+      GameError::NotEnoughPlayers { needed, have } => write!(
+        fmt,
+        "Need at least {} players to start, but only have {}",
+        needed, have
+      ),
+      GameError::NotEveryoneReady => write!(fmt, "Waiting for everyone to get ready"),
+      GameError::NeverLeft => write!(fmt, "You never left"),
+      GameError::AlreadyPlaying => write!(fmt, "You're already playing"),
+      GameError::EmptyMessage => write!(fmt, "There's nothing to send"),
+      GameError::MessageTooLong { max } => {
+        write!(fmt, "Messages can't be longer than {} characters", max)
+      }
+      GameError::QuickChatOnly => write!(fmt, "Only quick chat is allowed in this room"),
+      GameError::NoSuchLandmark => write!(fmt, "There's no such place on this map"),
+      GameError::ColorTaken { color } => write!(fmt, "Someone's already wearing {:?}", color),
+      GameError::NotInGameMode { action, mode } => {
+        write!(fmt, "You can't {} in {}", action, mode.name())
+      }
+      GameError::InSuddenDeath { action } => write!(fmt, "You can't {} in sudden death", action),
+      GameError::TurnedOff { action } => write!(fmt, "This room doesn't let anyone {}", action),
+      GameError::NotDragging => write!(fmt, "You aren't dragging a body"),
+      GameError::BadSetting {
+        setting: Setting::Map,
+      } => write!(fmt, "That map can't be picked from the lobby"),
+      GameError::BadSetting { setting } => {
+        let (min, max) = setting.bounds();
+        let unit = if setting.is_time() { " seconds" } else { "" };
+        write!(
+          fmt,
+          "The {} has to be between {} and {}{}",
+          setting, min, max, unit
+        )
+      }
+    }
+  }
+}
+
+impl std::error::Error for GameError {}
@@ -0,0 +1,95 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+// Something that happened in the game, for playing sounds and animations and
+// keeping stats, without having to work it out from the difference between
+// two snapshots.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GameEvent {
+  GameStarted,
+  // Either an impostor's kill, or a sheriff's shot. A sheriff who shoots a
+  // crewmate is their own victim.
+  PlayerKilled { killer: UUID, victim: UUID },
+  // The victim had a shield from the medic.
+  KillBlocked { killer: UUID, victim: UUID },
+  TaskCompleted { uuid: UUID, index: usize },
+  MeetingCalled { reason: MeetingReason },
+  // Who the vote was for stays secret until the votes are counted.
+  VoteCast { voter: UUID },
+  PlayerEjected { uuid: UUID },
+  SabotageStarted { kind: Sabotage },
+  SabotageFixed { kind: Sabotage },
+  DoorClosed { index: usize },
+  DoorOpened { index: usize },
+  // Down to the last crewmate and the last impostor.
+  SuddenDeathStarted,
+  // A shapeshifter's disguise wore off, for a shimmer where they're standing.
+  DisguiseFaded { uuid: UUID },
+  GameWon { team: Team },
+  // Time ran out with no winner.
+  GameDrawn,
+}
+
+impl GameEvent {
+  // The players who get told about this, if not everyone. Kills are only
+  // known to the two people involved, until someone finds the body. A
+  // disguise fading is only seen by whoever's looking.
+  pub fn private_to(&self, state: &GameState) -> Option<Vec<UUID>> {
+    match self {
+      GameEvent::PlayerKilled { killer, victim } | GameEvent::KillBlocked { killer, victim } => {
+        Some(vec![*killer, *victim])
+      }
+      GameEvent::TaskCompleted { uuid, .. } => Some(vec![*uuid]),
+      GameEvent::DisguiseFaded { uuid } => Some(state.witnesses(*uuid)),
+      _ => None,
+    }
+  }
+}
+
+impl GameState {
+  // Only the server's game has anything to say, players hear about what
+  // happened from it.
+  pub(crate) fn emit(&mut self, event: GameEvent) {
+    if !self.partial_view {
+      self.events.push(event);
+    }
+  }
+
+  // Everything that's happened since the last time anyone asked.
+  pub fn drain_events(&mut self) -> Vec<GameEvent> {
+    std::mem::take(&mut self.events)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_kills_are_only_told_to_those_involved() {
+    let roles = [
+      Role::Impostor,
+      Role::Crewmate,
+      Role::Crewmate,
+      Role::Crewmate,
+    ];
+    let (mut state, uuids) = game_with(&roles);
+    state.note_kill(uuids[0], uuids[1], None).unwrap();
+    let events = state.drain_events();
+    assert_eq!(
+      events,
+      vec![GameEvent::PlayerKilled {
+        killer: uuids[0],
+        victim: uuids[1]
+      }]
+    );
+    assert_eq!(events[0].private_to(&state), Some(vec![uuids[0], uuids[1]]));
+    assert!(state.drain_events().is_empty());
+
+    // Players don't make anything up on their own.
+    let mut view = state.view_for(uuids[2]);
+    view.emit(GameEvent::GameStarted);
+    assert!(view.drain_events().is_empty());
+  }
+}
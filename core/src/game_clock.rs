@@ -0,0 +1,74 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+// Who gets the game when the clock runs out on it.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum TimeLimitOutcome {
+  // The impostors didn't get them in time.
+  CrewWins,
+  // The crew didn't get their work done in time.
+  ImpostorsWin,
+  #[default]
+  Draw,
+}
+
+impl GameState {
+  // The game clock keeps going through meetings, so that no one can stall
+  // their way out of a loss by talking for ever.
+  pub(crate) fn progress_game_clock(&mut self, elapsed: Duration) {
+    if !matches!(self.status, GameStatus::Playing(_)) {
+      return;
+    }
+    let remaining = match self.game_time_remaining {
+      None => return,
+      Some(remaining) => remaining.saturating_sub(elapsed),
+    };
+    self.game_time_remaining = Some(remaining);
+    // Players wait for the server to say how it ended.
+    if remaining > Duration::from_secs(0) || self.partial_view {
+      return;
+    }
+    match self.settings.time_limit_outcome {
+      TimeLimitOutcome::CrewWins => self.win(Team::Crew),
+      TimeLimitOutcome::ImpostorsWin => self.win(Team::Impostors),
+      TimeLimitOutcome::Draw => {
+        self.status = GameStatus::Draw;
+        self.emit(GameEvent::GameDrawn);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_the_clock_runs_out() {
+    let (mut state, _) = game_with(&[]);
+    state.settings.game_time_limit = Some(Duration::from_secs(1));
+    state.game_time_remaining = state.settings.game_time_limit;
+    state.simulate(Duration::from_millis(500));
+    assert_eq!(state.status, GameStatus::Playing(PlayState::Night));
+    assert!(state.game_time_remaining < Some(Duration::from_secs(1)));
+    state.simulate(Duration::from_millis(600));
+    assert_eq!(state.status, GameStatus::Draw);
+    assert_eq!(state.drain_events(), vec![GameEvent::GameDrawn]);
+
+    // Or one side takes it, if that's how the room's set up.
+    state.settings.time_limit_outcome = TimeLimitOutcome::ImpostorsWin;
+    state.status = GameStatus::Playing(PlayState::Night);
+    state.game_time_remaining = Some(TICK);
+    state.simulate(TICK);
+    assert_eq!(state.status, GameStatus::Won(Team::Impostors));
+
+    // Without a limit, it goes on for as long as it takes.
+    state.status = GameStatus::Playing(PlayState::Night);
+    state.game_time_remaining = None;
+    state.simulate(Duration::from_secs(3600));
+    assert_eq!(state.status, GameStatus::Playing(PlayState::Night));
+  }
+}
@@ -0,0 +1,107 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+// The rules of the game itself, as opposed to how they're tuned.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum GameMode {
+  #[default]
+  Classic,
+  // Everyone knows who the impostor is, and there's no talking or voting.
+  // The crew does their tasks in the dark while the impostor hunts them down,
+  // getting faster as the game goes on. The crew wins by finishing their
+  // tasks, the impostor by getting everyone first.
+  HideAndSeek,
+}
+
+impl GameMode {
+  pub fn name(self) -> &'static str {
+    match self {
+      GameMode::Classic => "Classic",
+      GameMode::HideAndSeek => "Hide & Seek",
+    }
+  }
+
+  pub fn has_meetings(self) -> bool {
+    self == GameMode::Classic
+  }
+
+  pub fn reveals_impostors(self) -> bool {
+    self == GameMode::HideAndSeek
+  }
+
+  // How much of their usual vision the crew gets.
+  pub fn crew_vision(self) -> f64 {
+    match self {
+      GameMode::Classic => 1.0,
+      GameMode::HideAndSeek => 0.6,
+    }
+  }
+}
+
+// How long it takes the seeker to get up to full speed, and how fast that is.
+pub const HUNT_RAMP_TIME: Duration = Duration::from_secs(180);
+pub const HUNT_TOP_SPEED: f64 = 1.5;
+
+impl StatusEffect {
+  pub fn speed_multiplier(&self) -> f64 {
+    match self.kind {
+      // Starts at normal speed, and works up to top speed as the time runs
+      // out. It stays there for the rest of the game.
+      StatusEffectKind::Hunting => {
+        let left = self.time_remaining.as_secs_f64() / HUNT_RAMP_TIME.as_secs_f64();
+        HUNT_TOP_SPEED - (HUNT_TOP_SPEED - 1.0) * left.min(1.0)
+      }
+      kind => kind.speed_multiplier(),
+    }
+  }
+}
+
+impl GameState {
+  // Sets up the mode's own rules once everyone's been dealt their roles.
+  pub(crate) fn start_game_mode(&mut self) {
+    if self.settings.game_mode != GameMode::HideAndSeek {
+      return;
+    }
+    for player in self.players.values_mut() {
+      if player.impostor {
+        player.add_status_effect(StatusEffectKind::Hunting, HUNT_RAMP_TIME);
+      }
+    }
+  }
+
+  pub(crate) fn check_meetings_allowed(&self, action: Action) -> Result<(), GameError> {
+    let mode = self.settings.game_mode;
+    if !mode.has_meetings() {
+      return Err(GameError::NotInGameMode { action, mode });
+    }
+    if self.sudden_death_remaining.is_some() {
+      return Err(GameError::InSuddenDeath { action });
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+
+  #[test]
+  fn test_the_seeker_speeds_up() {
+    let mut seeker = Player::new(
+      UUID::random(),
+      "Seeker".to_string(),
+      Color::Red,
+      Position { x: 0.0, y: 0.0 },
+    );
+    seeker.add_status_effect(StatusEffectKind::Hunting, HUNT_RAMP_TIME);
+    assert_eq!(seeker.speed_multiplier(), 1.0);
+    seeker.progress_status_effects(HUNT_RAMP_TIME / 2);
+    assert!((seeker.speed_multiplier() - 1.25).abs() < 0.0001);
+    // Once they're at top speed, they stay there.
+    seeker.progress_status_effects(HUNT_RAMP_TIME);
+    assert_eq!(seeker.speed_multiplier(), HUNT_TOP_SPEED);
+    seeker.progress_status_effects(HUNT_RAMP_TIME);
+    assert_eq!(seeker.speed_multiplier(), HUNT_TOP_SPEED);
+  }
+}
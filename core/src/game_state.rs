@@ -4,21 +4,123 @@ use rand::Rng;
 use serde::de::{self, Visitor};
 use serde::Deserializer;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeSet;
+use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::{collections::BTreeMap, iter::FromIterator};
-use std::{f64::consts::PI, fmt};
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct Settings {
   pub speed: f64,
   pub kill_distance: f64,
   pub task_distance: f64,
+  // How long a task takes to do, standing still.
+  pub task_work_time: Duration,
   pub report_distance: f64,
-  pub crew_vision: f64,
-  pub impostor_vision: f64,
+  pub vision_radius: VisionRadius,
+  // How long everyone gets to talk things over before voting opens.
+  pub discussion_time: Duration,
   pub voting_time: Duration,
-  pub num_tasks: usize,
+  // Whether to reveal if the player thrown out the airlock was an impostor.
+  pub confirm_ejects: bool,
+  // Lets players change their vote until they lock it in (or time runs out).
+  pub allow_vote_changes: bool,
+  // Keeps everyone's votes secret until they're counted.
+  pub anonymous_votes: bool,
+  pub tie_policy: TiePolicy,
+  pub runoff_voting_time: Duration,
+  pub short_tasks: usize,
+  pub long_tasks: usize,
+  // Tasks that every crewmate gets, in the same spots.
+  pub common_tasks: usize,
+  pub task_mix: TaskMix,
+  pub task_bar_mode: TaskBarMode,
+  pub impostor_count: usize,
+  pub min_players: usize,
+  pub max_players: usize,
+  // How long a player who drops out mid-game has to come back.
+  pub reconnect_grace: Duration,
+  // Pause the game if fewer than this many players are still connected.
+  pub pause_below_players: usize,
+  // How long to wait before picking the game back up after a pause.
+  pub resume_countdown: Duration,
+  pub kill_cooldown: Duration,
+  pub emergency_meetings_per_player: usize,
+  pub emergency_cooldown: Duration,
+  pub lights_sabotage_cooldown: Duration,
+  pub reactor_sabotage_cooldown: Duration,
+  pub doors_sabotage_cooldown: Duration,
+  pub comms_sabotage_cooldown: Duration,
+  pub reactor_meltdown_time: Duration,
+  pub doors_locked_time: Duration,
+  // The chance of each special crew role showing up in a game.
+  pub medic_chance: f64,
+  pub sheriff_chance: f64,
+  pub engineer_chance: f64,
+  // If set, crewmates get a speed boost this long after finishing a task.
+  pub task_speed_boost: Option<Duration>,
+  // If set, the crew is slowed for this long whenever something is sabotaged.
+  pub sabotage_slowdown: Option<Duration>,
+  // If set, bodies disappear after lying around for this long.
+  pub body_decay_time: Option<Duration>,
+  // How far the crew can see while the lights are out.
+  pub lights_out_vision: f64,
+  pub map_id: MapId,
+  // Only allow canned chat messages, so there's nothing to moderate.
+  pub quick_chat_only: bool,
+  // How many of the other players it takes to vote someone out of the lobby.
+  pub vote_kick_fraction: f64,
+  pub game_mode: GameMode,
+  // If set, the game's called once it's gone on this long.
+  pub game_time_limit: Option<Duration>,
+  pub time_limit_outcome: TimeLimitOutcome,
+  // If set, when it's down to one crewmate against one impostor, the
+  // crewmate gets this long to finish their tasks before the impostor wins.
+  pub sudden_death_time: Option<Duration>,
+  // If set, players who sit in the lobby without doing anything for this
+  // long are shown the door.
+  pub afk_kick_time: Option<Duration>,
+  // Lets impostors drag bodies somewhere they're less likely to be found.
+  pub allow_body_drag: bool,
+  // If set, impostors can leave a decoy of themselves behind, which the crew
+  // see for this long.
+  pub decoy_time: Option<Duration>,
+  // If set, impostors can take on someone else's looks for this long, and
+  // then have to wait out the cooldown before doing it again.
+  pub shapeshift_time: Option<Duration>,
+  pub shapeshift_cooldown: Duration,
+}
+
+impl Settings {
+  pub fn sabotage_cooldown(&self, kind: Sabotage) -> Duration {
+    match kind {
+      Sabotage::Lights => self.lights_sabotage_cooldown,
+      Sabotage::Reactor => self.reactor_sabotage_cooldown,
+      Sabotage::Doors => self.doors_sabotage_cooldown,
+      Sabotage::Comms => self.comms_sabotage_cooldown,
+    }
+  }
+}
+
+// What to do when the vote is tied.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum TiePolicy {
+  // No one goes out the airlock.
+  Skip,
+  // Vote again, but only between the tied targets.
+  Runoff,
+  // Let fate decide between the tied targets.
+  RandomAmongTied,
+}
+
+// How far players can see at night, in map units.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct VisionRadius {
+  pub crew: f64,
+  pub impostors: f64,
 }
 
 impl Default for Settings {
@@ -27,11 +129,58 @@ impl Default for Settings {
       speed: 2.0,
       kill_distance: 64.0,
       task_distance: 32.0,
+      task_work_time: Duration::from_millis(1500),
       report_distance: 96.0,
-      crew_vision: 176.0,
-      impostor_vision: 256.0,
+      vision_radius: VisionRadius {
+        crew: 176.0,
+        impostors: 256.0,
+      },
+      discussion_time: Duration::from_secs(15),
       voting_time: Duration::from_secs(120),
-      num_tasks: 6,
+      confirm_ejects: true,
+      allow_vote_changes: false,
+      anonymous_votes: false,
+      tie_policy: TiePolicy::Skip,
+      runoff_voting_time: Duration::from_secs(30),
+      short_tasks: 3,
+      long_tasks: 2,
+      common_tasks: 1,
+      task_mix: TaskMix::default(),
+      task_bar_mode: TaskBarMode::Always,
+      impostor_count: 1,
+      min_players: 4,
+      max_players: 10,
+      reconnect_grace: Duration::from_secs(60),
+      pause_below_players: 3,
+      resume_countdown: Duration::from_secs(3),
+      kill_cooldown: Duration::from_secs(20),
+      emergency_meetings_per_player: 1,
+      emergency_cooldown: Duration::from_secs(15),
+      lights_sabotage_cooldown: Duration::from_secs(30),
+      reactor_sabotage_cooldown: Duration::from_secs(45),
+      doors_sabotage_cooldown: Duration::from_secs(20),
+      comms_sabotage_cooldown: Duration::from_secs(30),
+      reactor_meltdown_time: Duration::from_secs(30),
+      doors_locked_time: Duration::from_secs(10),
+      lights_out_vision: 64.0,
+      map_id: MapId::default(),
+      quick_chat_only: false,
+      vote_kick_fraction: 2.0 / 3.0,
+      game_mode: GameMode::Classic,
+      game_time_limit: None,
+      time_limit_outcome: TimeLimitOutcome::Draw,
+      sudden_death_time: None,
+      afk_kick_time: None,
+      allow_body_drag: false,
+      decoy_time: None,
+      shapeshift_time: None,
+      shapeshift_cooldown: Duration::from_secs(30),
+      body_decay_time: None,
+      task_speed_boost: None,
+      sabotage_slowdown: None,
+      medic_chance: 0.0,
+      sheriff_chance: 0.0,
+      engineer_chance: 0.0,
     }
   }
 }
@@ -43,15 +192,45 @@ impl Default for GameState {
 }
 
 // The full game state
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct GameState {
   pub status: GameStatus,
   pub settings: Settings,
   pub map: Map,
   pub players: BTreeMap<UUID, Player>,
   pub bodies: Vec<DeadBody>,
+  pub decoys: Vec<Decoy>,
+  // Time until the emergency button can be pressed again.
+  pub emergency_cooldown: Duration,
+  pub sabotage: SabotageState,
+  pub doors: DoorState,
+  // What came of the last election, for the reveal screen.
+  pub ejection: Option<EjectionResult>,
+  pub spectators: BTreeMap<UUID, Spectator>,
+  // The player in charge of the lobby.
+  pub host: Option<UUID>,
+  // How long until the game's called, if there's a time limit.
+  pub game_time_remaining: Option<Duration>,
+  // How long the last crewmate has left, once it's come down to sudden death.
+  pub sudden_death_remaining: Option<Duration>,
+  pub activity: ActivityLog,
+  // Players only get told part of what's going on, so they leave counting
+  // votes and deciding who's won to the server.
+  pub partial_view: bool,
+  pub position_history: PositionHistory,
+  // Time that's passed but not yet been simulated, because it doesn't add up
+  // to a whole tick.
+  pub unsimulated_time: Duration,
+  pub rng: GameRng,
+  // What's happened that no one's asked about yet.
+  pub events: Vec<GameEvent>,
 }
 
+// How much time a single step of the simulation covers. Everything moves a
+// whole tick at a time, so that the server and every client get exactly the
+// same results no matter their frame rate.
+pub const TICK: Duration = Duration::from_millis(16);
+
 impl GameState {
   pub fn new() -> Self {
     GameState {
@@ -59,26 +238,95 @@ impl GameState {
       settings: Settings::default(),
       players: BTreeMap::new(),
       bodies: Vec::new(),
-      map: Map::first_map(),
+      decoys: Vec::new(),
+      map: Map::default(),
+      emergency_cooldown: Duration::from_secs(0),
+      sabotage: SabotageState::default(),
+      doors: DoorState::default(),
+      ejection: None,
+      spectators: BTreeMap::new(),
+      host: None,
+      game_time_remaining: None,
+      sudden_death_remaining: None,
+      activity: ActivityLog::default(),
+      partial_view: false,
+      position_history: PositionHistory::default(),
+      unsimulated_time: Duration::from_secs(0),
+      rng: GameRng::default(),
+      events: Vec::new(),
     }
   }
 
+  // Runs as many ticks as fit in the time that's passed, saving the rest for
+  // next time.
   pub fn simulate(&mut self, elapsed: Duration) -> bool {
+    for _ in 0..self.ticks_due(elapsed) {
+      self.simulate_tick();
+    }
+    self.status.finished()
+  }
+
+  // How many whole ticks are ready to simulate, now that more time's passed.
+  pub fn ticks_due(&mut self, elapsed: Duration) -> u32 {
+    self.unsimulated_time += elapsed;
+    let ticks = (self.unsimulated_time.as_nanos() / TICK.as_nanos()) as u32;
+    self.unsimulated_time -= TICK * ticks;
+    ticks
+  }
+
+  pub fn simulate_tick(&mut self) -> bool {
+    let elapsed = TICK;
+    self.position_history.tick += 1;
     self.status.progress_time(elapsed);
+    self.progress_reconnect_timers(elapsed);
+    self.progress_game_clock(elapsed);
+    self.progress_sudden_death(elapsed);
     match &self.status {
-      GameStatus::Lobby | GameStatus::Playing(PlayState::Night) => self.simulate_night(elapsed),
+      GameStatus::Lobby => self.simulate_night(elapsed),
+      GameStatus::Playing(PlayState::Night) => {
+        self.simulate_night(elapsed);
+        self.simulate_interactions(elapsed);
+        self.simulate_sabotage(elapsed);
+        self.simulate_doors(elapsed);
+        self.progress_decoys(elapsed);
+        self.progress_disguises(elapsed);
+      }
       GameStatus::Playing(PlayState::Voting(day_state)) => {
-        if self.is_voting_over(day_state) {
-          self.status = GameStatus::Playing(PlayState::TallyingVotes(TallyingState::new(
-            day_state.get_votes_against(),
-          )))
+        if day_state.phase == DayPhase::Discussion {
+          if day_state.time_remaining == Duration::from_secs(0) {
+            let voting_time = self.settings.voting_time;
+            if let GameStatus::Playing(PlayState::Voting(day_state)) = &mut self.status {
+              day_state.phase = DayPhase::Voting;
+              day_state.time_remaining = voting_time;
+            }
+          }
+        } else if !self.partial_view && self.is_voting_over(day_state) {
+          let mut tally = TallyingState::new(ElectionTally::new(day_state, &self.players));
+          let tied = tally.tally.tied();
+          match self.settings.tie_policy {
+            TiePolicy::Skip => {}
+            TiePolicy::RandomAmongTied => tally = tally.with_tie_breaker(day_state.tie_breaker),
+            // If the runoff ties too then no one goes out the airlock.
+            TiePolicy::Runoff if day_state.candidates.is_none() && !tied.is_empty() => {
+              let runoff = day_state.runoff(tied, self.settings.runoff_voting_time);
+              self.status = GameStatus::Playing(PlayState::Voting(runoff));
+              return self.status.finished();
+            }
+            TiePolicy::Runoff => {}
+          }
+          self.status = GameStatus::Playing(PlayState::TallyingVotes(tally))
         }
       }
       GameStatus::Playing(PlayState::TallyingVotes(state)) => {
         if state.is_over() {
-          self.status = GameStatus::Playing(PlayState::ViewingOutcome(ViewOutcomeState::new(
-            state.determine_outcome_of_election(),
+          let outcome = state.determine_outcome_of_election();
+          self.ejection = Some(EjectionResult::new(
+            outcome,
+            state.tally.votes_against().clone(),
             self,
+          ));
+          self.status = GameStatus::Playing(PlayState::ViewingOutcome(ViewOutcomeState::new(
+            outcome, self,
           )))
         }
       }
@@ -92,19 +340,28 @@ impl GameState {
               if let Some(player) = self.players.get_mut(&uuid) {
                 player.dead = true;
               }
+              self.emit(GameEvent::PlayerEjected { uuid });
             }
           }
-          self.bodies.clear();
           self.map.place_players_at_night_start(&mut self.players);
+          self.reset_ability_cooldowns();
+          self.reset_round_abilities();
+          self.emergency_cooldown = self.settings.emergency_cooldown;
           self.check_for_victories();
-          // Now it's night!
-          self.status = GameStatus::Playing(PlayState::Night);
+          // Now it's night! Unless that was the end of it.
+          if let GameStatus::Playing(_) = self.status {
+            self.status = GameStatus::Playing(PlayState::Night);
+          }
         }
       }
-      GameStatus::Connecting | GameStatus::Disconnected | GameStatus::Won(_) => {
+      GameStatus::Paused { .. } => self.simulate_paused(),
+      GameStatus::Connecting | GameStatus::Disconnected | GameStatus::Won(_) | GameStatus::Draw => {
         // Nothing to simulate
       }
     }
+    if !self.partial_view {
+      self.position_history.record(&self.players);
+    }
 
     self.status.finished()
   }
@@ -114,111 +371,392 @@ impl GameState {
     if day_state.time_remaining <= Duration::from_secs(0) {
       return true;
     }
-    // Or after all eligable players have recorded a vote.
+    // Or after all eligable players have recorded a vote (and locked it in,
+    // if they're allowed to change it).
     self
       .players
       .iter()
       .filter(|(_, p)| p.eligable_to_vote())
-      .all(|(uuid, _)| day_state.votes.contains_key(uuid))
+      .all(|(uuid, _)| day_state.is_locked_in(*uuid, &self.settings))
+  }
+
+  pub fn note_vote(&mut self, voter: UUID, target: VoteTarget) -> Result<(), GameError> {
+    let allow_vote_changes = self.settings.allow_vote_changes;
+    let day_state = match &mut self.status {
+      GameStatus::Playing(PlayState::Voting(day_state)) => day_state,
+      _ => {
+        return Err(GameError::WrongStatus {
+          action: Action::Vote,
+        })
+      }
+    };
+    if !day_state.accepting_votes() {
+      return Err(GameError::StillDiscussing);
+    }
+    if !day_state.can_vote_for(target) {
+      return Err(GameError::InvalidTarget {
+        action: Action::Vote,
+      });
+    }
+    if day_state.locked_in.contains(&voter) {
+      return Err(GameError::VoteLockedIn);
+    }
+    match day_state.votes.entry(voter) {
+      Entry::Vacant(o) => {
+        o.insert(target);
+      }
+      Entry::Occupied(mut o) if allow_vote_changes => {
+        o.insert(target);
+      }
+      Entry::Occupied(_) => return Err(GameError::AlreadyVoted),
+    }
+    self.emit(GameEvent::VoteCast { voter });
+    Ok(())
+  }
+
+  pub fn note_vote_locked_in(&mut self, voter: UUID) -> Result<(), GameError> {
+    let day_state = match &mut self.status {
+      GameStatus::Playing(PlayState::Voting(day_state)) => day_state,
+      _ => {
+        return Err(GameError::WrongStatus {
+          action: Action::LockInVote,
+        })
+      }
+    };
+    if !day_state.votes.contains_key(&voter) {
+      return Err(GameError::NoVoteToLockIn);
+    }
+    day_state.locked_in.insert(voter);
+    Ok(())
   }
 
   fn simulate_night(&mut self, elapsed: Duration) {
-    // elapsed is the time, in milliseconds, that has passed since the
-    // last time we simulated.
-    // By making our simulations relative to the amount of time that's passed,
-    // the game will progress the same regardless of the frame rate, which may
-    // vary between 30fps and 144fps even if our performance is perfect!
-    let time_steps_passed =
-      (elapsed.as_nanos() as f64) / (Duration::from_millis(16).as_nanos() as f64);
+    self.emergency_cooldown = self
+      .emergency_cooldown
+      .checked_sub(elapsed)
+      .unwrap_or_else(|| Duration::from_secs(0));
+    for body in self.bodies.iter_mut() {
+      body.age += elapsed;
+    }
+    if let Some(decay_time) = self.settings.body_decay_time {
+      // Hide the body long enough and no one will ever find it.
+      self.bodies.retain(|b| b.age < decay_time);
+    }
 
     for (_, player) in self.players.iter_mut() {
-      if player.velocity.dx == 0.0 && player.velocity.dy == 0.0 {
-        continue;
+      player.kill_cooldown = player
+        .kill_cooldown
+        .checked_sub(elapsed)
+        .unwrap_or_else(|| Duration::from_secs(0));
+      for task in player.tasks.iter_mut() {
+        task.progress_time(elapsed);
       }
+      let speed_multiplier = player.speed_multiplier();
+      player.progress_status_effects(elapsed);
 
-      let mut movement_vector = Velocity {
-        dx: player.velocity.dx * time_steps_passed,
-        dy: player.velocity.dy * time_steps_passed,
-      };
-
-      // Unless you're a ghost, you gotta worry about colliding with the furniture.
-      if !player.dead {
-        for shape in self.map.static_geometry.iter() {
-          movement_vector = shape.collide(player.position, Player::radius(), movement_vector, 0.10);
-        }
+      if player.in_vent.is_some() || (player.velocity.dx == 0.0 && player.velocity.dy == 0.0) {
+        continue;
       }
 
-      // Advance the player
-      let new_pos = Position {
-        x: player.position.x + movement_vector.dx,
-        y: player.position.y + movement_vector.dy,
+      // Velocity is how fast the player wants to go at the normal speed.
+      let movement_vector = Velocity {
+        dx: player.velocity.dx * speed_multiplier,
+        dy: player.velocity.dy * speed_multiplier,
       };
-
-      // Bound their new position within within the map
-      let new_pos = self
-        .map
-        .constrain_circle_within_bounds(new_pos, Player::radius());
-
-      player.position = new_pos;
+      player.position = self.map.move_player(player, movement_vector, &self.doors);
     }
+    self.move_dragged_bodies();
   }
 
-  fn win(&mut self, team: Team) {
+  pub(crate) fn win(&mut self, team: Team) {
     self.status = GameStatus::Won(team);
+    self.emit(GameEvent::GameWon { team });
   }
 
-  pub fn get_game_start_info(&self) -> StartInfo {
+  // Deals out everyone's tasks and roles. The same seed, players and settings
+  // always deal out the same game.
+  pub fn get_game_start_info(&self, seed: u64) -> StartInfo {
+    let mut rng = GameRng::new(seed);
+    let common_tasks = self.map.gen_common_tasks(&self.settings, &mut rng);
     let mut assignments: BTreeMap<UUID, PlayerStartInfo> = self
       .players
       .keys()
-      .map(|k| (*k, PlayerStartInfo::new(&self.map, &self.settings)))
+      .map(|k| {
+        let info = PlayerStartInfo::new(&self.map, &self.settings, &common_tasks, &mut rng);
+        (*k, info)
+      })
       .collect();
-    let impostor_index = rand::thread_rng().gen_range(0, self.players.len());
+    let num_players = self.players.len();
+    // Leave at least one crewmate whenever we can, otherwise there's no one to deceive.
+    let num_impostors = self
+      .settings
+      .impostor_count
+      .min(num_players.saturating_sub(1))
+      .max(1)
+      .min(num_players);
+    let impostor_indexes: BTreeSet<usize> =
+      rand::seq::index::sample(&mut rng, num_players, num_impostors)
+        .into_iter()
+        .collect();
     for (i, (_uuid, player_start_info)) in assignments.iter_mut().enumerate() {
-      if i == impostor_index {
+      if impostor_indexes.contains(&i) {
         player_start_info.team = Team::Impostors;
+        player_start_info.role = Role::Impostor;
+        player_start_info.tasks = self
+          .map
+          .gen_fake_tasks(&self.settings, &common_tasks, &mut rng);
       }
     }
+    assign_roles(&self.settings, assignments.values_mut(), &mut rng);
     StartInfo {
+      seed,
       assignments: assignments.into_iter().collect(),
     }
   }
 
-  pub fn note_game_started(&mut self, start_info: &StartInfo) -> Result<(), String> {
+  pub fn note_game_started(&mut self, start_info: &StartInfo) -> Result<(), GameError> {
     if self.status != GameStatus::Lobby {
-      return Err(format!(
-        "Internal error: got a message to start a game when not in the lobby!? Game status: {:?}",
-        self.status
-      ));
+      return Err(GameError::WrongStatus {
+        action: Action::StartGame,
+      });
     }
+    self.check_enough_players()?;
     for (uuid, start_info) in start_info.assignments.iter() {
       if let Some(player) = self.players.get_mut(uuid) {
         player.impostor = start_info.team == Team::Impostors;
+        player.role = start_info.role;
+        player.shielded = false;
+        player.ready = false;
         player.tasks = start_info.tasks.clone();
       } else {
-        return Err(format!(
-          "Unable to find player with uuid {} when starting game.",
-          uuid
-        ));
+        return Err(GameError::UnknownPlayer { uuid: *uuid });
       }
     }
+    self.rng = GameRng::for_play(start_info.seed);
     self.status = GameStatus::Playing(PlayState::Night);
     self.map.place_players_at_game_start(&mut self.players);
+    self.reset_ability_cooldowns();
+    self.start_game_mode();
+    self.game_time_remaining = self.settings.game_time_limit;
+    self.sudden_death_remaining = None;
+    self.reset_activity();
+    self.emergency_cooldown = self.settings.emergency_cooldown;
+    self.sabotage.reset_cooldowns(&self.settings);
+    self.doors = DoorState::default();
+    self.decoys.clear();
+    self.ejection = None;
+    self.emit(GameEvent::GameStarted);
     Ok(())
   }
 
-  pub fn note_death(&mut self, body: DeadBody) -> Result<(), String> {
+  // Gathers everyone around the table and starts the vote.
+  pub fn start_meeting(&mut self, reason: MeetingReason) {
+    let context = MeetingContext::new(reason, &self.bodies);
+    // Whatever the impostors broke gets patched up during the meeting.
+    self.sabotage.active = None;
+    self.doors = DoorState::default();
+    self.ejection = None;
+    // The bodies get cleaned up too, we only remember whose body was reported.
+    self.release_all_bodies();
+    self.bodies.clear();
+    self.decoys.clear();
+    self.drop_disguises();
+    self.status = GameStatus::Playing(PlayState::Voting(VotingState {
+      votes: BTreeMap::new(),
+      locked_in: BTreeSet::new(),
+      phase: DayPhase::Discussion,
+      time_remaining: self.settings.discussion_time,
+      context,
+      candidates: None,
+      tie_breaker: self.rng.gen(),
+    }));
+    self.map.place_players_at_meeting(&mut self.players);
+    self.mark_afk_players();
+    self.emit(GameEvent::MeetingCalled { reason });
+  }
+
+  // seen_at is the tick the reporter was looking at when they reported it.
+  pub fn note_body_reported(
+    &mut self,
+    reporter: UUID,
+    dead_body_color: Color,
+    seen_at: Option<u64>,
+  ) -> Result<(), GameError> {
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return Err(GameError::WrongStatus {
+        action: Action::ReportBody,
+      });
+    }
+    self.check_meetings_allowed(Action::ReportBody)?;
+    let body = *self
+      .bodies
+      .iter()
+      .find(|b| b.color == dead_body_color)
+      .ok_or(GameError::NoSuchBody {
+        color: dead_body_color,
+      })?;
+    match self.players.get(&reporter) {
+      // Reporter is a spectator lol
+      None => return Err(GameError::UnknownPlayer { uuid: reporter }),
+      // ... but nobody came
+      Some(p) if p.dead => {
+        return Err(GameError::Dead {
+          action: Action::ReportBody,
+        })
+      }
+      Some(p) if p.in_kill_animation() => {
+        return Err(GameError::InKillAnimation {
+          action: Action::ReportBody,
+        })
+      }
+      Some(_) => (),
+    };
+    let slop_for_latency = 32.0;
+    let range = self.settings.report_distance + slop_for_latency;
+    if !self.in_range_as_seen_at(reporter, seen_at, body.position, range) {
+      return Err(GameError::OutOfRange {
+        action: Action::ReportBody,
+      });
+    }
+    // oh shit it's on
+    self.start_meeting(MeetingReason::ReportedBody {
+      reporter,
+      body_color: body.color,
+    });
+    Ok(())
+  }
+
+  // Whether the given player is allowed to press the emergency button right now.
+  pub fn can_call_emergency_meeting(&self, caller: UUID) -> Result<(), GameError> {
+    let action = Action::CallMeeting;
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return Err(GameError::WrongStatus { action });
+    }
+    self.check_meetings_allowed(action)?;
+    if self.emergency_cooldown > Duration::from_secs(0) {
+      return Err(GameError::OnCooldown {
+        action,
+        remaining: self.emergency_cooldown,
+      });
+    }
+    if self.sabotage.is_active(Sabotage::Reactor) {
+      return Err(GameError::BlockedBySabotage {
+        action,
+        sabotage: Sabotage::Reactor,
+      });
+    }
+    let player = match self.players.get(&caller) {
+      None => return Err(GameError::UnknownPlayer { uuid: caller }),
+      Some(p) => p,
+    };
+    if player.dead {
+      return Err(GameError::Dead { action });
+    }
+    if player.in_kill_animation() {
+      return Err(GameError::InKillAnimation { action });
+    }
+    if player.emergency_meetings_called >= self.settings.emergency_meetings_per_player {
+      return Err(GameError::NoMeetingsLeft);
+    }
+    if !self.map.emergency_button.within_reach(player.position) {
+      return Err(GameError::OutOfRange { action });
+    }
+    Ok(())
+  }
+
+  pub fn call_emergency_meeting(&mut self, caller: UUID) -> Result<(), GameError> {
+    self.can_call_emergency_meeting(caller)?;
+    if let Some(player) = self.players.get_mut(&caller) {
+      player.emergency_meetings_called += 1;
+    }
+    self.start_meeting(MeetingReason::EmergencyButton { caller });
+    Ok(())
+  }
+
+  fn reset_ability_cooldowns(&mut self) {
     for (_, player) in self.players.iter_mut() {
-      if player.color == body.color {
-        player.dead = true;
+      player.kill_cooldown = self.settings.kill_cooldown;
+      player.shapeshift_cooldown = self.settings.shapeshift_cooldown;
+    }
+  }
+
+  // Checks that the killer is a living impostor who's close enough to the
+  // victim, and only then leaves a body behind. Close enough means on the
+  // killer's screen, i.e. as of the tick they were looking at.
+  pub fn note_kill(
+    &mut self,
+    killer: UUID,
+    victim: UUID,
+    seen_at: Option<u64>,
+  ) -> Result<(), GameError> {
+    let action = Action::Kill;
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return Err(GameError::WrongStatus { action });
+    }
+    let killer_player = match self.players.get(&killer) {
+      None => return Err(GameError::UnknownPlayer { uuid: killer }),
+      Some(p) => p,
+    };
+    if killer_player.dead {
+      return Err(GameError::Dead { action });
+    }
+    if !killer_player.impostor {
+      return Err(GameError::NotAllowedForRole { action });
+    }
+    if killer_player.in_vent.is_some() {
+      return Err(GameError::InVent { action });
+    }
+    if killer_player.in_kill_animation() {
+      return Err(GameError::InKillAnimation { action });
+    }
+    if killer_player.kill_cooldown > Duration::from_secs(0) {
+      return Err(GameError::OnCooldown {
+        action,
+        remaining: killer_player.kill_cooldown,
+      });
+    }
+    let victim_player = match self.players.get(&victim) {
+      Some(p) if !p.dead && !p.impostor && p.in_vent.is_none() => p,
+      _ => return Err(GameError::InvalidTarget { action }),
+    };
+    let slop_for_latency = 32.0;
+    let range = self.settings.kill_distance + slop_for_latency;
+    if !self.in_range_as_seen_at(victim, seen_at, killer_player.position, range) {
+      return Err(GameError::OutOfRange { action });
+    }
+    let body = DeadBody {
+      killer: Some(killer),
+      ..DeadBody::new(victim_player.color, victim_player.position)
+    };
+
+    // Even a blocked kill uses it up.
+    if let Some(killer) = self.players.get_mut(&killer) {
+      killer.kill_cooldown = self.settings.kill_cooldown;
+    }
+    if let Some(victim_player) = self.players.get_mut(&victim) {
+      if victim_player.shielded {
+        // Saved by the medic! But only this once.
+        victim_player.shielded = false;
+        self.emit(GameEvent::KillBlocked { killer, victim });
+        return Ok(());
       }
+      victim_player.dead = true;
     }
+    // Move the killer on top of the new body.
+    if let Some(killer) = self.players.get_mut(&killer) {
+      killer.position = body.position;
+    }
+    self.freeze_for_kill(killer, victim);
+    self.emit(GameEvent::PlayerKilled { killer, victim });
     self.bodies.push(body);
     self.check_for_impostor_win();
     Ok(())
   }
 
   fn check_for_impostor_win(&mut self) {
+    if self.partial_view {
+      return;
+    }
     let mut impostor_count = 0;
     let mut crew_count = 0;
     for (_, player) in self.players.iter() {
@@ -231,7 +769,16 @@ impl GameState {
         crew_count += 1;
       }
     }
-    if impostor_count >= crew_count {
+    // With multiple impostors, they win as soon as they can no longer be
+    // outvoted. With no voting, they have to get everyone.
+    let outnumbered = match self.settings.game_mode {
+      GameMode::Classic => impostor_count >= crew_count,
+      GameMode::HideAndSeek => crew_count == 0,
+    };
+    if impostor_count > 0
+      && outnumbered
+      && !self.sudden_death_instead_of_win(impostor_count, crew_count)
+    {
       self.win(Team::Impostors);
     }
   }
@@ -240,38 +787,62 @@ impl GameState {
     &mut self,
     player_uuid: UUID,
     finished: FinishedTask,
-  ) -> Result<(), String> {
-    if let Some(player) = self.players.get_mut(&player_uuid) {
-      if let Some(task) = player.tasks.get_mut(finished.index) {
-        task.finished = true;
-      }
+  ) -> Result<(), GameError> {
+    let player = self
+      .players
+      .get_mut(&player_uuid)
+      .ok_or(GameError::UnknownPlayer { uuid: player_uuid })?;
+    let task = player
+      .tasks
+      .get_mut(finished.index)
+      .ok_or(GameError::NoSuchTask {
+        index: finished.index,
+      })?;
+    task.activate()?;
+    let task_finished = task.finished;
+    if let (true, Some(boost)) = (task_finished, self.settings.task_speed_boost) {
+      player.add_status_effect(StatusEffectKind::SpeedBoost, boost);
+    }
+    if task_finished {
+      self.emit(GameEvent::TaskCompleted {
+        uuid: player_uuid,
+        index: finished.index,
+      });
     }
     self.check_for_crew_win();
     Ok(())
   }
 
   fn check_for_crew_win(&mut self) {
+    if self.partial_view {
+      return;
+    }
+    // Ghosts keep working on their tasks after death, and those count too.
     let all_crew_tasks_finished = self
       .players
       .values()
       .filter(|p| !p.impostor)
-      .all(|p| p.tasks.iter().all(|t| t.finished));
+      .all(|p| p.tasks.iter().all(|t| t.finished || t.fake));
     let no_impostors_left = self
       .players
       .values()
       .find(|p| p.impostor && !p.dead)
       .is_none();
+    // There's no getting rid of the seeker, only outlasting them.
+    let no_impostors_left = no_impostors_left && self.settings.game_mode.has_meetings();
     if all_crew_tasks_finished || no_impostors_left {
       self.win(Team::Crew);
     }
   }
 
-  fn check_for_victories(&mut self) {
+  pub(crate) fn check_for_victories(&mut self) {
     match self.status {
       GameStatus::Connecting
       | GameStatus::Disconnected
       | GameStatus::Lobby
-      | GameStatus::Won(_) => return,
+      | GameStatus::Paused { .. }
+      | GameStatus::Won(_)
+      | GameStatus::Draw => return,
       GameStatus::Playing(_) => (),
     }
     // The game might be over because the crew has won!
@@ -281,7 +852,21 @@ impl GameState {
   }
 
   pub fn handle_disconnection(&mut self, disconnected_player: UUID) {
+    if self.spectators.remove(&disconnected_player).is_some() {
+      return;
+    }
+    if !self.note_disconnected(disconnected_player) {
+      self.remove_player(disconnected_player);
+    }
+    self.pause_if_too_few_players();
+  }
+
+  // Takes a player out of the game for good.
+  pub(crate) fn remove_player(&mut self, disconnected_player: UUID) {
     self.players.remove(&disconnected_player);
+    if self.is_host(disconnected_player) {
+      self.promote_next_host();
+    }
     // The game might be over, because we're out of players
     if self.players.is_empty() {
       self.status = GameStatus::Disconnected;
@@ -289,7 +874,10 @@ impl GameState {
     self.check_for_victories();
     // We might be voting, in which case we want to remove all votes for the
     // disconnected player, so that players can vote for someone else if they wish.
+    // Their own vote goes too, since they're not around to cast it.
     if let GameStatus::Playing(PlayState::Voting(day)) = &mut self.status {
+      day.votes.remove(&disconnected_player);
+      day.locked_in.remove(&disconnected_player);
       let mut voters_for_disonnected = Vec::new();
       for (voter, target) in day.votes.iter_mut() {
         if let VoteTarget::Player { uuid } = target {
@@ -300,87 +888,114 @@ impl GameState {
       }
       for voter in voters_for_disonnected {
         day.votes.remove(&voter);
+        day.locked_in.remove(&voter);
       }
     }
   }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+// The built-in maps borrow everything from constants, while maps loaded from
+// data files own theirs.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Map {
-  width: f64,
-  height: f64,
-  start_circle: (Position, f64),
-  meeting_circle: (Position, f64),
-  pub static_geometry: &'static [Shape],
-}
-
-const FIRST_CONFERENCE_TABLE: Position = Position { x: 275.0, y: 275.0 };
-const FIRST_MAP_GEOMETRY: &[Shape] = &[
-  // conference table
-  Shape::Circle {
-    radius: 75.0,
-    center: FIRST_CONFERENCE_TABLE,
-    outline_width: 1.0,
-    outline_color: "#000",
-    fill_color: "#358",
-  },
-  // nega table
-  Shape::Circle {
-    radius: 62.0,
-    center: Position { x: 875.0, y: 505.0 },
-    outline_width: 1.0,
-    outline_color: "#000",
-    fill_color: "#a22",
-  },
-  Shape::Circle {
-    radius: 62.0,
-    center: Position { x: 1675.0, y: 95.0 },
-    outline_width: 1.0,
-    outline_color: "#000",
-    fill_color: "#a22",
-  },
-  Shape::Circle {
-    radius: 140.0,
-    center: Position {
-      x: 2475.0,
-      y: 190.0,
-    },
-    outline_width: 1.0,
-    outline_color: "#000",
-    fill_color: "#666",
-  },
-  Shape::Circle {
-    radius: 140.0,
-    center: Position {
-      x: 2475.0,
-      y: 190.0 + 140.0 + 140.0 + 30.0,
-    },
-    outline_width: 1.0,
-    outline_color: "#000",
-    fill_color: "#666",
-  },
-  Shape::Circle {
-    radius: 62.0,
-    center: Position {
-      x: 2875.0,
-      y: 115.0,
-    },
-    outline_width: 1.0,
-    outline_color: "#000",
-    fill_color: "#a22",
-  },
-];
+  pub id: MapId,
+  pub name: Cow<'static, str>,
+  pub(crate) width: f64,
+  pub(crate) height: f64,
+  // Where everyone starts out, when they join and when the game begins.
+  pub spawn_area: Ring,
+  // Where everyone's gathered for a meeting, and sent back to afterwards.
+  pub meeting_spot: Ring,
+  pub static_geometry: Cow<'static, [Shape]>,
+  // Only solid while they're closed.
+  pub doors: Cow<'static, [Door]>,
+  pub fix_stations: Cow<'static, [FixStation]>,
+  pub vents: Cow<'static, [Vent]>,
+  // Where tasks can be handed out. Anywhere clear of the furniture, if empty.
+  pub task_spots: Cow<'static, [Position]>,
+  pub emergency_button: EmergencyButton,
+  // The named rooms. They can overlap, in which case the first one wins.
+  #[serde(default)]
+  pub regions: Cow<'static, [Region]>,
+  // Solid to some players, or some ways, but not others.
+  #[serde(default)]
+  pub passages: Cow<'static, [Passage]>,
+  // Where tasks have to be done from. Tasks outside of all of them can be
+  // done from anywhere within Settings::task_distance.
+  #[serde(default)]
+  pub interactables: Cow<'static, [Interactable]>,
+  // Only for looking at. Nothing collides with these.
+  #[serde(default)]
+  pub decorations: Cow<'static, [Decoration]>,
+  // For keeping an eye on the rest of the map, from a distance.
+  #[serde(default)]
+  pub admin_consoles: Cow<'static, [AdminConsole]>,
+  #[serde(default)]
+  pub security_consoles: Cow<'static, [SecurityConsole]>,
+  #[serde(default)]
+  pub cameras: Cow<'static, [Camera]>,
+  // Worked out from the furniture, so there's no need to send it anywhere.
+  #[serde(skip)]
+  pub(crate) grid: SpatialGrid,
+}
 
-impl Map {
-  fn first_map() -> Map {
-    Map {
-      width: 3036.0,
-      height: 768.0,
-      start_circle: (FIRST_CONFERENCE_TABLE, 100.0),
-      meeting_circle: (FIRST_CONFERENCE_TABLE, 100.0),
-      static_geometry: FIRST_MAP_GEOMETRY,
+// A circle that players get spread out evenly around the edge of.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct Ring {
+  pub center: Position,
+  pub radius: f64,
+}
+
+impl Ring {
+  // The spot for the given one of count players, going around the ring.
+  pub fn spot(&self, index: usize, count: usize) -> Position {
+    self.at_turn(index as f64 / count.max(1) as f64)
+  }
+
+  // Somewhere on the ring, from 0 to 1 of the way around.
+  fn at_turn(&self, turn: f64) -> Position {
+    let angle = turn * 2.0 * std::f64::consts::PI;
+    Position {
+      x: self.center.x + self.radius * angle.sin(),
+      y: self.center.y + self.radius * angle.cos(),
     }
   }
+}
+
+// A named part of the map, like a room, for telling people where they are.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Region {
+  pub name: Cow<'static, str>,
+  // Goes around the outside in order, like a Shape::Polygon.
+  pub points: Cow<'static, [Position]>,
+}
+
+impl Region {
+  pub fn contains(&self, position: Position) -> bool {
+    polygon_contains(&self.points, position)
+  }
+}
+
+// A button that any living player can press to call everyone
+// to the table for a vote.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct EmergencyButton {
+  pub position: Position,
+  // How close a player must be to press the button.
+  pub reach: f64,
+}
+
+impl EmergencyButton {
+  pub fn within_reach(&self, position: Position) -> bool {
+    self.position.distance(&position) <= self.reach
+  }
+}
+
+impl Map {
+  // The named part of the map that this is in, if any.
+  pub fn region_at(&self, position: Position) -> Option<&Region> {
+    self.regions.iter().find(|r| r.contains(position))
+  }
 
   pub fn width(&self) -> f64 {
     self.width
@@ -390,6 +1005,41 @@ impl Map {
     self.height
   }
 
+  // Where the player ends up if they try to move by the given amount.
+  pub fn move_player(
+    &self,
+    player: &Player,
+    mut movement_vector: Velocity,
+    doors: &DoorState,
+  ) -> Position {
+    // Unless you're a ghost, you gotta worry about colliding with the furniture.
+    // Ghosts drift right through it, and through locked doors and passages too.
+    if !player.is_ghost() {
+      let reach = Player::radius() + movement_vector.magnitude();
+      let shapes: Vec<&Shape> = self
+        .shapes_near(player.position, reach)
+        .chain(self.closed_doors(doors))
+        .chain(self.blocking_passages(player, movement_vector))
+        .collect();
+      movement_vector = sweep_and_slide(
+        &shapes,
+        player.position,
+        Player::radius(),
+        movement_vector,
+        0.10,
+      );
+    }
+
+    // Advance the player
+    let new_pos = Position {
+      x: player.position.x + movement_vector.dx,
+      y: player.position.y + movement_vector.dy,
+    };
+
+    // Bound their new position within within the map
+    self.constrain_circle_within_bounds(new_pos, Player::radius())
+  }
+
   pub fn constrain_circle_within_bounds(&self, center: Position, radius: f64) -> Position {
     Position {
       x: center.x.min(self.width - radius).max(0.0 + radius),
@@ -400,161 +1050,524 @@ impl Map {
     }
   }
 
-  pub fn gen_tasks<T: FromIterator<Task>>(&self, settings: &Settings) -> T {
-    // In the future, maps may want to have more of an opinion about the
-    // kinds of tasks generated.
-    (0..settings.num_tasks)
-      .map(|_| loop {
-        let mut rng = rand::thread_rng();
-        let position = Position {
-          x: rng.gen_range(30.0, self.width - 30.0),
-          y: rng.gen_range(30.0, self.height - 30.0),
-        };
-        if self
-          .static_geometry
-          .iter()
-          .any(|s| s.collides_with(position, 30.0))
-        {
-          continue;
-        }
-        return Task {
-          finished: false,
-          position,
-        };
+  // Generates one player's tasks, on top of the common tasks everyone shares.
+  pub fn gen_tasks<T: FromIterator<Task>>(
+    &self,
+    settings: &Settings,
+    common_tasks: &[Task],
+    rng: &mut GameRng,
+  ) -> T {
+    let short_tasks: Vec<Task> = (0..settings.short_tasks)
+      .map(|_| Task::new(TaskKind::Short, self.gen_task_position(rng)).with_minigame(rng))
+      .collect();
+    let long_tasks: Vec<Task> = (0..settings.long_tasks)
+      .map(|_| {
+        self
+          .gen_long_task(&settings.task_mix, rng)
+          .with_minigame(rng)
       })
+      .collect();
+    common_tasks
+      .iter()
+      .cloned()
+      .chain(short_tasks)
+      .chain(long_tasks)
       .collect()
   }
 
-  pub fn get_spawn_in_position(&self) -> Position {
-    let starting_position_seed: f64 = rand::random();
-    let (circle, radius) = self.start_circle;
-    Position {
-      x: circle.x + (radius * (starting_position_seed * 2.0 * std::f64::consts::PI).sin()),
-      y: circle.y + (radius * (starting_position_seed * 2.0 * std::f64::consts::PI).cos()),
+  // Impostors get their own list of tasks to pretend to do, including the
+  // common ones so they can blend in.
+  pub fn gen_fake_tasks(
+    &self,
+    settings: &Settings,
+    common_tasks: &[Task],
+    rng: &mut GameRng,
+  ) -> Vec<Task> {
+    self
+      .gen_tasks::<Vec<Task>>(settings, common_tasks, rng)
+      .into_iter()
+      .map(Task::into_fake)
+      .collect()
+  }
+
+  pub fn gen_common_tasks(&self, settings: &Settings, rng: &mut GameRng) -> Vec<Task> {
+    (0..settings.common_tasks)
+      .map(|_| Task::new(TaskKind::Short, self.gen_task_position(rng)).with_minigame(rng))
+      .collect()
+  }
+
+  // Draws a long task from this map's pool, picking its kind according to the mix.
+  fn gen_long_task(&self, mix: &TaskMix, rng: &mut GameRng) -> Task {
+    let total = mix.long + mix.multi_stage + mix.timed;
+    let mut pick = rng.gen_range(0, total.max(1));
+    let position = self.gen_task_position(rng);
+    if pick < mix.long || total == 0 {
+      return Task::new(TaskKind::Long { steps: 3 }, position);
     }
+    pick -= mix.long;
+    if pick < mix.multi_stage {
+      let mut stages = vec![position];
+      stages.extend((0..2).map(|_| self.gen_task_position(rng)));
+      return Task::new(TaskKind::MultiStage { stages }, position);
+    }
+    let wait = Duration::from_secs(10);
+    Task::new(TaskKind::Timed { wait }, position)
+  }
+
+  fn gen_task_position(&self, rng: &mut GameRng) -> Position {
+    if !self.task_spots.is_empty() {
+      return self.task_spots[rng.gen_range(0, self.task_spots.len())];
+    }
+    // Uploaded maps have to come with task spots, but in case one slips
+    // through, give up looking for a clear patch of floor eventually rather
+    // than hang the room.
+    let tries = if self.width > 60.0 && self.height > 60.0 {
+      100
+    } else {
+      0
+    };
+    for _ in 0..tries {
+      let position = Position {
+        x: rng.gen_range(30.0, self.width - 30.0),
+        y: rng.gen_range(30.0, self.height - 30.0),
+      };
+      if !self
+        .static_geometry
+        .iter()
+        .any(|s| s.collides_with(position, 30.0))
+      {
+        return position;
+      }
+    }
+    // Everyone has to be able to stand around the spawn, so that'll do.
+    self.get_spawn_in_position(rng)
+  }
+
+  pub fn get_spawn_in_position(&self, rng: &mut GameRng) -> Position {
+    self.spawn_area.at_turn(rng.gen())
   }
 
   pub fn place_players_at_game_start(&self, players: &mut BTreeMap<UUID, Player>) {
-    self.place_players_around_circle(players, self.start_circle);
+    place_players_around(players, self.spawn_area);
   }
 
   pub fn place_players_at_night_start(&self, players: &mut BTreeMap<UUID, Player>) {
-    self.place_players_around_circle(players, self.meeting_circle);
+    place_players_around(players, self.meeting_spot);
   }
 
-  fn place_players_around_circle(
-    &self,
-    players: &mut BTreeMap<UUID, Player>,
-    (center, radius): (Position, f64),
-  ) {
-    let num_players = players.len() as f64;
-    for (i, (_, p)) in players.iter_mut().enumerate() {
-      let offset = ((i as f64) / num_players) * 2.0 * std::f64::consts::PI;
-      p.position = Position {
-        x: center.x + (radius * offset.sin()),
-        y: center.y + (radius * offset.cos()),
-      };
-      p.velocity = Velocity::default();
-    }
+  pub fn place_players_at_meeting(&self, players: &mut BTreeMap<UUID, Player>) {
+    place_players_around(players, self.meeting_spot);
+  }
+}
+
+fn place_players_around(players: &mut BTreeMap<UUID, Player>, ring: Ring) {
+  let count = players.len();
+  for (i, (_, p)) in players.iter_mut().enumerate() {
+    p.position = ring.spot(i, count);
+    p.velocity = Velocity::default();
+    p.in_vent = None;
+    p.interaction = None;
   }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Shape {
   Circle {
     radius: f64,
     center: Position,
-    fill_color: &'static str,
+    fill_color: Cow<'static, str>,
     outline_width: f64,
-    outline_color: &'static str,
+    outline_color: Cow<'static, str>,
+  },
+  // Lined up with the axes, for walls and rooms.
+  Rect {
+    top_left: Position,
+    width: f64,
+    height: f64,
+    fill_color: Cow<'static, str>,
+    outline_width: f64,
+    outline_color: Cow<'static, str>,
+  },
+  // The points go around the outside in order, and the last one joins back
+  // up with the first.
+  Polygon {
+    points: Cow<'static, [Position]>,
+    fill_color: Cow<'static, str>,
+    outline_width: f64,
+    outline_color: Cow<'static, str>,
+  },
+  // A straight wall from one point to another, with rounded ends.
+  Wall {
+    start: Position,
+    end: Position,
+    thickness: f64,
+    color: Cow<'static, str>,
   },
 }
 
-impl Shape {
-  pub fn collide(
-    &self,
-    center: Position,
-    radius: f64,
-    movement_vector: Velocity,
-    friction: f64,
-  ) -> Velocity {
-    match self {
-      Shape::Circle {
-        radius: self_radius,
-        center: self_center,
-        ..
-      } => {
-        // Circle on circle collision
-        // https://www.gamasutra.com/view/feature/131424/pool_hall_lessons_fast_accurate_.php?print=1
-
-        // Quick check to see whether, given starting locations and the magnitude of the
-        // movement these two could collide if the player moved directly at this shape.
-        let sum_radii = radius + self_radius;
-        let dist = center.distance(self_center) - sum_radii;
-        if movement_vector.magnitude() < dist {
-          // Too far away, early exit.
-          return movement_vector;
-        }
+// Where a circle moving along a vector first touches a wall, as a fraction of
+// the way along the vector, and which way the wall pushes back.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SweepHit {
+  pub t: f64,
+  pub normal: Velocity,
+}
 
-        let n: Velocity = movement_vector.normalize();
+// How far a circle actually gets when it tries to move by movement_vector.
+// It goes as far as it can before it first touches any of the shapes, however
+// far it was going, so nothing gets skipped over on a big step. Then it
+// slides along the surface with whatever movement it had left, minus the
+// part going into the surface, so players glide along walls rather than
+// sticking to them. A slide can run into something else, like the other side
+// of an inside corner, so that gets checked again a couple of times.
+pub fn sweep_and_slide(
+  shapes: &[&Shape],
+  center: Position,
+  radius: f64,
+  movement_vector: Velocity,
+  friction: f64,
+) -> Velocity {
+  let mut position = center;
+  let mut remaining = movement_vector;
+  let mut total = Velocity::default();
+  for _ in 0..3 {
+    if remaining.magnitude() == 0.0 {
+      break;
+    }
+    let hit = shapes
+      .iter()
+      .filter_map(|shape| shape.sweep(position, radius, remaining))
+      .min_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal));
+    let hit = match hit {
+      Some(hit) => hit,
+      None => {
+        total = total.add(&remaining);
+        break;
+      }
+    };
+    let allowed: Velocity = remaining.times(hit.t);
+    total = total.add(&allowed);
+    position = position.move_by(allowed);
+    DebugOverlay::note_contact(Contact {
+      at: position.move_by(hit.normal.times(-radius)),
+      normal: hit.normal,
+    });
+    // Whatever's left over, along the tangent of the surface, and slowed down
+    // by rubbing against it.
+    let leftover: Velocity = remaining.times(1.0 - hit.t);
+    let into_surface = leftover.dot_product(&hit.normal);
+    remaining = leftover
+      .minus::<Velocity>(&hit.normal.times::<Velocity>(into_surface))
+      .times(1.0 - friction);
+  }
+  total
+}
 
-        // Determine if A is moving towards B. If not, they're not colliding
-        let c = self_center.sub(center);
-        let d = n.dot_product(&c);
-        if d <= 0.0 {
-          return movement_vector;
-        }
+// A circle moving from center by movement, against a point it could bump into.
+fn sweep_circle_against_point(
+  center: Position,
+  radius: f64,
+  movement: Velocity,
+  point: Position,
+) -> Option<SweepHit> {
+  let d: Velocity = center.minus(&point);
+  let a = movement.dot_product(&movement);
+  let b = 2.0 * d.dot_product(&movement);
+  let c = d.dot_product(&d) - radius * radius;
+  if c < 0.0 {
+    // Already touching, so only stop it if it's going further in.
+    if b < 0.0 && d.magnitude() > 0.0 {
+      return Some(SweepHit {
+        t: 0.0,
+        normal: d.normalize(),
+      });
+    }
+    return None;
+  }
+  let discriminant = b * b - 4.0 * a * c;
+  if a == 0.0 || discriminant < 0.0 {
+    return None;
+  }
+  let t = (-b - discriminant.sqrt()) / (2.0 * a);
+  if !(0.0..=1.0).contains(&t) {
+    return None;
+  }
+  let normal: Velocity = d
+    .add::<Velocity>(&movement.times::<Velocity>(t))
+    .normalize();
+  Some(SweepHit { t, normal })
+}
 
-        let length_c = c.magnitude();
-        let f = (length_c * length_c) - (d * d);
-        let radii_squared = sum_radii * sum_radii;
-        if f >= radii_squared {
-          // the closest point on the movement vector is still too far away to collide
-          return movement_vector;
+// A circle moving from center by movement, against the segment from start to
+// end, including its two ends.
+fn sweep_circle_against_segment(
+  center: Position,
+  radius: f64,
+  movement: Velocity,
+  (start, end): (Position, Position),
+) -> Option<SweepHit> {
+  let along: Velocity = end.minus(&start);
+  let length = along.magnitude();
+  let mut earliest: Option<SweepHit> = None;
+  if length > 0.0 {
+    let mut normal = Velocity {
+      dx: -along.dy / length,
+      dy: along.dx / length,
+    };
+    let mut gap = center.minus::<Velocity>(&start).dot_product(&normal);
+    if gap < 0.0 {
+      normal = normal.times(-1.0);
+      gap = -gap;
+    }
+    let approach = -movement.dot_product(&normal);
+    if approach > 0.0 {
+      let t = ((gap - radius) / approach).max(0.0);
+      if t <= 1.0 {
+        let touching = center.move_by(movement.times(t));
+        let how_far_along = touching.minus::<Velocity>(&start).dot_product(&along) / length;
+        if (0.0..=length).contains(&how_far_along) {
+          earliest = Some(SweepHit { t, normal });
         }
+      }
+    }
+  }
+  for point in [start, end].iter() {
+    if let Some(hit) = sweep_circle_against_point(center, radius, movement, *point) {
+      if earliest.as_ref().is_none_or(|e| hit.t < e.t) {
+        earliest = Some(hit);
+      }
+    }
+  }
+  earliest
+}
 
-        let t = radii_squared - f;
-        if t < 0.0 {
-          return movement_vector;
-        }
+fn segments_intersect((a, b): (Position, Position), (c, d): (Position, Position)) -> bool {
+  let cross =
+    |o: Position, p: Position, q: Position| (p.x - o.x) * (q.y - o.y) - (p.y - o.y) * (q.x - o.x);
+  let (d1, d2) = (cross(c, d, a), cross(c, d, b));
+  let (d3, d4) = (cross(a, b, c), cross(a, b, d));
+  ((d1 > 0.0) != (d2 > 0.0)) && ((d3 > 0.0) != (d4 > 0.0))
+}
 
-        let t_sqrt = t.sqrt();
-        let distance = d - t_sqrt;
-        let movement_magnitude = movement_vector.magnitude();
+// Counts how many sides a ray going right from the point crosses.
+fn polygon_contains(points: &[Position], point: Position) -> bool {
+  let mut inside = false;
+  for i in 0..points.len() {
+    let (start, end) = (points[i], points[(i + 1) % points.len()]);
+    if (start.y > point.y) != (end.y > point.y) {
+      let x = start.x + (point.y - start.y) / (end.y - start.y) * (end.x - start.x);
+      if point.x < x {
+        inside = !inside;
+      }
+    }
+  }
+  inside
+}
+
+fn closest_point_on_segment(point: Position, (start, end): (Position, Position)) -> Position {
+  let along: Velocity = end.minus(&start);
+  let length_squared = along.dot_product(&along);
+  let t = if length_squared == 0.0 {
+    0.0
+  } else {
+    (point.minus::<Velocity>(&start).dot_product(&along) / length_squared).clamp(0.0, 1.0)
+  };
+  start.move_by(along.times(t))
+}
+
+impl Shape {
+  // The smallest box, lined up with the axes, that the shape fits in. Returns
+  // the top left and bottom right corners.
+  pub fn bounds(&self) -> (Position, Position) {
+    let (min, max) = match self {
+      Shape::Circle { radius, center, .. } => (
+        Position {
+          x: center.x - radius,
+          y: center.y - radius,
+        },
+        Position {
+          x: center.x + radius,
+          y: center.y + radius,
+        },
+      ),
+      _ => {
+        let corners = self.corners();
+        let min = Position {
+          x: corners.iter().map(|c| c.x).fold(f64::INFINITY, f64::min),
+          y: corners.iter().map(|c| c.y).fold(f64::INFINITY, f64::min),
+        };
+        let max = Position {
+          x: corners
+            .iter()
+            .map(|c| c.x)
+            .fold(f64::NEG_INFINITY, f64::max),
+          y: corners
+            .iter()
+            .map(|c| c.y)
+            .fold(f64::NEG_INFINITY, f64::max),
+        };
+        (min, max)
+      }
+    };
+    // Walls stick out a bit either side of the line they're drawn along.
+    let margin = match self {
+      Shape::Wall { thickness, .. } => thickness / 2.0,
+      _ => 0.0,
+    };
+    (
+      Position {
+        x: min.x - margin,
+        y: min.y - margin,
+      },
+      Position {
+        x: max.x + margin,
+        y: max.y + margin,
+      },
+    )
+  }
 
-        if movement_magnitude < distance {
-          return movement_vector;
+  // Roughly the middle of the shape.
+  pub fn center(&self) -> Position {
+    match self {
+      Shape::Circle { center, .. } => *center,
+      Shape::Rect {
+        top_left,
+        width,
+        height,
+        ..
+      } => Position {
+        x: top_left.x + width / 2.0,
+        y: top_left.y + height / 2.0,
+      },
+      Shape::Wall { start, end, .. } => Position {
+        x: (start.x + end.x) / 2.0,
+        y: (start.y + end.y) / 2.0,
+      },
+      Shape::Polygon { points, .. } => {
+        let count = points.len().max(1) as f64;
+        Position {
+          x: points.iter().map(|p| p.x).sum::<f64>() / count,
+          y: points.iter().map(|p| p.y).sum::<f64>() / count,
         }
+      }
+    }
+  }
 
-        let new_velocity = n.times(distance);
+  // The corners, going around the outside. Circles don't have any, and walls
+  // just have their two ends.
+  pub fn corners(&self) -> Vec<Position> {
+    match self {
+      Shape::Circle { .. } => vec![],
+      Shape::Rect {
+        top_left,
+        width,
+        height,
+        ..
+      } => vec![
+        *top_left,
+        Position {
+          x: top_left.x + width,
+          y: top_left.y,
+        },
+        Position {
+          x: top_left.x + width,
+          y: top_left.y + height,
+        },
+        Position {
+          x: top_left.x,
+          y: top_left.y + height,
+        },
+      ],
+      Shape::Polygon { points, .. } => points.to_vec(),
+      Shape::Wall { start, end, .. } => vec![*start, *end],
+    }
+  }
 
-        let new_position = center.move_by(new_velocity);
-        let vector_between_centers: Velocity = self_center.minus(&new_position);
-        let angle = n.angle_between(&vector_between_centers);
-        let angle_multiplier = angle * 2.0 / PI;
+  fn edges(&self) -> Vec<(Position, Position)> {
+    let corners = self.corners();
+    (0..corners.len())
+      .map(|i| (corners[i], corners[(i + 1) % corners.len()]))
+      .collect()
+  }
 
-        let slope_of_tangent_line = -(1.0 / self_center.slope(&new_position));
-        let tangent_point: Position = vector_between_centers
-          .normalize::<Position>()
-          .times(*self_radius);
-        let second_tangent_point = Position {
-          x: tangent_point.x + 1.0,
-          y: tangent_point.y + slope_of_tangent_line,
-        };
-        let tangent_vector: Velocity = tangent_point.sub(second_tangent_point).normalize();
-        let reversed_tangent_vector: Velocity = tangent_vector.times(-1.0);
-        let tangent_vector = if tangent_vector.distance(&n) < reversed_tangent_vector.distance(&n) {
-          tangent_vector
+  pub fn contains(&self, point: Position) -> bool {
+    match self {
+      Shape::Circle { radius, center, .. } => center.distance(&point) < *radius,
+      Shape::Wall { .. } => false,
+      Shape::Rect {
+        top_left,
+        width,
+        height,
+        ..
+      } => {
+        point.x > top_left.x
+          && point.x < top_left.x + width
+          && point.y > top_left.y
+          && point.y < top_left.y + height
+      }
+      Shape::Polygon { points, .. } => polygon_contains(points, point),
+    }
+  }
+
+  // When a circle moving from center along the whole of movement would first
+  // touch this shape, if it would at all.
+  pub fn sweep(&self, center: Position, radius: f64, movement: Velocity) -> Option<SweepHit> {
+    match self {
+      Shape::Circle {
+        radius: self_radius,
+        center: self_center,
+        ..
+      } => sweep_circle_against_point(center, radius + self_radius, movement, *self_center),
+      Shape::Rect { .. } | Shape::Polygon { .. } => self
+        .edges()
+        .into_iter()
+        .filter_map(|edge| sweep_circle_against_segment(center, radius, movement, edge))
+        .min_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal)),
+      Shape::Wall {
+        start,
+        end,
+        thickness,
+        ..
+      } => sweep_circle_against_segment(center, radius + thickness / 2.0, movement, (*start, *end)),
+    }
+  }
+
+  // How far a circle actually gets when it tries to move by movement_vector,
+  // with only this shape in the way. See sweep_and_slide.
+  pub fn collide(
+    &self,
+    center: Position,
+    radius: f64,
+    movement_vector: Velocity,
+    friction: f64,
+  ) -> Velocity {
+    sweep_and_slide(&[self], center, radius, movement_vector, friction)
+  }
+
+  // Whether this shape is in the way of a straight line between the two points.
+  pub fn blocks_line_of_sight(&self, from: Position, to: Position) -> bool {
+    match self {
+      Shape::Circle { radius, center, .. } => {
+        // Find the closest point on the segment to the center of the circle.
+        let segment: Velocity = to.minus(&from);
+        let length_squared = segment.dot_product(&segment);
+        let t = if length_squared == 0.0 {
+          0.0
         } else {
-          reversed_tangent_vector
+          (center.minus::<Velocity>(&from).dot_product(&segment) / length_squared).clamp(0.0, 1.0)
         };
-
-        let leftover_magnitude = movement_magnitude - distance;
-        return new_velocity.add(
-          &tangent_vector
-            .times::<Velocity>(leftover_magnitude * angle_multiplier * (1.0 - friction)),
-        );
+        let closest = from.move_by(segment.times(t));
+        closest.distance(center) < *radius
+      }
+      Shape::Rect { .. } | Shape::Polygon { .. } => {
+        self.contains(from)
+          || self.contains(to)
+          || self
+            .edges()
+            .into_iter()
+            .any(|edge| segments_intersect((from, to), edge))
       }
+      Shape::Wall { start, end, .. } => segments_intersect((from, to), (*start, *end)),
     }
   }
 
@@ -563,11 +1576,39 @@ impl Shape {
       Shape::Circle { radius, center, .. } => {
         let distance = center.distance(&other_position);
         let collided = distance < (radius + other_radius);
+        #[cfg(feature = "debug_collision")]
         if collided {
-          console_log!("{:?} collided with {:?}", center, other_position);
+          tracing::trace!(?center, ?other_position, "Collided");
         }
         collided
       }
+      Shape::Rect {
+        top_left,
+        width,
+        height,
+        ..
+      } => {
+        let closest = Position {
+          x: other_position.x.clamp(top_left.x, top_left.x + width),
+          y: other_position.y.clamp(top_left.y, top_left.y + height),
+        };
+        closest.distance(&other_position) < other_radius
+      }
+      Shape::Polygon { .. } => {
+        self.contains(other_position)
+          || self.edges().into_iter().any(|edge| {
+            closest_point_on_segment(other_position, edge).distance(&other_position) < other_radius
+          })
+      }
+      Shape::Wall {
+        start,
+        end,
+        thickness,
+        ..
+      } => {
+        closest_point_on_segment(other_position, (*start, *end)).distance(&other_position)
+          < other_radius + thickness / 2.0
+      }
     }
   }
 }
@@ -602,6 +1643,10 @@ impl UUID {
   pub fn random() -> UUID {
     UUID { v: rand::random() }
   }
+
+  pub fn random_from(rng: &mut impl Rng) -> UUID {
+    UUID { v: rng.gen() }
+  }
 }
 
 impl Serialize for UUID {
@@ -612,6 +1657,10 @@ impl Serialize for UUID {
   where
     S: serde::Serializer,
   {
+    // Hex is for humans, binary formats can have the raw bytes.
+    if !serializer.is_human_readable() {
+      return self.v.serialize(serializer);
+    }
     serializer.serialize_str(&format!("{}", self))
   }
 }
@@ -635,13 +1684,20 @@ impl<'de> Visitor<'de> for UUIDVisitor {
     let mut bytes = [0; 16];
     for (i, byte) in bytes.iter_mut().enumerate() {
       let si = i * 2;
-      let hex_byte = &value[si..si + 2];
-      *byte = match u8::from_str_radix(hex_byte, 16) {
-        Ok(v) => v,
-        Err(_) => {
+      // Anything that isn't ASCII can't be hex, and slicing it by bytes might
+      // land in the middle of a character. from_str_radix takes a leading +
+      // too, which we don't.
+      let parsed = value
+        .get(si..si + 2)
+        .filter(|hex_byte| hex_byte.bytes().all(|b| b.is_ascii_hexdigit()))
+        .and_then(|hex_byte| u8::from_str_radix(hex_byte, 16).ok());
+      *byte = match parsed {
+        Some(v) => v,
+        None => {
           return Err(E::custom(format!(
-            "expected hex, but found {} at offset {}",
-            hex_byte, si
+            "expected hex, but found {:?} at offset {}",
+            value.as_bytes()[si..si + 2].to_vec(),
+            si
           )))
         }
       }
@@ -654,6 +1710,11 @@ impl<'de> Deserialize<'de> for UUID {
   where
     D: Deserializer<'de>,
   {
+    if !deserializer.is_human_readable() {
+      return Ok(UUID {
+        v: <[u8; 16]>::deserialize(deserializer)?,
+      });
+    }
     deserializer.deserialize_str(UUIDVisitor)
   }
 }
@@ -753,6 +1814,8 @@ impl Position {
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
 pub struct StartInfo {
+  // What the game was dealt from, so that it can be dealt the same way again.
+  pub seed: u64,
   pub assignments: Vec<(UUID, PlayerStartInfo)>,
 }
 
@@ -788,6 +1851,13 @@ pub enum Color {
   Yellow,
   Purple,
   Gray,
+  // More, so that bigger lobbies don't run out.
+  Cyan,
+  Brown,
+  Lime,
+  Maroon,
+  Tan,
+  Coral,
 }
 
 impl Color {
@@ -803,6 +1873,12 @@ impl Color {
       Color::Yellow,
       Color::Purple,
       Color::Gray,
+      Color::Cyan,
+      Color::Brown,
+      Color::Lime,
+      Color::Maroon,
+      Color::Tan,
+      Color::Coral,
     ]
   }
 
@@ -818,6 +1894,12 @@ impl Color {
       Color::Yellow => "#ffff66",
       Color::Purple => "#8a2be2",
       Color::Gray => "#333333",
+      Color::Cyan => "#38fedc",
+      Color::Brown => "#71491e",
+      Color::Lime => "#a8e63c",
+      Color::Maroon => "#6b2b3c",
+      Color::Tan => "#d6c59a",
+      Color::Coral => "#ec7578",
     }
   }
 
@@ -826,23 +1908,30 @@ impl Color {
   // Determined by just kinda... eyeballing it, you know?
   pub fn text_outline_color(&self) -> &'static str {
     match self {
-      Color::Red | Color::Blue | Color::Black | Color::Purple | Color::Gray => "#fff",
-      Color::Orange | Color::Yellow | Color::White | Color::Pink | Color::Green => "#000",
+      Color::Red
+      | Color::Blue
+      | Color::Black
+      | Color::Purple
+      | Color::Gray
+      | Color::Brown
+      | Color::Maroon => "#fff",
+      Color::Orange
+      | Color::Yellow
+      | Color::White
+      | Color::Pink
+      | Color::Green
+      | Color::Cyan
+      | Color::Lime
+      | Color::Tan
+      | Color::Coral => "#000",
     }
   }
 
-  pub fn random() -> Color {
-    Color::all()[rand::thread_rng().gen_range(0, Color::all().len())]
+  pub fn random(rng: &mut impl Rng) -> Color {
+    Color::all()[rng.gen_range(0, Color::all().len())]
   }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
-pub struct Task {
-  pub position: Position,
-  pub finished: bool,
-}
-impl Task {}
-
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
 pub struct Player {
   pub name: String,
@@ -853,6 +1942,30 @@ pub struct Player {
   pub impostor: bool,
   pub tasks: Vec<Task>,
   pub velocity: Velocity,
+  pub kill_cooldown: Duration,
+  pub emergency_meetings_called: usize,
+  // The index of the vent this player is hiding in, if any.
+  pub in_vent: Option<usize>,
+  pub role: Role,
+  // Protected by the medic from the next kill attempt.
+  pub shielded: bool,
+  pub status_effects: Vec<StatusEffect>,
+  // Whether they're ready to start, while in the lobby.
+  pub ready: bool,
+  // Lost their connection, but might still come back.
+  pub disconnected: bool,
+  pub reconnect_time_remaining: Duration,
+  // How long it takes to hear back from them, once we've pinged them.
+  pub latency: Option<Duration>,
+  // The task they're in the middle of, if any.
+  pub interaction: Option<Interaction>,
+  pub cosmetics: Cosmetics,
+  // Hasn't done anything in a whole day and night, so no one's waiting on
+  // them to vote.
+  pub afk: bool,
+  // Who they're passing themselves off as, if anyone.
+  pub disguise: Option<Disguise>,
+  pub shapeshift_cooldown: Duration,
 }
 
 impl Player {
@@ -866,11 +1979,42 @@ impl Player {
       impostor: false,
       tasks: vec![],
       velocity: Velocity::default(),
+      kill_cooldown: Duration::from_secs(0),
+      emergency_meetings_called: 0,
+      in_vent: None,
+      role: Role::Crewmate,
+      shielded: false,
+      status_effects: vec![],
+      ready: false,
+      disconnected: false,
+      reconnect_time_remaining: Duration::from_secs(0),
+      latency: None,
+      interaction: None,
+      cosmetics: Cosmetics::default(),
+      afk: false,
+      disguise: None,
+      shapeshift_cooldown: Duration::from_secs(0),
     }
   }
 
   pub fn eligable_to_vote(&self) -> bool {
-    !self.dead
+    !self.dead && !self.afk
+  }
+
+  pub fn is_ghost(&self) -> bool {
+    self.dead
+  }
+
+  // Whether this player should be drawn for the given viewer. Ghosts can only
+  // be seen by other ghosts, and by spectators (who have no player).
+  pub fn is_visible_to(&self, viewer: Option<&Player>) -> bool {
+    if !self.is_ghost() {
+      return true;
+    }
+    match viewer {
+      None => true,
+      Some(viewer) => viewer.is_ghost(),
+    }
   }
 
   pub fn radius() -> f64 {
@@ -882,26 +2026,35 @@ impl Player {
       return None;
     }
     if self.impostor {
-      Some(settings.impostor_vision)
+      Some(settings.vision_radius.impostors)
     } else {
-      Some(settings.crew_vision)
+      Some(settings.vision_radius.crew * settings.game_mode.crew_vision())
     }
   }
-
-  pub fn can_see(&self, settings: &Settings, status: &GameStatus, other: &Position) -> bool {
-    let vision = match self.vision(&settings, status) {
-      None => return true,
-      Some(v) => v,
-    };
-    let distance = self.position.distance(other);
-    distance <= vision
-  }
 }
 
 #[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
 pub struct DeadBody {
   pub color: Color,
   pub position: Position,
+  // How long this body has been lying around.
+  pub age: Duration,
+  // Who did it, when the server knows. Never shown to players during the game.
+  pub killer: Option<UUID>,
+  // The impostor hauling it around, if any.
+  pub dragged_by: Option<UUID>,
+}
+
+impl DeadBody {
+  pub fn new(color: Color, position: Position) -> Self {
+    DeadBody {
+      color,
+      position,
+      age: Duration::from_secs(0),
+      killer: None,
+      dragged_by: None,
+    }
+  }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
@@ -909,7 +2062,15 @@ pub enum GameStatus {
   Connecting,
   Lobby,
   Playing(PlayState),
+  // Everything's frozen until the game can carry on from play_state.
+  Paused {
+    reason: PauseReason,
+    resumes_in: Option<Duration>,
+    play_state: PlayState,
+  },
   Won(Team),
+  // Time ran out, and the room said that means no one wins.
+  Draw,
   Disconnected,
 }
 
@@ -919,8 +2080,16 @@ impl GameStatus {
       GameStatus::Connecting
       | GameStatus::Disconnected
       | GameStatus::Won(_)
+      | GameStatus::Draw
       | GameStatus::Lobby => {}
       GameStatus::Playing(PlayState::Night) => {}
+      GameStatus::Paused { resumes_in, .. } => {
+        if let Some(resumes_in) = resumes_in {
+          *resumes_in = resumes_in
+            .checked_sub(elapsed)
+            .unwrap_or_else(|| Duration::from_secs(0));
+        }
+      }
       GameStatus::Playing(PlayState::Voting(state)) => {
         state.time_remaining = state
           .time_remaining
@@ -947,7 +2116,9 @@ impl GameStatus {
       GameStatus::Connecting => matches!(other, GameStatus::Connecting),
       GameStatus::Lobby => matches!(other, GameStatus::Lobby),
       GameStatus::Playing(_) => matches!(other, GameStatus::Playing(_)),
+      GameStatus::Paused { .. } => matches!(other, GameStatus::Paused { .. }),
       GameStatus::Won(_) => matches!(other, GameStatus::Won(_)),
+      GameStatus::Draw => matches!(other, GameStatus::Draw),
       GameStatus::Disconnected => matches!(other, GameStatus::Disconnected),
     }
   }
@@ -957,7 +2128,9 @@ impl GameStatus {
       GameStatus::Connecting => false,
       GameStatus::Lobby => false,
       GameStatus::Playing(_) => false,
+      GameStatus::Paused { .. } => false,
       GameStatus::Won(_) => true,
+      GameStatus::Draw => true,
       GameStatus::Disconnected => true,
     }
   }
@@ -974,10 +2147,105 @@ pub enum PlayState {
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct VotingState {
   pub votes: BTreeMap<UUID, VoteTarget>,
+  // Players whose votes can't be changed anymore.
+  pub locked_in: BTreeSet<UUID>,
+  pub phase: DayPhase,
+  // Time left in the current phase.
   pub time_remaining: Duration,
+  pub context: MeetingContext,
+  // During a runoff, the only targets that can be voted for.
+  pub candidates: Option<Vec<VoteTarget>>,
+  // Picked when the meeting starts, so that everyone breaks ties the same way.
+  pub tie_breaker: u64,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum DayPhase {
+  // Talk it over. No voting yet!
+  Discussion,
+  Voting,
+}
+
+// Why everyone got dragged into a meeting.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum MeetingReason {
+  EmergencyButton { caller: UUID },
+  ReportedBody { reporter: UUID, body_color: Color },
+}
+
+// What we know about how a meeting came about. Kept around for stats,
+// replays and moderators, so that fishy self-reports can be flagged.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MeetingContext {
+  pub reason: MeetingReason,
+  // How long the reported body had been lying there.
+  pub time_since_kill: Option<Duration>,
+  // Who made the reported body, if the server knows.
+  pub killer: Option<UUID>,
+}
+
+impl MeetingContext {
+  pub fn new(reason: MeetingReason, bodies: &[DeadBody]) -> Self {
+    let body = match reason {
+      MeetingReason::EmergencyButton { .. } => None,
+      MeetingReason::ReportedBody { body_color, .. } => {
+        bodies.iter().find(|b| b.color == body_color)
+      }
+    };
+    MeetingContext {
+      reason,
+      time_since_kill: body.map(|b| b.age),
+      killer: body.and_then(|b| b.killer),
+    }
+  }
+
+  pub fn reporter(&self) -> Option<UUID> {
+    match self.reason {
+      MeetingReason::EmergencyButton { .. } => None,
+      MeetingReason::ReportedBody { reporter, .. } => Some(reporter),
+    }
+  }
+
+  // The killer reported their own handiwork.
+  pub fn is_self_report(&self) -> bool {
+    self.reporter().is_some() && self.reporter() == self.killer
+  }
 }
 
 impl VotingState {
+  pub fn accepting_votes(&self) -> bool {
+    self.phase == DayPhase::Voting
+  }
+
+  pub fn can_vote_for(&self, target: VoteTarget) -> bool {
+    match &self.candidates {
+      None => true,
+      Some(candidates) => candidates.contains(&target),
+    }
+  }
+
+  // A new round of voting between just the given candidates.
+  fn runoff(&self, candidates: Vec<VoteTarget>, time_remaining: Duration) -> Self {
+    Self {
+      votes: BTreeMap::new(),
+      locked_in: BTreeSet::new(),
+      phase: DayPhase::Voting,
+      time_remaining,
+      context: self.context,
+      candidates: Some(candidates),
+      tie_breaker: self.tie_breaker,
+    }
+  }
+
+  // Whether the given player's vote is final. Without vote changes, any vote is.
+  pub fn is_locked_in(&self, voter: UUID, settings: &Settings) -> bool {
+    if settings.allow_vote_changes {
+      self.locked_in.contains(&voter)
+    } else {
+      self.votes.contains_key(&voter)
+    }
+  }
+
   pub fn get_votes_against(&self) -> BTreeMap<VoteTarget, Vec<UUID>> {
     let mut votes_against = BTreeMap::new();
     for (uuid, target) in self.votes.iter() {
@@ -998,118 +2266,570 @@ pub enum VoteTarget {
 
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct TallyingState {
-  pub votes_against: BTreeMap<VoteTarget, Vec<UUID>>,
+  pub tally: ElectionTally,
   pub time_remaining: Duration,
+  // If set, ties are broken by picking one of the tied targets with this.
+  pub tie_breaker: Option<u64>,
 }
 
 impl TallyingState {
-  fn new(votes_against: BTreeMap<VoteTarget, Vec<UUID>>) -> Self {
+  fn new(tally: ElectionTally) -> Self {
     Self {
-      votes_against,
+      tally,
       time_remaining: Duration::from_secs(10),
+      tie_breaker: None,
     }
   }
 
+  fn with_tie_breaker(mut self, tie_breaker: u64) -> Self {
+    self.tie_breaker = Some(tie_breaker);
+    self
+  }
+
   fn is_over(&self) -> bool {
     self.time_remaining <= Duration::from_secs(0)
   }
 
   fn determine_outcome_of_election(&self) -> VoteOutcome {
-    // The winner is the one with the most votes!
-    let mut targets_and_votes = self
-      .votes_against
-      .iter()
-      .map(|(target, voters)| {
-        let target_outcome = match target {
-          VoteTarget::Skip => VoteOutcome::Skip,
-          VoteTarget::Player { uuid } => VoteOutcome::Player { uuid: *uuid },
-        };
-        (target_outcome, voters.len())
-      })
-      .collect::<Vec<_>>();
-    targets_and_votes.sort_by_key(|(_target, count)| *count);
-    let mut most_voted = targets_and_votes.into_iter().rev();
-    if let Some((winner, winner_votes)) = most_voted.next() {
-      if let Some((_runner_up, runner_up_votes)) = most_voted.next() {
-        if runner_up_votes == winner_votes {
-          return VoteOutcome::Tie;
-        }
+    let tied = self.tally.tied();
+    match self.tie_breaker {
+      Some(tie_breaker) if !tied.is_empty() => {
+        tied[(tie_breaker % tied.len() as u64) as usize].into()
       }
-      return winner;
+      _ => self.tally.winner(),
     }
-    // If no one voted, it's skip.
-    VoteOutcome::Tie
   }
 }
 
 #[cfg(test)]
 mod tests {
+  use crate::test_util::*;
   use crate::*;
+  use core::time::Duration;
+  use std::borrow::Cow;
 
   #[test]
-  fn test_vote_outcome_no_votes() {
-    let state = TallyingState::new(Default::default());
-    assert_eq!(state.determine_outcome_of_election(), VoteOutcome::Tie);
+  fn test_vote_outcome_tie_broken_among_tied() {
+    let p1 = UUID::random();
+    let p2 = UUID::random();
+    let tied = [VoteTarget::Player { uuid: p1 }, VoteTarget::Skip];
+    for tie_breaker in 0..4 {
+      let votes_against = vec![(tied[0], vec![p1]), (tied[1], vec![p2])];
+      let tally = ElectionTally::from_votes_against(votes_against.into_iter().collect());
+      let state = TallyingState::new(tally).with_tie_breaker(tie_breaker);
+      assert_ne!(state.determine_outcome_of_election(), VoteOutcome::Tie);
+    }
   }
 
   #[test]
-  fn test_vote_outcome_one_vote_skip() {
-    let uuid = UUID::random();
-    let state = TallyingState::new(vec![(VoteTarget::Skip, vec![uuid])].into_iter().collect());
-    assert_eq!(state.determine_outcome_of_election(), VoteOutcome::Skip);
+  fn test_tied_vote_goes_to_runoff() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate; 3]);
+    state.settings.tie_policy = TiePolicy::Runoff;
+    state.start_meeting(MeetingReason::EmergencyButton { caller: uuids[0] });
+    state.simulate(state.settings.discussion_time + TICK);
+    let (a, b) = (
+      VoteTarget::Player { uuid: uuids[0] },
+      VoteTarget::Player { uuid: uuids[1] },
+    );
+    state.note_vote(uuids[0], b).unwrap();
+    state.note_vote(uuids[1], a).unwrap();
+    state.note_vote(uuids[2], VoteTarget::Skip).unwrap();
+    state.simulate(Duration::from_millis(16));
+    match &state.status {
+      GameStatus::Playing(PlayState::Voting(day_state)) => {
+        assert!(day_state.can_vote_for(a));
+        assert!(day_state.can_vote_for(VoteTarget::Skip));
+      }
+      status => panic!("Expected a runoff, got {:?}", status),
+    }
+    // Only the tied targets are on the ballot.
+    assert!(state
+      .note_vote(uuids[0], VoteTarget::Player { uuid: uuids[2] })
+      .is_err());
   }
 
   #[test]
-  fn test_vote_outcome_one_vote_player() {
-    let uuid = UUID::random();
-    let state = TallyingState::new(
-      vec![(VoteTarget::Player { uuid }, vec![uuid])]
-        .into_iter()
-        .collect(),
+  fn test_start_info_assigns_configured_impostor_count() {
+    let mut state = GameState::new();
+    state.settings.impostor_count = 2;
+    for _ in 0..5 {
+      let uuid = UUID::random();
+      let player = Player::new(
+        uuid,
+        "Test Player".to_string(),
+        Color::Red,
+        Position { x: 0.0, y: 0.0 },
+      );
+      state.players.insert(uuid, player);
+    }
+    let start_info = state.get_game_start_info(GameRng::random_seed());
+    let num_impostors = start_info
+      .assignments
+      .iter()
+      .filter(|(_, info)| info.team == Team::Impostors)
+      .count();
+    assert_eq!(num_impostors, 2);
+  }
+
+  #[test]
+  fn test_the_same_seed_deals_the_same_game() {
+    let mut state = GameState::new();
+    state.status = GameStatus::Lobby;
+    for i in 0..5 {
+      let uuid = UUID::random();
+      let position = state.map.get_spawn_in_position(&mut state.rng);
+      let player = Player::new(uuid, "Test Player".to_string(), Color::all()[i], position);
+      state.players.insert(uuid, player);
+    }
+    let start_info = state.get_game_start_info(7);
+    assert_eq!(state.get_game_start_info(7), start_info);
+    assert_ne!(state.get_game_start_info(8), start_info);
+
+    // Playing the game back from the seed gets the same tie breakers too.
+    let mut replayed = state.clone();
+    for state in [&mut state, &mut replayed].iter_mut() {
+      state.note_game_started(&start_info).unwrap();
+      state.start_meeting(MeetingReason::EmergencyButton {
+        caller: start_info.assignments[0].0,
+      });
+    }
+    assert_eq!(state.status, replayed.status);
+    assert_eq!(state.rng, replayed.rng);
+  }
+
+  #[test]
+  fn test_start_info_shares_common_tasks() {
+    let mut state = GameState::new();
+    state.settings.short_tasks = 2;
+    state.settings.long_tasks = 1;
+    state.settings.common_tasks = 2;
+    for i in 0..3 {
+      let uuid = UUID::random();
+      let position = Position { x: 0.0, y: 0.0 };
+      let player = Player::new(uuid, "Test Player".to_string(), Color::all()[i], position);
+      state.players.insert(uuid, player);
+    }
+    let start_info = state.get_game_start_info(GameRng::random_seed());
+    let crew: Vec<&PlayerStartInfo> = start_info
+      .assignments
+      .iter()
+      .map(|(_, info)| info)
+      .filter(|info| info.team == Team::Crew)
+      .collect();
+    for info in crew.iter() {
+      assert_eq!(info.tasks.len(), 5);
+      assert_eq!(info.tasks[..2], crew[0].tasks[..2]);
+    }
+    // Impostors only get fake tasks.
+    for (_, info) in start_info.assignments.iter() {
+      let all_fake = info.tasks.iter().all(|t| t.fake);
+      assert_eq!(all_fake, info.team == Team::Impostors);
+    }
+  }
+
+  #[test]
+  fn test_reporting_a_body_starts_a_meeting() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate]);
+    let reporter = uuids[0];
+    let body = DeadBody::new(
+      Color::Blue,
+      Position {
+        x: 1000.0,
+        y: 100.0,
+      },
     );
+    state.bodies.push(body);
+
+    assert!(state
+      .note_body_reported(reporter, Color::Blue, None)
+      .is_err());
+    state.players.get_mut(&reporter).unwrap().position = body.position;
+    state
+      .note_body_reported(reporter, Color::Blue, None)
+      .unwrap();
+    match &state.status {
+      GameStatus::Playing(PlayState::Voting(voting)) => assert_eq!(
+        voting.context.reason,
+        MeetingReason::ReportedBody {
+          reporter,
+          body_color: Color::Blue
+        }
+      ),
+      status => panic!("Expected a meeting, got {:?}", status),
+    }
+    assert!(state.bodies.is_empty());
+  }
+
+  #[test]
+  fn test_frame_rate_does_not_change_where_players_end_up() {
+    let mut states: Vec<GameState> = (0..2).map(|_| GameState::new()).collect();
+    let uuid = UUID::random();
+    for state in states.iter_mut() {
+      state.status = GameStatus::Lobby;
+      let position = Position { x: 600.0, y: 300.0 };
+      let mut player = Player::new(uuid, "Test Player".to_string(), Color::Red, position);
+      player.velocity = Velocity { dx: 2.0, dy: 0.0 };
+      state.players.insert(uuid, player);
+    }
+    // One second at 144fps, and one at 30fps.
+    for _ in 0..144 {
+      states[0].simulate(Duration::from_secs(1) / 144);
+    }
+    for _ in 0..30 {
+      states[1].simulate(Duration::from_secs(1) / 30);
+    }
+    assert_eq!(states[0].position_history.tick, 62);
+    assert_eq!(states[0].players[&uuid], states[1].players[&uuid]);
+  }
+
+  #[test]
+  fn test_self_reports_are_flagged() {
+    let roles = [
+      Role::Impostor,
+      Role::Crewmate,
+      Role::Crewmate,
+      Role::Crewmate,
+    ];
+    let (mut state, uuids) = game_with(&roles);
+    state.note_kill(uuids[0], uuids[1], None).unwrap();
+    state.simulate(Duration::from_secs(4));
+    let victim_color = state.players[&uuids[1]].color;
+    state
+      .note_body_reported(uuids[0], victim_color, None)
+      .unwrap();
+    match &state.status {
+      GameStatus::Playing(PlayState::Voting(voting)) => {
+        assert!(voting.context.is_self_report());
+        assert_eq!(voting.context.killer, Some(uuids[0]));
+        assert_eq!(voting.context.time_since_kill, Some(Duration::from_secs(4)));
+      }
+      status => panic!("Expected a meeting, got {:?}", status),
+    }
+
+    state.start_meeting(MeetingReason::EmergencyButton { caller: uuids[0] });
+    match &state.status {
+      GameStatus::Playing(PlayState::Voting(voting)) => {
+        assert!(!voting.context.is_self_report());
+        assert_eq!(voting.context.reporter(), None);
+      }
+      status => panic!("Expected a meeting, got {:?}", status),
+    }
+  }
+
+  #[test]
+  fn test_meetings_open_with_discussion() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate]);
+    let caller = uuids[0];
+    state.start_meeting(MeetingReason::EmergencyButton { caller });
+
+    let phase = |state: &GameState| match &state.status {
+      GameStatus::Playing(PlayState::Voting(day_state)) => Some(day_state.phase),
+      _ => None,
+    };
+    // Nobody has voted, but voting can't be over before it's begun.
+    state.simulate(Duration::from_millis(16));
+    assert_eq!(phase(&state), Some(DayPhase::Discussion));
+    state.simulate(state.settings.discussion_time + TICK);
+    assert_eq!(phase(&state), Some(DayPhase::Voting));
+  }
+
+  #[test]
+  fn test_ejection_result_confirms_impostors() {
+    let (mut state, uuids) = game_with(&[Role::Impostor, Role::Crewmate]);
+    let (impostor, voter) = (uuids[0], uuids[1]);
+    let mut votes_against = std::collections::BTreeMap::new();
+    votes_against.insert(VoteTarget::Player { uuid: impostor }, vec![voter]);
+    state.status = GameStatus::Playing(PlayState::TallyingVotes(TallyingState {
+      tally: ElectionTally::from_votes_against(votes_against.clone()),
+      time_remaining: Duration::from_secs(0),
+      tie_breaker: None,
+    }));
+    state.simulate(Duration::from_millis(16));
     assert_eq!(
-      state.determine_outcome_of_election(),
-      VoteOutcome::Player { uuid }
+      state.ejection,
+      Some(EjectionResult {
+        ejected: Some(impostor),
+        was_impostor: Some(true),
+        votes_against,
+      })
     );
   }
 
   #[test]
-  fn test_vote_outcome_one_majority_vote() {
-    let guilty = UUID::random();
-    let p1 = UUID::random();
-    let p2 = UUID::random();
-    let p3 = UUID::random();
-    let state = TallyingState::new(
-      vec![
-        (VoteTarget::Player { uuid: guilty }, vec![p1, p2, p3]),
-        (VoteTarget::Player { uuid: p1 }, vec![guilty]),
-      ]
-      .into_iter()
-      .collect(),
-    );
-    assert_eq!(
-      state.determine_outcome_of_election(),
-      VoteOutcome::Player { uuid: guilty }
+  fn test_ejecting_the_last_impostor_ends_the_game() {
+    let (mut state, uuids) = game_with(&[Role::Impostor, Role::Crewmate, Role::Crewmate]);
+    let impostor = uuids[0];
+    let outcome = VoteOutcome::Player { uuid: impostor };
+    let mut viewing = ViewOutcomeState::new(outcome, &state);
+    viewing.time_remaining = Duration::from_secs(0);
+    state.status = GameStatus::Playing(PlayState::ViewingOutcome(viewing));
+    state.simulate(TICK);
+    assert_eq!(state.status, GameStatus::Won(Team::Crew));
+    assert!(state
+      .drain_events()
+      .contains(&GameEvent::GameWon { team: Team::Crew }));
+  }
+
+  #[test]
+  fn test_changing_votes_until_locked_in() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate, Role::Crewmate]);
+    state.settings.allow_vote_changes = true;
+    let (voter, other) = (uuids[0], uuids[1]);
+    state.start_meeting(MeetingReason::EmergencyButton { caller: voter });
+    state.simulate(state.settings.discussion_time + TICK);
+
+    for uuid in [voter, other].iter() {
+      state.note_vote(*uuid, VoteTarget::Skip).unwrap();
+    }
+    // Everyone's voted, but they can still change their minds.
+    state
+      .note_vote(voter, VoteTarget::Player { uuid: other })
+      .unwrap();
+    state.simulate(Duration::from_millis(16));
+    match &state.status {
+      GameStatus::Playing(PlayState::Voting(day_state)) => {
+        assert_eq!(day_state.votes[&voter], VoteTarget::Player { uuid: other })
+      }
+      status => panic!("Expected voting to continue, got {:?}", status),
+    }
+
+    state.note_vote_locked_in(voter).unwrap();
+    assert!(state.note_vote(voter, VoteTarget::Skip).is_err());
+    state.note_vote_locked_in(other).unwrap();
+    state.simulate(Duration::from_millis(16));
+    assert!(matches!(
+      state.status,
+      GameStatus::Playing(PlayState::TallyingVotes(_))
+    ));
+  }
+
+  #[test]
+  fn test_tasks_land_somewhere_even_on_cramped_maps() {
+    let mut rng = GameRng::new(3);
+    let on_spawn_ring = |map: &Map, position: Position| {
+      let ring = map.spawn_area;
+      (position.distance(&ring.center) - ring.radius).abs() < 0.001
+    };
+    // Too small to pick a spot well away from the walls.
+    let mut tiny = MapRegistry::get(MapId::Lobby).unwrap();
+    tiny.task_spots = Cow::Borrowed(&[]);
+    tiny.width = 50.0;
+    tiny.height = 50.0;
+    let position = tiny.gen_task_position(&mut rng);
+    assert!(on_spawn_ring(&tiny, position));
+
+    // Or with furniture from wall to wall.
+    let mut packed = MapRegistry::get(MapId::Lobby).unwrap();
+    packed.task_spots = Cow::Borrowed(&[]);
+    packed.static_geometry = Cow::Owned(vec![Shape::Rect {
+      top_left: Position { x: 0.0, y: 0.0 },
+      width: packed.width,
+      height: packed.height,
+      fill_color: Cow::Borrowed("#000"),
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+    }]);
+    let position = packed.gen_task_position(&mut rng);
+    assert!(on_spawn_ring(&packed, position));
+  }
+
+  #[test]
+  fn test_bodies_decay() {
+    let mut state = GameState::new();
+    state.settings.body_decay_time = Some(Duration::from_secs(5));
+    state.status = GameStatus::Playing(PlayState::Night);
+    let position = Position { x: 100.0, y: 100.0 };
+    state.bodies.push(DeadBody::new(Color::Red, position));
+    state.simulate(Duration::from_secs(4));
+    assert_eq!(state.bodies.len(), 1);
+    state.simulate(Duration::from_secs(1) + TICK);
+    assert!(state.bodies.is_empty());
+  }
+
+  #[test]
+  fn test_kills_are_validated() {
+    let (mut state, uuids) = game_at(&[
+      (Role::Impostor, Position { x: 100.0, y: 100.0 }),
+      (
+        Role::Crewmate,
+        Position {
+          x: 1000.0,
+          y: 100.0,
+        },
+      ),
+      (
+        Role::Crewmate,
+        Position {
+          x: 1000.0,
+          y: 100.0,
+        },
+      ),
+    ]);
+    let (impostor, victim, bystander) = (uuids[0], uuids[1], uuids[2]);
+
+    // Too far away.
+    assert!(state.note_kill(impostor, victim, None).is_err());
+    // Crewmates can't kill.
+    assert!(state.note_kill(bystander, victim, None).is_err());
+    state.players.get_mut(&impostor).unwrap().position = Position { x: 990.0, y: 100.0 };
+    state.note_kill(impostor, victim, None).unwrap();
+    assert!(state.players[&victim].dead);
+    assert_eq!(state.bodies.len(), 1);
+  }
+
+  #[test]
+  fn test_ghosts_pass_through_furniture() {
+    let (table, table_radius) = match GameState::new().map.static_geometry[0] {
+      Shape::Circle { center, radius, .. } => (center, radius),
+      _ => panic!("The table should be round"),
+    };
+    let start = Position {
+      x: table.x - 100.0,
+      y: table.y + 20.0,
+    };
+    let (mut state, uuids) = game_at(&[(Role::Crewmate, start), (Role::Crewmate, start)]);
+    let (living, ghost) = (uuids[0], uuids[1]);
+    for player in state.players.values_mut() {
+      player.velocity = Velocity { dx: 2.0, dy: 0.0 };
+    }
+    state.players.get_mut(&ghost).unwrap().dead = true;
+    for _ in 0..60 {
+      state.simulate(Duration::from_millis(16));
+      // The living player never ends up inside the table...
+      let distance = state.players[&living].position.distance(&table);
+      assert!(distance >= table_radius + Player::radius() - 1.0);
+    }
+    // ...but the ghost went right through it.
+    assert!(state.players[&ghost].position.x > table.x);
+  }
+
+  #[test]
+  fn test_players_slide_rather_than_stick() {
+    let wall = Shape::Wall {
+      start: Position { x: 0.0, y: 100.0 },
+      end: Position { x: 200.0, y: 100.0 },
+      thickness: 10.0,
+      color: Cow::Borrowed("#000"),
+    };
+    // Pushing into the wall at an angle keeps all of the sideways movement.
+    let start = Position { x: 50.0, y: 80.0 };
+    let moved = wall.collide(start, 10.0, Velocity { dx: 10.0, dy: 10.0 }, 0.0);
+    let end = start.move_by(moved);
+    assert!((end.x - 60.0).abs() < 0.001);
+    assert!((end.y - 85.0).abs() < 0.001);
+    // Even when starting right up against it.
+    let moved = wall.collide(end, 10.0, Velocity { dx: 10.0, dy: 10.0 }, 0.0);
+    assert!((moved.dx - 10.0).abs() < 0.001);
+    assert!(moved.dy.abs() < 0.001);
+    // Friction takes some of it away.
+    let moved = wall.collide(end, 10.0, Velocity { dx: 10.0, dy: 10.0 }, 0.5);
+    assert!((moved.dx - 5.0).abs() < 0.001);
+    // Walking off the end of the wall goes around its rounded end.
+    let past_the_end = Position { x: 210.0, y: 85.0 };
+    assert!(!wall.collides_with(past_the_end, 10.0));
+    assert!(wall.collides_with(Position { x: 100.0, y: 86.0 }, 10.0));
+
+    // Walking into the side of something round slides around it too.
+    let table = Shape::Circle {
+      radius: 50.0,
+      center: Position { x: 0.0, y: 0.0 },
+      fill_color: Cow::Borrowed("#000"),
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+    };
+    let start = Position { x: -60.0, y: 10.0 };
+    let moved = table.collide(start, 10.0, Velocity { dx: 10.0, dy: 0.0 }, 0.0);
+    let end = start.move_by(moved);
+    assert!(end.y > start.y);
+    assert!(end.distance(&Position { x: 0.0, y: 0.0 }) >= 60.0 - 0.001);
+  }
+
+  #[test]
+  fn test_big_steps_dont_go_through_thin_walls() {
+    let mut map = MapRegistry::get(MapId::Lobby).unwrap();
+    let wall = |start, end| Shape::Wall {
+      start,
+      end,
+      thickness: 2.0,
+      color: Cow::Borrowed("#000"),
+    };
+    // Two walls meeting in a corner.
+    let corner = Position { x: 600.0, y: 500.0 };
+    map.static_geometry = Cow::Owned(vec![
+      wall(Position { x: 600.0, y: 300.0 }, corner),
+      wall(Position { x: 400.0, y: 500.0 }, corner),
+    ]);
+    map.reindex();
+    let doors = DoorState::default();
+    let mut player = Player::new(
+      UUID::random(),
+      "Test Player".to_string(),
+      Color::Red,
+      Position { x: 560.0, y: 400.0 },
     );
+    // Far further in one step than the wall is thick.
+    let end = map.move_player(&player, Velocity { dx: 200.0, dy: 0.0 }, &doors);
+    assert!(end.x < 600.0);
+
+    // Heading into the corner slides into it along one wall, but doesn't get
+    // out through the other.
+    for _ in 0..20 {
+      player.position = map.move_player(&player, Velocity { dx: 30.0, dy: 30.0 }, &doors);
+      assert!(player.position.x < 600.0 - Player::radius() + 0.001);
+      assert!(player.position.y < 500.0 - Player::radius() + 0.001);
+    }
+    assert!(player.position.distance(&corner) < 20.0);
   }
 
   #[test]
-  fn test_vote_outcome_tie() {
-    let p1 = UUID::random();
-    let p2 = UUID::random();
-    let p3 = UUID::random();
-    let p4 = UUID::random();
-    let p5 = UUID::random();
-    let state = TallyingState::new(
-      vec![
-        (VoteTarget::Player { uuid: p1 }, vec![p1, p2]),
-        (VoteTarget::Player { uuid: p2 }, vec![p3, p4]),
-        (VoteTarget::Skip, vec![p5]),
-      ]
-      .into_iter()
-      .collect(),
+  fn test_players_slide_along_walls() {
+    let wall = Shape::Rect {
+      top_left: Position { x: 100.0, y: 0.0 },
+      width: 20.0,
+      height: 200.0,
+      fill_color: Cow::Borrowed("#000"),
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+    };
+    // Walking diagonally into the wall stops short of it, and slides along.
+    let start = Position { x: 85.0, y: 100.0 };
+    let moved = wall.collide(start, 10.0, Velocity { dx: 10.0, dy: 10.0 }, 0.0);
+    let end = start.move_by(moved);
+    assert!((end.x - 90.0).abs() < 0.001);
+    assert!((end.y - 110.0).abs() < 0.001);
+    assert!(!wall.collides_with(end, 9.9));
+    // Walking away from it is fine.
+    let away = Velocity { dx: -5.0, dy: 0.0 };
+    assert_eq!(wall.collide(end, 10.0, away, 0.0), away);
+    assert!(wall.blocks_line_of_sight(start, Position { x: 200.0, y: 100.0 }));
+    assert!(!wall.blocks_line_of_sight(start, Position { x: 50.0, y: 300.0 }));
+
+    let triangle = Shape::Polygon {
+      points: Cow::Borrowed(&[
+        Position { x: 0.0, y: 0.0 },
+        Position { x: 100.0, y: 0.0 },
+        Position { x: 0.0, y: 100.0 },
+      ]),
+      fill_color: Cow::Borrowed("#000"),
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+    };
+    assert!(triangle.collides_with(Position { x: 20.0, y: 20.0 }, 1.0));
+    assert!(!triangle.collides_with(Position { x: 80.0, y: 80.0 }, 10.0));
+    // Heading straight at the slanted side gets stopped at it.
+    let start = Position { x: 80.0, y: 80.0 };
+    let moved = triangle.collide(
+      start,
+      10.0,
+      Velocity {
+        dx: -40.0,
+        dy: -40.0,
+      },
+      0.0,
     );
-    assert_eq!(state.determine_outcome_of_election(), VoteOutcome::Tie);
+    let end = start.move_by(moved);
+    assert!(end.x > 50.0 && end.y > 50.0);
+    assert!(!triangle.collides_with(end, 9.9));
   }
 }
 
@@ -1133,6 +2853,11 @@ impl ViewOutcomeState {
       VoteOutcome::Tie => "Vote was a tie. No one went out the airlock.".to_string(),
       VoteOutcome::Skip => "Voted to skip. No one went out the airlock.".to_string(),
       VoteOutcome::Player { uuid } => match game_state.players.get(&uuid) {
+        Some(player) if game_state.settings.confirm_ejects => format!(
+          "{} was thrown out the airlock. They were {}an impostor.",
+          player.name,
+          if player.impostor { "" } else { "not " }
+        ),
         Some(player) => format!("{} was thrown out the airlock", player.name),
         None => "Can't find who to throw out the airlock? Disconnected player?".to_string(),
       },
@@ -1153,6 +2878,38 @@ impl ViewOutcomeState {
   }
 }
 
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EjectionResult {
+  pub ejected: Option<UUID>,
+  // Only known when the settings confirm ejects.
+  pub was_impostor: Option<bool>,
+  pub votes_against: BTreeMap<VoteTarget, Vec<UUID>>,
+}
+
+impl EjectionResult {
+  fn new(
+    outcome: VoteOutcome,
+    votes_against: BTreeMap<VoteTarget, Vec<UUID>>,
+    game_state: &GameState,
+  ) -> Self {
+    let ejected = match outcome {
+      VoteOutcome::Player { uuid } => Some(uuid),
+      VoteOutcome::Tie | VoteOutcome::Skip => None,
+    };
+    let was_impostor = match ejected {
+      Some(uuid) if game_state.settings.confirm_ejects => {
+        game_state.players.get(&uuid).map(|p| p.impostor)
+      }
+      _ => None,
+    };
+    Self {
+      ejected,
+      was_impostor,
+      votes_against,
+    }
+  }
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Team {
   Crew,
@@ -0,0 +1,120 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+// A game that's still going, saved so that the next server to start up can
+// pick it up where this one left off. Rather than saving the state itself, we
+// save everything that went into it and play it back.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedGame {
+  pub recording: RecordedGame,
+  // How far the game had gotten, which may be well after the last thing
+  // anyone said.
+  pub tick: u64,
+  // These are secret, so the recording doesn't have them, but players need
+  // them to get back into the game once it's been restored.
+  rejoin_tokens: BTreeMap<UUID, UUID>,
+}
+
+impl SavedGame {
+  pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(bincode::serialize(self)?)
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+    Ok(bincode::deserialize(bytes)?)
+  }
+}
+
+// Swallows everything the server says while it's catching up on a saved game,
+// since no one is listening yet.
+struct Restoring;
+impl Broadcaster for Restoring {
+  fn broadcast(&self, _: &ServerToClientMessage) -> Result<(), Box<dyn Error>> {
+    Ok(())
+  }
+
+  fn send_to_player(&self, _: &UUID, _: &ServerToClientMessage) -> Result<(), Box<dyn Error>> {
+    Ok(())
+  }
+
+  fn can_send_to_players(&self) -> bool {
+    false
+  }
+}
+
+impl GameServer {
+  // Only games that are being recorded can be saved.
+  pub fn save(&self) -> Option<SavedGame> {
+    Some(SavedGame {
+      recording: self.recording()?.clone(),
+      tick: self.state.position_history.tick,
+      rejoin_tokens: self.rejoin_tokens.clone(),
+    })
+  }
+
+  // Plays a saved game back up to where it was saved. Everyone who was
+  // connected then is treated as having just lost their connection, so they
+  // have a little while to rejoin.
+  pub fn restore(
+    saved: SavedGame,
+    broadcaster: Box<dyn Broadcaster>,
+  ) -> Result<GameServer, Box<dyn Error>> {
+    let mut game_server = GameServer::new(Box::new(Restoring), false);
+    game_server.version = saved.recording.version.clone();
+    game_server.state.status = GameStatus::Lobby;
+    game_server.state.rng = GameRng::new(saved.recording.seed);
+    game_server.state.set_map(saved.recording.map.clone());
+    for entry in saved.recording.entries.iter() {
+      game_server.catch_up_to(entry.tick)?;
+      match &entry.event {
+        RecordingEvent::Message(message) => game_server.handle_message_playback(message)?,
+        RecordingEvent::Disconnect(uuid) => game_server.disconnected(*uuid)?,
+      }
+    }
+    game_server.catch_up_to(saved.tick)?;
+    if game_server.state.position_history.tick != saved.tick {
+      return Err(
+        format!(
+          "Saved game finished at tick {} before reaching tick {}",
+          game_server.state.position_history.tick, saved.tick
+        )
+        .into(),
+      );
+    }
+
+    game_server.broadcaster = broadcaster;
+    game_server.recording = Some(saved.recording);
+    game_server.rejoin_tokens = saved.rejoin_tokens;
+    for connection in game_server.heartbeats.connections() {
+      game_server.disconnected(connection)?;
+    }
+    Ok(game_server)
+  }
+
+  fn catch_up_to(&mut self, tick: u64) -> Result<(), Box<dyn Error>> {
+    while self.state.position_history.tick < tick && !self.state.status.finished() {
+      self.simulate(TICK)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Restoring;
+  use crate::*;
+
+  #[test]
+  fn test_games_without_recordings_cant_be_saved() {
+    let server = GameServer::new(Box::new(Restoring), false);
+    assert!(server.save().is_none());
+    let server = GameServer::new(Box::new(Restoring), true);
+    let saved = server.save().unwrap();
+    let saved = SavedGame::from_bytes(&saved.to_bytes().unwrap()).unwrap();
+    let restored = GameServer::restore(saved, Box::new(Restoring)).unwrap();
+    assert_eq!(restored.state.status, GameStatus::Lobby);
+    assert!(restored.state.players.is_empty());
+  }
+}
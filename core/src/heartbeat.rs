@@ -0,0 +1,133 @@
+use crate::*;
+use core::time::Duration;
+use instant::Instant;
+use std::collections::BTreeMap;
+
+// How we're hearing from a single connection.
+#[derive(Debug, Clone)]
+struct Heartbeat {
+  since_heard: Duration,
+  since_ping: Duration,
+  // The ping we're waiting to hear back about, and when we sent it.
+  outstanding: Option<(u64, Instant)>,
+  latency: Option<Duration>,
+}
+
+// Pings everyone every so often, to work out how laggy their connection is,
+// and to notice when they've gone quiet for long enough that they're
+// probably never coming back.
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeats {
+  connections: BTreeMap<UUID, Heartbeat>,
+  last_ping_id: u64,
+}
+
+// What needs doing about everyone's connections after some time has passed.
+#[derive(Debug, Default, PartialEq)]
+pub struct HeartbeatActions {
+  // Connections that are due a ping, and the id to send it with.
+  pub pings: Vec<(UUID, u64)>,
+  pub timed_out: Vec<UUID>,
+}
+
+impl Heartbeats {
+  pub const PING_INTERVAL: Duration = Duration::from_secs(1);
+  // Long enough that a hiccup in someone's wifi doesn't kick them out.
+  pub const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+  pub fn note_heard(&mut self, connection: UUID) {
+    let heartbeat = self.connections.entry(connection).or_insert(Heartbeat {
+      since_heard: Duration::from_secs(0),
+      // Ping them right away, so we know how they're doing from the start.
+      since_ping: Self::PING_INTERVAL,
+      outstanding: None,
+      latency: None,
+    });
+    heartbeat.since_heard = Duration::from_secs(0);
+  }
+
+  // Returns whether we'd heard from them at all.
+  pub fn remove(&mut self, connection: UUID) -> bool {
+    self.connections.remove(&connection).is_some()
+  }
+
+  // Everyone we're still hearing from.
+  pub fn connections(&self) -> Vec<UUID> {
+    self.connections.keys().copied().collect()
+  }
+
+  pub fn progress_time(&mut self, elapsed: Duration, now: Instant) -> HeartbeatActions {
+    let mut actions = HeartbeatActions::default();
+    for (connection, heartbeat) in self.connections.iter_mut() {
+      heartbeat.since_heard += elapsed;
+      heartbeat.since_ping += elapsed;
+      if heartbeat.since_heard >= Self::IDLE_TIMEOUT {
+        actions.timed_out.push(*connection);
+        continue;
+      }
+      if heartbeat.since_ping >= Self::PING_INTERVAL {
+        self.last_ping_id += 1;
+        heartbeat.since_ping = Duration::from_secs(0);
+        heartbeat.outstanding = Some((self.last_ping_id, now));
+        actions.pings.push((*connection, self.last_ping_id));
+      }
+    }
+    actions
+  }
+
+  // Returns the connection's latency, smoothed out over the last few pings so
+  // that it doesn't jump around too much. Pongs for pings we've given up on
+  // don't count.
+  pub fn note_pong(&mut self, connection: UUID, id: u64, now: Instant) -> Option<Duration> {
+    let heartbeat = self.connections.get_mut(&connection)?;
+    let sent_at = match heartbeat.outstanding {
+      Some((outstanding, sent_at)) if outstanding == id => sent_at,
+      _ => return heartbeat.latency,
+    };
+    heartbeat.outstanding = None;
+    let round_trip = now.duration_since(sent_at);
+    heartbeat.latency = Some(match heartbeat.latency {
+      None => round_trip,
+      Some(latency) => (latency * 7 + round_trip) / 8,
+    });
+    heartbeat.latency
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_quiet_connections_time_out() {
+    let mut heartbeats = Heartbeats::default();
+    let chatty = UUID::random();
+    let quiet = UUID::random();
+    heartbeats.note_heard(chatty);
+    heartbeats.note_heard(quiet);
+    let clock = VirtualClock::new();
+
+    let actions = heartbeats.progress_time(Duration::from_millis(16), clock.now());
+    assert_eq!(actions.pings.len(), 2);
+    assert!(actions.timed_out.is_empty());
+    let (_, id) = actions.pings.iter().find(|(c, _)| *c == chatty).unwrap();
+    clock.advance(Duration::from_millis(50));
+    assert_eq!(
+      heartbeats.note_pong(chatty, *id, clock.now()),
+      Some(Duration::from_millis(50))
+    );
+    assert_eq!(heartbeats.note_pong(quiet, 12345, clock.now()), None);
+
+    for _ in 0..9 {
+      heartbeats.note_heard(chatty);
+      let actions = heartbeats.progress_time(Duration::from_secs(1), clock.now());
+      assert!(actions.timed_out.is_empty());
+    }
+    heartbeats.note_heard(chatty);
+    let actions = heartbeats.progress_time(Duration::from_secs(1), clock.now());
+    assert_eq!(actions.timed_out, vec![quiet]);
+    assert!(heartbeats.remove(quiet));
+    assert!(!heartbeats.remove(quiet));
+  }
+}
@@ -0,0 +1,147 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+// Which way a player is trying to go. This is all that clients tell us about
+// their movement, the server works out where they actually end up.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MovementInput {
+  pub up: bool,
+  pub down: bool,
+  pub left: bool,
+  pub right: bool,
+}
+
+impl MovementInput {
+  pub fn velocity(&self, speed: f64) -> Velocity {
+    let mut dx = 0.0;
+    let mut dy = 0.0;
+    if self.up && !self.down {
+      dy = -speed
+    } else if self.down {
+      dy = speed
+    }
+    if self.left && !self.right {
+      dx = -speed
+    } else if self.right {
+      dx = speed
+    }
+    Velocity { dx, dy }
+  }
+}
+
+// Tells a player which of their inputs they're currently moving with, and
+// how long the server has had them moving with it, so that they can work out
+// which of their own moves the server hasn't caught up with yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputAck {
+  pub sequence: u64,
+  pub elapsed: Duration,
+}
+
+// Inputs that have come in from players, but haven't been simulated yet.
+#[derive(Default, Debug, Clone)]
+pub struct InputBuffer {
+  pending: BTreeMap<UUID, VecDeque<(u64, MovementInput)>>,
+  applied: BTreeMap<UUID, InputAck>,
+}
+
+impl InputBuffer {
+  // Nobody needs this many inputs queued up, so past this we drop the oldest.
+  const MAX_PENDING: usize = 32;
+
+  pub fn push(&mut self, uuid: UUID, sequence: u64, input: MovementInput) {
+    let pending = self.pending.entry(uuid).or_default();
+    if pending.len() >= Self::MAX_PENDING {
+      pending.pop_front();
+    }
+    pending.push_back((sequence, input));
+  }
+
+  pub fn remove(&mut self, uuid: UUID) {
+    self.pending.remove(&uuid);
+    self.applied.remove(&uuid);
+  }
+
+  pub fn ack(&self, uuid: UUID) -> Option<InputAck> {
+    self.applied.get(&uuid).copied()
+  }
+
+  // Everyone has been moving with their current input for a bit longer.
+  pub fn progress_time(&mut self, elapsed: Duration) {
+    for ack in self.applied.values_mut() {
+      ack.elapsed += elapsed;
+    }
+  }
+
+  // Applies the oldest input for each player. Taking them one tick at a time
+  // means that even a quick tap gets at least a tick of movement. Returns
+  // whether anyone's velocity changed.
+  pub fn apply_next(&mut self, state: &mut GameState) -> bool {
+    let mut changed = false;
+    let speed = state.settings.speed;
+    for (uuid, pending) in self.pending.iter_mut() {
+      let (sequence, input) = match pending.pop_front() {
+        Some(next) => next,
+        None => continue,
+      };
+      self.applied.insert(
+        *uuid,
+        InputAck {
+          sequence,
+          elapsed: Duration::from_secs(0),
+        },
+      );
+      if let Some(player) = state.players.get_mut(uuid) {
+        let velocity = input.velocity(speed);
+        changed |= player.velocity != velocity;
+        player.velocity = velocity;
+      }
+    }
+    self.pending.retain(|_, pending| !pending.is_empty());
+    changed
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_inputs_are_applied_a_tick_at_a_time() {
+    let position = Position { x: 600.0, y: 300.0 };
+    let (mut state, uuids) = game_at(&[(Role::Crewmate, position)]);
+    state.status = GameStatus::Lobby;
+    let uuid = uuids[0];
+
+    let mut inputs = InputBuffer::default();
+    let right = MovementInput {
+      right: true,
+      ..MovementInput::default()
+    };
+    inputs.push(uuid, 1, right);
+    inputs.push(uuid, 2, MovementInput::default());
+
+    // A tap on the key still moves us for a tick.
+    assert!(inputs.apply_next(&mut state));
+    state.simulate(Duration::from_millis(16));
+    inputs.progress_time(Duration::from_millis(16));
+    assert_eq!(
+      inputs.ack(uuid),
+      Some(InputAck {
+        sequence: 1,
+        elapsed: Duration::from_millis(16)
+      })
+    );
+    let moved_to = state.players[&uuid].position;
+    assert!(moved_to.x > position.x);
+
+    assert!(inputs.apply_next(&mut state));
+    state.simulate(Duration::from_millis(16));
+    assert_eq!(state.players[&uuid].position, moved_to);
+    assert!(!inputs.apply_next(&mut state));
+  }
+}
@@ -0,0 +1,218 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+// Part of the map where tasks get worked on. Whoever's working on a task in
+// it has to be standing inside it, rather than just close by.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Interactable {
+  pub zone: Shape,
+}
+
+// A task that a player is partway through working on.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct Interaction {
+  // Indexes into the player's tasks.
+  pub task: usize,
+  // How much longer they have to keep still for.
+  pub time_remaining: Duration,
+}
+
+impl Map {
+  // Whether someone standing at the given spot can work on something at the
+  // target. Tasks that aren't in any of the map's zones can be worked on
+  // from anywhere close enough.
+  pub fn can_interact(&self, from: Position, target: Position, task_distance: f64) -> bool {
+    match self.interactables.iter().find(|i| i.zone.contains(target)) {
+      Some(interactable) => interactable.zone.contains(from),
+      None => from.distance(&target) <= task_distance,
+    }
+  }
+}
+
+impl Task {
+  // How long it takes to do the task, or the next bit of it, without moving.
+  pub fn work_time(&self, settings: &Settings) -> Duration {
+    match self.kind {
+      TaskKind::Long { .. } => settings.task_work_time * 2,
+      TaskKind::Short | TaskKind::MultiStage { .. } | TaskKind::Timed { .. } => {
+        settings.task_work_time
+      }
+    }
+  }
+}
+
+impl GameState {
+  // Starts working on one of the player's tasks. They have to stay put until
+  // it's done, or they'll have to start over.
+  pub fn begin_interaction(&mut self, uuid: UUID, index: usize) -> Result<(), GameError> {
+    let player = self
+      .players
+      .get(&uuid)
+      .ok_or(GameError::UnknownPlayer { uuid })?;
+    if player.in_vent.is_some() {
+      return Err(GameError::InVent {
+        action: Action::FinishTask,
+      });
+    }
+    if player.in_kill_animation() {
+      return Err(GameError::InKillAnimation {
+        action: Action::FinishTask,
+      });
+    }
+    let task = player
+      .tasks
+      .get(index)
+      .ok_or(GameError::NoSuchTask { index })?;
+    task.check_workable()?;
+    let task_distance = self.settings.task_distance;
+    if !self
+      .map
+      .can_interact(player.position, task.position, task_distance)
+    {
+      return Err(GameError::OutOfRange {
+        action: Action::FinishTask,
+      });
+    }
+    let time_remaining = task.work_time(&self.settings);
+    let has_minigame = task.minigame.is_some();
+    if let Some(player) = self.players.get_mut(&uuid) {
+      player.interaction = Some(Interaction {
+        task: index,
+        time_remaining,
+      });
+    }
+    if time_remaining == Duration::from_secs(0) && !has_minigame {
+      return self.complete_interaction(uuid, None);
+    }
+    Ok(())
+  }
+
+  // Finishes off the task the player's been working on, once they've put in
+  // the time, and solved its puzzle if it has one.
+  pub fn complete_interaction(
+    &mut self,
+    uuid: UUID,
+    solution: Option<&MinigameSolution>,
+  ) -> Result<(), GameError> {
+    let player = self
+      .players
+      .get_mut(&uuid)
+      .ok_or(GameError::UnknownPlayer { uuid })?;
+    let interaction = match player.interaction.take() {
+      Some(interaction) => interaction,
+      None => {
+        return Err(GameError::WrongStatus {
+          action: Action::FinishTask,
+        })
+      }
+    };
+    if interaction.time_remaining > Duration::from_secs(0) {
+      player.interaction = Some(interaction);
+      return Err(GameError::OnCooldown {
+        action: Action::FinishTask,
+        remaining: interaction.time_remaining,
+      });
+    }
+    if let Some(task) = player.tasks.get(interaction.task) {
+      if let Err(e) = task.check_solution(solution) {
+        // They can have another go without starting over.
+        player.interaction = Some(interaction);
+        return Err(e);
+      }
+    }
+    self.note_finished_task(
+      uuid,
+      FinishedTask {
+        index: interaction.task,
+      },
+    )
+  }
+
+  // Ticks down everyone's work, and gives up on it for anyone who's moved.
+  pub(crate) fn simulate_interactions(&mut self, elapsed: Duration) {
+    let mut done = vec![];
+    for player in self.players.values_mut() {
+      let interaction = match player.interaction.as_mut() {
+        Some(interaction) => interaction,
+        None => continue,
+      };
+      if player.velocity != Velocity::default() || player.in_vent.is_some() {
+        player.interaction = None;
+        continue;
+      }
+      interaction.time_remaining = interaction
+        .time_remaining
+        .checked_sub(elapsed)
+        .unwrap_or_else(|| Duration::from_secs(0));
+      // Puzzles have to be solved by the player before they're done.
+      let solved_without_puzzle = player
+        .tasks
+        .get(interaction.task)
+        .is_none_or(|t| t.minigame.is_none());
+      if interaction.time_remaining == Duration::from_secs(0) && solved_without_puzzle {
+        done.push(player.uuid);
+      }
+    }
+    for uuid in done {
+      // Whatever went wrong, they'll just have to try again.
+      let _ = self.complete_interaction(uuid, None);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+  use std::borrow::Cow;
+
+  #[test]
+  fn test_tasks_take_standing_still_to_finish() {
+    let spot = Position { x: 200.0, y: 500.0 };
+    let mut map = MapRegistry::get(MapId::Lobby).unwrap();
+    map.interactables = Cow::Owned(vec![Interactable {
+      zone: Shape::Rect {
+        top_left: Position { x: 150.0, y: 450.0 },
+        width: 100.0,
+        height: 100.0,
+        fill_color: Cow::Borrowed("#000"),
+        outline_width: 1.0,
+        outline_color: Cow::Borrowed("#000"),
+      },
+    }]);
+    // Plus someone to keep the game going.
+    let (mut state, uuids) = game_at(&[
+      (Role::Crewmate, Position { x: 260.0, y: 500.0 }),
+      (Role::Impostor, Position { x: 700.0, y: 100.0 }),
+    ]);
+    state.set_map(map);
+    let uuid = uuids[0];
+    state.players.get_mut(&uuid).unwrap().tasks = vec![Task::new(TaskKind::Short, spot)];
+
+    // Close by, but outside the zone.
+    assert_eq!(
+      state.begin_interaction(uuid, 0),
+      Err(GameError::OutOfRange {
+        action: Action::FinishTask
+      })
+    );
+    state.players.get_mut(&uuid).unwrap().position = Position { x: 240.0, y: 500.0 };
+    state.begin_interaction(uuid, 0).unwrap();
+    assert!(state.complete_interaction(uuid, None).is_err());
+
+    // Wandering off means starting over.
+    state.simulate(state.settings.task_work_time / 2);
+    state.players.get_mut(&uuid).unwrap().velocity = Velocity { dx: 0.0, dy: 1.0 };
+    state.simulate(Duration::from_millis(100));
+    assert_eq!(state.players[&uuid].interaction, None);
+    state.players.get_mut(&uuid).unwrap().velocity = Velocity::default();
+    state.players.get_mut(&uuid).unwrap().position = Position { x: 240.0, y: 500.0 };
+
+    state.begin_interaction(uuid, 0).unwrap();
+    state.simulate(state.settings.task_work_time + Duration::from_millis(100));
+    assert!(state.players[&uuid].tasks[0].finished);
+    assert_eq!(state.players[&uuid].interaction, None);
+  }
+}
@@ -0,0 +1,110 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::Display;
+
+// Why someone was taken out of the room, for telling them on their way out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KickReason {
+  Kicked,
+  // And they're not allowed back in.
+  Banned,
+  // By everyone else in the lobby.
+  VotedOut,
+  // Sat in the lobby for too long without doing anything.
+  Idle,
+}
+
+impl Display for KickReason {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    match self {
+      KickReason::Kicked => write!(fmt, "The host kicked you out of the game"),
+      KickReason::Banned => write!(fmt, "The host banned you from this room"),
+      KickReason::VotedOut => write!(fmt, "The other players voted to kick you out"),
+      KickReason::Idle => write!(fmt, "You were idle in the lobby for too long"),
+    }
+  }
+}
+
+impl GameState {
+  // Only the host can kick people out, and they can't kick themselves.
+  pub fn check_kick(&self, host: UUID, target: UUID) -> Result<(), GameError> {
+    let action = Action::Kick;
+    if !self.is_host(host) {
+      return Err(GameError::NotHost { action });
+    }
+    if !self.players.contains_key(&target) && !self.spectators.contains_key(&target) {
+      return Err(GameError::UnknownPlayer { uuid: target });
+    }
+    if host == target {
+      return Err(GameError::InvalidTarget { action });
+    }
+    Ok(())
+  }
+}
+
+impl GameServer {
+  // Gets someone ready to be thrown out at the host's request. They're told
+  // why, and their rejoin token is forgotten so they can't come straight back
+  // in. Returns the connection to hang up on. Whoever's running the room does
+  // that, and lets us know it's gone with disconnected() like any other.
+  pub fn kick(
+    &mut self,
+    host_connection: UUID,
+    target: UUID,
+    reason: KickReason,
+  ) -> Result<UUID, Box<dyn Error>> {
+    let host = self.player_for_connection(host_connection);
+    let checked = self.state.check_kick(host, target).and_then(|()| {
+      // There's no connection to hang up on for a bot.
+      if self.bots.contains(target) {
+        return Err(GameError::InvalidTarget {
+          action: Action::Kick,
+        });
+      }
+      Ok(())
+    });
+    if let Err(e) = checked {
+      self
+        .broadcaster
+        .send_to_player(&host_connection, &ServerToClientMessage::ActionFailed(e))?;
+      return Err(e.into());
+    }
+    tracing::info!(%target, ?reason, "Kicked out by the host");
+    self.rejoin_tokens.remove(&target);
+    let connection = self.connection_for_player(target);
+    self
+      .broadcaster
+      .send_to_player(&connection, &ServerToClientMessage::Kicked { reason })?;
+    Ok(connection)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_only_the_host_kicks_people_out() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate, Role::Crewmate]);
+    state.status = GameStatus::Lobby;
+    let (host, guest) = (uuids[0], uuids[1]);
+    state.host = Some(host);
+    let action = Action::Kick;
+    assert_eq!(
+      state.check_kick(guest, host),
+      Err(GameError::NotHost { action })
+    );
+    assert_eq!(
+      state.check_kick(host, host),
+      Err(GameError::InvalidTarget { action })
+    );
+    let stranger = UUID::random();
+    assert_eq!(
+      state.check_kick(host, stranger),
+      Err(GameError::UnknownPlayer { uuid: stranger })
+    );
+    assert_eq!(state.check_kick(host, guest), Ok(()));
+  }
+}
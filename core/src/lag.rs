@@ -0,0 +1,95 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+// Where everyone has been over the last few ticks. By the time a kill gets to
+// us the victim has moved on, so we judge it by where they were on the
+// killer's screen instead.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PositionHistory {
+  // How many ticks have been simulated.
+  pub tick: u64,
+  positions: BTreeMap<UUID, VecDeque<(u64, Position)>>,
+}
+
+impl PositionHistory {
+  // About half a second's worth of ticks. Any further back than
+  // that and we'd be letting people kill from across the room.
+  const LENGTH: usize = 32;
+
+  pub(crate) fn record(&mut self, players: &BTreeMap<UUID, Player>) {
+    self.positions.retain(|uuid, _| players.contains_key(uuid));
+    for (uuid, player) in players.iter() {
+      let history = self.positions.entry(*uuid).or_default();
+      if history.len() >= Self::LENGTH {
+        history.pop_front();
+      }
+      history.push_back((self.tick, player.position));
+    }
+  }
+
+  // Where the player was as of the given tick, or as close to it as we
+  // remember.
+  pub fn position_at(&self, uuid: UUID, tick: u64) -> Option<Position> {
+    let history = self.positions.get(&uuid)?;
+    history
+      .iter()
+      .find(|(recorded, _)| *recorded >= tick)
+      .or_else(|| history.back())
+      .map(|(_, position)| *position)
+  }
+
+  // Keeps the tick, which players need to tell us what they've seen, but
+  // not where everyone's been.
+  pub(crate) fn without_positions(&self) -> Self {
+    PositionHistory {
+      tick: self.tick,
+      positions: BTreeMap::new(),
+    }
+  }
+}
+
+impl GameState {
+  // Whether the given player is within range of the position, either as of
+  // the tick that someone was looking at or right now.
+  pub(crate) fn in_range_as_seen_at(
+    &self,
+    uuid: UUID,
+    seen_at: Option<u64>,
+    position: Position,
+    range: f64,
+  ) -> bool {
+    let now = self.players.get(&uuid).map(|p| p.position);
+    let then = seen_at.and_then(|tick| self.position_history.position_at(uuid, tick));
+    now
+      .iter()
+      .chain(then.iter())
+      .any(|p| p.distance(&position) <= range)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_kills_are_judged_by_what_the_killer_saw() {
+    let (mut state, uuids) = game_at(&[
+      (Role::Impostor, Position { x: 600.0, y: 300.0 }),
+      (Role::Crewmate, Position { x: 650.0, y: 300.0 }),
+    ]);
+    let (impostor, victim) = (uuids[0], uuids[1]);
+    state.simulate_tick();
+    let seen_at = state.position_history.tick;
+
+    // By the time the kill gets to the server, the victim's walked away.
+    state.players.get_mut(&victim).unwrap().velocity = Velocity { dx: 2.0, dy: 0.0 };
+    for _ in 0..30 {
+      state.simulate_tick();
+    }
+    assert!(state.note_kill(impostor, victim, None).is_err());
+    state.note_kill(impostor, victim, Some(seen_at)).unwrap();
+    assert!(state.players[&victim].dead);
+  }
+}
@@ -1,39 +1,121 @@
 #![warn(rust_2018_idioms)]
 
+mod afk;
+mod bot;
+mod chat;
+mod checksum;
+mod clock;
+mod cosmetics;
+mod debug_overlay;
+mod decorations;
+mod decoy;
+mod doors;
+mod drag;
+mod effects;
+mod election;
+mod error;
+mod events;
+mod game_clock;
+mod game_mode;
 mod game_state;
+mod handoff;
+mod heartbeat;
+mod input;
+mod interaction;
+mod kick;
+mod lag;
+mod lobby;
+mod logging;
+mod map_editor;
+mod map_file;
+mod maps;
+mod minigame;
+mod minimap;
+mod passages;
+mod pathfinding;
+mod pause;
 mod player;
+mod player_name;
+mod prediction;
+mod presets;
 mod protocol;
+mod quick_chat;
+mod rate_limit;
+mod reconnect;
 mod replay;
+mod rng;
+mod role;
+mod sabotage;
 mod server;
+mod settings_patch;
+mod shapeshift;
+mod spatial;
+mod spectator;
+mod state_snapshot;
+mod stats;
+mod sudden_death;
+mod summary;
+mod surveillance;
+mod task;
+mod vent;
+mod visibility;
+mod voice;
+mod vote_kick;
+pub use crate::afk::*;
+pub use crate::bot::*;
+pub use crate::chat::*;
+pub use crate::checksum::*;
+pub use crate::clock::*;
+pub use crate::cosmetics::*;
+pub use crate::debug_overlay::*;
+pub use crate::decorations::*;
+pub use crate::decoy::*;
+pub use crate::doors::*;
+pub use crate::effects::*;
+pub use crate::election::*;
+pub use crate::error::*;
+pub use crate::events::*;
+pub use crate::game_clock::*;
+pub use crate::game_mode::*;
 pub use crate::game_state::*;
+pub use crate::handoff::*;
+pub use crate::heartbeat::*;
+pub use crate::input::*;
+pub use crate::interaction::*;
+pub use crate::kick::*;
+pub use crate::lag::*;
+pub use crate::lobby::*;
+pub use crate::logging::*;
+pub use crate::map_editor::*;
+pub use crate::map_file::*;
+pub use crate::maps::*;
+pub use crate::minigame::*;
+pub use crate::minimap::*;
+pub use crate::passages::*;
+pub use crate::pause::*;
 pub use crate::player::*;
+pub use crate::player_name::*;
+pub use crate::prediction::*;
+pub use crate::presets::*;
 pub use crate::protocol::*;
+pub use crate::quick_chat::*;
+pub use crate::rate_limit::*;
 pub use crate::replay::*;
+pub use crate::rng::*;
+pub use crate::role::*;
+pub use crate::sabotage::*;
 pub use crate::server::*;
+pub use crate::settings_patch::*;
+pub use crate::shapeshift::*;
+pub use crate::spatial::*;
+pub use crate::spectator::*;
+pub use crate::stats::*;
+pub use crate::summary::*;
+pub use crate::surveillance::*;
+pub use crate::task::*;
+pub use crate::vent::*;
+pub use crate::visibility::*;
+pub use crate::voice::*;
+pub use crate::vote_kick::*;
+mod test_util;
 mod tests;
-
-#[cfg(target_arch = "wasm32")]
-use wasm_bindgen::prelude::*;
-
-// Set up a console.log macro. This has to live in the main file of the crate
-// apparently, for macro-related reasons.
-#[cfg(target_arch = "wasm32")]
-#[wasm_bindgen]
-extern "C" {
-  #[wasm_bindgen(js_namespace = console)]
-  pub fn log(s: &str);
-}
-
-// If running in wasm, console_log! calls the wasm_bindgen console.log.
-#[macro_export]
-#[cfg(target_arch = "wasm32")]
-macro_rules! console_log {
-    ($($t:tt)*) => (crate::log(&format_args!($($t)*).to_string()))
-}
-
-// If running outside of wasm, console_log! prints to stdout.
-#[cfg(not(target_arch = "wasm32"))]
-#[macro_export]
-macro_rules! console_log {
-    ($($t:tt)*) => (println!($($t)*))
-}
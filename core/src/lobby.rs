@@ -0,0 +1,331 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+// Why someone couldn't join a game.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JoinError {
+  GameFull { max_players: usize },
+  RoomNotFound,
+  WrongPassword,
+  // The map they wanted to play on can't be played on.
+  BadMap(MapError),
+  // The host banned them.
+  Banned,
+  BadName(NameError),
+}
+
+impl Display for JoinError {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    match self {
+      JoinError::GameFull { max_players } => {
+        write!(
+          fmt,
+          "The game is full, it only has room for {} players",
+          max_players
+        )
+      }
+      JoinError::RoomNotFound => write!(fmt, "There's no room with that code"),
+      JoinError::WrongPassword => write!(fmt, "That's not the password for this room"),
+      JoinError::BadMap(e) => write!(fmt, "Can't play on that map: {}", e),
+      JoinError::Banned => write!(fmt, "You've been banned from this room"),
+      JoinError::BadName(e) => write!(fmt, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for JoinError {}
+
+impl GameState {
+  pub fn check_room_for_player(&self) -> Result<(), JoinError> {
+    let max_players = self.settings.max_players;
+    if self.players.len() >= max_players {
+      return Err(JoinError::GameFull { max_players });
+    }
+    Ok(())
+  }
+
+  // Colors that no one's wearing yet.
+  pub fn available_colors(&self) -> Vec<Color> {
+    Color::all()
+      .iter()
+      .copied()
+      .filter(|color| !self.players.values().any(|p| p.color == *color))
+      .collect()
+  }
+
+  // What a new player gets to wear: the color they asked for if it's free,
+  // otherwise the first one that is.
+  pub fn assign_color(&self, preferred: Color) -> Option<Color> {
+    let available = self.available_colors();
+    if available.contains(&preferred) {
+      return Some(preferred);
+    }
+    available.first().copied()
+  }
+
+  // Changes color in the lobby, as long as no one else has it already.
+  pub fn note_color_requested(&mut self, uuid: UUID, color: Color) -> Result<(), GameError> {
+    if self.status != GameStatus::Lobby {
+      return Err(GameError::WrongStatus {
+        action: Action::ChangeColor,
+      });
+    }
+    let taken = self
+      .players
+      .values()
+      .any(|p| p.color == color && p.uuid != uuid);
+    let player = self
+      .players
+      .get_mut(&uuid)
+      .ok_or(GameError::UnknownPlayer { uuid })?;
+    if taken {
+      return Err(GameError::ColorTaken { color });
+    }
+    player.color = color;
+    Ok(())
+  }
+
+  // There's no game to be had with fewer players than this.
+  pub fn check_enough_players(&self) -> Result<(), GameError> {
+    if self.players.len() < self.settings.min_players {
+      return Err(GameError::NotEnoughPlayers {
+        needed: self.settings.min_players,
+        have: self.players.len(),
+      });
+    }
+    Ok(())
+  }
+
+  // The first player to show up runs the lobby, until they hand it off.
+  pub(crate) fn claim_host_if_vacant(&mut self, uuid: UUID) {
+    if self.host.is_none() && self.players.contains_key(&uuid) {
+      self.host = Some(uuid);
+    }
+  }
+
+  // Hands the lobby to whoever has the lowest UUID, so that everyone agrees
+  // on who's next without having to talk about it.
+  pub(crate) fn promote_next_host(&mut self) {
+    self.host = self.players.keys().next().copied();
+  }
+
+  pub fn is_host(&self, uuid: UUID) -> bool {
+    self.host == Some(uuid)
+  }
+
+  pub fn note_ready(&mut self, uuid: UUID, ready: bool) -> Result<(), GameError> {
+    if self.status != GameStatus::Lobby {
+      return Err(GameError::WrongStatus {
+        action: Action::GetReady,
+      });
+    }
+    let player = self
+      .players
+      .get_mut(&uuid)
+      .ok_or(GameError::UnknownPlayer { uuid })?;
+    player.ready = ready;
+    Ok(())
+  }
+
+  pub fn note_host_transferred(&mut self, from: UUID, to: UUID) -> Result<(), GameError> {
+    if !self.is_host(from) {
+      return Err(GameError::NotHost {
+        action: Action::TransferHost,
+      });
+    }
+    if !self.players.contains_key(&to) {
+      return Err(GameError::UnknownPlayer { uuid: to });
+    }
+    self.host = Some(to);
+    Ok(())
+  }
+
+  // Everyone other than the host has said they're ready. The host says so by
+  // starting the game.
+  pub fn everyone_ready(&self) -> bool {
+    self
+      .players
+      .values()
+      .all(|p| p.ready || self.is_host(p.uuid))
+  }
+
+  // Once a game's been won (or drawn), the host can take everyone back to the lobby
+  // for another. Whoever's still here keeps their color and the room keeps
+  // its settings, but everything about the last game is forgotten. Anyone
+  // who dropped out and never came back is let go.
+  pub fn note_returned_to_lobby(&mut self, requester: UUID) -> Result<(), GameError> {
+    let action = Action::ReturnToLobby;
+    if !matches!(self.status, GameStatus::Won(_) | GameStatus::Draw) {
+      return Err(GameError::WrongStatus { action });
+    }
+    if !self.is_host(requester) {
+      return Err(GameError::NotHost { action });
+    }
+    self.players.retain(|_, player| !player.disconnected);
+    if !self
+      .host
+      .is_some_and(|host| self.players.contains_key(&host))
+    {
+      self.promote_next_host();
+    }
+    for player in self.players.values_mut() {
+      player.reset_for_lobby();
+    }
+    self.map.place_players_at_game_start(&mut self.players);
+    self.bodies.clear();
+    self.decoys.clear();
+    self.emergency_cooldown = Duration::from_secs(0);
+    self.sabotage = SabotageState::default();
+    self.doors = DoorState::default();
+    self.ejection = None;
+    self.game_time_remaining = None;
+    self.sudden_death_remaining = None;
+    self.reset_activity();
+    self.status = GameStatus::Lobby;
+    Ok(())
+  }
+
+  // Only the host can start the game, and only once there are enough
+  // players and everyone's ready.
+  pub fn can_start_game(&self, requester: UUID) -> Result<(), GameError> {
+    let action = Action::StartGame;
+    if self.status != GameStatus::Lobby {
+      return Err(GameError::WrongStatus { action });
+    }
+    if !self.is_host(requester) {
+      return Err(GameError::NotHost { action });
+    }
+    self.check_enough_players()?;
+    if !self.everyone_ready() {
+      return Err(GameError::NotEveryoneReady);
+    }
+    Ok(())
+  }
+}
+
+impl Player {
+  // Back to how they were when they first joined, bar where they're standing.
+  pub(crate) fn reset_for_lobby(&mut self) {
+    self.dead = false;
+    self.impostor = false;
+    self.role = Role::Crewmate;
+    self.tasks = vec![];
+    self.velocity = Velocity::default();
+    self.kill_cooldown = Duration::from_secs(0);
+    self.emergency_meetings_called = 0;
+    self.in_vent = None;
+    self.shielded = false;
+    self.status_effects = vec![];
+    self.ready = false;
+    self.reconnect_time_remaining = Duration::from_secs(0);
+    self.interaction = None;
+    self.afk = false;
+    self.disguise = None;
+    self.shapeshift_cooldown = Duration::from_secs(0);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_only_the_host_starts_once_ready() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate; 3]);
+    state.status = GameStatus::Lobby;
+    state.settings.min_players = 3;
+    for uuid in uuids.iter() {
+      state.claim_host_if_vacant(*uuid);
+    }
+    assert!(state.is_host(uuids[0]));
+    assert!(state.can_start_game(uuids[0]).is_err());
+
+    state.note_ready(uuids[1], true).unwrap();
+    state.note_ready(uuids[2], true).unwrap();
+    assert!(state.can_start_game(uuids[1]).is_err());
+    state.can_start_game(uuids[0]).unwrap();
+
+    assert!(state.note_host_transferred(uuids[1], uuids[2]).is_err());
+    state.note_host_transferred(uuids[0], uuids[1]).unwrap();
+    assert!(state.is_host(uuids[1]));
+    // The old host never said they were ready.
+    assert!(state.can_start_game(uuids[1]).is_err());
+    state.note_ready(uuids[0], true).unwrap();
+    state.can_start_game(uuids[1]).unwrap();
+
+    state.settings.min_players = 4;
+    assert!(state.can_start_game(uuids[1]).is_err());
+  }
+
+  #[test]
+  fn test_host_migrates_on_disconnect() {
+    let (mut state, mut uuids) = game_with(&[Role::Crewmate; 3]);
+    state.status = GameStatus::Lobby;
+    let host = uuids[1];
+    state.host = Some(host);
+    state.handle_disconnection(host);
+    uuids.retain(|uuid| *uuid != host);
+    assert_eq!(state.host, uuids.iter().min().copied());
+  }
+
+  #[test]
+  fn test_everyone_gets_their_own_color() {
+    let mut state = GameState::new();
+    state.status = GameStatus::Lobby;
+    // Everyone wants red.
+    let uuids: Vec<UUID> = (0..12).map(|_| UUID::random()).collect();
+    for uuid in uuids.iter() {
+      let color = state.assign_color(Color::Red).unwrap();
+      let position = Position { x: 0.0, y: 0.0 };
+      let player = Player::new(*uuid, "Test Player".to_string(), color, position);
+      state.players.insert(*uuid, player);
+    }
+    let colors: std::collections::BTreeSet<Color> =
+      state.players.values().map(|p| p.color).collect();
+    assert_eq!(colors.len(), 12);
+    assert_eq!(state.players[&uuids[0]].color, Color::Red);
+    assert_eq!(state.available_colors().len(), Color::all().len() - 12);
+
+    let free = state.available_colors()[0];
+    assert_eq!(
+      state.note_color_requested(uuids[1], Color::Red),
+      Err(GameError::ColorTaken { color: Color::Red })
+    );
+    state.note_color_requested(uuids[1], free).unwrap();
+    assert_eq!(state.players[&uuids[1]].color, free);
+    // Their old color's up for grabs now.
+    assert!(state.available_colors().contains(&Color::all()[1]));
+    assert!(!state.available_colors().contains(&free));
+    // Asking for what you're already wearing is fine.
+    state.note_color_requested(uuids[1], free).unwrap();
+
+    state.status = GameStatus::Playing(PlayState::Night);
+    assert!(state
+      .note_color_requested(uuids[2], Color::all()[1])
+      .is_err());
+  }
+
+  #[test]
+  fn test_player_limits() {
+    let mut state = GameState::new();
+    state.status = GameStatus::Lobby;
+    state.settings.max_players = 2;
+    for i in 0..2 {
+      state.check_room_for_player().unwrap();
+      let uuid = UUID::random();
+      let position = Position { x: 0.0, y: 0.0 };
+      let player = Player::new(uuid, "Test Player".to_string(), Color::all()[i], position);
+      state.players.insert(uuid, player);
+    }
+    assert_eq!(
+      state.check_room_for_player(),
+      Err(JoinError::GameFull { max_players: 2 })
+    );
+    let start_info = state.get_game_start_info(GameRng::random_seed());
+    assert!(state.note_game_started(&start_info).is_err());
+    assert_eq!(state.status, GameStatus::Lobby);
+  }
+}
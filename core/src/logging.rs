@@ -0,0 +1,276 @@
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+// How log lines get written out. Plain is for reading in the browser's
+// console, JSON is for the server's logs, where something else is going to
+// be searching through them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+  Plain,
+  Json,
+}
+
+// Everything that's logged goes through tracing, and this turns it into
+// lines of text, with whatever the enclosing spans say about where it
+// happened (which room, which tick, which player) tacked on.
+pub struct LogSubscriber {
+  format: LogFormat,
+  max_level: Level,
+  write: Box<dyn Fn(&str) + Send + Sync>,
+  next_id: AtomicU64,
+  spans: Mutex<BTreeMap<u64, SpanData>>,
+}
+
+struct SpanData {
+  fields: Map<String, Value>,
+  // Handles to the span that are still around. It's forgotten once there
+  // aren't any.
+  handles: usize,
+}
+
+thread_local! {
+  // The spans we're inside of, innermost last.
+  static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+impl LogSubscriber {
+  pub fn new(
+    format: LogFormat,
+    max_level: Level,
+    write: impl Fn(&str) + Send + Sync + 'static,
+  ) -> Self {
+    LogSubscriber {
+      format,
+      max_level,
+      write: Box::new(write),
+      next_id: AtomicU64::new(1),
+      spans: Mutex::new(BTreeMap::new()),
+    }
+  }
+
+  fn spans(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, SpanData>> {
+    self
+      .spans
+      .lock()
+      .expect("Internal Error: log spans poisoned")
+  }
+
+  fn format_line(&self, metadata: &Metadata<'_>, fields: Map<String, Value>) -> String {
+    let mut context = Map::new();
+    {
+      let spans = self.spans();
+      ENTERED.with(|entered| {
+        for id in entered.borrow().iter() {
+          if let Some(span) = spans.get(id) {
+            context.extend(span.fields.clone());
+          }
+        }
+      });
+    }
+    match self.format {
+      LogFormat::Json => {
+        let mut line = Map::new();
+        line.insert("level".to_string(), metadata.level().to_string().into());
+        line.insert("target".to_string(), metadata.target().into());
+        line.extend(context);
+        line.extend(fields);
+        Value::Object(line).to_string()
+      }
+      LogFormat::Plain => {
+        let mut line = metadata.level().to_string();
+        for (key, value) in context.iter() {
+          line.push_str(&format!(" {}={}", key, plain(value)));
+        }
+        line.push(':');
+        let mut fields = fields;
+        if let Some(message) = fields.remove("message") {
+          line.push_str(&format!(" {}", plain(&message)));
+        }
+        for (key, value) in fields.iter() {
+          line.push_str(&format!(" {}={}", key, plain(value)));
+        }
+        line
+      }
+    }
+  }
+}
+
+fn plain(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    other => other.to_string(),
+  }
+}
+
+// Collects up the fields of a span or an event.
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl<'a> Visit for Fields<'a> {
+  fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+    self
+      .0
+      .insert(field.name().to_string(), format!("{:?}", value).into());
+  }
+
+  fn record_str(&mut self, field: &Field, value: &str) {
+    self.0.insert(field.name().to_string(), value.into());
+  }
+
+  fn record_u64(&mut self, field: &Field, value: u64) {
+    self.0.insert(field.name().to_string(), value.into());
+  }
+
+  fn record_i64(&mut self, field: &Field, value: i64) {
+    self.0.insert(field.name().to_string(), value.into());
+  }
+
+  fn record_f64(&mut self, field: &Field, value: f64) {
+    self.0.insert(field.name().to_string(), value.into());
+  }
+
+  fn record_bool(&mut self, field: &Field, value: bool) {
+    self.0.insert(field.name().to_string(), value.into());
+  }
+}
+
+impl Subscriber for LogSubscriber {
+  fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+    *metadata.level() <= self.max_level
+  }
+
+  fn max_level_hint(&self) -> Option<LevelFilter> {
+    Some(LevelFilter::from_level(self.max_level))
+  }
+
+  fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+    let mut fields = Map::new();
+    attributes.record(&mut Fields(&mut fields));
+    self.spans().insert(id, SpanData { fields, handles: 1 });
+    Id::from_u64(id)
+  }
+
+  fn record(&self, span: &Id, values: &Record<'_>) {
+    if let Some(span) = self.spans().get_mut(&span.into_u64()) {
+      values.record(&mut Fields(&mut span.fields));
+    }
+  }
+
+  fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+  fn event(&self, event: &Event<'_>) {
+    let mut fields = Map::new();
+    event.record(&mut Fields(&mut fields));
+    let line = self.format_line(event.metadata(), fields);
+    (self.write)(&line);
+  }
+
+  fn enter(&self, span: &Id) {
+    ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+  }
+
+  fn exit(&self, span: &Id) {
+    ENTERED.with(|entered| {
+      let mut entered = entered.borrow_mut();
+      if let Some(index) = entered.iter().rposition(|id| *id == span.into_u64()) {
+        entered.remove(index);
+      }
+    });
+  }
+
+  fn clone_span(&self, id: &Id) -> Id {
+    if let Some(span) = self.spans().get_mut(&id.into_u64()) {
+      span.handles += 1;
+    }
+    id.clone()
+  }
+
+  fn try_close(&self, id: Id) -> bool {
+    let mut spans = self.spans();
+    let closed = match spans.get_mut(&id.into_u64()) {
+      Some(span) => {
+        span.handles -= 1;
+        span.handles == 0
+      }
+      None => false,
+    };
+    if closed {
+      spans.remove(&id.into_u64());
+    }
+    closed
+  }
+}
+
+// Sends everything that's logged from here on to the given function, e.g.
+// console.log in the browser, or stdout on the server. Only the first call
+// does anything.
+pub fn init_logging(
+  format: LogFormat,
+  max_level: Level,
+  write: impl Fn(&str) + Send + Sync + 'static,
+) {
+  let _ = tracing::subscriber::set_global_default(LogSubscriber::new(format, max_level, write));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use std::sync::{Arc, Mutex};
+  use tracing::Level;
+
+  #[test]
+  fn test_log_lines_say_where_they_came_from() {
+    let lines: Arc<Mutex<Vec<String>>> = Arc::default();
+    let written = lines.clone();
+    let subscriber = LogSubscriber::new(LogFormat::Json, Level::INFO, move |line| {
+      written.lock().unwrap().push(line.to_string())
+    });
+    tracing::subscriber::with_default(subscriber, || {
+      let room = tracing::info_span!("room", room = "ABCDE");
+      let _room = room.enter();
+      {
+        let message = tracing::info_span!("message", tick = 12u64, uuid = "someone");
+        let _message = message.enter();
+        tracing::info!(kind = "Vote", "Handling a message");
+        tracing::debug!("Too chatty to bother with");
+      }
+      tracing::warn!("Something's up");
+    });
+    let lines = lines.lock().unwrap();
+    assert_eq!(lines.len(), 2);
+    let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(first["level"], "INFO");
+    assert_eq!(first["message"], "Handling a message");
+    assert_eq!(first["room"], "ABCDE");
+    assert_eq!(first["tick"], 12);
+    assert_eq!(first["uuid"], "someone");
+    assert_eq!(first["kind"], "Vote");
+    // Once out of the message's span, only the room is left.
+    let second: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(second["level"], "WARN");
+    assert_eq!(second["room"], "ABCDE");
+    assert_eq!(second.get("tick"), None);
+
+    let lines: Arc<Mutex<Vec<String>>> = Arc::default();
+    let written = lines.clone();
+    let subscriber = LogSubscriber::new(LogFormat::Plain, Level::INFO, move |line| {
+      written.lock().unwrap().push(line.to_string())
+    });
+    tracing::subscriber::with_default(subscriber, || {
+      let _room = tracing::info_span!("room", room = "ABCDE").entered();
+      tracing::info!(kind = "Vote", "Handling a message");
+    });
+    assert_eq!(
+      *lines.lock().unwrap(),
+      vec!["INFO room=ABCDE: Handling a message kind=Vote".to_string()]
+    );
+  }
+}
@@ -0,0 +1,252 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+// One change to a map that's being worked on. These come from the editor in
+// the browser as JSON, so that the rules about what makes a map playable only
+// live here.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MapEdit {
+  AddShape {
+    shape: Shape,
+  },
+  // Indexes into Map::static_geometry.
+  MoveShape {
+    index: usize,
+    by: Velocity,
+  },
+  DeleteShape {
+    index: usize,
+  },
+  PlaceTaskSpot {
+    position: Position,
+  },
+  RemoveTaskSpot {
+    index: usize,
+  },
+  PlaceVent {
+    position: Position,
+    connections: Vec<usize>,
+  },
+  // Anything that led to the vent doesn't any more.
+  DeleteVent {
+    index: usize,
+  },
+  AddDecoration {
+    decoration: Decoration,
+  },
+  // Indexes into Map::decorations.
+  DeleteDecoration {
+    index: usize,
+  },
+  // Where everyone starts the game, spread around a circle.
+  SetSpawn {
+    center: Position,
+    radius: f64,
+  },
+  // Where everyone gathers for meetings.
+  SetMeetingSpot {
+    center: Position,
+    radius: f64,
+  },
+}
+
+impl Shape {
+  pub fn translate(&mut self, by: Velocity) {
+    match self {
+      Shape::Circle { center, .. } => *center = center.move_by(by),
+      Shape::Rect { top_left, .. } => *top_left = top_left.move_by(by),
+      Shape::Polygon { points, .. } => {
+        for point in points.to_mut().iter_mut() {
+          *point = point.move_by(by);
+        }
+      }
+      Shape::Wall { start, end, .. } => {
+        *start = start.move_by(by);
+        *end = end.move_by(by);
+      }
+    }
+  }
+}
+
+// A map that's being edited. It's allowed to be unplayable along the way,
+// it just has to be playable by the time it's finished.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapDraft {
+  map: Map,
+}
+
+impl MapDraft {
+  // Starts from an existing map, built in or otherwise.
+  pub fn new(map: Map) -> Self {
+    MapDraft {
+      map: Map {
+        id: MapId::Custom,
+        ..map
+      },
+    }
+  }
+
+  pub fn map(&self) -> &Map {
+    &self.map
+  }
+
+  pub fn apply(&mut self, edit: MapEdit) -> Result<(), MapError> {
+    let map = &mut self.map;
+    match edit {
+      MapEdit::AddShape { shape } => map.static_geometry.to_mut().push(shape),
+      MapEdit::MoveShape { index, by } => map
+        .static_geometry
+        .to_mut()
+        .get_mut(index)
+        .ok_or(MapError::NoSuch(MapFeature::Shape { index }))?
+        .translate(by),
+      MapEdit::DeleteShape { index } => {
+        if index >= map.static_geometry.len() {
+          return Err(MapError::NoSuch(MapFeature::Shape { index }));
+        }
+        map.static_geometry.to_mut().remove(index);
+      }
+      MapEdit::PlaceTaskSpot { position } => map.task_spots.to_mut().push(position),
+      MapEdit::RemoveTaskSpot { index } => {
+        if index >= map.task_spots.len() {
+          return Err(MapError::NoSuch(MapFeature::Task { index }));
+        }
+        map.task_spots.to_mut().remove(index);
+      }
+      MapEdit::PlaceVent {
+        position,
+        connections,
+      } => map.vents.to_mut().push(Vent {
+        position,
+        connections: Cow::Owned(connections),
+      }),
+      MapEdit::DeleteVent { index } => {
+        if index >= map.vents.len() {
+          return Err(MapError::NoSuch(MapFeature::Vent { index }));
+        }
+        let vents = map.vents.to_mut();
+        vents.remove(index);
+        // Everything after the deleted vent moves down one.
+        for vent in vents.iter_mut() {
+          let connections: Vec<usize> = vent
+            .connections
+            .iter()
+            .filter(|to| **to != index)
+            .map(|to| if *to > index { to - 1 } else { *to })
+            .collect();
+          vent.connections = Cow::Owned(connections);
+        }
+      }
+      MapEdit::AddDecoration { decoration } => map.decorations.to_mut().push(decoration),
+      MapEdit::DeleteDecoration { index } => {
+        if index >= map.decorations.len() {
+          return Err(MapError::NoSuch(MapFeature::Decoration { index }));
+        }
+        map.decorations.to_mut().remove(index);
+      }
+      MapEdit::SetSpawn { center, radius } => map.spawn_area = Ring { center, radius },
+      MapEdit::SetMeetingSpot { center, radius } => map.meeting_spot = Ring { center, radius },
+    }
+    map.reindex();
+    Ok(())
+  }
+
+  // Whether the map could be played on as it is: everything's on the map,
+  // everyone could walk everywhere they need to from where they start, and
+  // so on.
+  pub fn validate(&self) -> Result<(), MapError> {
+    self.map.validate()
+  }
+
+  pub fn finish(self) -> Result<Map, MapError> {
+    self.validate()?;
+    Ok(self.map)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use std::borrow::Cow;
+
+  #[test]
+  fn test_drafts_have_to_be_playable_to_finish() {
+    let lobby = MapRegistry::get(MapId::Lobby).unwrap();
+    let mut draft = MapDraft::new(lobby.clone());
+    draft.validate().unwrap();
+
+    // Box in the first task spot.
+    let spot = lobby.task_spots[0];
+    draft
+      .apply(MapEdit::AddShape {
+        shape: Shape::Circle {
+          radius: 40.0,
+          center: Position {
+            x: spot.x + 500.0,
+            y: spot.y,
+          },
+          fill_color: Cow::Borrowed("#000"),
+          outline_width: 1.0,
+          outline_color: Cow::Borrowed("#000"),
+        },
+      })
+      .unwrap();
+    draft.validate().unwrap();
+    let index = draft.map().static_geometry.len() - 1;
+    draft
+      .apply(MapEdit::MoveShape {
+        index,
+        by: Velocity {
+          dx: -500.0,
+          dy: 0.0,
+        },
+      })
+      .unwrap();
+    assert_eq!(
+      draft.validate(),
+      Err(MapError::Unreachable(MapFeature::Task { index: 0 }))
+    );
+    assert!(draft.clone().finish().is_err());
+    draft.apply(MapEdit::DeleteShape { index }).unwrap();
+
+    // Deleting a vent takes it out of everywhere it was connected to.
+    let vents = draft.map().vents.len();
+    draft
+      .apply(MapEdit::PlaceVent {
+        position: Position { x: 200.0, y: 300.0 },
+        connections: vec![0],
+      })
+      .unwrap();
+    draft.apply(MapEdit::DeleteVent { index: 0 }).unwrap();
+    assert_eq!(draft.map().vents.len(), vents);
+    assert!(draft
+      .map()
+      .vents
+      .iter()
+      .all(|v| v.connections.iter().all(|to| *to < vents)));
+    assert_eq!(
+      draft.apply(MapEdit::DeleteVent { index: vents }),
+      Err(MapError::NoSuch(MapFeature::Vent { index: vents }))
+    );
+
+    // Meetings can't be held on top of the table.
+    let meeting_spot = draft.map().meeting_spot;
+    draft
+      .apply(MapEdit::SetMeetingSpot {
+        center: lobby.static_geometry[0].center(),
+        radius: 10.0,
+      })
+      .unwrap();
+    assert_eq!(draft.validate(), Err(MapError::SpawnBlocked));
+    draft
+      .apply(MapEdit::SetMeetingSpot {
+        center: meeting_spot.center,
+        radius: meeting_spot.radius,
+      })
+      .unwrap();
+
+    let map = draft.finish().unwrap();
+    assert_eq!(map.id, MapId::Custom);
+  }
+}
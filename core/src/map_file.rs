@@ -0,0 +1,492 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::Display;
+
+// Limits on maps that players upload, so that no one can tie the server up
+// with a map that takes forever to check, or to play on.
+pub const MAX_MAP_UPLOAD_BYTES: usize = 256 * 1024;
+// Things on the map, counting every corner of every shape.
+pub const MAX_MAP_PARTS: usize = 2_000;
+// The walkable grid gets big fast.
+pub const MAX_MAP_SIDE: f64 = 10_000.0;
+
+// Something on a map that a problem can be pinned on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapFeature {
+  Shape { index: usize },
+  Task { index: usize },
+  Vent { index: usize },
+  FixStation { index: usize },
+  EmergencyButton,
+  Region { index: usize },
+  Decoration { index: usize },
+  AdminConsole { index: usize },
+  SecurityConsole { index: usize },
+  Camera { index: usize },
+  Interactable { index: usize },
+}
+
+impl Display for MapFeature {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    match self {
+      MapFeature::Shape { index } => write!(fmt, "shape #{}", index),
+      MapFeature::Task { index } => write!(fmt, "task spot #{}", index),
+      MapFeature::Vent { index } => write!(fmt, "vent #{}", index),
+      MapFeature::FixStation { index } => write!(fmt, "fix station #{}", index),
+      MapFeature::EmergencyButton => write!(fmt, "the emergency button"),
+      MapFeature::Region { index } => write!(fmt, "region #{}", index),
+      MapFeature::Decoration { index } => write!(fmt, "decoration #{}", index),
+      MapFeature::AdminConsole { index } => write!(fmt, "admin console #{}", index),
+      MapFeature::SecurityConsole { index } => write!(fmt, "security console #{}", index),
+      MapFeature::Camera { index } => write!(fmt, "camera #{}", index),
+      MapFeature::Interactable { index } => write!(fmt, "task zone #{}", index),
+    }
+  }
+}
+
+// Why a map can't be played on.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MapError {
+  BadSize { width: f64, height: f64 },
+  OutOfBounds(MapFeature),
+  // Someone would be put inside the furniture at the start of the game or of
+  // a meeting.
+  SpawnBlocked,
+  // There's no walking there from where everyone starts.
+  Unreachable(MapFeature),
+  BadVentConnection { vent: usize, to: usize },
+  MissingFixStation(Sabotage),
+  // Nowhere to hand out tasks.
+  NoTaskSpots,
+  // An edit referred to something that isn't there.
+  NoSuch(MapFeature),
+  TooBig { bytes: usize, max: usize },
+  TooComplex { parts: usize, max: usize },
+  // It's not a map at all.
+  Unreadable,
+}
+
+impl Display for MapError {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    match self {
+      MapError::BadSize { width, height } => {
+        write!(fmt, "A map can't be {} by {}", width, height)
+      }
+      MapError::OutOfBounds(feature) => write!(fmt, "{} is off the edge of the map", feature),
+      MapError::SpawnBlocked => write!(fmt, "Players would spawn inside something solid"),
+      MapError::Unreachable(feature) => {
+        write!(fmt, "There's no way to walk to {}", feature)
+      }
+      MapError::BadVentConnection { vent, to } => {
+        write!(
+          fmt,
+          "Vent #{} leads to vent #{}, which doesn't exist",
+          vent, to
+        )
+      }
+      MapError::MissingFixStation(sabotage) => {
+        write!(fmt, "There's nowhere to fix the {:?}", sabotage)
+      }
+      MapError::NoTaskSpots => write!(fmt, "There's nowhere on the map to do tasks"),
+      MapError::NoSuch(feature) => write!(fmt, "There's no {}", feature),
+      MapError::TooBig { bytes, max } => {
+        write!(
+          fmt,
+          "The map is {} bytes, it can't be more than {}",
+          bytes, max
+        )
+      }
+      MapError::TooComplex { parts, max } => write!(
+        fmt,
+        "The map has {} things on it, it can't have more than {}",
+        parts, max
+      ),
+      MapError::Unreadable => write!(fmt, "That doesn't look like a map"),
+    }
+  }
+}
+
+impl Error for MapError {}
+
+// The map, cut up into squares about the size of a player, to work out where
+// someone could walk to.
+struct WalkableGrid {
+  columns: usize,
+  rows: usize,
+  reached: Vec<bool>,
+}
+
+impl WalkableGrid {
+  const CELL_SIZE: f64 = 10.0;
+
+  // Everywhere that can be walked to from the given position, with the doors
+  // open and every passage let through, since someone might be able to.
+  fn flood_from(map: &Map, start: Position) -> Self {
+    let columns = (map.width() / Self::CELL_SIZE).ceil() as usize;
+    let rows = (map.height() / Self::CELL_SIZE).ceil() as usize;
+    let mut grid = WalkableGrid {
+      columns,
+      rows,
+      reached: vec![false; columns * rows],
+    };
+    let mut queue = VecDeque::new();
+    if let Some(cell) = grid.cell_at(start) {
+      grid.reached[cell] = true;
+      queue.push_back(cell);
+    }
+    while let Some(cell) = queue.pop_front() {
+      let (column, row) = (cell % columns, cell / columns);
+      let neighbours = [
+        (column.wrapping_sub(1), row),
+        (column + 1, row),
+        (column, row.wrapping_sub(1)),
+        (column, row + 1),
+      ];
+      for (column, row) in neighbours.iter() {
+        if *column >= columns || *row >= rows {
+          continue;
+        }
+        let neighbour = row * columns + column;
+        if grid.reached[neighbour] || !is_clear(map, grid.center(neighbour)) {
+          continue;
+        }
+        grid.reached[neighbour] = true;
+        queue.push_back(neighbour);
+      }
+    }
+    grid
+  }
+
+  fn cell_at(&self, position: Position) -> Option<usize> {
+    if position.x < 0.0 || position.y < 0.0 {
+      return None;
+    }
+    let column = (position.x / Self::CELL_SIZE) as usize;
+    let row = (position.y / Self::CELL_SIZE) as usize;
+    if column >= self.columns || row >= self.rows {
+      return None;
+    }
+    Some(row * self.columns + column)
+  }
+
+  fn center(&self, cell: usize) -> Position {
+    Position {
+      x: ((cell % self.columns) as f64 + 0.5) * Self::CELL_SIZE,
+      y: ((cell / self.columns) as f64 + 0.5) * Self::CELL_SIZE,
+    }
+  }
+
+  fn reaches(&self, position: Position) -> bool {
+    self
+      .cell_at(position)
+      .is_some_and(|cell| self.reached[cell])
+  }
+
+  fn reaches_within(&self, position: Position, distance: f64) -> bool {
+    (0..self.reached.len())
+      .filter(|cell| self.reached[*cell])
+      .any(|cell| self.center(cell).distance(&position) <= distance)
+  }
+}
+
+fn in_bounds(map: &Map, position: Position) -> bool {
+  position.x >= 0.0 && position.x <= map.width() && position.y >= 0.0 && position.y <= map.height()
+}
+
+// Whether a player could stand here.
+pub(crate) fn is_clear(map: &Map, position: Position) -> bool {
+  let radius = Player::radius();
+  position.x >= radius
+    && position.y >= radius
+    && position.x <= map.width() - radius
+    && position.y <= map.height() - radius
+    && !map
+      .shapes_near(position, radius)
+      .any(|s| s.collides_with(position, radius))
+}
+
+impl Map {
+  // Reads a map from a JSON data file, and makes sure that it can be played
+  // on.
+  pub fn from_reader(reader: impl std::io::Read) -> Result<Map, Box<dyn Error>> {
+    let mut map: Map = serde_json::from_reader(reader)?;
+    map.id = MapId::Custom;
+    map.validate()?;
+    // Only once it's known to be a sensible size.
+    map.reindex();
+    Ok(map)
+  }
+
+  // Reads a map that a player sent us.
+  pub fn from_upload(json: &str) -> Result<Map, MapError> {
+    if json.len() > MAX_MAP_UPLOAD_BYTES {
+      return Err(MapError::TooBig {
+        bytes: json.len(),
+        max: MAX_MAP_UPLOAD_BYTES,
+      });
+    }
+    let mut map: Map = serde_json::from_str(json).map_err(|_| MapError::Unreadable)?;
+    map.id = MapId::Custom;
+    map.validate()?;
+    map.reindex();
+    Ok(map)
+  }
+
+  // How many things there are on the map. Every corner of a shape counts,
+  // since each one is another edge to collide with.
+  pub fn parts(&self) -> usize {
+    let shape_parts = |shape: &Shape| 1 + shape.corners().len();
+    self.static_geometry.iter().map(shape_parts).sum::<usize>()
+      + self
+        .doors
+        .iter()
+        .map(|d| shape_parts(&d.shape))
+        .sum::<usize>()
+      + self
+        .passages
+        .iter()
+        .map(|p| shape_parts(&p.shape))
+        .sum::<usize>()
+      + self
+        .interactables
+        .iter()
+        .map(|i| shape_parts(&i.zone))
+        .sum::<usize>()
+      + self
+        .decorations
+        .iter()
+        .map(|d| shape_parts(&d.shape))
+        .sum::<usize>()
+      + self
+        .regions
+        .iter()
+        .map(|r| 1 + r.points.len())
+        .sum::<usize>()
+      + self
+        .vents
+        .iter()
+        .map(|v| 1 + v.connections.len())
+        .sum::<usize>()
+      + self.task_spots.len()
+      + self.fix_stations.len()
+      + self.admin_consoles.len()
+      + self.security_consoles.len()
+      + self.cameras.len()
+  }
+
+  pub fn validate(&self) -> Result<(), MapError> {
+    let (width, height) = (self.width(), self.height());
+    let sensible_side = |side: f64| side > 0.0 && side <= MAX_MAP_SIDE;
+    if !(sensible_side(width) && sensible_side(height)) {
+      return Err(MapError::BadSize { width, height });
+    }
+    let parts = self.parts();
+    if parts > MAX_MAP_PARTS {
+      return Err(MapError::TooComplex {
+        parts,
+        max: MAX_MAP_PARTS,
+      });
+    }
+    let mut features: Vec<(MapFeature, Position)> = vec![];
+    for (index, shape) in self
+      .static_geometry
+      .iter()
+      .chain(self.doors.iter().map(|d| &d.shape))
+      .chain(self.passages.iter().map(|p| &p.shape))
+      .enumerate()
+    {
+      match shape {
+        Shape::Circle { center, .. } => features.push((MapFeature::Shape { index }, *center)),
+        _ => {
+          for corner in shape.corners() {
+            features.push((MapFeature::Shape { index }, corner));
+          }
+        }
+      }
+    }
+    for (index, spot) in self.task_spots.iter().enumerate() {
+      features.push((MapFeature::Task { index }, *spot));
+    }
+    for (index, vent) in self.vents.iter().enumerate() {
+      features.push((MapFeature::Vent { index }, vent.position));
+    }
+    for (index, station) in self.fix_stations.iter().enumerate() {
+      features.push((MapFeature::FixStation { index }, station.position));
+    }
+    features.push((MapFeature::EmergencyButton, self.emergency_button.position));
+    for (index, region) in self.regions.iter().enumerate() {
+      for point in region.points.iter() {
+        features.push((MapFeature::Region { index }, *point));
+      }
+    }
+    for (index, decoration) in self.decorations.iter().enumerate() {
+      match &decoration.shape {
+        Shape::Circle { center, .. } => features.push((MapFeature::Decoration { index }, *center)),
+        shape => {
+          for corner in shape.corners() {
+            features.push((MapFeature::Decoration { index }, corner));
+          }
+        }
+      }
+    }
+    for (index, interactable) in self.interactables.iter().enumerate() {
+      match &interactable.zone {
+        Shape::Circle { center, .. } => {
+          features.push((MapFeature::Interactable { index }, *center))
+        }
+        shape => {
+          for corner in shape.corners() {
+            features.push((MapFeature::Interactable { index }, corner));
+          }
+        }
+      }
+    }
+    for (index, console) in self.admin_consoles.iter().enumerate() {
+      features.push((MapFeature::AdminConsole { index }, console.position));
+    }
+    for (index, console) in self.security_consoles.iter().enumerate() {
+      features.push((MapFeature::SecurityConsole { index }, console.position));
+    }
+    for (index, camera) in self.cameras.iter().enumerate() {
+      features.push((MapFeature::Camera { index }, camera.position));
+    }
+    for (feature, position) in features.iter() {
+      if !in_bounds(self, *position) {
+        return Err(MapError::OutOfBounds(*feature));
+      }
+    }
+
+    for (index, vent) in self.vents.iter().enumerate() {
+      if let Some(to) = vent.connections.iter().find(|to| **to >= self.vents.len()) {
+        return Err(MapError::BadVentConnection {
+          vent: index,
+          to: *to,
+        });
+      }
+    }
+    for sabotage in [Sabotage::Lights, Sabotage::Reactor, Sabotage::Comms].iter() {
+      if !self.fix_stations.iter().any(|s| s.sabotage == *sabotage) {
+        return Err(MapError::MissingFixStation(*sabotage));
+      }
+    }
+    if self.task_spots.is_empty() {
+      return Err(MapError::NoTaskSpots);
+    }
+
+    // Everyone gets spread around these circles, so every spot on them has
+    // to be somewhere a player could stand.
+    let rings = [self.spawn_area, self.meeting_spot];
+    let spawn_points: Vec<Position> = rings
+      .iter()
+      .flat_map(|ring| (0..16).map(move |i| ring.spot(i, 16)))
+      .collect();
+    // There's a lot of checking whether a spot's clear to come, so the
+    // furniture had better be sorted out first.
+    let indexed = self.clone().indexed();
+    if !spawn_points.iter().all(|p| is_clear(&indexed, *p)) {
+      return Err(MapError::SpawnBlocked);
+    }
+
+    let grid = WalkableGrid::flood_from(&indexed, spawn_points[0]);
+    for (index, spot) in self.task_spots.iter().enumerate() {
+      if !is_clear(&indexed, *spot) || !grid.reaches(*spot) {
+        return Err(MapError::Unreachable(MapFeature::Task { index }));
+      }
+    }
+    for (index, vent) in self.vents.iter().enumerate() {
+      if !grid.reaches(vent.position) {
+        return Err(MapError::Unreachable(MapFeature::Vent { index }));
+      }
+    }
+    for (index, station) in self.fix_stations.iter().enumerate() {
+      if !grid.reaches(station.position) {
+        return Err(MapError::Unreachable(MapFeature::FixStation { index }));
+      }
+    }
+    let button = self.emergency_button;
+    if !grid.reaches_within(button.position, button.reach) {
+      return Err(MapError::Unreachable(MapFeature::EmergencyButton));
+    }
+    for (index, console) in self.admin_consoles.iter().enumerate() {
+      if !grid.reaches_within(console.position, console.reach) {
+        return Err(MapError::Unreachable(MapFeature::AdminConsole { index }));
+      }
+    }
+    for (index, console) in self.security_consoles.iter().enumerate() {
+      if !grid.reaches_within(console.position, console.reach) {
+        return Err(MapError::Unreachable(MapFeature::SecurityConsole { index }));
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use std::borrow::Cow;
+
+  #[test]
+  fn test_maps_survive_being_written_out_and_read_back_in() {
+    for id in MapRegistry::ids() {
+      let map = MapRegistry::get(*id).unwrap();
+      map.validate().unwrap();
+      let json = serde_json::to_string(&map).unwrap();
+      let loaded = Map::from_reader(json.as_bytes()).unwrap();
+      assert_eq!(loaded.id, MapId::Custom);
+      assert_eq!(loaded.static_geometry, map.static_geometry);
+      assert_eq!(loaded.task_spots, map.task_spots);
+      assert_eq!(loaded.regions, map.regions);
+      // Players start out somewhere with a name.
+      assert!(map.region_at(map.spawn_area.center).is_some());
+    }
+    let ship = MapRegistry::get(MapId::Ship).unwrap();
+    let lights = ship.fix_stations[0].position;
+    assert_eq!(ship.region_at(lights).unwrap().name, "Electrical");
+
+    // A task buried in the furniture is no use to anyone.
+    let mut map = MapRegistry::get(MapId::Lobby).unwrap();
+    let spot = map.task_spots[0];
+    let mut geometry = map.static_geometry.to_vec();
+    geometry.push(Shape::Circle {
+      radius: 40.0,
+      center: spot,
+      fill_color: Cow::Borrowed("#000"),
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+    });
+    map.static_geometry = Cow::Owned(geometry);
+    assert_eq!(
+      map.validate(),
+      Err(MapError::Unreachable(MapFeature::Task { index: 0 }))
+    );
+
+    // Nor is a map with nowhere to do them at all.
+    let mut map = MapRegistry::get(MapId::Lobby).unwrap();
+    map.task_spots = Cow::Borrowed(&[]);
+    assert_eq!(map.validate(), Err(MapError::NoTaskSpots));
+  }
+
+  #[test]
+  fn test_uploaded_maps_have_limits() {
+    let map = MapRegistry::get(MapId::Station).unwrap();
+    let json = serde_json::to_string(&map).unwrap();
+    let uploaded = Map::from_upload(&json).unwrap();
+    assert_eq!(uploaded.id, MapId::Custom);
+    assert_eq!(Map::from_upload("{}"), Err(MapError::Unreadable));
+    let padded = format!("{}{}", json, " ".repeat(MAX_MAP_UPLOAD_BYTES));
+    assert!(matches!(
+      Map::from_upload(&padded),
+      Err(MapError::TooBig { .. })
+    ));
+
+    let mut busy = map.clone();
+    busy.task_spots = Cow::Owned(vec![busy.task_spots[0]; MAX_MAP_PARTS]);
+    assert!(matches!(busy.validate(), Err(MapError::TooComplex { .. })));
+    let mut huge = map;
+    huge.width = MAX_MAP_SIDE * 10.0;
+    assert!(matches!(huge.validate(), Err(MapError::BadSize { .. })));
+  }
+}
@@ -0,0 +1,773 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+// Which map to play on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapId {
+  // The original long, thin ship.
+  #[default]
+  Ship,
+  // Rooms around a central hub, with the meeting table in the middle.
+  Station,
+  // A single small room, for quick games and for trying things out.
+  Lobby,
+  // Loaded from a data file, and sent to everyone who joins.
+  Custom,
+}
+
+// All the maps there are to play on.
+pub struct MapRegistry;
+
+impl MapRegistry {
+  pub fn ids() -> &'static [MapId] {
+    &[MapId::Ship, MapId::Station, MapId::Lobby]
+  }
+
+  // Custom maps don't live here, they have to be loaded.
+  pub fn get(id: MapId) -> Option<Map> {
+    match id {
+      MapId::Ship => Some(ship()),
+      MapId::Station => Some(station()),
+      MapId::Lobby => Some(lobby()),
+      MapId::Custom => None,
+    }
+  }
+}
+
+impl Default for Map {
+  fn default() -> Self {
+    ship()
+  }
+}
+
+impl GameState {
+  // Moves the game over to another map. Only makes sense before the game
+  // starts, since everyone stays where they are.
+  pub fn set_map(&mut self, map: Map) {
+    self.settings.map_id = map.id;
+    self.map = map.indexed();
+  }
+}
+
+const SHIP_CONFERENCE_TABLE: Position = Position { x: 275.0, y: 275.0 };
+const SHIP_GEOMETRY: &[Shape] = &[
+  // conference table
+  Shape::Circle {
+    radius: 75.0,
+    center: SHIP_CONFERENCE_TABLE,
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#358"),
+  },
+  // nega table
+  Shape::Circle {
+    radius: 62.0,
+    center: Position { x: 875.0, y: 505.0 },
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#a22"),
+  },
+  Shape::Circle {
+    radius: 62.0,
+    center: Position { x: 1675.0, y: 95.0 },
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#a22"),
+  },
+  Shape::Circle {
+    radius: 140.0,
+    center: Position {
+      x: 2475.0,
+      y: 190.0,
+    },
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#666"),
+  },
+  Shape::Circle {
+    radius: 140.0,
+    center: Position {
+      x: 2475.0,
+      y: 190.0 + 140.0 + 140.0 + 30.0,
+    },
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#666"),
+  },
+  Shape::Circle {
+    radius: 62.0,
+    center: Position {
+      x: 2875.0,
+      y: 115.0,
+    },
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#a22"),
+  },
+];
+
+const SHIP_DOORS: &[Door] = &[
+  Door {
+    shape: Shape::Circle {
+      radius: 60.0,
+      center: Position {
+        x: 1275.0,
+        y: 384.0,
+      },
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+      fill_color: Cow::Borrowed("#bbb"),
+    },
+  },
+  Door {
+    shape: Shape::Circle {
+      radius: 60.0,
+      center: Position {
+        x: 2175.0,
+        y: 384.0,
+      },
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+      fill_color: Cow::Borrowed("#bbb"),
+    },
+  },
+];
+
+const SHIP_FIX_STATIONS: &[FixStation] = &[
+  FixStation {
+    sabotage: Sabotage::Lights,
+    position: Position {
+      x: 1675.0,
+      y: 400.0,
+    },
+  },
+  FixStation {
+    sabotage: Sabotage::Reactor,
+    position: Position {
+      x: 2875.0,
+      y: 500.0,
+    },
+  },
+  FixStation {
+    sabotage: Sabotage::Comms,
+    position: Position { x: 760.0, y: 700.0 },
+  },
+];
+
+const SHIP_VENTS: &[Vent] = &[
+  Vent {
+    position: Position { x: 150.0, y: 650.0 },
+    connections: Cow::Borrowed(&[1]),
+  },
+  Vent {
+    position: Position {
+      x: 1400.0,
+      y: 100.0,
+    },
+    connections: Cow::Borrowed(&[0, 2]),
+  },
+  Vent {
+    position: Position {
+      x: 1900.0,
+      y: 700.0,
+    },
+    connections: Cow::Borrowed(&[1, 3]),
+  },
+  Vent {
+    position: Position {
+      x: 2950.0,
+      y: 650.0,
+    },
+    connections: Cow::Borrowed(&[2]),
+  },
+];
+
+const SHIP_TASK_SPOTS: &[Position] = &[
+  Position { x: 150.0, y: 150.0 },
+  Position { x: 500.0, y: 600.0 },
+  Position { x: 700.0, y: 150.0 },
+  Position {
+    x: 1000.0,
+    y: 650.0,
+  },
+  Position {
+    x: 1100.0,
+    y: 200.0,
+  },
+  Position {
+    x: 1500.0,
+    y: 600.0,
+  },
+  Position {
+    x: 1850.0,
+    y: 250.0,
+  },
+  Position {
+    x: 2000.0,
+    y: 650.0,
+  },
+  Position {
+    x: 2250.0,
+    y: 100.0,
+  },
+  Position {
+    x: 2700.0,
+    y: 400.0,
+  },
+  Position {
+    x: 2950.0,
+    y: 300.0,
+  },
+  Position {
+    x: 2700.0,
+    y: 700.0,
+  },
+];
+
+const SHIP_DECORATIONS: &[Decoration] = &[
+  // A rug under the conference table.
+  Decoration {
+    shape: Shape::Circle {
+      radius: 125.0,
+      center: SHIP_CONFERENCE_TABLE,
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#ccc"),
+      fill_color: Cow::Borrowed("#ddd"),
+    },
+    layer: Layer::Floor,
+    z: 0,
+  },
+  // A pipe running along the ceiling of Electrical.
+  Decoration {
+    shape: Shape::Wall {
+      start: Position { x: 1300.0, y: 60.0 },
+      end: Position { x: 2150.0, y: 60.0 },
+      thickness: 12.0,
+      color: Cow::Borrowed("#7779"),
+    },
+    layer: Layer::Overhead,
+    z: 0,
+  },
+];
+
+const SHIP_ADMIN_CONSOLES: &[AdminConsole] = &[AdminConsole {
+  position: Position { x: 700.0, y: 700.0 },
+  reach: 40.0,
+}];
+
+const SHIP_SECURITY_CONSOLES: &[SecurityConsole] = &[SecurityConsole {
+  position: Position {
+    x: 2450.0,
+    y: 700.0,
+  },
+  reach: 40.0,
+}];
+
+// Up in the corners, looking down into Electrical and the Reactor.
+const SHIP_CAMERAS: &[Camera] = &[
+  Camera {
+    position: Position { x: 1290.0, y: 20.0 },
+    facing: Velocity { dx: 1.0, dy: 1.0 },
+    spread: 1.4,
+    range: 900.0,
+  },
+  Camera {
+    position: Position { x: 2190.0, y: 20.0 },
+    facing: Velocity { dx: 1.0, dy: 1.0 },
+    spread: 1.4,
+    range: 900.0,
+  },
+];
+
+// Split into thirds at the doors.
+const SHIP_REGIONS: &[Region] = &[
+  Region {
+    name: Cow::Borrowed("Cafeteria"),
+    points: Cow::Borrowed(&[
+      Position { x: 0.0, y: 0.0 },
+      Position { x: 1275.0, y: 0.0 },
+      Position {
+        x: 1275.0,
+        y: 768.0,
+      },
+      Position { x: 0.0, y: 768.0 },
+    ]),
+  },
+  Region {
+    name: Cow::Borrowed("Electrical"),
+    points: Cow::Borrowed(&[
+      Position { x: 1275.0, y: 0.0 },
+      Position { x: 2175.0, y: 0.0 },
+      Position {
+        x: 2175.0,
+        y: 768.0,
+      },
+      Position {
+        x: 1275.0,
+        y: 768.0,
+      },
+    ]),
+  },
+  Region {
+    name: Cow::Borrowed("Reactor"),
+    points: Cow::Borrowed(&[
+      Position { x: 2175.0, y: 0.0 },
+      Position { x: 3036.0, y: 0.0 },
+      Position {
+        x: 3036.0,
+        y: 768.0,
+      },
+      Position {
+        x: 2175.0,
+        y: 768.0,
+      },
+    ]),
+  },
+];
+
+fn ship() -> Map {
+  Map {
+    id: MapId::Ship,
+    name: Cow::Borrowed("The First Ship"),
+    width: 3036.0,
+    height: 768.0,
+    spawn_area: Ring {
+      center: SHIP_CONFERENCE_TABLE,
+      radius: 100.0,
+    },
+    meeting_spot: Ring {
+      center: SHIP_CONFERENCE_TABLE,
+      radius: 100.0,
+    },
+    static_geometry: Cow::Borrowed(SHIP_GEOMETRY),
+    doors: Cow::Borrowed(SHIP_DOORS),
+    fix_stations: Cow::Borrowed(SHIP_FIX_STATIONS),
+    vents: Cow::Borrowed(SHIP_VENTS),
+    task_spots: Cow::Borrowed(SHIP_TASK_SPOTS),
+    regions: Cow::Borrowed(SHIP_REGIONS),
+    passages: Cow::Borrowed(&[]),
+    interactables: Cow::Borrowed(&[]),
+    decorations: Cow::Borrowed(SHIP_DECORATIONS),
+    admin_consoles: Cow::Borrowed(SHIP_ADMIN_CONSOLES),
+    security_consoles: Cow::Borrowed(SHIP_SECURITY_CONSOLES),
+    cameras: Cow::Borrowed(SHIP_CAMERAS),
+    grid: SpatialGrid::default(),
+    // The button sits in the middle of the conference table.
+    emergency_button: EmergencyButton {
+      position: SHIP_CONFERENCE_TABLE,
+      reach: 75.0 + 40.0,
+    },
+  }
+  .indexed()
+}
+
+const STATION_HUB: Position = Position { x: 800.0, y: 600.0 };
+const STATION_GEOMETRY: &[Shape] = &[
+  // The meeting table, in the middle of the hub.
+  Shape::Circle {
+    radius: 90.0,
+    center: STATION_HUB,
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#385"),
+  },
+  // Pillars holding up the corners of the hub.
+  Shape::Rect {
+    top_left: Position { x: 450.0, y: 250.0 },
+    width: 100.0,
+    height: 100.0,
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#666"),
+  },
+  Shape::Rect {
+    top_left: Position {
+      x: 1050.0,
+      y: 250.0,
+    },
+    width: 100.0,
+    height: 100.0,
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#666"),
+  },
+  Shape::Rect {
+    top_left: Position { x: 450.0, y: 850.0 },
+    width: 100.0,
+    height: 100.0,
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#666"),
+  },
+  Shape::Rect {
+    top_left: Position {
+      x: 1050.0,
+      y: 850.0,
+    },
+    width: 100.0,
+    height: 100.0,
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#666"),
+  },
+  // The walls between the hub and the rooms to either side of it, with a
+  // gap in the middle of each to walk through.
+  Shape::Wall {
+    start: Position { x: 350.0, y: 0.0 },
+    end: Position { x: 350.0, y: 450.0 },
+    thickness: 10.0,
+    color: Cow::Borrowed("#444"),
+  },
+  Shape::Wall {
+    start: Position { x: 350.0, y: 750.0 },
+    end: Position {
+      x: 350.0,
+      y: 1200.0,
+    },
+    thickness: 10.0,
+    color: Cow::Borrowed("#444"),
+  },
+  Shape::Wall {
+    start: Position { x: 1250.0, y: 0.0 },
+    end: Position {
+      x: 1250.0,
+      y: 450.0,
+    },
+    thickness: 10.0,
+    color: Cow::Borrowed("#444"),
+  },
+  Shape::Wall {
+    start: Position {
+      x: 1250.0,
+      y: 750.0,
+    },
+    end: Position {
+      x: 1250.0,
+      y: 1200.0,
+    },
+    thickness: 10.0,
+    color: Cow::Borrowed("#444"),
+  },
+  // The lights panel, sticking out of the west wall.
+  Shape::Polygon {
+    points: Cow::Borrowed(&[
+      Position { x: 0.0, y: 540.0 },
+      Position { x: 70.0, y: 600.0 },
+      Position { x: 0.0, y: 660.0 },
+    ]),
+    outline_width: 1.0,
+    outline_color: Cow::Borrowed("#000"),
+    fill_color: Cow::Borrowed("#aa4"),
+  },
+];
+
+const STATION_DOORS: &[Door] = &[
+  Door {
+    shape: Shape::Circle {
+      radius: 60.0,
+      center: Position { x: 800.0, y: 250.0 },
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+      fill_color: Cow::Borrowed("#bbb"),
+    },
+  },
+  Door {
+    shape: Shape::Circle {
+      radius: 60.0,
+      center: Position { x: 800.0, y: 950.0 },
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+      fill_color: Cow::Borrowed("#bbb"),
+    },
+  },
+];
+
+const STATION_FIX_STATIONS: &[FixStation] = &[
+  FixStation {
+    sabotage: Sabotage::Lights,
+    position: Position { x: 150.0, y: 600.0 },
+  },
+  FixStation {
+    sabotage: Sabotage::Reactor,
+    position: Position {
+      x: 1450.0,
+      y: 600.0,
+    },
+  },
+  FixStation {
+    sabotage: Sabotage::Comms,
+    position: Position {
+      x: 1000.0,
+      y: 650.0,
+    },
+  },
+];
+
+const STATION_VENTS: &[Vent] = &[
+  Vent {
+    position: Position { x: 100.0, y: 100.0 },
+    connections: Cow::Borrowed(&[1, 2]),
+  },
+  Vent {
+    position: Position {
+      x: 1500.0,
+      y: 100.0,
+    },
+    connections: Cow::Borrowed(&[0, 3]),
+  },
+  Vent {
+    position: Position {
+      x: 100.0,
+      y: 1100.0,
+    },
+    connections: Cow::Borrowed(&[0, 3]),
+  },
+  Vent {
+    position: Position {
+      x: 1500.0,
+      y: 1100.0,
+    },
+    connections: Cow::Borrowed(&[1, 2]),
+  },
+];
+
+const STATION_TASK_SPOTS: &[Position] = &[
+  Position { x: 300.0, y: 100.0 },
+  Position { x: 800.0, y: 100.0 },
+  Position {
+    x: 1300.0,
+    y: 100.0,
+  },
+  Position { x: 150.0, y: 400.0 },
+  Position {
+    x: 1450.0,
+    y: 400.0,
+  },
+  Position { x: 300.0, y: 600.0 },
+  Position {
+    x: 1300.0,
+    y: 600.0,
+  },
+  Position { x: 150.0, y: 800.0 },
+  Position {
+    x: 1450.0,
+    y: 800.0,
+  },
+  Position {
+    x: 300.0,
+    y: 1100.0,
+  },
+  Position {
+    x: 800.0,
+    y: 1100.0,
+  },
+  Position {
+    x: 1300.0,
+    y: 1100.0,
+  },
+];
+
+// Either side of the meeting table.
+const STATION_ADMIN_CONSOLES: &[AdminConsole] = &[AdminConsole {
+  position: Position { x: 600.0, y: 600.0 },
+  reach: 40.0,
+}];
+
+const STATION_SECURITY_CONSOLES: &[SecurityConsole] = &[SecurityConsole {
+  position: Position {
+    x: 1000.0,
+    y: 600.0,
+  },
+  reach: 40.0,
+}];
+
+// In the far corners of the rooms off the hub, looking back towards it.
+const STATION_CAMERAS: &[Camera] = &[
+  Camera {
+    position: Position { x: 20.0, y: 20.0 },
+    facing: Velocity { dx: 1.0, dy: 1.0 },
+    spread: 1.4,
+    range: 900.0,
+  },
+  Camera {
+    position: Position {
+      x: 1580.0,
+      y: 1180.0,
+    },
+    facing: Velocity { dx: -1.0, dy: -1.0 },
+    spread: 1.4,
+    range: 900.0,
+  },
+];
+
+// Split up by the walls either side of the hub.
+const STATION_REGIONS: &[Region] = &[
+  Region {
+    name: Cow::Borrowed("Electrical"),
+    points: Cow::Borrowed(&[
+      Position { x: 0.0, y: 0.0 },
+      Position { x: 350.0, y: 0.0 },
+      Position {
+        x: 350.0,
+        y: 1200.0,
+      },
+      Position { x: 0.0, y: 1200.0 },
+    ]),
+  },
+  Region {
+    name: Cow::Borrowed("The Hub"),
+    points: Cow::Borrowed(&[
+      Position { x: 350.0, y: 0.0 },
+      Position { x: 1250.0, y: 0.0 },
+      Position {
+        x: 1250.0,
+        y: 1200.0,
+      },
+      Position {
+        x: 350.0,
+        y: 1200.0,
+      },
+    ]),
+  },
+  Region {
+    name: Cow::Borrowed("Reactor"),
+    points: Cow::Borrowed(&[
+      Position { x: 1250.0, y: 0.0 },
+      Position { x: 1600.0, y: 0.0 },
+      Position {
+        x: 1600.0,
+        y: 1200.0,
+      },
+      Position {
+        x: 1250.0,
+        y: 1200.0,
+      },
+    ]),
+  },
+];
+
+fn station() -> Map {
+  Map {
+    id: MapId::Station,
+    name: Cow::Borrowed("The Station"),
+    width: 1600.0,
+    height: 1200.0,
+    spawn_area: Ring {
+      center: STATION_HUB,
+      radius: 130.0,
+    },
+    meeting_spot: Ring {
+      center: STATION_HUB,
+      radius: 130.0,
+    },
+    static_geometry: Cow::Borrowed(STATION_GEOMETRY),
+    doors: Cow::Borrowed(STATION_DOORS),
+    fix_stations: Cow::Borrowed(STATION_FIX_STATIONS),
+    vents: Cow::Borrowed(STATION_VENTS),
+    task_spots: Cow::Borrowed(STATION_TASK_SPOTS),
+    regions: Cow::Borrowed(STATION_REGIONS),
+    passages: Cow::Borrowed(&[]),
+    interactables: Cow::Borrowed(&[]),
+    decorations: Cow::Borrowed(&[]),
+    admin_consoles: Cow::Borrowed(STATION_ADMIN_CONSOLES),
+    security_consoles: Cow::Borrowed(STATION_SECURITY_CONSOLES),
+    cameras: Cow::Borrowed(STATION_CAMERAS),
+    grid: SpatialGrid::default(),
+    emergency_button: EmergencyButton {
+      position: STATION_HUB,
+      reach: 90.0 + 40.0,
+    },
+  }
+  .indexed()
+}
+
+const LOBBY_TABLE: Position = Position { x: 400.0, y: 300.0 };
+const LOBBY_GEOMETRY: &[Shape] = &[Shape::Circle {
+  radius: 50.0,
+  center: LOBBY_TABLE,
+  outline_width: 1.0,
+  outline_color: Cow::Borrowed("#000"),
+  fill_color: Cow::Borrowed("#853"),
+}];
+
+const LOBBY_FIX_STATIONS: &[FixStation] = &[
+  FixStation {
+    sabotage: Sabotage::Lights,
+    position: Position { x: 60.0, y: 300.0 },
+  },
+  FixStation {
+    sabotage: Sabotage::Reactor,
+    position: Position { x: 740.0, y: 300.0 },
+  },
+  FixStation {
+    sabotage: Sabotage::Comms,
+    position: Position { x: 400.0, y: 540.0 },
+  },
+];
+
+const LOBBY_VENTS: &[Vent] = &[
+  Vent {
+    position: Position { x: 60.0, y: 540.0 },
+    connections: Cow::Borrowed(&[1]),
+  },
+  Vent {
+    position: Position { x: 740.0, y: 60.0 },
+    connections: Cow::Borrowed(&[0]),
+  },
+];
+
+const LOBBY_TASK_SPOTS: &[Position] = &[
+  Position { x: 100.0, y: 100.0 },
+  Position { x: 400.0, y: 60.0 },
+  Position { x: 700.0, y: 200.0 },
+  Position { x: 700.0, y: 500.0 },
+  Position { x: 400.0, y: 540.0 },
+  Position { x: 100.0, y: 400.0 },
+];
+
+const LOBBY_REGIONS: &[Region] = &[Region {
+  name: Cow::Borrowed("The Lobby"),
+  points: Cow::Borrowed(&[
+    Position { x: 0.0, y: 0.0 },
+    Position { x: 800.0, y: 0.0 },
+    Position { x: 800.0, y: 600.0 },
+    Position { x: 0.0, y: 600.0 },
+  ]),
+}];
+
+fn lobby() -> Map {
+  Map {
+    id: MapId::Lobby,
+    name: Cow::Borrowed("The Lobby"),
+    width: 800.0,
+    height: 600.0,
+    spawn_area: Ring {
+      center: LOBBY_TABLE,
+      radius: 80.0,
+    },
+    meeting_spot: Ring {
+      center: LOBBY_TABLE,
+      radius: 80.0,
+    },
+    static_geometry: Cow::Borrowed(LOBBY_GEOMETRY),
+    // Nowhere to lock anyone in.
+    doors: Cow::Borrowed(&[]),
+    fix_stations: Cow::Borrowed(LOBBY_FIX_STATIONS),
+    vents: Cow::Borrowed(LOBBY_VENTS),
+    task_spots: Cow::Borrowed(LOBBY_TASK_SPOTS),
+    regions: Cow::Borrowed(LOBBY_REGIONS),
+    passages: Cow::Borrowed(&[]),
+    interactables: Cow::Borrowed(&[]),
+    decorations: Cow::Borrowed(&[]),
+    admin_consoles: Cow::Borrowed(&[]),
+    security_consoles: Cow::Borrowed(&[]),
+    cameras: Cow::Borrowed(&[]),
+    grid: SpatialGrid::default(),
+    emergency_button: EmergencyButton {
+      position: LOBBY_TABLE,
+      reach: 50.0 + 40.0,
+    },
+  }
+  .indexed()
+}
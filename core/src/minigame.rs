@@ -0,0 +1,209 @@
+use crate::*;
+use core::time::Duration;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// The little puzzle that has to be solved to do a task. Generated up front
+// from the game's seed, so the server knows the answer and can turn away
+// anyone claiming to have done a task they never solved.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum TaskMinigame {
+  // Wires come in on the left in order, and each has to be plugged into the
+  // slot on the right that matches its color. order[slot] is the wire that
+  // goes in that slot.
+  Wires {
+    order: Vec<usize>,
+  },
+  // Swipe the card, not too fast and not too slow.
+  CardSwipe {
+    fastest: Duration,
+    slowest: Duration,
+  },
+  // Type in the code that's written on the note next to it.
+  Keypad {
+    code: Vec<u8>,
+  },
+}
+
+// What the player did, to be checked against the puzzle.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum MinigameSolution {
+  // connections[wire] is the slot that wire was plugged into.
+  Wires { connections: Vec<usize> },
+  CardSwipe { took: Duration },
+  Keypad { code: Vec<u8> },
+}
+
+impl TaskMinigame {
+  const WIRES: usize = 4;
+  const CODE_LENGTH: usize = 5;
+
+  pub fn generate(rng: &mut GameRng) -> Self {
+    match rng.gen_range(0, 3) {
+      0 => {
+        let mut order: Vec<usize> = (0..Self::WIRES).collect();
+        order.shuffle(rng);
+        TaskMinigame::Wires { order }
+      }
+      1 => {
+        let fastest = Duration::from_millis(rng.gen_range(300, 700));
+        TaskMinigame::CardSwipe {
+          fastest,
+          slowest: fastest + Duration::from_millis(400),
+        }
+      }
+      _ => TaskMinigame::Keypad {
+        code: (0..Self::CODE_LENGTH)
+          .map(|_| rng.gen_range(0, 10))
+          .collect(),
+      },
+    }
+  }
+
+  pub fn is_solved_by(&self, solution: &MinigameSolution) -> bool {
+    match (self, solution) {
+      (TaskMinigame::Wires { order }, MinigameSolution::Wires { connections }) => {
+        connections.len() == order.len()
+          && connections
+            .iter()
+            .enumerate()
+            .all(|(wire, slot)| order.get(*slot) == Some(&wire))
+      }
+      (TaskMinigame::CardSwipe { fastest, slowest }, MinigameSolution::CardSwipe { took }) => {
+        took >= fastest && took <= slowest
+      }
+      (TaskMinigame::Keypad { code }, MinigameSolution::Keypad { code: entered }) => {
+        code == entered
+      }
+      _ => false,
+    }
+  }
+
+  // The right answer, for anyone who knows the puzzle.
+  pub fn solution(&self) -> MinigameSolution {
+    match self {
+      TaskMinigame::Wires { order } => {
+        let mut connections = vec![0; order.len()];
+        for (slot, wire) in order.iter().enumerate() {
+          if let Some(connection) = connections.get_mut(*wire) {
+            *connection = slot;
+          }
+        }
+        MinigameSolution::Wires { connections }
+      }
+      TaskMinigame::CardSwipe { fastest, slowest } => MinigameSolution::CardSwipe {
+        took: (*fastest + *slowest) / 2,
+      },
+      TaskMinigame::Keypad { code } => MinigameSolution::Keypad { code: code.clone() },
+    }
+  }
+}
+
+impl Task {
+  pub fn with_minigame(self, rng: &mut GameRng) -> Self {
+    Task {
+      minigame: Some(TaskMinigame::generate(rng)),
+      ..self
+    }
+  }
+
+  // Whether the given solution is good enough to work on the task. Tasks
+  // without a puzzle don't need one.
+  pub fn check_solution(&self, solution: Option<&MinigameSolution>) -> Result<(), GameError> {
+    match (&self.minigame, solution) {
+      (None, _) => Ok(()),
+      (Some(minigame), Some(solution)) if minigame.is_solved_by(solution) => Ok(()),
+      (Some(_), _) => Err(GameError::WrongSolution),
+    }
+  }
+}
+
+impl GameState {
+  // Finishes off the task the player's working on, if they've solved its
+  // puzzle. They have to have stayed put since they started.
+  pub fn solve_minigame(
+    &mut self,
+    uuid: UUID,
+    solution: &MinigameSolution,
+  ) -> Result<(), GameError> {
+    self.complete_interaction(uuid, Some(solution))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_minigames_only_accept_real_solutions() {
+    let wires = TaskMinigame::Wires {
+      order: vec![2, 0, 1],
+    };
+    assert!(wires.is_solved_by(&MinigameSolution::Wires {
+      connections: vec![1, 2, 0]
+    }));
+    assert!(!wires.is_solved_by(&MinigameSolution::Wires {
+      connections: vec![0, 1, 2]
+    }));
+    assert!(!wires.is_solved_by(&MinigameSolution::Wires {
+      connections: vec![1, 2]
+    }));
+
+    let swipe = TaskMinigame::CardSwipe {
+      fastest: Duration::from_millis(500),
+      slowest: Duration::from_millis(900),
+    };
+    let took = |ms| MinigameSolution::CardSwipe {
+      took: Duration::from_millis(ms),
+    };
+    assert!(swipe.is_solved_by(&took(700)));
+    assert!(!swipe.is_solved_by(&took(100)));
+    assert!(!swipe.is_solved_by(&took(1500)));
+
+    let keypad = TaskMinigame::Keypad {
+      code: vec![1, 2, 3],
+    };
+    assert!(keypad.is_solved_by(&MinigameSolution::Keypad {
+      code: vec![1, 2, 3]
+    }));
+    assert!(!keypad.is_solved_by(&took(700)));
+
+    // Same seed, same puzzles, so the server and clients agree.
+    let mut first = GameRng::new(7);
+    let mut second = GameRng::new(7);
+    for _ in 0..10 {
+      assert_eq!(
+        TaskMinigame::generate(&mut first),
+        TaskMinigame::generate(&mut second)
+      );
+    }
+
+    let (mut state, uuids) = game_with(&[Role::Crewmate, Role::Impostor]);
+    let uuid = uuids[0];
+    let spot = state.players[&uuid].position;
+    let mut task = Task::new(TaskKind::Short, spot);
+    task.minigame = Some(keypad);
+    state.players.get_mut(&uuid).unwrap().tasks = vec![task];
+
+    // Standing there long enough isn't enough on its own.
+    state.begin_interaction(uuid, 0).unwrap();
+    state.simulate(state.settings.task_work_time + Duration::from_millis(100));
+    assert!(!state.players[&uuid].tasks[0].finished);
+    assert_eq!(
+      state.solve_minigame(uuid, &MinigameSolution::Keypad { code: vec![3] }),
+      Err(GameError::WrongSolution)
+    );
+    state
+      .solve_minigame(
+        uuid,
+        &MinigameSolution::Keypad {
+          code: vec![1, 2, 3],
+        },
+      )
+      .unwrap();
+    assert!(state.players[&uuid].tasks[0].finished);
+  }
+}
@@ -0,0 +1,112 @@
+use crate::*;
+
+// What goes on a given player's minimap, on top of the map itself. Only ever
+// what that player is allowed to know, so it's safe to show all of it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Minimap {
+  // Where the viewer is. Spectators aren't anywhere.
+  pub you: Option<Position>,
+  // The viewer's own unfinished tasks, wherever they are.
+  pub tasks: Vec<Position>,
+  // For impostors, everything there is to sabotage.
+  pub sabotage_targets: Vec<(Sabotage, Position)>,
+  // Somewhere the current sabotage can be fixed from. Everyone knows these,
+  // since the alarm's going off.
+  pub fix_stations: Vec<Position>,
+}
+
+impl GameState {
+  pub fn minimap(&self, viewer: UUID) -> Minimap {
+    let fix_stations = match self.sabotage.active {
+      Some(active) => self
+        .map
+        .fix_stations
+        .iter()
+        .filter(|s| s.sabotage == active.kind)
+        .map(|s| s.position)
+        .collect(),
+      None => vec![],
+    };
+    let viewer = match self.players.get(&viewer) {
+      Some(viewer) => viewer,
+      None => {
+        return Minimap {
+          fix_stations,
+          ..Minimap::default()
+        }
+      }
+    };
+    let sabotage_targets = if viewer.impostor {
+      self.sabotage_targets()
+    } else {
+      vec![]
+    };
+    Minimap {
+      you: Some(viewer.position),
+      tasks: viewer
+        .tasks
+        .iter()
+        .filter(|t| !t.finished)
+        .map(|t| t.position)
+        .collect(),
+      sabotage_targets,
+      fix_stations,
+    }
+  }
+
+  // Where each kind of sabotage does its damage: the doors themselves, or
+  // wherever it has to be fixed from.
+  fn sabotage_targets(&self) -> Vec<(Sabotage, Position)> {
+    let map = &self.map;
+    map
+      .fix_stations
+      .iter()
+      .map(|s| (s.sabotage, s.position))
+      .chain(
+        map
+          .doors
+          .iter()
+          .map(|d| (Sabotage::Doors, d.shape.center())),
+      )
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_minimaps_only_show_what_you_know() {
+    let (mut state, uuids) = game_at(&[
+      (Role::Crewmate, Position { x: 100.0, y: 100.0 }),
+      (Role::Impostor, Position { x: 200.0, y: 100.0 }),
+    ]);
+    let (crewmate, impostor) = (uuids[0], uuids[1]);
+    let task_spot = Position { x: 300.0, y: 300.0 };
+    let mut finished = Task::new(TaskKind::Short, Position { x: 50.0, y: 50.0 });
+    finished.finished = true;
+    state.players.get_mut(&crewmate).unwrap().tasks =
+      vec![Task::new(TaskKind::Short, task_spot), finished];
+
+    let minimap = state.minimap(crewmate);
+    assert_eq!(minimap.you, Some(Position { x: 100.0, y: 100.0 }));
+    assert_eq!(minimap.tasks, vec![task_spot]);
+    assert!(minimap.sabotage_targets.is_empty());
+    assert!(minimap.fix_stations.is_empty());
+
+    let minimap = state.minimap(impostor);
+    assert!(minimap
+      .sabotage_targets
+      .iter()
+      .any(|(kind, _)| *kind == Sabotage::Doors));
+    assert!(minimap
+      .sabotage_targets
+      .iter()
+      .any(|(kind, _)| *kind == Sabotage::Lights));
+
+    // Spectators don't have tasks, or anywhere to be.
+    assert_eq!(state.minimap(UUID::random()), Minimap::default());
+  }
+}
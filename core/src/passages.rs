@@ -0,0 +1,105 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+// Who gets through a passage, and which way.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Passability {
+  // A crawlspace that only impostors know about. Solid to the crew.
+  ImpostorOnly,
+  // A gap too narrow for an impostor to squeeze through.
+  CrewOnly,
+  // A drop that can only be crossed heading roughly the given way, and never
+  // climbed back up.
+  OneWay { direction: Velocity },
+}
+
+// Part of the map that's solid to some players, or some ways, but not
+// others. Vents don't need these, since each one already lists the vents it
+// leads to, so a vent that leads somewhere with no way back is one-way too.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Passage {
+  pub shape: Shape,
+  pub passability: Passability,
+}
+
+impl Passage {
+  // Whether this is solid to the given player, moving the given way.
+  pub fn blocks(&self, player: &Player, movement: Velocity) -> bool {
+    match self.passability {
+      Passability::ImpostorOnly => !player.impostor,
+      Passability::CrewOnly => player.impostor,
+      Passability::OneWay { direction } => movement.dot_product(&direction) <= 0.0,
+    }
+  }
+}
+
+impl Map {
+  // The passages that the given player can't get through, moving the given
+  // way.
+  pub fn blocking_passages<'a>(
+    &'a self,
+    player: &'a Player,
+    movement: Velocity,
+  ) -> impl Iterator<Item = &'a Shape> {
+    self
+      .passages
+      .iter()
+      .filter(move |p| p.blocks(player, movement))
+      .map(|p| &p.shape)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use std::borrow::Cow;
+
+  #[test]
+  fn test_passages_let_some_through_and_not_others() {
+    let mut map = MapRegistry::get(MapId::Lobby).unwrap();
+    let strip = |x: f64, passability| Passage {
+      shape: Shape::Rect {
+        top_left: Position { x, y: 0.0 },
+        width: 20.0,
+        height: 600.0,
+        fill_color: Cow::Borrowed("#000"),
+        outline_width: 1.0,
+        outline_color: Cow::Borrowed("#000"),
+      },
+      passability,
+    };
+    map.passages = Cow::Owned(vec![
+      strip(100.0, Passability::CrewOnly),
+      strip(
+        200.0,
+        Passability::OneWay {
+          direction: Velocity { dx: 1.0, dy: 0.0 },
+        },
+      ),
+    ]);
+    let doors = DoorState::default();
+    let walk = |player: &Player, from: f64, dx: f64| {
+      let mut player = player.clone();
+      player.position = Position { x: from, y: 500.0 };
+      for _ in 0..40 {
+        player.position = map.move_player(&player, Velocity { dx, dy: 0.0 }, &doors);
+      }
+      player.position.x
+    };
+    let crewmate = Player::new(
+      UUID::random(),
+      "Test Player".to_string(),
+      Color::Red,
+      Position { x: 0.0, y: 0.0 },
+    );
+    let mut impostor = crewmate.clone();
+    impostor.impostor = true;
+
+    // Only the crew fit through the gap.
+    assert!(walk(&crewmate, 50.0, 4.0) > 150.0);
+    assert!(walk(&impostor, 50.0, 4.0) < 100.0);
+    // Anyone can drop down, but no one can climb back up.
+    assert!(walk(&impostor, 150.0, 4.0) > 250.0);
+    assert!(walk(&impostor, 250.0, -4.0) > 220.0);
+  }
+}
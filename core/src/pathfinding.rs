@@ -0,0 +1,176 @@
+use crate::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// Cuts the map up into squares, like the reachability check does, and finds
+// a way across them. Moves are in whole cells, straight or diagonal.
+const CELL_SIZE: f64 = 10.0;
+const STRAIGHT: u32 = 10;
+const DIAGONAL: u32 = 14;
+
+impl Map {
+  // A way for a player to walk from one spot to another, without going
+  // through the furniture or any closed doors. The path's given as the spots
+  // to head for in turn, ending at the destination. Passages are let through,
+  // since whether they can be used depends on who's using them.
+  pub fn find_path(
+    &self,
+    from: Position,
+    to: Position,
+    doors: &DoorState,
+  ) -> Option<Vec<Position>> {
+    let columns = (self.width() / CELL_SIZE).ceil() as usize;
+    let rows = (self.height() / CELL_SIZE).ceil() as usize;
+    let cell_at = |position: Position| {
+      let column = (position.x / CELL_SIZE).floor();
+      let row = (position.y / CELL_SIZE).floor();
+      if column < 0.0 || row < 0.0 || column as usize >= columns || row as usize >= rows {
+        return None;
+      }
+      Some(row as usize * columns + column as usize)
+    };
+    let center = |cell: usize| Position {
+      x: ((cell % columns) as f64 + 0.5) * CELL_SIZE,
+      y: ((cell / columns) as f64 + 0.5) * CELL_SIZE,
+    };
+    let start = cell_at(from)?;
+    let goal = cell_at(to)?;
+    let radius = Player::radius();
+    // Wherever the player is now, and wherever they want to be, are fine as
+    // they are, even if the middle of their square isn't.
+    let walkable = |cell: usize| {
+      if cell == start || cell == goal {
+        return true;
+      }
+      let position = center(cell);
+      is_clear(self, position)
+        && !self
+          .closed_doors(doors)
+          .any(|door| door.collides_with(position, radius))
+    };
+    let heuristic = |cell: usize| {
+      let dx = ((cell % columns) as i64 - (goal % columns) as i64).unsigned_abs() as u32;
+      let dy = ((cell / columns) as i64 - (goal / columns) as i64).unsigned_abs() as u32;
+      STRAIGHT * dx.max(dy) + (DIAGONAL - STRAIGHT) * dx.min(dy)
+    };
+
+    let mut cost = vec![u32::MAX; columns * rows];
+    let mut came_from = vec![usize::MAX; columns * rows];
+    let mut open = BinaryHeap::new();
+    cost[start] = 0;
+    open.push(Reverse((heuristic(start), start)));
+    while let Some(Reverse((_, cell))) = open.pop() {
+      if cell == goal {
+        break;
+      }
+      let (column, row) = ((cell % columns) as i64, (cell / columns) as i64);
+      for (dx, dy) in [
+        (-1, 0),
+        (1, 0),
+        (0, -1),
+        (0, 1),
+        (-1, -1),
+        (1, -1),
+        (-1, 1),
+        (1, 1),
+      ]
+      .iter()
+      {
+        let (next_column, next_row) = (column + dx, row + dy);
+        if next_column < 0
+          || next_row < 0
+          || next_column as usize >= columns
+          || next_row as usize >= rows
+        {
+          continue;
+        }
+        let next = next_row as usize * columns + next_column as usize;
+        if !walkable(next) {
+          continue;
+        }
+        let diagonal = *dx != 0 && *dy != 0;
+        // No cutting corners.
+        if diagonal
+          && !(walkable(row as usize * columns + next_column as usize)
+            && walkable(next_row as usize * columns + column as usize))
+        {
+          continue;
+        }
+        let step = if diagonal { DIAGONAL } else { STRAIGHT };
+        let next_cost = cost[cell] + step;
+        if next_cost < cost[next] {
+          cost[next] = next_cost;
+          came_from[next] = cell;
+          open.push(Reverse((next_cost + heuristic(next), next)));
+        }
+      }
+    }
+    if cost[goal] == u32::MAX {
+      return None;
+    }
+
+    let mut cells = vec![goal];
+    while let Some(&cell) = cells.last() {
+      if cell == start {
+        break;
+      }
+      cells.push(came_from[cell]);
+    }
+    cells.reverse();
+    // Only the corners matter, not every cell along the straight bits.
+    let mut path: Vec<Position> = cells
+      .windows(3)
+      .filter(|w| w[1] as i64 - w[0] as i64 != w[2] as i64 - w[1] as i64)
+      .map(|w| center(w[1]))
+      .collect();
+    path.push(to);
+    Some(path)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use core::time::Duration;
+  use std::borrow::Cow;
+
+  #[test]
+  fn test_paths_go_around_walls_and_doors() {
+    let mut map = MapRegistry::get(MapId::Lobby).unwrap();
+    let rect = |x, y, width, height| Shape::Rect {
+      top_left: Position { x, y },
+      width,
+      height,
+      fill_color: Cow::Borrowed("#000"),
+      outline_width: 1.0,
+      outline_color: Cow::Borrowed("#000"),
+    };
+    // A wall down the left of the room, with a gap at the bottom that has a
+    // door in it.
+    map.static_geometry = Cow::Owned(vec![rect(200.0, 0.0, 20.0, 500.0)]);
+    map.doors = Cow::Owned(vec![Door {
+      shape: rect(200.0, 500.0, 20.0, 100.0),
+    }]);
+    map.reindex();
+    let from = Position { x: 100.0, y: 100.0 };
+    let to = Position { x: 300.0, y: 100.0 };
+
+    let mut doors = DoorState::default();
+    let path = map.find_path(from, to, &doors).unwrap();
+    assert_eq!(path.last(), Some(&to));
+    // It has to go around the bottom of the wall.
+    assert!(path.iter().any(|p| p.y > 500.0));
+    let mut at = from;
+    for next in path.iter() {
+      assert!(map.line_of_sight(at, *next));
+      at = *next;
+    }
+
+    doors.closed.insert(0, Duration::from_secs(10));
+    assert_eq!(map.find_path(from, to, &doors), None);
+    // Still fine on the same side of the wall, though.
+    assert!(map
+      .find_path(from, Position { x: 100.0, y: 400.0 }, &doors)
+      .is_some());
+  }
+}
@@ -0,0 +1,105 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum PauseReason {
+  // Too many people lost their connection to keep going.
+  NotEnoughPlayers,
+}
+
+impl GameState {
+  fn connected_players(&self) -> usize {
+    self.players.values().filter(|p| !p.disconnected).count()
+  }
+
+  // Freezes the game in place if too many players have dropped out.
+  pub(crate) fn pause_if_too_few_players(&mut self) {
+    if self.connected_players() >= self.settings.pause_below_players {
+      return;
+    }
+    let play_state = match &self.status {
+      GameStatus::Playing(play_state) => play_state.clone(),
+      _ => return,
+    };
+    self.status = GameStatus::Paused {
+      reason: PauseReason::NotEnoughPlayers,
+      resumes_in: None,
+      play_state,
+    };
+  }
+
+  // Once enough players are back, count down and then pick up where we left off.
+  pub(crate) fn simulate_paused(&mut self) {
+    let enough_players = self.connected_players() >= self.settings.pause_below_players;
+    let countdown = self.settings.resume_countdown;
+    if let GameStatus::Paused {
+      resumes_in,
+      play_state,
+      ..
+    } = &mut self.status
+    {
+      match resumes_in {
+        None if enough_players => *resumes_in = Some(countdown),
+        Some(remaining) if *remaining == Duration::from_secs(0) => {
+          self.status = GameStatus::Playing(play_state.clone());
+        }
+        _ => {}
+      }
+    }
+  }
+
+  // The host can decide to carry on with whoever's left.
+  pub fn note_force_resume(&mut self, requester: UUID) -> Result<(), GameError> {
+    let action = Action::ResumeGame;
+    if !self.is_host(requester) {
+      return Err(GameError::NotHost { action });
+    }
+    match &self.status {
+      GameStatus::Paused { play_state, .. } => {
+        self.status = GameStatus::Playing(play_state.clone());
+        Ok(())
+      }
+      _ => Err(GameError::WrongStatus { action }),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_pauses_until_players_come_back() {
+    let roles = [
+      Role::Impostor,
+      Role::Crewmate,
+      Role::Crewmate,
+      Role::Crewmate,
+    ];
+    let (mut state, uuids) = game_with(&roles);
+    state.host = Some(uuids[0]);
+    state.players.get_mut(&uuids[0]).unwrap().kill_cooldown = Duration::from_secs(10);
+    state.settings.pause_below_players = 4;
+
+    state.handle_disconnection(uuids[1]);
+    assert!(matches!(state.status, GameStatus::Paused { .. }));
+    // Nothing moves while we're paused.
+    let cooldown = state.players[&uuids[0]].kill_cooldown;
+    state.simulate(Duration::from_secs(1));
+    assert_eq!(state.players[&uuids[0]].kill_cooldown, cooldown);
+
+    state.note_rejoined(uuids[1]).unwrap();
+    state.simulate_tick();
+    state.simulate(state.settings.resume_countdown + TICK);
+    state.simulate_tick();
+    assert_eq!(state.status, GameStatus::Playing(PlayState::Night));
+
+    state.handle_disconnection(uuids[1]);
+    assert!(state.note_force_resume(uuids[2]).is_err());
+    state.note_force_resume(uuids[0]).unwrap();
+    assert_eq!(state.status, GameStatus::Playing(PlayState::Night));
+  }
+}
@@ -18,9 +18,24 @@ pub struct InputState {
   pub skip_back: bool,
   pub skip_forward: bool,
   pub pause_playback: bool,
+  pub sabotage_lights: bool,
+  pub sabotage_reactor: bool,
+  pub sabotage_doors: bool,
+  pub sabotage_comms: bool,
+  pub drag: bool,
+  pub decoy: bool,
 }
 
 impl InputState {
+  pub fn movement(&self) -> MovementInput {
+    MovementInput {
+      up: self.up,
+      down: self.down,
+      left: self.left,
+      right: self.right,
+    }
+  }
+
   // Returns an InputState with buttons set to true if they
   // aren't pressed on self, but are set on newer_input.
   fn get_new_presses(&self, newer_input: InputState) -> InputState {
@@ -36,6 +51,12 @@ impl InputState {
       skip_back: !self.skip_back && newer_input.skip_back,
       skip_forward: !self.skip_forward && newer_input.skip_forward,
       pause_playback: !self.pause_playback && newer_input.pause_playback,
+      sabotage_lights: !self.sabotage_lights && newer_input.sabotage_lights,
+      sabotage_reactor: !self.sabotage_reactor && newer_input.sabotage_reactor,
+      sabotage_doors: !self.sabotage_doors && newer_input.sabotage_doors,
+      sabotage_comms: !self.sabotage_comms && newer_input.sabotage_comms,
+      drag: !self.drag && newer_input.drag,
+      decoy: !self.decoy && newer_input.decoy,
     }
   }
 }
@@ -44,25 +65,68 @@ impl InputState {
 pub struct GameAsPlayer {
   pub my_uuid: UUID,
   inputs: InputState,
+  prediction: Prediction,
   pub state: GameState,
   pub socket: Box<dyn GameTx>,
   pub contextual_state: ContextualState,
   pub displayed_messages: Vec<DisplayMessage>,
+  // The last task progress the server told us about.
+  pub task_bar: Option<f64>,
+  // What the console we're standing at is showing us, if anything.
+  pub surveillance: Option<Surveillance>,
+  // Lets us get back into the game if the connection drops.
+  pub rejoin_token: Option<UUID>,
+  // The code other players can use to join this game.
+  pub room_code: Option<String>,
+  // The public rooms on the server, the last time we asked.
+  pub room_list: Vec<RoomSummary>,
+  // How we've done over all our games, the last time we asked.
+  pub stats: Option<PlayerStats>,
+  // How the game went, once it's over.
+  pub summary: Option<GameSummary>,
+  // What the server says has happened, waiting for the UI to pick it up.
+  pub events: Vec<GameEvent>,
+  // What everyone's been saying, oldest first.
+  pub chat: Vec<ChatMessage>,
+  // Voice signals from other players, waiting for the UI to pick them up.
+  pub signals: Vec<(UUID, VoiceSignal)>,
+  // The tick we last told the server our checksum for.
+  last_checksum_tick: Option<u64>,
 }
 
 // A game from the perspective of a particular player.
 impl GameAsPlayer {
   pub fn new(uuid: UUID, socket: Box<dyn GameTx>) -> GameAsPlayer {
     GameAsPlayer {
-      state: GameState::new(),
+      state: GameState {
+        partial_view: true,
+        ..GameState::new()
+      },
       inputs: InputState::default(),
+      prediction: Prediction::default(),
       contextual_state: ContextualState::Blank,
       my_uuid: uuid,
       socket,
       displayed_messages: Vec::new(),
+      task_bar: None,
+      surveillance: None,
+      rejoin_token: None,
+      room_code: None,
+      room_list: vec![],
+      stats: None,
+      summary: None,
+      events: vec![],
+      chat: vec![],
+      signals: vec![],
+      last_checksum_tick: None,
     }
   }
 
+  // Everything the server's told us about since the last time we asked.
+  pub fn take_events(&mut self) -> Vec<GameEvent> {
+    std::mem::take(&mut self.events)
+  }
+
   // Is there a way to avoid duplicating the logic between local_player and local_player_mut?
   pub fn local_player(&self) -> Option<&Player> {
     self.state.players.get(&self.my_uuid)
@@ -83,9 +147,159 @@ impl GameAsPlayer {
       message.pass_time(elapsed);
     }
     self.displayed_messages.retain(|m| !m.is_expired());
+    self.prediction.simulate(elapsed);
     self.state.simulate(elapsed)
   }
 
+  // The server decides who, if anyone, gets to hear it.
+  pub fn send_chat(&mut self, text: String) -> Result<(), String> {
+    self.socket.send(&ClientToServerMessage::Chat { text })
+  }
+
+  pub fn send_quick_chat(&mut self, quick: QuickChat) -> Result<(), String> {
+    self.socket.send(&ClientToServerMessage::QuickChat(quick))
+  }
+
+  pub fn send_signal(&mut self, to: UUID, signal: VoiceSignal) -> Result<(), String> {
+    self
+      .socket
+      .send(&ClientToServerMessage::Signal { to, signal })
+  }
+
+  pub fn take_signals(&mut self) -> Vec<(UUID, VoiceSignal)> {
+    std::mem::take(&mut self.signals)
+  }
+
+  // The players we should be able to hear right now.
+  pub fn audible_peers(&self, max_distance: f64) -> Vec<UUID> {
+    self
+      .state
+      .audible_pairs(max_distance)
+      .into_iter()
+      .filter_map(|(a, b)| match (a == self.my_uuid, b == self.my_uuid) {
+        (true, _) => Some(b),
+        (_, true) => Some(a),
+        _ => None,
+      })
+      .collect()
+  }
+
+  // How loud each of the players we can hear should be, from 0 to 1.
+  pub fn peer_volumes(&self, max_distance: f64) -> Vec<(UUID, f64)> {
+    let me = match self.local_player() {
+      Some(me) => me,
+      None => return vec![],
+    };
+    self
+      .audible_peers(max_distance)
+      .into_iter()
+      .filter_map(|uuid| {
+        let them = self.state.players.get(&uuid)?;
+        Some((uuid, self.state.volume_between(me, them, max_distance)))
+      })
+      .collect()
+  }
+
+  // The puzzle for the task we're working on, for the client to put up.
+  pub fn current_minigame(&self) -> Option<&TaskMinigame> {
+    let me = self.local_player()?;
+    let interaction = me.interaction.as_ref()?;
+    me.tasks.get(interaction.task)?.minigame.as_ref()
+  }
+
+  pub fn solve_minigame(&mut self, solution: MinigameSolution) -> Result<(), String> {
+    self
+      .state
+      .solve_minigame(self.my_uuid, &solution)
+      .map_err(|e| e.to_string())?;
+    self
+      .socket
+      .send(&ClientToServerMessage::SolveMinigame { solution })
+  }
+
+  // For the host, to fill up the lobby when there aren't enough people.
+  pub fn add_bots(&mut self, count: usize) -> Result<(), String> {
+    self.socket.send(&ClientToServerMessage::AddBots { count })
+  }
+
+  // For the host, to get rid of someone, and maybe keep them out for good.
+  pub fn kick_player(&mut self, uuid: UUID, ban: bool) -> Result<(), String> {
+    let message = if ban {
+      ClientToServerMessage::BanPlayer { uuid }
+    } else {
+      ClientToServerMessage::KickPlayer { uuid }
+    };
+    self.socket.send(&message)
+  }
+
+  // The next color along from ours that no one's wearing, for flipping
+  // through them in the lobby.
+  pub fn next_color(&self) -> Option<Color> {
+    let current = self.local_player()?.color;
+    let available = self.state.available_colors();
+    let all = Color::all();
+    let start = all.iter().position(|c| *c == current)?;
+    (1..all.len())
+      .map(|offset| all[(start + offset) % all.len()])
+      .find(|color| available.contains(color))
+  }
+
+  pub fn request_color(&mut self, color: Color) -> Result<(), String> {
+    self
+      .state
+      .note_color_requested(self.my_uuid, color)
+      .map_err(|e| e.to_string())?;
+    self
+      .socket
+      .send(&ClientToServerMessage::RequestColor(color))
+  }
+
+  // Only for the host, once the game's over.
+  pub fn return_to_lobby(&mut self) -> Result<(), String> {
+    self
+      .state
+      .note_returned_to_lobby(self.my_uuid)
+      .map_err(|e| e.to_string())?;
+    self.summary = None;
+    self.socket.send(&ClientToServerMessage::ReturnToLobby())
+  }
+
+  // Only for the host, in the lobby.
+  pub fn update_settings(&mut self, patch: SettingsPatch) -> Result<(), String> {
+    self
+      .state
+      .patched_settings(self.my_uuid, &patch)
+      .map_err(|e| e.to_string())?;
+    self
+      .socket
+      .send(&ClientToServerMessage::UpdateSettings(Box::new(patch)))
+  }
+
+  // The answer turns up in self.stats.
+  pub fn request_stats(&mut self) -> Result<(), String> {
+    self.socket.send(&ClientToServerMessage::GetStats())
+  }
+
+  // For impostors, to pass themselves off as someone else for a while.
+  pub fn shapeshift(&mut self, target: UUID) -> Result<(), String> {
+    self
+      .socket
+      .send(&ClientToServerMessage::Shapeshift { target })
+  }
+
+  // For everyone in the lobby, for when the host can't or won't.
+  pub fn vote_kick(&mut self, target: UUID) -> Result<(), String> {
+    self
+      .socket
+      .send(&ClientToServerMessage::VoteKick { target })
+  }
+
+  pub fn set_muted(&mut self, uuid: UUID, muted: bool) -> Result<(), String> {
+    self
+      .socket
+      .send(&ClientToServerMessage::Mute { uuid, muted })
+  }
+
   // Take the given inputs from the local player
   pub fn take_input(&mut self, new_input: InputState) -> Result<(), String> {
     match &self.state.status {
@@ -103,10 +317,19 @@ impl GameAsPlayer {
         self.inputs = new_input;
         Ok(())
       }
+      GameStatus::Paused { .. } => {
+        let resuming = !self.inputs.play && new_input.play;
+        self.inputs = new_input;
+        if resuming && self.state.is_host(self.my_uuid) {
+          self.socket.send(&ClientToServerMessage::ResumeGame())?;
+        }
+        Ok(())
+      }
       GameStatus::Playing(PlayState::TallyingVotes(_))
       | GameStatus::Playing(PlayState::ViewingOutcome(_))
       | GameStatus::Connecting
       | GameStatus::Won(_)
+      | GameStatus::Draw
       | GameStatus::Disconnected => {
         // Nothing to do
         Ok(())
@@ -124,19 +347,43 @@ impl GameAsPlayer {
       return Ok(()); // quick exit for the boring case
     }
     // Read the parts of the local player that we care about.
-    let is_killing = player.impostor && !current_input.kill && new_input.kill;
+    let is_killing = player.impostor && !player.dead && !current_input.kill && new_input.kill;
+    // The medic and the sheriff use the kill button for their abilities.
+    let using_ability = !player.impostor && !player.dead && !current_input.kill && new_input.kill;
+    let is_impostor = player.impostor && !player.dead;
     let position = player.position;
+    let in_vent = player.in_vent;
     let activating = !current_input.activate && new_input.activate;
-    let reporting = !current_input.report && new_input.report;
+    let reporting = !player.dead && !current_input.report && new_input.report;
     let starting_play =
       self.state.status == GameStatus::Lobby && !current_input.play && new_input.play;
+    let pressed = current_input.get_new_presses(new_input);
     self.inputs = new_input;
     // ok, we're done touching player at this point. we redeclare it
     // below so we can use it again, next time mutably.
 
+    // The server works out where we end up, all it needs to know is which
+    // way we're trying to go. This way we don't send anything unless the
+    // movement keys actually changed, reducing data leakage to HAXXORZ.
+    let movement = new_input.movement();
+    if let Some(sequence) = self.prediction.note_input(movement) {
+      self
+        .socket
+        .send(&ClientToServerMessage::Input { sequence, movement })?;
+    }
+
+    if let Some(vent) = in_vent {
+      // While in a vent, the only things you can do are hop to
+      // another vent, or climb back out.
+      return self.take_vent_input(vent, pressed);
+    }
+
     if is_killing {
       self.kill_player_near(position)?;
     }
+    if using_ability {
+      self.use_ability_near(position)?;
+    }
     if activating {
       self.activate_near(position)?;
     }
@@ -146,23 +393,67 @@ impl GameAsPlayer {
     if reporting {
       self.report_body_near(position)?;
     }
-
-    let speed_changed: bool;
-    {
-      let new_speed = self.get_speed();
-      let player = self.local_player_mut().unwrap();
-      speed_changed = new_speed != player.velocity;
-      player.velocity = new_speed;
+    if pressed.sabotage_lights {
+      self.sabotage(Sabotage::Lights)?;
+    }
+    if pressed.sabotage_reactor {
+      self.sabotage(Sabotage::Reactor)?;
+    }
+    if pressed.sabotage_doors {
+      self.sabotage(Sabotage::Doors)?;
     }
+    if pressed.sabotage_comms {
+      self.sabotage(Sabotage::Comms)?;
+    }
+    if pressed.drag {
+      self.drag_or_release_body()?;
+    }
+    if pressed.decoy && is_impostor {
+      self.socket.send(&ClientToServerMessage::DropDecoy())?;
+    }
+
+    // Move locally right away, rather than waiting to hear back from the server.
+    let new_speed = movement.velocity(self.state.settings.speed);
+    self.local_player_mut().unwrap().velocity = new_speed;
+    Ok(())
+  }
 
-    // This way we don't send a MoveMessage unless movement keys actually changed,
-    // reducing data leakage to HAXXORZ.
-    if speed_changed {
-      let player = self.local_player().unwrap();
-      self.socket.send(&ClientToServerMessage::Move(MoveMessage {
-        speed: player.velocity,
-        position: player.position,
-      }))?;
+  fn take_vent_input(&mut self, vent: usize, pressed: InputState) -> Result<(), String> {
+    if pressed.activate {
+      self.socket.send(&ClientToServerMessage::ExitVent())?;
+      return Ok(());
+    }
+    let mut direction = Velocity::default();
+    if pressed.up {
+      direction.dy -= 1.0;
+    }
+    if pressed.down {
+      direction.dy += 1.0;
+    }
+    if pressed.left {
+      direction.dx -= 1.0;
+    }
+    if pressed.right {
+      direction.dx += 1.0;
+    }
+    if direction.magnitude() == 0.0 {
+      return Ok(());
+    }
+    // Hop to whichever connected vent is most in the direction pressed.
+    let vents = &self.state.map.vents;
+    let from = vents[vent].position;
+    let mut best: Option<(usize, f64)> = None;
+    for to in vents[vent].connections.iter() {
+      let towards: Velocity = vents[*to].position.minus(&from);
+      let alignment = direction
+        .normalize::<Velocity>()
+        .dot_product(&towards.normalize::<Velocity>());
+      if alignment > 0.0 && best.map(|(_, b)| alignment > b).unwrap_or(true) {
+        best = Some((*to, alignment));
+      }
+    }
+    if let Some((to, _)) = best {
+      self.socket.send(&ClientToServerMessage::MoveVent { to })?;
     }
     Ok(())
   }
@@ -184,11 +475,11 @@ impl GameAsPlayer {
       // The dead don't get a vote.
       return Ok(None);
     }
-    let has_voted = day_state.votes.contains_key(&player.uuid);
-    if has_voted {
+    if day_state.is_locked_in(player.uuid, &self.state.settings) {
       // Nothing to do but wait if you've already voted.
       return Ok(None);
     }
+    let current_vote = day_state.votes.get(&player.uuid).copied();
     let mut voting_state = match self.contextual_state {
       ContextualState::Voting(voting) => voting,
       ContextualState::Blank => {
@@ -207,6 +498,8 @@ impl GameAsPlayer {
       .map(|(idx, (uuid, _p))| TargetInVotingTable::new(idx, VoteTarget::Player { uuid: *uuid }))
       .collect();
     vote_targets.push(TargetInVotingTable::new(10, VoteTarget::Skip));
+    // In a runoff, only the tied targets can be picked.
+    vote_targets.retain(|vt| day_state.can_vote_for(vt.target));
     match voting_state.highlighted_player {
       None => {
         if pressed.up || pressed.down || pressed.left || pressed.right {
@@ -286,89 +579,182 @@ impl GameAsPlayer {
         voting_state.highlighted_player = Some(highlighted.target);
       }
     }
-    if pressed.activate {
+    // You can look around during the discussion, but not vote yet.
+    if pressed.activate && day_state.accepting_votes() {
       if let Some(target) = voting_state.highlighted_player {
-        self.socket.send(&ClientToServerMessage::Vote { target })?;
-        voting_state.highlighted_player = None;
+        if !self.state.settings.allow_vote_changes {
+          self.socket.send(&ClientToServerMessage::Vote { target })?;
+          voting_state.highlighted_player = None;
+        } else if current_vote == Some(target) {
+          // Voting for the same target again locks it in.
+          self.socket.send(&ClientToServerMessage::LockInVote())?;
+          voting_state.highlighted_player = None;
+        } else {
+          self.socket.send(&ClientToServerMessage::Vote { target })?;
+        }
       }
     }
     Ok(Some(voting_state))
   }
 
-  fn get_speed(&self) -> Velocity {
-    let mut dx = 0.0;
-    let mut dy = 0.0;
-    if self.inputs.up && !self.inputs.down {
-      dy = -self.state.settings.speed
-    } else if self.inputs.down {
-      dy = self.state.settings.speed
-    }
-    if self.inputs.left && !self.inputs.right {
-      dx = -self.state.settings.speed
-    } else if self.inputs.right {
-      dx = self.state.settings.speed
-    }
-    Velocity { dx, dy }
-  }
-
   fn kill_player_near(&mut self, position: Position) -> Result<(), String> {
-    let mut killed_player: Option<DeadBody> = None;
+    match self.local_player() {
+      Some(p) if p.kill_cooldown == Duration::from_secs(0) => (),
+      // Still on cooldown, the server won't accept this kill.
+      _ => return Ok(()),
+    }
+    let mut victim: Option<UUID> = None;
     let mut closest_distance = self.state.settings.kill_distance;
 
-    for (_, player) in self.state.players.iter_mut() {
-      if player.impostor || player.uuid == self.my_uuid || player.dead {
+    for (_, player) in self.state.players.iter() {
+      if player.impostor || player.uuid == self.my_uuid || player.dead || player.in_vent.is_some() {
         continue;
       }
 
       let distance = position.distance(&player.position);
       if distance < closest_distance {
-        killed_player = Some(DeadBody {
-          position: player.position,
-          color: player.color,
-        });
+        victim = Some(player.uuid);
         closest_distance = distance;
       }
     }
 
-    if let Some(body) = killed_player {
-      self.state.note_death(body)?;
-      self.socket.send(&ClientToServerMessage::Killed(body))?;
-      // Move the killer on top of the new body.
-      if let Some(player) = self.local_player_mut() {
-        player.position = body.position;
-      }
+    // Only the server knows who the medic has shielded, so the kill waits
+    // for its say so, rather than showing a death that might not happen.
+    if let Some(victim) = victim {
+      self.socket.send(&ClientToServerMessage::Kill {
+        victim,
+        seen_at: self.seen_tick(),
+      })?;
     }
 
     Ok(())
   }
 
+  fn use_ability_near(&mut self, position: Position) -> Result<(), String> {
+    let role = match self.local_player() {
+      Some(p) => p.role,
+      None => return Ok(()),
+    };
+    let target = self
+      .state
+      .players
+      .values()
+      .filter(|p| p.uuid != self.my_uuid && !p.dead && p.in_vent.is_none())
+      .map(|p| (p.uuid, position.distance(&p.position)))
+      .filter(|(_, distance)| *distance < self.state.settings.kill_distance)
+      .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+      .map(|(uuid, _)| uuid);
+    let target = match target {
+      Some(target) => target,
+      None => return Ok(()),
+    };
+    match role {
+      Role::Medic { shield_used: false } => {
+        self
+          .socket
+          .send(&ClientToServerMessage::Shield { target })?;
+      }
+      Role::Sheriff => {
+        self
+          .socket
+          .send(&ClientToServerMessage::SheriffShot { target })?;
+      }
+      _ => (),
+    }
+    Ok(())
+  }
+
   fn activate_near(&mut self, position: Position) -> Result<(), String> {
-    let mut closest_distance = self.state.settings.task_distance;
-    let local_player = match self.local_player_mut() {
+    let local_player = match self.local_player() {
       Some(player) => player,
       None => return Ok(()),
     };
     let is_imp = local_player.impostor;
 
-    let mut finished_task: Option<FinishedTask> = None;
+    let task_distance = self.state.settings.task_distance;
+    let mut closest_distance = f64::INFINITY;
+    let mut task_near: Option<usize> = None;
     for (index, task) in local_player.tasks.iter().enumerate() {
+      // Timed tasks that are still cooking can't be worked on.
+      if task.finished || task.is_waiting() {
+        continue;
+      }
+      if !self
+        .state
+        .map
+        .can_interact(position, task.position, task_distance)
+      {
+        continue;
+      }
       let distance = position.distance(&task.position);
       if distance < closest_distance {
-        finished_task = Some(FinishedTask { index });
+        task_near = Some(index);
         closest_distance = distance;
       }
     }
-    if let Some(finished_task) = finished_task {
+    if let Some(task) = task_near {
       if !is_imp {
-        self.state.note_finished_task(self.my_uuid, finished_task)?;
+        self
+          .state
+          .begin_interaction(self.my_uuid, task)
+          .map_err(|e| e.to_string())?;
         self
           .socket
-          .send(&ClientToServerMessage::FinishedTask(finished_task))?;
+          .send(&ClientToServerMessage::BeginInteraction { task })?;
       }
+      return Ok(());
+    }
+    // Impostors can duck into vents.
+    if self.state.vent_near(self.my_uuid).is_some() {
+      self.socket.send(&ClientToServerMessage::EnterVent())?;
+      return Ok(());
+    }
+    // Not near a task, but maybe there's a sabotage to fix?
+    if self.state.fixable_sabotage_near(self.my_uuid).is_some() {
+      self.socket.send(&ClientToServerMessage::FixSabotage())?;
+      return Ok(());
+    }
+    // Or maybe we're near the emergency button?
+    if self.state.can_call_emergency_meeting(self.my_uuid).is_ok() {
+      self.socket.send(&ClientToServerMessage::CallMeeting())?;
     }
     Ok(())
   }
 
+  fn sabotage(&mut self, kind: Sabotage) -> Result<(), String> {
+    // Let the server be the judge of timing, but don't bother it with
+    // sabotages that we know can't work.
+    if self.state.can_sabotage(self.my_uuid, kind).is_ok() {
+      self.socket.send(&ClientToServerMessage::Sabotage(kind))?;
+    }
+    Ok(())
+  }
+
+  // Every so often, right after we've caught up with the server, we tell it
+  // what our game looks like so that it can tell if we've drifted.
+  fn send_checksum_if_due(&mut self) -> Result<(), String> {
+    // About once a second.
+    const CHECKSUM_INTERVAL: u64 = 60;
+    let tick = self.state.position_history.tick;
+    if let Some(last) = self.last_checksum_tick {
+      if tick < last + CHECKSUM_INTERVAL {
+        return Ok(());
+      }
+    }
+    self.last_checksum_tick = Some(tick);
+    self.socket.send(&ClientToServerMessage::Checksum {
+      tick,
+      checksum: self.state.checksum(),
+    })
+  }
+
+  // The server tick that what we're looking at is from. Everyone else is
+  // dead reckoned forward from the last snapshot, a tick at a time just like
+  // the server does.
+  fn seen_tick(&self) -> u64 {
+    self.state.position_history.tick
+  }
+
   fn report_body_near(&mut self, position: Position) -> Result<(), String> {
     let mut closest_distance = self.state.settings.report_distance;
     let mut nearest_body_color: Option<Color> = None;
@@ -382,6 +768,20 @@ impl GameAsPlayer {
     if let Some(color) = nearest_body_color {
       self.socket.send(&ClientToServerMessage::ReportBody {
         dead_body_color: color,
+        seen_at: self.seen_tick(),
+      })?;
+    }
+    Ok(())
+  }
+
+  // Picks up the nearest body, or puts down the one we've already got.
+  fn drag_or_release_body(&mut self) -> Result<(), String> {
+    if self.state.dragging(self.my_uuid) {
+      return self.socket.send(&ClientToServerMessage::ReleaseBody());
+    }
+    if let Some(color) = self.state.body_to_drag_near(self.my_uuid) {
+      self.socket.send(&ClientToServerMessage::DragBody {
+        dead_body_color: color,
       })?;
     }
     Ok(())
@@ -389,7 +789,7 @@ impl GameAsPlayer {
 
   pub fn disconnected(&mut self) -> Result<(), String> {
     match self.state.status {
-      GameStatus::Won(_) => (), // do nothing, this is expected
+      GameStatus::Won(_) | GameStatus::Draw => (), // do nothing, this is expected
       _ => self.update_status(GameStatus::Disconnected),
     };
     Ok(())
@@ -398,10 +798,7 @@ impl GameAsPlayer {
   // Returns the distance that this client should be able to see.
   // Returns None is they should see everything.
   pub fn vision(&self) -> Option<f64> {
-    self
-      .local_player()
-      .map(|p| p.vision(&self.state.settings, &self.state.status))
-      .flatten()
+    self.local_player().map(|p| self.state.vision(p)).flatten()
   }
 
   // Returns whether this client is for a player that won.
@@ -420,7 +817,13 @@ impl GameAsPlayer {
   }
 
   pub fn handle_msg(&mut self, message: ServerToClientMessage) -> Result<(), String> {
-    console_log!("Player handling message: {}", message.kind());
+    let _span = tracing::info_span!(
+      "player",
+      uuid = %self.my_uuid,
+      tick = self.state.position_history.tick
+    )
+    .entered();
+    tracing::debug!(kind = message.kind(), "Handling a message");
     match message {
       ServerToClientMessage::Welcome {
         connection_id: uuid,
@@ -431,9 +834,43 @@ impl GameAsPlayer {
         status,
         bodies,
         players,
+        emergency_cooldown,
+        sabotage,
+        doors,
+        task_progress,
+        ejection,
+        spectators,
+        host,
+        game_time_remaining,
+        sudden_death_remaining,
+        input_ack,
+        tick,
+        map_id,
+        surveillance,
       }) => {
+        if let (true, Some(map)) = (self.state.map.id != map_id, MapRegistry::get(map_id)) {
+          self.state.set_map(map);
+        }
+        self.state.position_history.tick = tick;
+        self.surveillance = surveillance;
         self.update_status(status);
+        // While comms are down, even the last progress we heard about is
+        // out of reach.
+        if task_progress.is_some()
+          || self.state.status == GameStatus::Lobby
+          || sabotage.is_active(Sabotage::Comms)
+        {
+          self.task_bar = task_progress;
+        }
         self.state.bodies = bodies;
+        self.state.emergency_cooldown = emergency_cooldown;
+        self.state.sabotage = sabotage;
+        self.state.doors = doors;
+        self.state.ejection = ejection;
+        self.state.host = host;
+        self.state.game_time_remaining = game_time_remaining;
+        self.state.sudden_death_remaining = sudden_death_remaining;
+        self.state.spectators = spectators.into_iter().map(|s| (s.uuid, s)).collect();
         // handle disconnections
         let server_uuids: BTreeSet<_> = players.iter().map(|p| p.uuid).collect();
         let local_uuids: BTreeSet<_> = self.state.players.iter().map(|(u, _)| *u).collect();
@@ -441,6 +878,10 @@ impl GameAsPlayer {
           self.state.players.remove(uuid);
         }
 
+        let my_server_position = players
+          .iter()
+          .find(|p| p.uuid == self.my_uuid)
+          .map(|p| p.position);
         for player in players {
           match self.state.players.get_mut(&player.uuid) {
             None => {
@@ -456,37 +897,212 @@ impl GameAsPlayer {
                 tasks,
                 position,
                 velocity: speed,
+                kill_cooldown,
+                emergency_meetings_called,
+                in_vent,
+                role,
+                shielded,
+                status_effects,
+                ready,
+                disconnected,
+                reconnect_time_remaining,
+                latency,
+                interaction,
+                cosmetics,
+                afk,
+                disguise,
+                shapeshift_cooldown,
               } = player;
               local_player.name = name;
               local_player.color = color;
               local_player.dead = dead;
               local_player.impostor = impostor;
               local_player.tasks = tasks;
+              local_player.kill_cooldown = kill_cooldown;
+              local_player.emergency_meetings_called = emergency_meetings_called;
+              local_player.in_vent = in_vent;
+              local_player.role = role;
+              local_player.shielded = shielded;
+              local_player.status_effects = status_effects;
+              local_player.ready = ready;
+              local_player.disconnected = disconnected;
+              local_player.reconnect_time_remaining = reconnect_time_remaining;
+              local_player.latency = latency;
+              local_player.interaction = interaction;
+              local_player.cosmetics = cosmetics;
+              local_player.afk = afk;
+              local_player.disguise = disguise;
+              local_player.shapeshift_cooldown = shapeshift_cooldown;
               // Always trust our local speed over the server
               if player.uuid != self.my_uuid {
                 local_player.velocity = speed;
               }
-              // Avoid jitter by ignoring position updates (and instead use local reconning
-              // based on speeds) unless the distance is greater than some small amount.
-              if position.distance(&local_player.position) > 30.0 {
+              // The server decides where everyone is. We know better than it
+              // does where we're going though.
+              if player.uuid != self.my_uuid {
+                local_player.position = position;
+              } else if input_ack.is_some() {
+                // Reconciled below, once everything else is up to date.
+              } else if position.distance(&local_player.position) > 30.0 {
+                // Avoid jitter by ignoring position updates (and instead use local
+                // reconning based on speeds) unless the distance is greater than
+                // some small amount.
                 local_player.position = position;
               }
             }
           }
         }
+        if let (Some(ack), Some(server_position)) = (input_ack, my_server_position) {
+          if let Some(me) = self.state.players.get(&self.my_uuid) {
+            let position = self
+              .prediction
+              .reconcile(ack, &self.state, me, server_position);
+            if let Some(me) = self.local_player_mut() {
+              me.position = position;
+            }
+          }
+        }
+        self.send_checksum_if_due()?;
+      }
+      ServerToClientMessage::Resync(snapshot) => {
+        // Somewhere along the way we went wrong, so start over from exactly
+        // what the server says, down to where we are.
+        self.prediction.forget_moves();
+        let my_server_position = snapshot
+          .players
+          .iter()
+          .find(|p| p.uuid == self.my_uuid)
+          .map(|p| p.position);
+        self.handle_msg(ServerToClientMessage::Snapshot(Snapshot {
+          input_ack: None,
+          ..snapshot
+        }))?;
+        if let (Some(position), Some(me)) = (my_server_position, self.local_player_mut()) {
+          me.position = position;
+        }
       }
       ServerToClientMessage::Replay(_recorded_game) => {
         // Nothing to handle here. The JS client handles this itself.
       }
+      ServerToClientMessage::GameSummary(summary) => {
+        self.summary = Some(summary);
+      }
       ServerToClientMessage::DisplayMessage(display_message) => {
         self.displayed_messages.push(display_message);
       }
+      ServerToClientMessage::JoinedRoom { code } => {
+        self.room_code = Some(code);
+      }
+      ServerToClientMessage::RoomList { rooms } => {
+        self.room_list = rooms;
+      }
+      ServerToClientMessage::Stats(stats) => {
+        self.stats = Some(stats);
+      }
+      ServerToClientMessage::Settings(settings) => {
+        // If the map's changed, the next snapshot catches us up on it.
+        self.state.settings = settings;
+      }
+      ServerToClientMessage::Ping { id } => {
+        self.socket.send(&ClientToServerMessage::Pong { id })?;
+      }
+      ServerToClientMessage::ServerHello { .. } => {
+        // Nothing to handle here, the connection takes care of this.
+      }
+      ServerToClientMessage::IncompatibleVersion(e) => {
+        self.displayed_messages.push(DisplayMessage {
+          message: Message::PlainString(e.to_string()),
+          duration: Duration::from_secs(3600),
+          delay_before_show: Duration::from_secs(0),
+        });
+      }
+      ServerToClientMessage::JoinFailed(e) => {
+        self.displayed_messages.push(DisplayMessage {
+          message: Message::PlainString(format!("Couldn't join: {}", e)),
+          duration: Duration::from_secs(10),
+          delay_before_show: Duration::from_secs(0),
+        });
+      }
+      ServerToClientMessage::ActionFailed(e) => {
+        self.displayed_messages.push(DisplayMessage {
+          message: Message::PlainString(e.to_string()),
+          duration: Duration::from_secs(3),
+          delay_before_show: Duration::from_secs(0),
+        });
+      }
+      ServerToClientMessage::Events(events) => {
+        self.events.extend(events);
+      }
+      ServerToClientMessage::Map(map) => {
+        self.state.set_map(map);
+      }
+      ServerToClientMessage::Signal { from, signal } => {
+        self.signals.push((from, signal));
+      }
+      ServerToClientMessage::Chat(chat) => {
+        let (color, name) = match self.state.players.get(&chat.sender) {
+          Some(player) => (Some(player.color), player.name.clone()),
+          None => match self.state.spectators.get(&chat.sender) {
+            Some(spectator) => (None, spectator.name.clone()),
+            None => (None, "Someone".to_string()),
+          },
+        };
+        let prefix = match chat.channel {
+          ChatChannel::Everyone => "",
+          ChatChannel::Ghosts => "(ghost) ",
+        };
+        self.displayed_messages.push(DisplayMessage {
+          message: Message::FormattingString(vec![
+            FormattedText {
+              color,
+              text: format!("{}{}", prefix, name),
+            },
+            FormattedText {
+              color: None,
+              text: format!(": {}", chat.content.describe(&self.state)),
+            },
+          ]),
+          duration: Duration::from_secs(10),
+          delay_before_show: Duration::from_secs(0),
+        });
+        self.chat.push(chat);
+      }
+      ServerToClientMessage::RejoinToken { token } => {
+        self.rejoin_token = Some(token);
+      }
+      ServerToClientMessage::Kicked { reason } => {
+        // It's no good to us anymore.
+        self.rejoin_token = None;
+        self.displayed_messages.push(DisplayMessage {
+          message: Message::PlainString(reason.to_string()),
+          duration: Duration::from_secs(3600),
+          delay_before_show: Duration::from_secs(0),
+        });
+      }
+      ServerToClientMessage::HostChanged { host } => {
+        self.state.host = host;
+        if self.state.is_host(self.my_uuid) {
+          self.displayed_messages.push(DisplayMessage {
+            message: Message::PlainString("You're the host now! Press P to start".to_string()),
+            duration: Duration::from_secs(10),
+            delay_before_show: Duration::from_secs(0),
+          });
+        }
+      }
     }
     Ok(())
   }
 
+  // The host starts the game, everyone else says whether they're ready.
   fn start(&mut self) -> Result<(), String> {
-    self.socket.send(&ClientToServerMessage::StartGame())?;
+    if self.state.is_host(self.my_uuid) {
+      self.socket.send(&ClientToServerMessage::StartGame())?;
+      return Ok(());
+    }
+    if let Some(player) = self.local_player() {
+      let ready = !player.ready;
+      self.socket.send(&ClientToServerMessage::Ready { ready })?;
+    }
     Ok(())
   }
 
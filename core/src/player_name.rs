@@ -0,0 +1,154 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::Display;
+
+// A name that's fit to show everyone else. Anything that came in over the
+// wire gets turned into one of these before it's shown to anybody.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PlayerName(String);
+
+// Why a name wasn't allowed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NameError {
+  Empty,
+  TooLong { max: usize },
+  // Turned down by the server's name filter.
+  NotAllowed,
+}
+
+impl Display for NameError {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    match self {
+      NameError::Empty => write!(fmt, "Names can't be blank"),
+      NameError::TooLong { max } => write!(fmt, "Names can be at most {} characters", max),
+      NameError::NotAllowed => write!(fmt, "That name isn't allowed"),
+    }
+  }
+}
+
+impl Error for NameError {}
+
+// Decides whether a name is ok to use, e.g. to keep out profanity. Gets the
+// name once it's been cleaned up.
+pub type NameFilter = Box<dyn Fn(&str) -> bool + Send>;
+
+impl PlayerName {
+  pub const MAX_LENGTH: usize = 20;
+
+  // Control characters are dropped, so nobody can mess up the chat or
+  // sneak in invisible differences, and runs of spaces are squashed to one.
+  pub fn new(raw: &str) -> Result<Self, NameError> {
+    let cleaned: String = raw.chars().filter(|c| !c.is_control()).collect();
+    let name = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() {
+      return Err(NameError::Empty);
+    }
+    if name.chars().count() > Self::MAX_LENGTH {
+      return Err(NameError::TooLong {
+        max: Self::MAX_LENGTH,
+      });
+    }
+    Ok(PlayerName(name))
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+
+  // The same name, with a number stuck on the end if it'd be mistaken for
+  // one that's already taken. Differences in case don't count.
+  pub fn deduplicated<'a>(self, taken: impl Iterator<Item = &'a str> + Clone) -> PlayerName {
+    let clashes = |name: &str| {
+      taken
+        .clone()
+        .any(|other| other.to_lowercase() == name.to_lowercase())
+    };
+    if !clashes(&self.0) {
+      return self;
+    }
+    let mut n = 2;
+    loop {
+      let suffix = format!(" #{}", n);
+      let room = Self::MAX_LENGTH - suffix.chars().count();
+      let base: String = self.0.chars().take(room).collect();
+      let candidate = format!("{}{}", base.trim_end(), suffix);
+      if !clashes(&candidate) {
+        return PlayerName(candidate);
+      }
+      n += 1;
+    }
+  }
+}
+
+impl Display for PlayerName {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    write!(fmt, "{}", self.0)
+  }
+}
+
+impl From<PlayerName> for String {
+  fn from(name: PlayerName) -> String {
+    name.0
+  }
+}
+
+impl GameState {
+  // Everyone's name, players and spectators both, since they can all chat.
+  pub fn names_in_use(&self) -> impl Iterator<Item = &str> + Clone {
+    self
+      .players
+      .values()
+      .map(|p| p.name.as_str())
+      .chain(self.spectators.values().map(|s| s.name.as_str()))
+  }
+}
+
+impl GameServer {
+  // Names get checked with this as well as the usual rules.
+  pub fn with_name_filter(mut self, filter: impl Fn(&str) -> bool + Send + 'static) -> Self {
+    self.name_filter = Some(Box::new(filter));
+    self
+  }
+
+  // Turns whatever someone asked to be called into a name they can have.
+  pub(crate) fn check_name(&self, raw: &str) -> Result<PlayerName, NameError> {
+    let name = PlayerName::new(raw)?;
+    if let Some(filter) = &self.name_filter {
+      if !filter(name.as_str()) {
+        return Err(NameError::NotAllowed);
+      }
+    }
+    Ok(name.deduplicated(self.state.names_in_use()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+
+  #[test]
+  fn test_names_are_cleaned_up_and_kept_apart() {
+    assert_eq!(
+      PlayerName::new("  Red\u{0}\n  Red ").unwrap().as_str(),
+      "Red Red"
+    );
+    assert_eq!(PlayerName::new(" \t"), Err(NameError::Empty));
+    assert_eq!(
+      PlayerName::new(&"x".repeat(21)),
+      Err(NameError::TooLong { max: 20 })
+    );
+
+    let taken = ["Red Red", "red red #2"];
+    let name = PlayerName::new("RED RED").unwrap();
+    assert_eq!(
+      name.deduplicated(taken.iter().copied()).as_str(),
+      "RED RED #3"
+    );
+    // Long names get cut short to make room.
+    let name = PlayerName::new(&"y".repeat(20)).unwrap();
+    let taken = ["y".repeat(20)];
+    let deduplicated = name.deduplicated(taken.iter().map(|s| s.as_str()));
+    assert_eq!(deduplicated.as_str(), format!("{} #2", "y".repeat(17)));
+  }
+}
@@ -0,0 +1,167 @@
+use crate::*;
+use core::time::Duration;
+use std::collections::VecDeque;
+
+// A movement input we've acted on locally, that the server might not have
+// caught up with yet.
+#[derive(Clone, Copy, Debug)]
+struct PredictedMove {
+  sequence: u64,
+  movement: MovementInput,
+  // How long we've been moving like this, on our end.
+  elapsed: Duration,
+}
+
+// Lets the local player move as soon as they press a key, rather than waiting
+// a round trip to hear where the server put them. When the server does tell
+// us, we start from where it says we were and replay everything it hadn't
+// seen yet on top.
+#[derive(Default, Debug)]
+pub struct Prediction {
+  last_sequence: u64,
+  moves: VecDeque<PredictedMove>,
+}
+
+impl Prediction {
+  // Returns the sequence number to send the input to the server with, or None
+  // if we're already moving like that and there's nothing to tell it.
+  pub fn note_input(&mut self, movement: MovementInput) -> Option<u64> {
+    let current = self.moves.back().map(|m| m.movement).unwrap_or_default();
+    if self.last_sequence != 0 && current == movement {
+      return None;
+    }
+    self.last_sequence += 1;
+    self.moves.push_back(PredictedMove {
+      sequence: self.last_sequence,
+      movement,
+      elapsed: Duration::from_secs(0),
+    });
+    Some(self.last_sequence)
+  }
+
+  // Drops everything we've predicted, for when it turns out we'd been
+  // getting it wrong.
+  pub fn forget_moves(&mut self) {
+    self.moves.clear();
+  }
+
+  pub fn simulate(&mut self, elapsed: Duration) {
+    if let Some(latest) = self.moves.back_mut() {
+      latest.elapsed += elapsed;
+    }
+  }
+
+  // Works out where we are now, given where the server says we were as of
+  // the given ack.
+  pub fn reconcile(
+    &mut self,
+    ack: InputAck,
+    state: &GameState,
+    me: &Player,
+    server_position: Position,
+  ) -> Position {
+    while let Some(oldest) = self.moves.front() {
+      if oldest.sequence >= ack.sequence {
+        break;
+      }
+      self.moves.pop_front();
+    }
+    let moving = matches!(
+      state.status,
+      GameStatus::Lobby | GameStatus::Playing(PlayState::Night)
+    );
+    if !moving || me.in_vent.is_some() {
+      return server_position;
+    }
+    let mut player = Player {
+      position: server_position,
+      ..me.clone()
+    };
+    for predicted in self.moves.iter() {
+      // The server's already moved us with the input it's acked for a bit.
+      let unseen = if predicted.sequence == ack.sequence {
+        predicted
+          .elapsed
+          .checked_sub(ack.elapsed)
+          .unwrap_or_default()
+      } else {
+        predicted.elapsed
+      };
+      player.position = replay_move(state, &player, predicted.movement, unseen);
+    }
+    player.position
+  }
+}
+
+// Moves the player the same way simulating the night would, for as many
+// whole ticks as fit in the time given.
+fn replay_move(
+  state: &GameState,
+  player: &Player,
+  movement: MovementInput,
+  elapsed: Duration,
+) -> Position {
+  let velocity = movement.velocity(state.settings.speed);
+  let speed_multiplier = player.speed_multiplier();
+  let movement_vector = Velocity {
+    dx: velocity.dx * speed_multiplier,
+    dy: velocity.dy * speed_multiplier,
+  };
+  let mut player = player.clone();
+  if velocity == Velocity::default() {
+    return player.position;
+  }
+  for _ in 0..(elapsed.as_nanos() / TICK.as_nanos()) {
+    player.position = state
+      .map
+      .move_player(&player, movement_vector, &state.doors);
+  }
+  player.position
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_replays_what_the_server_hasnt_seen() {
+    let mut state = GameState::new();
+    state.status = GameStatus::Lobby;
+    let uuid = UUID::random();
+    let start = Position { x: 600.0, y: 300.0 };
+    let me = Player::new(uuid, "Test Player".to_string(), Color::Red, start);
+    let right = MovementInput {
+      right: true,
+      ..MovementInput::default()
+    };
+
+    let mut prediction = Prediction::default();
+    let sequence = prediction.note_input(right).unwrap();
+    assert_eq!(prediction.note_input(right), None);
+    prediction.simulate(Duration::from_millis(160));
+
+    // The server has only seen the first half of that, but we've kept going.
+    let ack = InputAck {
+      sequence,
+      elapsed: Duration::from_millis(80),
+    };
+    let server_position = Position {
+      x: start.x + 10.0,
+      y: start.y,
+    };
+    let position = prediction.reconcile(ack, &state, &me, server_position);
+    assert!((position.x - (start.x + 20.0)).abs() < 0.001);
+    assert_eq!(position.y, start.y);
+
+    // Once we stop, and the server catches up, we end up where it says.
+    let stopped = prediction.note_input(MovementInput::default()).unwrap();
+    prediction.simulate(Duration::from_millis(100));
+    let ack = InputAck {
+      sequence: stopped,
+      elapsed: Duration::from_millis(50),
+    };
+    let position = prediction.reconcile(ack, &state, &me, server_position);
+    assert_eq!(position, server_position);
+  }
+}
@@ -0,0 +1,166 @@
+use crate::*;
+use bincode::Options;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Ready-made rule sets, for hosts who don't want to fiddle with every setting.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SettingsPreset {
+  Classic,
+  // See GameMode::HideAndSeek.
+  HideAndSeek,
+  // Less to do and less time to talk about it.
+  ShortGames,
+}
+
+impl SettingsPreset {
+  pub fn all() -> &'static [SettingsPreset] {
+    &[
+      SettingsPreset::Classic,
+      SettingsPreset::HideAndSeek,
+      SettingsPreset::ShortGames,
+    ]
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      SettingsPreset::Classic => "Classic",
+      SettingsPreset::HideAndSeek => "Hide & Seek",
+      SettingsPreset::ShortGames => "Short games",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<SettingsPreset> {
+    SettingsPreset::all()
+      .iter()
+      .copied()
+      .find(|preset| preset.name() == name)
+  }
+
+  pub fn settings(self) -> Settings {
+    let classic = Settings::default();
+    match self {
+      SettingsPreset::Classic => classic,
+      SettingsPreset::HideAndSeek => Settings {
+        game_mode: GameMode::HideAndSeek,
+        impostor_count: 1,
+        speed: 2.5,
+        kill_cooldown: Duration::from_secs(5),
+        emergency_meetings_per_player: 0,
+        vision_radius: VisionRadius {
+          crew: 96.0,
+          impostors: 320.0,
+        },
+        short_tasks: 4,
+        long_tasks: 2,
+        common_tasks: 1,
+        ..classic
+      },
+      SettingsPreset::ShortGames => Settings {
+        game_time_limit: Some(Duration::from_secs(600)),
+        discussion_time: Duration::from_secs(5),
+        voting_time: Duration::from_secs(45),
+        kill_cooldown: Duration::from_secs(15),
+        short_tasks: 2,
+        long_tasks: 0,
+        common_tasks: 1,
+        ..classic
+      },
+    }
+  }
+}
+
+// Bumped whenever Settings changes shape, so old codes are turned away rather
+// than read as something they never said.
+const SETTINGS_CODE_VERSION: u8 = 9;
+const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+impl Settings {
+  // The exact rules, as a string that can be pasted into another room (or
+  // saved for next time).
+  pub fn to_code(&self) -> String {
+    use base64::Engine;
+    let mut bytes = vec![SETTINGS_CODE_VERSION];
+    bincode::DefaultOptions::new()
+      .serialize_into(&mut bytes, self)
+      .expect("Internal Error: unable to serialize settings");
+    BASE64.encode(bytes)
+  }
+
+  pub fn from_code(code: &str) -> Result<Settings, Box<dyn Error>> {
+    use base64::Engine;
+    let bytes = BASE64.decode(code.trim())?;
+    match bytes.split_first() {
+      Some((&SETTINGS_CODE_VERSION, rest)) => Ok(
+        bincode::DefaultOptions::new()
+          .with_limit(4096)
+          .deserialize(rest)?,
+      ),
+      Some(_) => Err("That settings code is from a different version of the game".into()),
+      None => Err("That settings code is empty".into()),
+    }
+  }
+}
+
+impl From<&Settings> for SettingsPatch {
+  // Everything the host can change from the lobby, set to match.
+  fn from(settings: &Settings) -> Self {
+    SettingsPatch {
+      speed: Some(settings.speed),
+      kill_distance: Some(settings.kill_distance),
+      kill_cooldown: Some(settings.kill_cooldown),
+      discussion_time: Some(settings.discussion_time),
+      voting_time: Some(settings.voting_time),
+      confirm_ejects: Some(settings.confirm_ejects),
+      allow_vote_changes: Some(settings.allow_vote_changes),
+      anonymous_votes: Some(settings.anonymous_votes),
+      tie_policy: Some(settings.tie_policy),
+      impostor_count: Some(settings.impostor_count),
+      short_tasks: Some(settings.short_tasks),
+      long_tasks: Some(settings.long_tasks),
+      common_tasks: Some(settings.common_tasks),
+      emergency_meetings_per_player: Some(settings.emergency_meetings_per_player),
+      vision_radius: Some(settings.vision_radius),
+      map_id: Some(settings.map_id),
+      game_mode: Some(settings.game_mode),
+      game_time_limit: Some(settings.game_time_limit.unwrap_or_default()),
+      time_limit_outcome: Some(settings.time_limit_outcome),
+      sudden_death_time: Some(settings.sudden_death_time.unwrap_or_default()),
+      afk_kick_time: Some(settings.afk_kick_time.unwrap_or_default()),
+      allow_body_drag: Some(settings.allow_body_drag),
+      decoy_time: Some(settings.decoy_time.unwrap_or_default()),
+      shapeshift_time: Some(settings.shapeshift_time.unwrap_or_default()),
+      shapeshift_cooldown: Some(settings.shapeshift_cooldown),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+
+  #[test]
+  fn test_settings_codes_round_trip() {
+    for preset in SettingsPreset::all() {
+      let settings = preset.settings();
+      assert_eq!(SettingsPreset::from_name(preset.name()), Some(*preset));
+      // Every preset has to be something the host could have picked by hand.
+      let patch = SettingsPatch::from(&settings);
+      assert_eq!(Settings::default().patched(&patch), Ok(settings.clone()));
+
+      let code = settings.to_code();
+      assert!(code
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+      assert_eq!(Settings::from_code(&code).unwrap(), settings);
+    }
+
+    assert!(Settings::from_code("").is_err());
+    assert!(Settings::from_code("not a code!").is_err());
+    // Someone else's version of the settings.
+    let mut code = Settings::default().to_code();
+    code.replace_range(0..2, "AQ");
+    assert!(Settings::from_code(&code).is_err());
+  }
+}
@@ -1,13 +1,32 @@
 use crate::*;
+use bincode::Options;
 use core::fmt::Debug;
+use core::fmt::Display;
 use core::time::Duration;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::error::Error;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ClientToServerMessage {
-  Move(MoveMessage),
-  Killed(DeadBody),
-  FinishedTask(FinishedTask),
+  Input {
+    sequence: u64,
+    movement: MovementInput,
+  },
+  // seen_at is the server tick the player was looking at, for lag compensation.
+  Kill {
+    victim: UUID,
+    seen_at: u64,
+  },
+  // Starts working on one of our tasks. It's done once we've stood still
+  // for long enough.
+  BeginInteraction {
+    task: usize,
+  },
+  // Our answer to the puzzle for the task we're working on.
+  SolveMinigame {
+    solution: MinigameSolution,
+  },
   Join {
     version: String,
     details: JoinRequest,
@@ -15,33 +34,254 @@ pub enum ClientToServerMessage {
   Vote {
     target: VoteTarget,
   },
+  LockInVote(),
+  Shield {
+    target: UUID,
+  },
+  SheriffShot {
+    target: UUID,
+  },
   ReportBody {
     dead_body_color: Color,
+    seen_at: u64,
+  },
+  // Take back control of a player after losing the connection.
+  Rejoin {
+    uuid: UUID,
+    token: UUID,
+  },
+  // Sent before joining, to pick which game to join.
+  CreateRoom {
+    visibility: RoomVisibility,
+    password: Option<String>,
+    quick_chat_only: bool,
+    map_id: MapId,
+    // A map of the host's own, as JSON, which everyone who joins gets sent.
+    // Takes the place of map_id.
+    custom_map: Option<String>,
+  },
+  JoinRoom {
+    code: String,
+    password: Option<String>,
+  },
+  ListRooms(),
+  // How we've done over every game we've finished, for our profile.
+  GetStats(),
+  // Sent first thing, always as JSON. Makes sure we speak the same protocol,
+  // and picks how the rest of the conversation gets encoded.
+  ClientHello {
+    protocol_version: u32,
+    client_build: String,
+    encoding: Encoding,
+    // Who we are from one game to the next, if we'd like stats kept.
+    account: Option<PlayerAccountId>,
+  },
+  // What our game looked like just after the snapshot for the given tick, so
+  // the server can tell if we've drifted away from it.
+  Checksum {
+    tick: u64,
+    checksum: u64,
+  },
+  // Answers the server's ping with the same id.
+  Pong {
+    id: u64,
   },
   StartGame(),
+  ResumeGame(),
+  Ready {
+    ready: bool,
+  },
+  TransferHost {
+    to: UUID,
+  },
+  CallMeeting(),
+  Sabotage(Sabotage),
+  FixSabotage(),
+  EnterVent(),
+  ExitVent(),
+  MoveVent {
+    to: usize,
+  },
+  Chat {
+    text: String,
+  },
+  QuickChat(QuickChat),
+  // For setting up voice with another player, passed on to them as-is.
+  Signal {
+    to: UUID,
+    signal: VoiceSignal,
+  },
+  // Fills up the lobby with bots, for the host.
+  AddBots {
+    count: usize,
+  },
+  // Stop (or start again) passing on someone's chat messages to us.
+  Mute {
+    uuid: UUID,
+    muted: bool,
+  },
+  // For the host, to get rid of someone. Banned players can't join the room
+  // again, even on a new connection.
+  KickPlayer {
+    uuid: UUID,
+  },
+  BanPlayer {
+    uuid: UUID,
+  },
+  // Asks to wear a different color in the lobby. Only works if no one else
+  // is wearing it.
+  RequestColor(Color),
+  // For the host, once the game's been won, to play again with everyone
+  // who's still around.
+  ReturnToLobby(),
+  // For the host, in the lobby. Everyone hears about the new settings. Boxed,
+  // since it's a lot bigger than any of the other messages.
+  UpdateSettings(Box<SettingsPatch>),
+  // For anyone in the lobby. Enough of these and the target's out, host or not.
+  VoteKick {
+    target: UUID,
+  },
+  // For impostors, if the room allows it. The body follows them around until
+  // they let go.
+  DragBody {
+    dead_body_color: Color,
+  },
+  ReleaseBody(),
+  // For impostors, if the room allows it. Leaves a decoy of them where
+  // they're standing.
+  DropDecoy(),
+  // For impostors, if the room allows it. They look like the target to the
+  // crew for a while.
+  Shapeshift {
+    target: UUID,
+  },
 }
 
 impl ClientToServerMessage {
   #[allow(dead_code)]
   pub fn kind(&self) -> &'static str {
     match self {
-      ClientToServerMessage::Move(_) => "Move",
-      ClientToServerMessage::Killed(_) => "Killed",
-      ClientToServerMessage::FinishedTask(_) => "FinishedTask",
+      ClientToServerMessage::Input { .. } => "Input",
+      ClientToServerMessage::Kill { .. } => "Kill",
+      ClientToServerMessage::BeginInteraction { .. } => "BeginInteraction",
+      ClientToServerMessage::SolveMinigame { .. } => "SolveMinigame",
       ClientToServerMessage::Join { .. } => "Join",
+      ClientToServerMessage::CreateRoom { .. } => "CreateRoom",
+      ClientToServerMessage::JoinRoom { .. } => "JoinRoom",
+      ClientToServerMessage::ListRooms() => "ListRooms",
+      ClientToServerMessage::GetStats() => "GetStats",
+      ClientToServerMessage::ClientHello { .. } => "ClientHello",
+      ClientToServerMessage::Checksum { .. } => "Checksum",
+      ClientToServerMessage::Pong { .. } => "Pong",
       ClientToServerMessage::StartGame() => "StartGame",
+      ClientToServerMessage::ResumeGame() => "ResumeGame",
+      ClientToServerMessage::Ready { .. } => "Ready",
+      ClientToServerMessage::TransferHost { .. } => "TransferHost",
       ClientToServerMessage::Vote { .. } => "Vote",
+      ClientToServerMessage::LockInVote() => "LockInVote",
+      ClientToServerMessage::Shield { .. } => "Shield",
+      ClientToServerMessage::SheriffShot { .. } => "SheriffShot",
       ClientToServerMessage::ReportBody { .. } => "ReportBody",
+      ClientToServerMessage::Rejoin { .. } => "Rejoin",
+      ClientToServerMessage::CallMeeting() => "CallMeeting",
+      ClientToServerMessage::Sabotage(_) => "Sabotage",
+      ClientToServerMessage::FixSabotage() => "FixSabotage",
+      ClientToServerMessage::EnterVent() => "EnterVent",
+      ClientToServerMessage::ExitVent() => "ExitVent",
+      ClientToServerMessage::MoveVent { .. } => "MoveVent",
+      ClientToServerMessage::Chat { .. } => "Chat",
+      ClientToServerMessage::QuickChat(_) => "QuickChat",
+      ClientToServerMessage::Signal { .. } => "Signal",
+      ClientToServerMessage::Mute { .. } => "Mute",
+      ClientToServerMessage::AddBots { .. } => "AddBots",
+      ClientToServerMessage::KickPlayer { .. } => "KickPlayer",
+      ClientToServerMessage::BanPlayer { .. } => "BanPlayer",
+      ClientToServerMessage::VoteKick { .. } => "VoteKick",
+      ClientToServerMessage::RequestColor(_) => "RequestColor",
+      ClientToServerMessage::ReturnToLobby() => "ReturnToLobby",
+      ClientToServerMessage::UpdateSettings(_) => "UpdateSettings",
+      ClientToServerMessage::DragBody { .. } => "DragBody",
+      ClientToServerMessage::ReleaseBody() => "ReleaseBody",
+      ClientToServerMessage::DropDecoy() => "DropDecoy",
+      ClientToServerMessage::Shapeshift { .. } => "Shapeshift",
     }
   }
 }
 
+// Snapshots are by far the most common message, so boxing them wouldn't save us anything.
+#[allow(clippy::large_enum_variant)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ServerToClientMessage {
-  Welcome { connection_id: UUID },
+  ServerHello {
+    protocol_version: u32,
+    server_build: String,
+    encoding: Encoding,
+  },
+  IncompatibleVersion(IncompatibleVersion),
+  Welcome {
+    connection_id: UUID,
+  },
+  JoinedRoom {
+    code: String,
+  },
+  RoomList {
+    rooms: Vec<RoomSummary>,
+  },
+  Stats(PlayerStats),
+  // How the room's set up, when we join and whenever the host changes it.
+  Settings(Settings),
+  JoinFailed(JoinError),
+  // Something the player asked to do wasn't allowed.
+  ActionFailed(GameError),
+  // Things that happened since the last batch, for sounds and animations.
+  Events(Vec<GameEvent>),
+  Chat(ChatMessage),
+  // The map we're playing on, when it isn't one that's built in.
+  Map(Map),
+  Signal {
+    from: UUID,
+    signal: VoiceSignal,
+  },
   Snapshot(Snapshot),
+  // To see how laggy a player's connection is. They answer with a Pong.
+  Ping {
+    id: u64,
+  },
+  // Sent when a player's game has drifted from the server's. Unlike a regular
+  // snapshot, the player takes everything in it as is.
+  Resync(Snapshot),
   Replay(RecordedGame),
+  // Who everyone was and how they did, once the game's been won.
+  GameSummary(GameSummary),
   DisplayMessage(DisplayMessage),
+  HostChanged {
+    host: Option<UUID>,
+  },
+  // Only sent to the player it belongs to, keep it secret!
+  RejoinToken {
+    token: UUID,
+  },
+  // The host threw us out, and we're about to be hung up on.
+  Kicked {
+    reason: KickReason,
+  },
+}
+
+// Whether a room shows up when listing rooms, or can only be joined with its code.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomVisibility {
+  Public,
+  Private,
+}
+
+// What the server browser shows about a room.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RoomSummary {
+  pub code: String,
+  pub players: usize,
+  pub map_name: String,
+  pub in_progress: bool,
+  pub has_password: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -49,6 +289,7 @@ pub enum JoinRequest {
   JoinAsPlayer {
     preferred_color: Color,
     name: String,
+    cosmetics: Cosmetics,
   },
   JoinAsSpectator,
 }
@@ -57,10 +298,28 @@ impl ServerToClientMessage {
   #[allow(dead_code)]
   pub fn kind(&self) -> &'static str {
     match self {
+      ServerToClientMessage::ServerHello { .. } => "ServerHello",
+      ServerToClientMessage::IncompatibleVersion(_) => "IncompatibleVersion",
       ServerToClientMessage::Welcome { .. } => "Welcome",
+      ServerToClientMessage::JoinedRoom { .. } => "JoinedRoom",
+      ServerToClientMessage::RoomList { .. } => "RoomList",
+      ServerToClientMessage::Stats(_) => "Stats",
+      ServerToClientMessage::Settings(_) => "Settings",
+      ServerToClientMessage::JoinFailed(_) => "JoinFailed",
+      ServerToClientMessage::ActionFailed(_) => "ActionFailed",
+      ServerToClientMessage::Events(_) => "Events",
+      ServerToClientMessage::Chat(_) => "Chat",
+      ServerToClientMessage::Map(_) => "Map",
+      ServerToClientMessage::Signal { .. } => "Signal",
       ServerToClientMessage::Snapshot(_) => "Snapshot",
+      ServerToClientMessage::Resync(_) => "Resync",
+      ServerToClientMessage::Ping { .. } => "Ping",
       ServerToClientMessage::Replay(_) => "Replay",
+      ServerToClientMessage::GameSummary(_) => "GameSummary",
       ServerToClientMessage::DisplayMessage(_) => "DisplayMessage",
+      ServerToClientMessage::HostChanged { .. } => "HostChanged",
+      ServerToClientMessage::RejoinToken { .. } => "RejoinToken",
+      ServerToClientMessage::Kicked { .. } => "Kicked",
     }
   }
 }
@@ -69,10 +328,86 @@ pub fn get_version_sha() -> &'static str {
   env!("VERGEN_SHA")
 }
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
-pub struct MoveMessage {
-  pub speed: Velocity,
-  pub position: Position,
+// Bump this whenever the messages change shape, so that clients left over
+// from an old deploy find out they need to reload, rather than failing to
+// understand anything we say.
+pub const PROTOCOL_VERSION: u32 = 36;
+
+// Sent (as JSON, so that the client can understand it) when the client and
+// server don't speak the same protocol.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IncompatibleVersion {
+  pub client_protocol_version: u32,
+  pub server_protocol_version: u32,
+}
+
+impl IncompatibleVersion {
+  pub fn check(client_protocol_version: u32) -> Result<(), IncompatibleVersion> {
+    if client_protocol_version == PROTOCOL_VERSION {
+      return Ok(());
+    }
+    Err(IncompatibleVersion {
+      client_protocol_version,
+      server_protocol_version: PROTOCOL_VERSION,
+    })
+  }
+}
+
+impl Display for IncompatibleVersion {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    write!(
+      fmt,
+      "The game has been updated since this page was loaded (protocol version {} vs {}), reload to keep playing",
+      self.server_protocol_version, self.client_protocol_version
+    )
+  }
+}
+
+impl Error for IncompatibleVersion {}
+
+// How messages are written out on the wire. JSON is nice to read when
+// debugging, but binary is a lot smaller. Everyone starts out speaking JSON,
+// until the client asks for something else.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+  Json,
+  Binary,
+}
+
+// A message that's ready to go out over a websocket. Text frames are always
+// JSON and binary frames are always bincode, so either end can read whatever
+// it's sent no matter what was negotiated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodedMessage {
+  Text(String),
+  Binary(Vec<u8>),
+}
+
+impl Encoding {
+  pub fn encode<T: Serialize>(self, message: &T) -> Result<EncodedMessage, Box<dyn Error>> {
+    Ok(match self {
+      Encoding::Json => EncodedMessage::Text(serde_json::to_string(message)?),
+      Encoding::Binary => EncodedMessage::Binary(bincode::serialize(message)?),
+    })
+  }
+}
+
+// Nothing legit comes anywhere near this, but a few bytes claiming to be the
+// length of a huge list would otherwise have us try to allocate all of it.
+const MAX_DECODED_SIZE: u64 = 16 * 1024 * 1024;
+
+impl EncodedMessage {
+  pub fn decode<T: DeserializeOwned>(&self) -> Result<T, Box<dyn Error>> {
+    Ok(match self {
+      EncodedMessage::Text(text) => serde_json::from_str(text)?,
+      // The same settings as bincode::deserialize, just with a limit.
+      EncodedMessage::Binary(bytes) => bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_DECODED_SIZE)
+        .deserialize(bytes)?,
+    })
+  }
 }
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
@@ -85,6 +420,49 @@ pub struct Snapshot {
   pub status: GameStatus,
   pub bodies: Vec<DeadBody>,
   pub players: Vec<Player>,
+  pub emergency_cooldown: Duration,
+  pub sabotage: SabotageState,
+  pub doors: DoorState,
+  // How far along the crew is with their tasks, when the settings allow
+  // players to know.
+  pub task_progress: Option<f64>,
+  pub ejection: Option<EjectionResult>,
+  pub spectators: Vec<Spectator>,
+  pub host: Option<UUID>,
+  pub game_time_remaining: Option<Duration>,
+  pub sudden_death_remaining: Option<Duration>,
+  // How far the server has got with the recipient's own inputs.
+  pub input_ack: Option<InputAck>,
+  pub tick: u64,
+  pub map_id: MapId,
+  // What the recipient's console is showing them, if they're at one. Their
+  // view doesn't know where everyone is, so this is worked out beforehand.
+  pub surveillance: Option<Surveillance>,
+}
+
+impl Snapshot {
+  // The task bar is worked out from everyone's tasks, which the view might
+  // not have, so it's passed in separately.
+  pub fn new(view: &GameState, task_progress: Option<f64>) -> Self {
+    Snapshot {
+      status: view.status.clone(),
+      bodies: view.bodies.clone(),
+      players: view.players.values().cloned().collect(),
+      emergency_cooldown: view.emergency_cooldown,
+      sabotage: view.sabotage.clone(),
+      doors: view.doors.clone(),
+      task_progress,
+      ejection: view.ejection.clone(),
+      spectators: view.spectators.values().cloned().collect(),
+      host: view.host,
+      game_time_remaining: view.game_time_remaining,
+      sudden_death_remaining: view.sudden_death_remaining,
+      input_ack: None,
+      tick: view.position_history.tick,
+      map_id: view.map.id,
+      surveillance: None,
+    }
+  }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -100,6 +478,7 @@ pub struct Disconnected {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PlayerStartInfo {
   pub team: Team,
+  pub role: Role,
   pub tasks: Vec<Task>,
 }
 
@@ -145,10 +524,103 @@ impl DisplayMessage {
 }
 
 impl PlayerStartInfo {
-  pub fn new(map: &Map, settings: &Settings) -> Self {
+  pub fn new(map: &Map, settings: &Settings, common_tasks: &[Task], rng: &mut GameRng) -> Self {
     Self {
       team: Team::Crew,
-      tasks: map.gen_tasks(settings),
+      role: Role::Crewmate,
+      tasks: map.gen_tasks(settings, common_tasks, rng),
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_binary_and_json_encodings_round_trip() {
+    let players: Vec<(Role, Position)> = (0..4)
+      .map(|i| {
+        (
+          Role::Crewmate,
+          Position {
+            x: i as f64,
+            y: 0.5,
+          },
+        )
+      })
+      .collect();
+    let (mut state, _) = game_at(&players);
+    state.host = state.players.keys().next().copied();
+    let message = ServerToClientMessage::Snapshot(Snapshot::new(&state, state.task_bar()));
+    let as_json = serde_json::to_string(&message).unwrap();
+
+    let text = Encoding::Json.encode(&message).unwrap();
+    let binary = Encoding::Binary.encode(&message).unwrap();
+    let (text_len, binary_len) = match (&text, &binary) {
+      (EncodedMessage::Text(text), EncodedMessage::Binary(bytes)) => (text.len(), bytes.len()),
+      _ => panic!("Got the wrong kind of frames: {:?} {:?}", text, binary),
+    };
+    assert!(binary_len < text_len);
+
+    for encoded in [text, binary].iter() {
+      let decoded: ServerToClientMessage = encoded.decode().unwrap();
+      assert_eq!(serde_json::to_string(&decoded).unwrap(), as_json);
+    }
+  }
+
+  #[test]
+  fn test_garbage_comes_back_as_errors() {
+    use rand::Rng;
+    // The same sort of thing the fuzz targets do, just a lot less of it.
+    let mut rng = GameRng::new(79);
+    for _ in 0..2000 {
+      let length = rng.gen_range(0, 64);
+      let bytes: Vec<u8> = (0..length).map(|_| rng.gen()).collect();
+      let _ = EncodedMessage::Binary(bytes.clone()).decode::<ClientToServerMessage>();
+      let _ = EncodedMessage::Binary(bytes.clone()).decode::<ServerToClientMessage>();
+      let text = String::from_utf8_lossy(&bytes).to_string();
+      let _ = EncodedMessage::Text(text).decode::<ClientToServerMessage>();
+    }
+
+    // A list that says it's enormous, with nothing in it.
+    let mut huge = vec![0u8; 4];
+    huge.extend_from_slice(&u64::MAX.to_le_bytes());
+    assert!(EncodedMessage::Binary(huge)
+      .decode::<ServerToClientMessage>()
+      .is_err());
+
+    // 32 bytes long, but not 32 characters.
+    let uuid = format!("\"{}\"", "é".repeat(16));
+    assert!(serde_json::from_str::<UUID>(&uuid).is_err());
+    let uuid = format!("\"a{}{}\"", "é", "0".repeat(29));
+    assert!(serde_json::from_str::<UUID>(&uuid).is_err());
+    let uuid = format!("\"+f{}\"", "0".repeat(30));
+    assert!(serde_json::from_str::<UUID>(&uuid).is_err());
+    let uuid = UUID::random();
+    assert_eq!(
+      serde_json::from_str::<UUID>(&serde_json::to_string(&uuid).unwrap()).unwrap(),
+      uuid
+    );
+  }
+
+  #[test]
+  fn test_protocol_versions_must_match() {
+    IncompatibleVersion::check(PROTOCOL_VERSION).unwrap();
+    let mismatch = IncompatibleVersion::check(PROTOCOL_VERSION + 1).unwrap_err();
+    assert_eq!(mismatch.server_protocol_version, PROTOCOL_VERSION);
+    assert_eq!(mismatch.client_protocol_version, PROTOCOL_VERSION + 1);
+
+    // Old clients have to be able to read this, so it's always JSON.
+    let message = ServerToClientMessage::IncompatibleVersion(mismatch);
+    assert_eq!(
+      serde_json::to_string(&message).unwrap(),
+      format!(
+        r#"{{"IncompatibleVersion":{{"client_protocol_version":{},"server_protocol_version":{}}}}}"#,
+        PROTOCOL_VERSION + 1,
+        PROTOCOL_VERSION
+      )
+    );
+  }
+}
@@ -0,0 +1,178 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+// Somewhere on the map that players can point each other to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Landmark {
+  MeetingTable,
+  EmergencyButton,
+  // Indexes into Map::vents.
+  Vent { index: usize },
+  // Indexes into Map::fix_stations.
+  FixStation { index: usize },
+  // Indexes into Map::regions.
+  Region { index: usize },
+}
+
+// A canned message, for when there's no time to type or no keyboard to type
+// on. Everything in one refers to something that's really in the game, so
+// there's nothing in them that needs moderating.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuickChat {
+  Accuse { target: UUID },
+  Vouch { target: UUID },
+  SawNear { target: UUID, landmark: Landmark },
+  WasNear { landmark: Landmark },
+  // Indexes into the sender's own tasks. Impostors can point at their fake
+  // ones, lying is part of the game.
+  DoingTask { index: usize },
+  Skip,
+}
+
+impl Landmark {
+  fn describe(&self, map: &Map) -> String {
+    match self {
+      Landmark::MeetingTable => "the meeting table".to_string(),
+      Landmark::EmergencyButton => "the emergency button".to_string(),
+      Landmark::Vent { index } => format!("vent #{}", index + 1),
+      Landmark::FixStation { index } => match map.fix_stations.get(*index) {
+        Some(station) => format!("the {:?} fix station", station.sabotage),
+        None => "somewhere".to_string(),
+      },
+      Landmark::Region { index } => match map.regions.get(*index) {
+        Some(region) => region.name.to_string(),
+        None => "somewhere".to_string(),
+      },
+    }
+  }
+
+  // Whether someone's near it, or in it.
+  fn preposition(&self) -> &'static str {
+    match self {
+      Landmark::Region { .. } => "in",
+      _ => "near",
+    }
+  }
+}
+
+impl QuickChat {
+  // What it says, in the words of whoever's reading it.
+  pub fn describe(&self, state: &GameState) -> String {
+    let name_of = |uuid: &UUID| match state.players.get(uuid) {
+      Some(player) => player.name.clone(),
+      None => "someone".to_string(),
+    };
+    match self {
+      QuickChat::Accuse { target } => format!("I think it's {}", name_of(target)),
+      QuickChat::Vouch { target } => format!("{} is safe", name_of(target)),
+      QuickChat::SawNear { target, landmark } => format!(
+        "I saw {} {} {}",
+        name_of(target),
+        landmark.preposition(),
+        landmark.describe(&state.map)
+      ),
+      QuickChat::WasNear { landmark } => format!(
+        "I was {} {}",
+        landmark.preposition(),
+        landmark.describe(&state.map)
+      ),
+      QuickChat::DoingTask { index } => format!("I was doing task #{}", index + 1),
+      QuickChat::Skip => "Let's skip this one".to_string(),
+    }
+  }
+}
+
+impl GameState {
+  fn check_landmark(&self, landmark: Landmark) -> Result<(), GameError> {
+    let exists = match landmark {
+      Landmark::MeetingTable | Landmark::EmergencyButton => true,
+      Landmark::Vent { index } => index < self.map.vents.len(),
+      Landmark::FixStation { index } => index < self.map.fix_stations.len(),
+      Landmark::Region { index } => index < self.map.regions.len(),
+    };
+    if !exists {
+      return Err(GameError::NoSuchLandmark);
+    }
+    Ok(())
+  }
+
+  fn check_quick_chat_target(&self, target: UUID) -> Result<(), GameError> {
+    if !self.players.contains_key(&target) {
+      return Err(GameError::InvalidTarget {
+        action: Action::Chat,
+      });
+    }
+    Ok(())
+  }
+
+  pub fn note_quick_chat(&self, sender: UUID, quick: QuickChat) -> Result<ChatMessage, GameError> {
+    let channel = self.chat_channel_for(sender)?;
+    match quick {
+      QuickChat::Accuse { target } | QuickChat::Vouch { target } => {
+        self.check_quick_chat_target(target)?
+      }
+      QuickChat::SawNear { target, landmark } => {
+        self.check_quick_chat_target(target)?;
+        self.check_landmark(landmark)?;
+      }
+      QuickChat::WasNear { landmark } => self.check_landmark(landmark)?,
+      QuickChat::DoingTask { index } => {
+        let tasks = self.players.get(&sender).map_or(0, |p| p.tasks.len());
+        if index >= tasks {
+          return Err(GameError::NoSuchTask { index });
+        }
+      }
+      QuickChat::Skip => (),
+    }
+    Ok(ChatMessage {
+      sender,
+      channel,
+      content: ChatContent::Quick(quick),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_quick_chat_only_talks_about_real_things() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate, Role::Crewmate]);
+    state.status = GameStatus::Lobby;
+    state.players.get_mut(&uuids[1]).unwrap().name = "Player 1".to_string();
+    state.settings.quick_chat_only = true;
+    assert_eq!(
+      state.note_chat(uuids[0], "anything at all"),
+      Err(GameError::QuickChatOnly)
+    );
+
+    let accusation = QuickChat::Accuse { target: uuids[1] };
+    let chat = state.note_quick_chat(uuids[0], accusation).unwrap();
+    assert_eq!(chat.content.describe(&state), "I think it's Player 1");
+    let nobody = QuickChat::Vouch {
+      target: UUID::random(),
+    };
+    assert!(state.note_quick_chat(uuids[0], nobody).is_err());
+    let nowhere = QuickChat::WasNear {
+      landmark: Landmark::Vent {
+        index: state.map.vents.len(),
+      },
+    };
+    assert_eq!(
+      state.note_quick_chat(uuids[0], nowhere),
+      Err(GameError::NoSuchLandmark)
+    );
+    let alibi = QuickChat::WasNear {
+      landmark: Landmark::Region { index: 1 },
+    };
+    let chat = state.note_quick_chat(uuids[0], alibi).unwrap();
+    assert_eq!(chat.content.describe(&state), "I was in Electrical");
+    let no_task = QuickChat::DoingTask { index: 0 };
+    assert_eq!(
+      state.note_quick_chat(uuids[0], no_task),
+      Err(GameError::NoSuchTask { index: 0 })
+    );
+  }
+}
@@ -0,0 +1,233 @@
+use crate::*;
+use core::time::Duration;
+use instant::Instant;
+
+// Which allowance a message comes out of. Movement gets the most room, since
+// players send it every time they touch a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageBudget {
+  Movement,
+  // Killing, voting, doing tasks, and everything else that plays the game.
+  Action,
+  // Talking to the others, which mustn't use up what's left for voting.
+  Chat,
+  // Talking about the connection or the room, rather than playing.
+  Meta,
+  // Setting up voice connections takes a flurry of these, for each peer.
+  Signalling,
+  // Whatever we couldn't make sense of. A working client never sends any.
+  Undecodable,
+}
+
+impl ClientToServerMessage {
+  pub fn budget(&self) -> MessageBudget {
+    match self {
+      ClientToServerMessage::Input { .. } => MessageBudget::Movement,
+      ClientToServerMessage::Kill { .. }
+      | ClientToServerMessage::BeginInteraction { .. }
+      | ClientToServerMessage::SolveMinigame { .. }
+      | ClientToServerMessage::Vote { .. }
+      | ClientToServerMessage::LockInVote()
+      | ClientToServerMessage::Shield { .. }
+      | ClientToServerMessage::SheriffShot { .. }
+      | ClientToServerMessage::ReportBody { .. }
+      | ClientToServerMessage::CallMeeting()
+      | ClientToServerMessage::Sabotage(_)
+      | ClientToServerMessage::FixSabotage()
+      | ClientToServerMessage::EnterVent()
+      | ClientToServerMessage::ExitVent()
+      | ClientToServerMessage::MoveVent { .. }
+      | ClientToServerMessage::DragBody { .. }
+      | ClientToServerMessage::ReleaseBody()
+      | ClientToServerMessage::DropDecoy()
+      | ClientToServerMessage::Shapeshift { .. } => MessageBudget::Action,
+      ClientToServerMessage::Chat { .. } | ClientToServerMessage::QuickChat(_) => {
+        MessageBudget::Chat
+      }
+      ClientToServerMessage::Join { .. }
+      | ClientToServerMessage::Rejoin { .. }
+      | ClientToServerMessage::CreateRoom { .. }
+      | ClientToServerMessage::JoinRoom { .. }
+      | ClientToServerMessage::ListRooms()
+      | ClientToServerMessage::GetStats()
+      | ClientToServerMessage::ClientHello { .. }
+      | ClientToServerMessage::Checksum { .. }
+      | ClientToServerMessage::Pong { .. }
+      | ClientToServerMessage::StartGame()
+      | ClientToServerMessage::ResumeGame()
+      | ClientToServerMessage::Ready { .. }
+      | ClientToServerMessage::TransferHost { .. }
+      | ClientToServerMessage::AddBots { .. }
+      | ClientToServerMessage::KickPlayer { .. }
+      | ClientToServerMessage::BanPlayer { .. }
+      | ClientToServerMessage::VoteKick { .. }
+      | ClientToServerMessage::RequestColor(_)
+      | ClientToServerMessage::ReturnToLobby()
+      | ClientToServerMessage::UpdateSettings(_)
+      | ClientToServerMessage::Mute { .. } => MessageBudget::Meta,
+      ClientToServerMessage::Signal { .. } => MessageBudget::Signalling,
+    }
+  }
+}
+
+// Lets a burst of up to `capacity` through, and after that `per_second`.
+#[derive(Clone, Debug)]
+struct TokenBucket {
+  capacity: f64,
+  per_second: f64,
+  tokens: f64,
+  refilled_at: Instant,
+}
+
+impl TokenBucket {
+  fn new(capacity: f64, per_second: f64, now: Instant) -> Self {
+    TokenBucket {
+      capacity,
+      per_second,
+      tokens: capacity,
+      refilled_at: now,
+    }
+  }
+
+  fn take(&mut self, now: Instant) -> bool {
+    let elapsed = now
+      .checked_duration_since(self.refilled_at)
+      .unwrap_or_else(|| Duration::from_secs(0));
+    self.refilled_at = now;
+    self.tokens = (self.tokens + elapsed.as_secs_f64() * self.per_second).min(self.capacity);
+    if self.tokens < 1.0 {
+      return false;
+    }
+    self.tokens -= 1.0;
+    true
+  }
+}
+
+// What to do with a message from a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitVerdict {
+  Allow,
+  // They're going a bit fast, ignore this one.
+  Drop,
+  // They've kept going long after we started ignoring them, and are probably
+  // up to no good.
+  Disconnect,
+}
+
+// Keeps a single connection from flooding the server with messages. Each kind
+// of message has its own budget, so that mashing the keyboard can't stop
+// someone from voting. Going over budget gets messages dropped, and getting a
+// lot of messages dropped gets you disconnected.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+  movement: TokenBucket,
+  action: TokenBucket,
+  chat: TokenBucket,
+  meta: TokenBucket,
+  signalling: TokenBucket,
+  undecodable: TokenBucket,
+  dropped: TokenBucket,
+}
+
+impl RateLimiter {
+  pub fn new(now: Instant) -> Self {
+    RateLimiter {
+      movement: TokenBucket::new(120.0, 60.0, now),
+      action: TokenBucket::new(20.0, 5.0, now),
+      chat: TokenBucket::new(10.0, 2.0, now),
+      meta: TokenBucket::new(30.0, 10.0, now),
+      signalling: TokenBucket::new(200.0, 50.0, now),
+      undecodable: TokenBucket::new(5.0, 1.0, now),
+      dropped: TokenBucket::new(100.0, 10.0, now),
+    }
+  }
+
+  pub fn check(&mut self, message: &ClientToServerMessage, now: Instant) -> RateLimitVerdict {
+    self.charge(message.budget(), now)
+  }
+
+  pub fn check_undecodable(&mut self, now: Instant) -> RateLimitVerdict {
+    self.charge(MessageBudget::Undecodable, now)
+  }
+
+  fn charge(&mut self, budget: MessageBudget, now: Instant) -> RateLimitVerdict {
+    let bucket = match budget {
+      MessageBudget::Movement => &mut self.movement,
+      MessageBudget::Action => &mut self.action,
+      MessageBudget::Chat => &mut self.chat,
+      MessageBudget::Meta => &mut self.meta,
+      MessageBudget::Signalling => &mut self.signalling,
+      MessageBudget::Undecodable => &mut self.undecodable,
+    };
+    if bucket.take(now) {
+      RateLimitVerdict::Allow
+    } else if self.dropped.take(now) {
+      RateLimitVerdict::Drop
+    } else {
+      RateLimitVerdict::Disconnect
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use core::time::Duration;
+  use instant::Instant;
+
+  #[test]
+  fn test_floods_get_dropped_and_then_disconnected() {
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(start);
+    let input = ClientToServerMessage::Input {
+      sequence: 0,
+      movement: MovementInput::default(),
+    };
+    let vote = ClientToServerMessage::Vote {
+      target: VoteTarget::Skip,
+    };
+
+    // A burst of movement is fine, but it can't go on forever.
+    for _ in 0..120 {
+      assert_eq!(limiter.check(&input, start), RateLimitVerdict::Allow);
+    }
+    assert_eq!(limiter.check(&input, start), RateLimitVerdict::Drop);
+    // None of that is held against their vote.
+    assert_eq!(limiter.check(&vote, start), RateLimitVerdict::Allow);
+    // Nor is talking too much.
+    let chat = ClientToServerMessage::Chat {
+      text: "hi".to_string(),
+    };
+    for _ in 0..10 {
+      assert_eq!(limiter.check(&chat, start), RateLimitVerdict::Allow);
+    }
+    assert_eq!(limiter.check(&chat, start), RateLimitVerdict::Drop);
+    assert_eq!(limiter.check(&vote, start), RateLimitVerdict::Allow);
+    // Slowing down lets them back in.
+    let later = start + Duration::from_secs(1);
+    assert_eq!(limiter.check(&input, later), RateLimitVerdict::Allow);
+
+    // Keeping it up after being dropped a hundred times is too much.
+    let mut limiter = RateLimiter::new(start);
+    for _ in 0..120 {
+      assert_eq!(limiter.check(&input, start), RateLimitVerdict::Allow);
+    }
+    for _ in 0..100 {
+      assert_eq!(limiter.check(&input, start), RateLimitVerdict::Drop);
+    }
+    assert_eq!(limiter.check(&input, start), RateLimitVerdict::Disconnect);
+
+    // Nonsense counts against them too.
+    let mut limiter = RateLimiter::new(start);
+    for _ in 0..5 {
+      assert_eq!(limiter.check_undecodable(start), RateLimitVerdict::Allow);
+    }
+    for _ in 0..100 {
+      assert_eq!(limiter.check_undecodable(start), RateLimitVerdict::Drop);
+    }
+    assert_eq!(
+      limiter.check_undecodable(start),
+      RateLimitVerdict::Disconnect
+    );
+  }
+}
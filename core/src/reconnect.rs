@@ -0,0 +1,84 @@
+use crate::*;
+use core::time::Duration;
+
+impl GameState {
+  // Players who drop out mid-game get a little while to come back before
+  // we give up on them.
+  pub(crate) fn note_disconnected(&mut self, uuid: UUID) -> bool {
+    let grace = self.settings.reconnect_grace;
+    let in_game = matches!(
+      self.status,
+      GameStatus::Playing(_) | GameStatus::Paused { .. }
+    );
+    self.release_body(uuid);
+    if !in_game || grace == Duration::from_secs(0) {
+      return false;
+    }
+    match self.players.get_mut(&uuid) {
+      Some(player) if !player.disconnected => {
+        player.disconnected = true;
+        player.reconnect_time_remaining = grace;
+        true
+      }
+      _ => false,
+    }
+  }
+
+  pub fn note_rejoined(&mut self, uuid: UUID) -> Result<(), GameError> {
+    let player = match self.players.get_mut(&uuid) {
+      Some(p) if p.disconnected => p,
+      Some(_) => return Err(GameError::NeverLeft),
+      None => return Err(GameError::UnknownPlayer { uuid }),
+    };
+    player.disconnected = false;
+    player.reconnect_time_remaining = Duration::from_secs(0);
+    Ok(())
+  }
+
+  pub(crate) fn progress_reconnect_timers(&mut self, elapsed: Duration) {
+    let mut expired = vec![];
+    for player in self.players.values_mut().filter(|p| p.disconnected) {
+      player.reconnect_time_remaining = player
+        .reconnect_time_remaining
+        .checked_sub(elapsed)
+        .unwrap_or_else(|| Duration::from_secs(0));
+      if player.reconnect_time_remaining == Duration::from_secs(0) {
+        expired.push(player.uuid);
+      }
+    }
+    for uuid in expired {
+      self.remove_player(uuid);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_disconnected_players_can_rejoin_in_time() {
+    let roles = [
+      Role::Impostor,
+      Role::Crewmate,
+      Role::Crewmate,
+      Role::Crewmate,
+    ];
+    let (mut state, uuids) = game_with(&roles);
+    let grace = state.settings.reconnect_grace;
+
+    state.handle_disconnection(uuids[1]);
+    assert!(state.players[&uuids[1]].disconnected);
+    state.simulate(grace / 2);
+    state.note_rejoined(uuids[1]).unwrap();
+    assert!(!state.players[&uuids[1]].disconnected);
+    assert!(state.note_rejoined(uuids[1]).is_err());
+
+    state.handle_disconnection(uuids[2]);
+    state.simulate(grace + Duration::from_secs(1));
+    assert!(!state.players.contains_key(&uuids[2]));
+    assert!(state.note_rejoined(uuids[2]).is_err());
+  }
+}
@@ -6,25 +6,58 @@ use std::error::Error;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+// Everything that went into a game, so that we can play it back exactly as
+// it happened. The server's random decisions either come from the seed or are
+// recorded along with the message that prompted them.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RecordedGame {
   // The version of the software this was recorded with.
   pub version: String,
+  // What the server's random number generator started out with.
+  pub seed: u64,
+  // The map it was played on, in case it wasn't one of the built-in ones.
+  pub map: Map,
   pub entries: Vec<RecordingEntry>,
 }
 impl RecordedGame {
-  pub fn new(entries: Vec<RecordingEntry>) -> Self {
+  pub fn new(seed: u64) -> Self {
     Self {
       version: get_version_sha().to_string(),
-      entries,
+      seed,
+      map: Map::default(),
+      entries: Vec::new(),
     }
   }
+
+  // Recordings get long, so they're saved in the binary encoding.
+  pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(bincode::serialize(self)?)
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+    Ok(bincode::deserialize(bytes)?)
+  }
+
+  pub fn duration(&self) -> Duration {
+    // Assume that the final event marks the end of the recording.
+    self
+      .entries
+      .last()
+      .map(|e| tick_time(e.tick))
+      .unwrap_or_else(|| Duration::from_secs(0))
+  }
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RecordingEntry {
-  pub since_start: Duration,
+  // How many ticks had been simulated when this happened.
+  pub tick: u64,
   pub event: RecordingEvent,
 }
+
+// How far into the game the given tick is.
+pub fn tick_time(tick: u64) -> Duration {
+  Duration::from_nanos(TICK.as_nanos() as u64 * tick)
+}
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum RecordingEvent {
   Message(PlaybackMessage),
@@ -45,6 +78,7 @@ pub enum MaybeDecisionIfPlayingBackRecording {
 pub enum ServerDecision {
   StartInfo(StartInfo),
   NewPlayerPosition(Position),
+  Latency(Option<Duration>),
 }
 
 struct PlaybackBroadcaster {
@@ -60,6 +94,9 @@ impl Broadcaster for PlaybackBroadcaster {
     // ??? what to do here
     Ok(())
   }
+  fn can_send_to_players(&self) -> bool {
+    false
+  }
 }
 pub struct PlaybackTx {}
 impl GameTx for PlaybackTx {
@@ -69,61 +106,72 @@ impl GameTx for PlaybackTx {
 }
 
 pub struct PlaybackServer {
-  current_time: Duration,
+  // Time that's passed but doesn't yet add up to a whole tick.
+  unplayed_time: Duration,
   current_index: usize,
   paused: bool,
   recording: RecordedGame,
   game_server: GameServer,
+  // Keeps the server's time in step with the recording, rather than with how
+  // fast it's being played back.
+  clock: VirtualClock,
   pending_messages: Arc<Mutex<Vec<ServerToClientMessage>>>,
 }
 
 impl PlaybackServer {
   pub fn new(recording: RecordedGame) -> Self {
     let pending_messages = Arc::new(Mutex::new(Vec::new()));
-    let mut game_server = GameServer::new(
-      Box::new(PlaybackBroadcaster {
-        pending_messages: pending_messages.clone(),
-      }),
-      false,
-    );
-    game_server.version = recording.version.clone();
-    game_server.state.status = GameStatus::Lobby;
+    let clock = VirtualClock::new();
+    let game_server = Self::new_game_server(&recording, pending_messages.clone(), &clock);
     Self {
-      current_time: Duration::from_secs(0),
+      unplayed_time: Duration::from_secs(0),
       current_index: 0,
       paused: false,
       game_server,
+      clock,
       recording,
       pending_messages,
     }
   }
 
-  pub fn restart(&mut self) {
-    let mut game_server = GameServer::new(
-      Box::new(PlaybackBroadcaster {
-        pending_messages: self.pending_messages.clone(),
-      }),
-      false,
-    );
-    game_server.version = self.recording.version.clone();
+  fn new_game_server(
+    recording: &RecordedGame,
+    pending_messages: Arc<Mutex<Vec<ServerToClientMessage>>>,
+    clock: &VirtualClock,
+  ) -> GameServer {
+    let mut game_server =
+      GameServer::new(Box::new(PlaybackBroadcaster { pending_messages }), false)
+        .with_clock(clock.clone());
+    game_server.version = recording.version.clone();
     game_server.state.status = GameStatus::Lobby;
-    self.game_server = game_server;
-    self.current_time = Duration::from_millis(0);
+    game_server.state.rng = GameRng::new(recording.seed);
+    game_server.state.set_map(recording.map.clone());
+    game_server
+  }
+
+  pub fn restart(&mut self) {
+    self.clock = VirtualClock::new();
+    self.game_server =
+      Self::new_game_server(&self.recording, self.pending_messages.clone(), &self.clock);
+    self.unplayed_time = Duration::from_secs(0);
     self.current_index = 0;
   }
 
   pub fn duration(&self) -> Duration {
-    // Assume that the final message marks the end of the recording.
-    self
-      .recording
-      .entries
-      .last()
-      .map(|e| e.since_start)
-      .unwrap_or_else(|| Duration::from_secs(0))
+    self.recording.duration()
   }
 
   pub fn current_time(&self) -> Duration {
-    self.current_time
+    tick_time(self.current_tick())
+  }
+
+  fn current_tick(&self) -> u64 {
+    self.game_server.state.position_history.tick
+  }
+
+  // The game as the server saw it, at this point in the recording.
+  pub fn state(&self) -> &GameState {
+    &self.game_server.state
   }
 
   pub fn skip_to(
@@ -131,24 +179,37 @@ impl PlaybackServer {
     from_start: Duration,
     player: &mut GameAsPlayer,
   ) -> Result<(), Box<dyn Error>> {
-    if from_start < self.current_time {
+    let target_tick = (from_start.as_nanos() / TICK.as_nanos()) as u64;
+    if target_tick < self.current_tick() {
       self.restart();
       player.displayed_messages.clear();
     }
-    while self.current_time < from_start {
-      let elapsed = Duration::from_millis(16);
-      let finished = self.simulate(elapsed, player, true)?;
-      player.simulate(elapsed);
+    while self.current_tick() < target_tick {
+      let finished = self.step()?;
+      self.deliver_messages(player)?;
+      player.simulate(TICK);
       if finished {
         // the simulation is done, can't skip past this point
         break;
       }
     }
+    self.play_due_events()?;
     self.game_server.broadcast_snapshot()?;
     self.deliver_messages(player)?;
     Ok(())
   }
 
+  // Jumps to somewhere in the recording, given as a fraction of the way
+  // through it.
+  pub fn scrub_to(
+    &mut self,
+    fraction: f64,
+    player: &mut GameAsPlayer,
+  ) -> Result<(), Box<dyn Error>> {
+    let from_start = self.duration().mul_f64(fraction.clamp(0.0, 1.0));
+    self.skip_to(from_start, player)
+  }
+
   pub fn toggle_pause(&mut self) {
     self.paused = !self.paused;
   }
@@ -166,15 +227,32 @@ impl PlaybackServer {
     if self.paused && !force {
       return Ok(true);
     }
-    let new_time = self.current_time + elapsed;
-    let mut server_messages = 0;
-    loop {
-      let entry = match self.recording.entries.get(self.current_index) {
-        // Done with entries, just uh... continue simulating!
-        None => break,
-        Some(entry) => entry,
-      };
-      if entry.since_start > new_time {
+    self.unplayed_time += elapsed;
+    let mut finished = false;
+    while !finished && self.unplayed_time >= TICK {
+      self.unplayed_time -= TICK;
+      finished = self.step()?;
+    }
+    self.deliver_messages(player)?;
+    Ok(finished)
+  }
+
+  // Plays whatever happened before the next tick, and then simulates it, just
+  // like the server did. Returns true once there's nothing left to play.
+  fn step(&mut self) -> Result<bool, Box<dyn Error>> {
+    let events = self.play_due_events()?;
+    if self.game_server.state.status.finished() && events == 0 {
+      return Ok(true);
+    }
+    self.clock.advance(TICK);
+    self.game_server.simulate(TICK)?;
+    Ok(false)
+  }
+
+  fn play_due_events(&mut self) -> Result<usize, Box<dyn Error>> {
+    let mut events = 0;
+    while let Some(entry) = self.recording.entries.get(self.current_index) {
+      if entry.tick > self.game_server.state.position_history.tick {
         break;
       }
       self.current_index += 1;
@@ -188,15 +266,9 @@ impl PlaybackServer {
           self.game_server.disconnected(*uuid)?;
         }
       };
-      server_messages += 1;
-    }
-    if self.game_server.state.status.finished() && server_messages == 0 {
-      return Ok(true);
+      events += 1;
     }
-    self.game_server.simulate(elapsed)?;
-    self.current_time = new_time;
-    self.deliver_messages(player)?;
-    Ok(false)
+    Ok(events)
   }
 
   fn deliver_messages(&mut self, player: &mut GameAsPlayer) -> Result<(), Box<dyn Error>> {
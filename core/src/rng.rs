@@ -0,0 +1,124 @@
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+// Where everything random about a game comes from. Given the seed that a game
+// was started with, we can lay it out exactly the same way again, which is
+// what replays, tests, and tracking down desyncs need.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "SavedRng", into = "SavedRng")]
+pub struct GameRng {
+  seed: u64,
+  stream: u64,
+  // How many times we've been asked for something, to tell whether two of
+  // these have gotten out of step.
+  draws: u64,
+  rng: ChaCha8Rng,
+}
+
+impl GameRng {
+  // Setting up the game draws from one stream, and playing it from another,
+  // so that what happens in the game doesn't depend on how it was set up.
+  const SETUP: u64 = 0;
+  const PLAY: u64 = 1;
+
+  pub fn new(seed: u64) -> Self {
+    Self::with_stream(seed, Self::SETUP)
+  }
+
+  // The generator for once the game is underway.
+  pub(crate) fn for_play(seed: u64) -> Self {
+    Self::with_stream(seed, Self::PLAY)
+  }
+
+  fn with_stream(seed: u64, stream: u64) -> Self {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(stream);
+    GameRng {
+      seed,
+      stream,
+      draws: 0,
+      rng,
+    }
+  }
+
+  // A seed for a new game. It needs to be one that players can't guess,
+  // otherwise they could work out who the impostors are.
+  pub fn random_seed() -> u64 {
+    rand::random()
+  }
+
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+}
+
+impl Default for GameRng {
+  fn default() -> Self {
+    GameRng::new(0)
+  }
+}
+
+// The generator doesn't save itself, but all it takes to pick it up again is
+// where it was in its stream.
+#[derive(Clone, Serialize, Deserialize)]
+struct SavedRng {
+  seed: u64,
+  stream: u64,
+  draws: u64,
+  word_pos: u128,
+}
+
+impl From<GameRng> for SavedRng {
+  fn from(rng: GameRng) -> Self {
+    SavedRng {
+      seed: rng.seed,
+      stream: rng.stream,
+      draws: rng.draws,
+      // This version of rand_chacha can't say where it is before it's
+      // generated anything, but then it's at the start.
+      word_pos: if rng.draws == 0 {
+        0
+      } else {
+        rng.rng.get_word_pos()
+      },
+    }
+  }
+}
+
+impl From<SavedRng> for GameRng {
+  fn from(saved: SavedRng) -> Self {
+    let mut rng = GameRng::with_stream(saved.seed, saved.stream);
+    rng.rng.set_word_pos(saved.word_pos);
+    rng.draws = saved.draws;
+    rng
+  }
+}
+
+impl PartialEq for GameRng {
+  fn eq(&self, other: &Self) -> bool {
+    self.seed == other.seed && self.stream == other.stream && self.draws == other.draws
+  }
+}
+
+impl RngCore for GameRng {
+  fn next_u32(&mut self) -> u32 {
+    self.draws += 1;
+    self.rng.next_u32()
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    self.draws += 1;
+    self.rng.next_u64()
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    self.draws += 1;
+    self.rng.fill_bytes(dest)
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+    self.draws += 1;
+    self.rng.try_fill_bytes(dest)
+  }
+}
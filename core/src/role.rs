@@ -0,0 +1,240 @@
+use crate::*;
+use core::time::Duration;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Role {
+  Crewmate,
+  Impostor,
+  // Can protect one player from being killed, once per game.
+  Medic { shield_used: bool },
+  // Can shoot someone they suspect. If they're wrong, they die instead.
+  Sheriff,
+  // Can sneak through the vents, once per round.
+  Engineer { vented_this_round: bool },
+}
+
+impl Role {
+  pub fn name(self) -> &'static str {
+    match self {
+      Role::Crewmate => "Crewmate",
+      Role::Impostor => "Impostor",
+      Role::Medic { .. } => "Medic",
+      Role::Sheriff => "Sheriff",
+      Role::Engineer { .. } => "Engineer",
+    }
+  }
+
+  pub fn description(self) -> &'static str {
+    match self {
+      Role::Crewmate | Role::Impostor => "",
+      Role::Medic { .. } => "You can shield one player from the impostors (press Q near them)",
+      Role::Sheriff => "You can shoot an impostor (press Q), but if you're wrong you die",
+      Role::Engineer { .. } => "You can use a vent once each round (press E near one)",
+    }
+  }
+}
+
+impl Settings {
+  // The chance that a game will have each of the special crew roles.
+  fn role_chances(&self) -> [(Role, f64); 3] {
+    [
+      (Role::Medic { shield_used: false }, self.medic_chance),
+      (Role::Sheriff, self.sheriff_chance),
+      (
+        Role::Engineer {
+          vented_this_round: false,
+        },
+        self.engineer_chance,
+      ),
+    ]
+  }
+}
+
+// Hands out the special crew roles to plain crewmates.
+pub(crate) fn assign_roles<'a>(
+  settings: &Settings,
+  assignments: impl Iterator<Item = &'a mut PlayerStartInfo>,
+  rng: &mut GameRng,
+) {
+  let mut crew: Vec<&mut PlayerStartInfo> =
+    assignments.filter(|info| info.team == Team::Crew).collect();
+  for (role, chance) in settings.role_chances().iter() {
+    if crew.is_empty() || !rng.gen_bool(chance.clamp(0.0, 1.0)) {
+      continue;
+    }
+    let index = rng.gen_range(0, crew.len());
+    crew.swap_remove(index).role = *role;
+  }
+}
+
+impl GameState {
+  fn check_role_action(
+    &self,
+    action: Action,
+    uuid: UUID,
+    target: UUID,
+  ) -> Result<(&Player, &Player), GameError> {
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return Err(GameError::WrongStatus { action });
+    }
+    let player = match self.players.get(&uuid) {
+      None => return Err(GameError::UnknownPlayer { uuid }),
+      Some(p) if p.dead => return Err(GameError::Dead { action }),
+      Some(p) if p.in_vent.is_some() => return Err(GameError::InVent { action }),
+      Some(p) if p.in_kill_animation() => return Err(GameError::InKillAnimation { action }),
+      Some(p) => p,
+    };
+    let target = match self.players.get(&target) {
+      Some(t) if !t.dead && t.in_vent.is_none() && t.uuid != uuid => t,
+      _ => return Err(GameError::InvalidTarget { action }),
+    };
+    if player.position.distance(&target.position) > self.settings.kill_distance {
+      return Err(GameError::OutOfRange { action });
+    }
+    Ok((player, target))
+  }
+
+  pub fn note_shield(&mut self, medic: UUID, target: UUID) -> Result<(), GameError> {
+    let action = Action::Shield;
+    let (player, _) = self.check_role_action(action, medic, target)?;
+    if player.role != (Role::Medic { shield_used: false }) {
+      return Err(GameError::NotAllowedForRole { action });
+    }
+    if let Some(player) = self.players.get_mut(&medic) {
+      player.role = Role::Medic { shield_used: true };
+    }
+    if let Some(target) = self.players.get_mut(&target) {
+      target.shielded = true;
+    }
+    Ok(())
+  }
+
+  pub fn note_sheriff_shot(&mut self, sheriff: UUID, target: UUID) -> Result<(), GameError> {
+    let action = Action::Shoot;
+    let (player, target_player) = self.check_role_action(action, sheriff, target)?;
+    if player.role != Role::Sheriff {
+      return Err(GameError::NotAllowedForRole { action });
+    }
+    if player.kill_cooldown > Duration::from_secs(0) {
+      return Err(GameError::OnCooldown {
+        action,
+        remaining: player.kill_cooldown,
+      });
+    }
+    // Shoot the impostor, or die of shame.
+    let victim = if target_player.impostor {
+      target_player
+    } else {
+      player
+    };
+    let body = DeadBody {
+      killer: Some(sheriff),
+      ..DeadBody::new(victim.color, victim.position)
+    };
+    let victim = victim.uuid;
+    if let Some(player) = self.players.get_mut(&sheriff) {
+      player.kill_cooldown = self.settings.kill_cooldown;
+    }
+    if let Some(victim) = self.players.get_mut(&victim) {
+      victim.dead = true;
+    }
+    self.release_body(victim);
+    self.freeze_for_kill(sheriff, victim);
+    self.emit(GameEvent::PlayerKilled {
+      killer: sheriff,
+      victim,
+    });
+    self.bodies.push(body);
+    self.check_for_victories();
+    Ok(())
+  }
+
+  // Gives back the abilities that can be used once per round.
+  pub(crate) fn reset_round_abilities(&mut self) {
+    for player in self.players.values_mut() {
+      if let Role::Engineer { .. } = player.role {
+        player.role = Role::Engineer {
+          vented_this_round: false,
+        };
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_medic_shield_blocks_one_kill() {
+    let medic = Role::Medic { shield_used: false };
+    let roles = [Role::Impostor, medic, Role::Crewmate, Role::Crewmate];
+    let (mut state, uuids) = game_with(&roles);
+    state.note_shield(uuids[1], uuids[2]).unwrap();
+    // Only one shield per game.
+    assert!(state.note_shield(uuids[1], uuids[3]).is_err());
+
+    state.note_kill(uuids[0], uuids[2], None).unwrap();
+    assert!(!state.players[&uuids[2]].dead);
+    assert!(!state.players[&uuids[2]].shielded);
+    assert!(state.bodies.is_empty());
+  }
+
+  #[test]
+  fn test_blocked_kills_dont_give_the_killer_away() {
+    let medic = Role::Medic { shield_used: false };
+    let (mut state, uuids) = game_at(&[
+      (Role::Impostor, Position { x: 100.0, y: 100.0 }),
+      (medic, Position { x: 100.0, y: 100.0 }),
+      (Role::Crewmate, Position { x: 130.0, y: 100.0 }),
+      (Role::Crewmate, Position { x: 100.0, y: 100.0 }),
+    ]);
+    state.note_shield(uuids[1], uuids[2]).unwrap();
+    state.note_kill(uuids[0], uuids[2], None).unwrap();
+    // They stay where they were, rather than lunging onto their target.
+    let killer = &state.players[&uuids[0]];
+    assert_eq!(killer.position, Position { x: 100.0, y: 100.0 });
+    assert_eq!(killer.kill_cooldown, state.settings.kill_cooldown);
+
+    // A kill that goes through still puts them on the body.
+    state.players.get_mut(&uuids[0]).unwrap().kill_cooldown = Duration::from_secs(0);
+    state.note_kill(uuids[0], uuids[2], None).unwrap();
+    assert_eq!(
+      state.players[&uuids[0]].position,
+      Position { x: 130.0, y: 100.0 }
+    );
+  }
+
+  #[test]
+  fn test_sheriff_dies_when_wrong() {
+    let roles = [
+      Role::Impostor,
+      Role::Sheriff,
+      Role::Crewmate,
+      Role::Crewmate,
+    ];
+    let (mut state, uuids) = game_with(&roles);
+    state.note_sheriff_shot(uuids[1], uuids[2]).unwrap();
+    assert!(state.players[&uuids[1]].dead);
+    assert!(!state.players[&uuids[2]].dead);
+  }
+
+  #[test]
+  fn test_engineer_vents_once_per_round() {
+    let engineer = Role::Engineer {
+      vented_this_round: false,
+    };
+    let (mut state, uuids) = game_with(&[Role::Impostor, engineer, Role::Crewmate]);
+    let vent = state.map.vents[0].position;
+    state.players.get_mut(&uuids[1]).unwrap().position = vent;
+    state.note_entered_vent(uuids[1]).unwrap();
+    state.note_exited_vent(uuids[1]).unwrap();
+    assert!(state.vent_near(uuids[1]).is_none());
+    state.reset_round_abilities();
+    assert!(state.vent_near(uuids[1]).is_some());
+  }
+}
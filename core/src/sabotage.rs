@@ -0,0 +1,275 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Sabotage {
+  // Crew vision shrinks to almost nothing until someone fixes the lights.
+  Lights,
+  // If no one fixes the reactor before the countdown runs out, the impostors win.
+  Reactor,
+  // The doors slam shut for a while, then open back up on their own.
+  Doors,
+  // The crew lose their task lists and the task bar, and the admin and
+  // security consoles go dark, until someone fixes communications.
+  Comms,
+}
+
+impl Sabotage {
+  pub fn all() -> &'static [Sabotage] {
+    &[
+      Sabotage::Lights,
+      Sabotage::Reactor,
+      Sabotage::Doors,
+      Sabotage::Comms,
+    ]
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Sabotage::Lights => "Lights",
+      Sabotage::Reactor => "Reactor",
+      Sabotage::Doors => "Doors",
+      Sabotage::Comms => "Comms",
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ActiveSabotage {
+  pub kind: Sabotage,
+  // For the reactor this is the time until meltdown, for the doors it's the
+  // time until they reopen. The lights and comms stay out until someone fixes
+  // them.
+  pub time_remaining: Option<Duration>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SabotageState {
+  pub active: Option<ActiveSabotage>,
+  pub cooldowns: BTreeMap<Sabotage, Duration>,
+}
+
+impl SabotageState {
+  pub fn is_active(&self, kind: Sabotage) -> bool {
+    self.active.map(|a| a.kind) == Some(kind)
+  }
+
+  pub fn cooldown(&self, kind: Sabotage) -> Duration {
+    self
+      .cooldowns
+      .get(&kind)
+      .copied()
+      .unwrap_or_else(|| Duration::from_secs(0))
+  }
+
+  pub fn reset_cooldowns(&mut self, settings: &Settings) {
+    for kind in Sabotage::all() {
+      self
+        .cooldowns
+        .insert(*kind, settings.sabotage_cooldown(*kind));
+    }
+  }
+
+  // Ticks down cooldowns and the active sabotage's timer. Returns the
+  // active sabotage if its timer has just run out.
+  fn progress_time(&mut self, elapsed: Duration) -> Option<Sabotage> {
+    for cooldown in self.cooldowns.values_mut() {
+      *cooldown = cooldown
+        .checked_sub(elapsed)
+        .unwrap_or_else(|| Duration::from_secs(0));
+    }
+    let active = self.active.as_mut()?;
+    let time_remaining = active.time_remaining.as_mut()?;
+    *time_remaining = time_remaining
+      .checked_sub(elapsed)
+      .unwrap_or_else(|| Duration::from_secs(0));
+    if *time_remaining == Duration::from_secs(0) {
+      let kind = active.kind;
+      self.active = None;
+      return Some(kind);
+    }
+    None
+  }
+}
+
+// A spot on the map where a crewmate can undo a sabotage.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FixStation {
+  pub sabotage: Sabotage,
+  pub position: Position,
+}
+
+impl GameState {
+  pub fn can_sabotage(&self, saboteur: UUID, kind: Sabotage) -> Result<(), GameError> {
+    let action = Action::Sabotage;
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return Err(GameError::WrongStatus { action });
+    }
+    match self.players.get(&saboteur) {
+      Some(p) if !p.impostor => return Err(GameError::NotAllowedForRole { action }),
+      Some(p) if p.in_kill_animation() => return Err(GameError::InKillAnimation { action }),
+      Some(_) => (),
+      None => return Err(GameError::UnknownPlayer { uuid: saboteur }),
+    }
+    if let Some(active) = self.sabotage.active {
+      return Err(GameError::BlockedBySabotage {
+        action,
+        sabotage: active.kind,
+      });
+    }
+    let cooldown = self.sabotage.cooldown(kind);
+    if cooldown > Duration::from_secs(0) {
+      return Err(GameError::OnCooldown {
+        action,
+        remaining: cooldown,
+      });
+    }
+    Ok(())
+  }
+
+  pub fn note_sabotage(&mut self, saboteur: UUID, kind: Sabotage) -> Result<(), GameError> {
+    self.can_sabotage(saboteur, kind)?;
+    let time_remaining = match kind {
+      Sabotage::Lights | Sabotage::Comms => None,
+      Sabotage::Reactor => Some(self.settings.reactor_meltdown_time),
+      Sabotage::Doors => Some(self.settings.doors_locked_time),
+    };
+    self.sabotage.active = Some(ActiveSabotage {
+      kind,
+      time_remaining,
+    });
+    if let Some(slowdown) = self.settings.sabotage_slowdown {
+      for player in self.players.values_mut().filter(|p| !p.impostor) {
+        player.add_status_effect(StatusEffectKind::Slowed, slowdown);
+      }
+    }
+    self
+      .sabotage
+      .cooldowns
+      .insert(kind, self.settings.sabotage_cooldown(kind));
+    self.emit(GameEvent::SabotageStarted { kind });
+    if kind == Sabotage::Doors {
+      for index in 0..self.map.doors.len() {
+        self.close_door(index, self.settings.doors_locked_time)?;
+      }
+    }
+    Ok(())
+  }
+
+  // Returns the active sabotage if the given player is close enough to one of
+  // its fix stations to fix it.
+  pub fn fixable_sabotage_near(&self, fixer: UUID) -> Option<Sabotage> {
+    let active = self.sabotage.active?;
+    let player = self.players.get(&fixer)?;
+    if player.dead {
+      return None;
+    }
+    self
+      .map
+      .fix_stations
+      .iter()
+      .filter(|s| s.sabotage == active.kind)
+      .find(|s| s.position.distance(&player.position) <= self.settings.task_distance)
+      .map(|s| s.sabotage)
+  }
+
+  pub fn note_sabotage_fixed(&mut self, fixer: UUID) -> Result<(), GameError> {
+    let action = Action::FixSabotage;
+    if self
+      .players
+      .get(&fixer)
+      .is_some_and(|p| p.in_kill_animation())
+    {
+      return Err(GameError::InKillAnimation { action });
+    }
+    let kind = self
+      .fixable_sabotage_near(fixer)
+      .ok_or(GameError::OutOfRange { action })?;
+    self.sabotage.active = None;
+    self.emit(GameEvent::SabotageFixed { kind });
+    Ok(())
+  }
+
+  pub(crate) fn simulate_sabotage(&mut self, elapsed: Duration) {
+    if let Some(Sabotage::Reactor) = self.sabotage.progress_time(elapsed) {
+      // Kaboom.
+      self.status = GameStatus::Won(Team::Impostors);
+    }
+  }
+
+  // Returns the distance that the given player should be able to see.
+  // Returns None if they should see everything.
+  pub fn vision(&self, player: &Player) -> Option<f64> {
+    let vision = player.vision(&self.settings, &self.status)?;
+    if !player.impostor && self.sabotage.is_active(Sabotage::Lights) {
+      return Some(vision.min(self.settings.lights_out_vision));
+    }
+    Some(vision)
+  }
+
+  // Whether the given player's been cut off from their task list. Impostors
+  // only have fake tasks anyway.
+  pub fn comms_down_for(&self, player: &Player) -> bool {
+    !player.impostor && self.sabotage.is_active(Sabotage::Comms)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_unfixed_reactor_wins_for_impostors() {
+    let (mut state, uuids) = game_with(&[Role::Impostor, Role::Crewmate, Role::Crewmate]);
+    let impostor = uuids[0];
+
+    state.note_sabotage(impostor, Sabotage::Reactor).unwrap();
+    // Can't stack sabotages.
+    assert!(state.can_sabotage(impostor, Sabotage::Lights).is_err());
+    state.simulate(Duration::from_secs(5));
+    assert_eq!(state.status, GameStatus::Playing(PlayState::Night));
+    state.simulate(state.settings.reactor_meltdown_time);
+    assert_eq!(state.status, GameStatus::Won(Team::Impostors));
+  }
+
+  #[test]
+  fn test_comms_blackout_cuts_the_crew_off() {
+    let map = MapRegistry::get(MapId::Ship).unwrap();
+    let admin = map.admin_consoles[0].position;
+    let comms = map
+      .fix_stations
+      .iter()
+      .find(|s| s.sabotage == Sabotage::Comms)
+      .unwrap()
+      .position;
+    let (mut state, uuids) = game_at(&[
+      (Role::Impostor, admin),
+      (Role::Crewmate, admin),
+      (Role::Crewmate, comms),
+    ]);
+    state.set_map(map);
+    let (impostor, crew, fixer) = (uuids[0], uuids[1], uuids[2]);
+    for player in state.players.values_mut() {
+      player.tasks = vec![Task::new(TaskKind::Short, player.position)];
+    }
+    assert!(state.surveillance(crew).is_some());
+    assert_eq!(state.task_bar(), Some(0.0));
+
+    state.note_sabotage(impostor, Sabotage::Comms).unwrap();
+    assert_eq!(state.surveillance(crew), None);
+    assert_eq!(state.surveillance(impostor), None);
+    assert_eq!(state.task_bar(), None);
+    assert!(state.view_for(crew).players[&crew].tasks.is_empty());
+    assert!(state.visible_entities(crew).tasks.is_empty());
+    // The tasks are still there, the crew just can't see them.
+    assert_eq!(state.players[&crew].tasks.len(), 1);
+
+    state.note_sabotage_fixed(fixer).unwrap();
+    assert!(state.surveillance(crew).is_some());
+    assert_eq!(state.view_for(crew).players[&crew].tasks.len(), 1);
+  }
+}
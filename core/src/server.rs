@@ -1,14 +1,16 @@
 use crate::replay::MaybeDecisionIfPlayingBackRecording::*;
 use crate::replay::{RecordingEntry, RecordingEvent};
 use crate::*;
-use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
-use std::collections::BTreeSet;
 use std::error::Error;
 use std::time::Duration;
 
 use instant::Instant;
 
+// How long the host gets to take everyone back to the lobby once a game's
+// over, before the room is closed.
+pub const FINISHED_GAME_GRACE: Duration = Duration::from_secs(2 * /* minutes */ 60);
+
 pub trait Broadcaster: Send {
   fn broadcast(&self, message: &ServerToClientMessage) -> Result<(), Box<dyn Error>>;
   fn send_to_player(
@@ -16,6 +18,13 @@ pub trait Broadcaster: Send {
     uuid: &UUID,
     message: &ServerToClientMessage,
   ) -> Result<(), Box<dyn Error>>;
+  // Whether send_to_player actually reaches anyone. When it doesn't, everyone
+  // gets the same messages.
+  fn can_send_to_players(&self) -> bool {
+    true
+  }
+  // Hangs up on a connection that's gone quiet.
+  fn disconnect(&self, _connection: &UUID) {}
 }
 
 // Implements logic for a game server without knowing about the transport layer.
@@ -24,48 +33,142 @@ pub trait Broadcaster: Send {
 pub struct GameServer {
   pub version: String,
   pub state: GameState,
-  start_time: Instant,
   last_message_received_at: Instant,
-  broadcaster: Box<dyn Broadcaster>,
-  recording: Option<Vec<RecordingEntry>>,
+  clock: Box<dyn Clock>,
+  pub(crate) broadcaster: Box<dyn Broadcaster>,
+  pub(crate) recording: Option<RecordedGame>,
+  // Secret tokens that players can use to get back in after losing their connection.
+  pub(crate) rejoin_tokens: BTreeMap<UUID, UUID>,
+  // Connections that have rejoined as an existing player, and which player that is.
+  connections: BTreeMap<UUID, UUID>,
+  // Which way everyone's trying to go, waiting for the next tick.
+  inputs: InputBuffer,
+  checksums: ChecksumHistory,
+  pub(crate) heartbeats: Heartbeats,
+  pub(crate) bots: Bots,
+  pub(crate) lobby_votes: LobbyVotes,
+  pub(crate) name_filter: Option<NameFilter>,
+  pub(crate) tally: GameTally,
+  // How long the game's been over for, if it is.
+  time_since_game_ended: Duration,
 }
 
 impl GameServer {
   pub fn new(broadcaster: Box<dyn Broadcaster>, record_game: bool) -> Self {
-    let now = Instant::now();
+    let clock = SystemClock;
+    let now = clock.now();
+    let seed = GameRng::random_seed();
     Self {
       version: get_version_sha().to_string(),
-      state: GameState::new(),
-      start_time: now,
+      state: GameState {
+        rng: GameRng::new(seed),
+        ..GameState::new()
+      },
       last_message_received_at: now,
+      clock: Box::new(clock),
       broadcaster,
-      recording: if record_game { Some(Vec::new()) } else { None },
+      recording: if record_game {
+        Some(RecordedGame::new(seed))
+      } else {
+        None
+      },
+      rejoin_tokens: BTreeMap::new(),
+      connections: BTreeMap::new(),
+      inputs: InputBuffer::default(),
+      checksums: ChecksumHistory::default(),
+      heartbeats: Heartbeats::default(),
+      bots: Bots::default(),
+      lobby_votes: LobbyVotes::default(),
+      name_filter: None,
+      tally: GameTally::default(),
+      time_since_game_ended: Duration::from_secs(0),
+    }
+  }
+
+  // Runs off the given clock rather than the real time, so that tests and
+  // replays can say how much time has gone by.
+  pub fn with_clock(self, clock: impl Clock + 'static) -> Self {
+    GameServer {
+      last_message_received_at: clock.now(),
+      clock: Box::new(clock),
+      ..self
     }
   }
 
   pub fn simulate(&mut self, elapsed: Duration) -> Result<bool, Box<dyn Error>> {
+    let _span = tracing::info_span!("simulate", tick = self.state.position_history.tick).entered();
     let timeout_duration = std::time::Duration::from_secs(15 * /* minutes */60);
-    let timed_out = self.last_message_received_at.elapsed() > timeout_duration;
+    let now = self.clock.now();
+    let timed_out = now.duration_since(self.last_message_received_at) > timeout_duration;
     if self.state.status != GameStatus::Connecting && timed_out {
       self.state.status = GameStatus::Disconnected;
     }
-    let finished = self.state.simulate(elapsed);
-    if let GameStatus::Won(_) = self.state.status {
-      console_log!("Game won, trying to transmit save game");
+    // Recordings already know who timed out, and there's no one to ping.
+    if self.broadcaster.can_send_to_players() {
+      let HeartbeatActions { pings, timed_out } = self.heartbeats.progress_time(elapsed, now);
+      for (connection, id) in pings {
+        self
+          .broadcaster
+          .send_to_player(&connection, &ServerToClientMessage::Ping { id })?;
+      }
+      for connection in timed_out {
+        tracing::info!(%connection, "Haven't heard from them in a while, disconnecting");
+        self.disconnected(connection)?;
+        self.broadcaster.disconnect(&connection);
+      }
+    }
+    // Whatever the bots did is already in the recording.
+    if self.broadcaster.can_send_to_players() {
+      self.play_bots(elapsed);
+      self.kick_idle_players()?;
+    }
+    let already_won = matches!(self.state.status, GameStatus::Won(_) | GameStatus::Draw);
+    let mut velocities_changed = false;
+    let decoys = self.state.decoys.len();
+    let disguised = self.state.disguised_count();
+    for _ in 0..self.state.ticks_due(elapsed) {
+      velocities_changed |= self.inputs.apply_next(&mut self.state);
+      self.state.simulate_tick();
+      self.inputs.progress_time(TICK);
+    }
+    // A game that's been won sticks around for a bit, in case the host wants
+    // to take everyone back to the lobby for another.
+    if self.state.status.finished() {
+      self.time_since_game_ended += elapsed;
+    } else {
+      self.time_since_game_ended = Duration::from_secs(0);
+    }
+    let finished = self.state.status == GameStatus::Disconnected
+      || self.time_since_game_ended > FINISHED_GAME_GRACE;
+    // Once a decoy or a disguise fades, the crew need to see the impostor
+    // for who they are and where they are.
+    let faded = self.state.decoys.len() < decoys || self.state.disguised_count() < disguised;
+    if velocities_changed || faded {
+      self.broadcast_snapshot()?;
+    }
+    self.send_events()?;
+    if already_won {
+      return Ok(finished);
+    }
+    if let GameStatus::Won(_) | GameStatus::Draw = self.state.status {
       if let Some(recording) = &self.recording {
-        console_log!("Recording exists, transmitting...");
-        let replay = &ServerToClientMessage::Replay(RecordedGame::new(recording.to_vec()));
-        match self.broadcaster.broadcast(replay) {
-          Ok(()) => console_log!("Transmit successful!"),
-          Err(e) => console_log!("Error broadcasting replay: {}", e),
+        tracing::debug!("Game won, sending out the recording");
+        let replay = &ServerToClientMessage::Replay(recording.clone());
+        if let Err(e) = self.broadcaster.broadcast(replay) {
+          tracing::warn!(error = %e, "Unable to broadcast the replay");
         }
       }
     }
-    if let GameStatus::Won(team) = self.state.status {
+    let result = match self.state.status {
+      GameStatus::Won(team) => Some(format!("{:?} win!", team)),
+      GameStatus::Draw => Some("Time's up! It's a draw.".to_string()),
+      _ => None,
+    };
+    if let Some(result) = result {
       self
         .broadcaster
         .broadcast(&&ServerToClientMessage::DisplayMessage(DisplayMessage {
-          message: Message::PlainString(format!("{:?} win!", team)),
+          message: Message::PlainString(result),
           duration: Duration::from_secs(15),
           delay_before_show: Duration::from_secs(0),
         }))?;
@@ -73,8 +176,22 @@ impl GameServer {
     Ok(finished)
   }
 
-  pub fn disconnected(&mut self, disconnected_player: UUID) -> Result<(), Box<dyn Error>> {
-    self.record_event(&RecordingEvent::Disconnect(disconnected_player));
+  pub fn disconnected(&mut self, connection: UUID) -> Result<(), Box<dyn Error>> {
+    if !self.heartbeats.remove(connection) {
+      // Either we timed them out already, or they never said anything.
+      return Ok(());
+    }
+    self.record_event(&RecordingEvent::Disconnect(connection));
+    self.remove_connection(connection)
+  }
+
+  // Takes whoever's on the connection out of the game, as if they'd left.
+  pub(crate) fn remove_connection(&mut self, connection: UUID) -> Result<(), Box<dyn Error>> {
+    let disconnected_player = self.player_for_connection(connection);
+    self.connections.remove(&connection);
+    self.inputs.remove(disconnected_player);
+    self.checksums.remove(disconnected_player);
+    self.lobby_votes.remove(disconnected_player);
     if let Some(player) = self.state.players.get(&disconnected_player) {
       self
         .broadcaster
@@ -93,8 +210,17 @@ impl GameServer {
           delay_before_show: Duration::from_secs(0),
         }))?;
     }
+    let previous_host = self.state.host;
     self.state.handle_disconnection(disconnected_player);
+    if self.state.host != previous_host {
+      self
+        .broadcaster
+        .broadcast(&ServerToClientMessage::HostChanged {
+          host: self.state.host,
+        })?;
+    }
     self.broadcast_snapshot()?;
+    self.send_events()?;
     Ok(())
   }
 
@@ -103,12 +229,28 @@ impl GameServer {
     sender: UUID,
     message: ClientToServerMessage,
   ) -> Result<(), Box<dyn Error>> {
-    let decision = self.handle_message_internal(sender, &message, &LiveGame)?;
-    self.record_event(&RecordingEvent::Message(PlaybackMessage {
-      sender,
-      message,
-      decision,
-    }));
+    let decision = match self.handle_message_internal(sender, &message, &LiveGame) {
+      Ok(decision) => decision,
+      Err(e) => {
+        // Let them know why nothing happened, if it's something they can fix.
+        if let (Some(e), false) = (e.downcast_ref::<GameError>(), self.bots.contains(sender)) {
+          self
+            .broadcaster
+            .send_to_player(&sender, &ServerToClientMessage::ActionFailed(*e))?;
+        }
+        return Err(e);
+      }
+    };
+    // Voice doesn't change anything in the game, and there's no one to talk
+    // to in a replay.
+    if !matches!(message, ClientToServerMessage::Signal { .. }) {
+      self.record_event(&RecordingEvent::Message(PlaybackMessage {
+        sender,
+        message,
+        decision,
+      }));
+    }
+    self.send_events()?;
     Ok(())
   }
 
@@ -121,6 +263,7 @@ impl GameServer {
       &message.message,
       &Playback(message.decision.clone()),
     )?;
+    self.send_events()?;
     Ok(())
   }
 
@@ -130,27 +273,40 @@ impl GameServer {
     message: &ClientToServerMessage,
     prerecorded_decision: &MaybeDecisionIfPlayingBackRecording,
   ) -> Result<Option<ServerDecision>, Box<dyn Error>> {
-    self.last_message_received_at = Instant::now();
-    console_log!("Game server handling {:?}", message);
+    self.last_message_received_at = self.clock.now();
+    let connection = sender;
+    // Bots are always there, so there's no need to keep checking up on them.
+    if !self.bots.contains(connection) {
+      self.heartbeats.note_heard(connection);
+    }
+    let sender = self.player_for_connection(sender);
+    let _span = tracing::info_span!(
+      "message",
+      uuid = %sender,
+      tick = self.state.position_history.tick
+    )
+    .entered();
+    tracing::debug!(?message, "Handling a message");
+    if message.shows_activity() && self.state.note_activity(sender) {
+      // Everyone can see they're back.
+      self.broadcast_snapshot()?;
+    }
     match message {
       ClientToServerMessage::StartGame() => {
         if self.state.status != GameStatus::Lobby {
-          console_log!(
-            "Player {} tried to start a game from state {:?}",
-            sender,
-            self.state.status
-          );
+          tracing::info!(status = ?self.state.status, "Tried to start a game at the wrong time");
           return Ok(None);
         }
+        self.state.can_start_game(sender)?;
         let start_info = match prerecorded_decision {
-          LiveGame => self.state.get_game_start_info(),
+          LiveGame => self.state.get_game_start_info(GameRng::random_seed()),
           Playback(Some(ServerDecision::StartInfo(start_info))) => start_info.clone(),
           invalid => return Err(format!("Expected StartInfo when handling a recorded ClientToServerMessage::StartGame message, but got: {:?}", invalid).into()),
         };
         self.state.note_game_started(&start_info)?;
         self.broadcast_snapshot()?;
         for (uuid, player) in self.state.players.iter() {
-          self.broadcaster.send_to_player(
+          self.send_to_player(
             uuid,
             &ServerToClientMessage::DisplayMessage(DisplayMessage {
               message: Message::PlainString("The game has begun!".to_string()),
@@ -159,7 +315,7 @@ impl GameServer {
             }),
           )?;
           if player.impostor {
-            self.broadcaster.send_to_player(
+            self.send_to_player(
               uuid,
               &ServerToClientMessage::DisplayMessage(DisplayMessage {
                 message: Message::PlainString(
@@ -170,7 +326,7 @@ impl GameServer {
                 delay_before_show: Duration::from_secs(3),
               }),
             )?;
-            self.broadcaster.send_to_player(
+            self.send_to_player(
               uuid,
               &ServerToClientMessage::DisplayMessage(DisplayMessage {
                 message: Message::PlainString(
@@ -181,7 +337,7 @@ impl GameServer {
               }),
             )?;
           } else {
-            self.broadcaster.send_to_player(
+            self.send_to_player(
               uuid,
               &ServerToClientMessage::DisplayMessage(DisplayMessage {
                 message: Message::PlainString(
@@ -191,13 +347,27 @@ impl GameServer {
                 delay_before_show: Duration::from_secs(3),
               }),
             )?;
+            if player.role != Role::Crewmate {
+              self.send_to_player(
+                uuid,
+                &ServerToClientMessage::DisplayMessage(DisplayMessage {
+                  message: Message::PlainString(format!(
+                    "You are the {}! {}",
+                    player.role.name(),
+                    player.role.description()
+                  )),
+                  duration: Duration::from_secs(10),
+                  delay_before_show: Duration::from_secs(9),
+                }),
+              )?;
+            }
             let num_impostors = self
               .state
               .players
               .iter()
               .filter(|(_, p)| p.impostor)
               .count();
-            self.broadcaster.send_to_player(
+            self.send_to_player(
               uuid,
               &ServerToClientMessage::DisplayMessage(DisplayMessage {
                 message: Message::PlainString(format!("But beware, there's {} evil impostor{} on board. If you find a dead body, press R to report it", num_impostors, if num_impostors == 1 {""} else {"s"})),
@@ -209,71 +379,158 @@ impl GameServer {
         }
         return Ok(Some(ServerDecision::StartInfo(start_info)));
       }
-      ClientToServerMessage::Killed(body) => {
-        self.state.note_death(*body)?;
-        self.broadcast_snapshot()?;
+      ClientToServerMessage::CreateRoom { .. }
+      | ClientToServerMessage::JoinRoom { .. }
+      | ClientToServerMessage::ListRooms() => {
+        return Err("Rooms are handled before messages get to the game".into());
       }
-      ClientToServerMessage::ReportBody { dead_body_color } => {
-        match self.state.status {
-          GameStatus::Playing(PlayState::Night) => {
-            // This is fine.
-          }
-          _ => {
-            // Invalid time to report a body, ignore it
-            return Ok(None);
-          }
-        }
-        let body = match self
-          .state
-          .bodies
-          .iter()
-          .find(|b| b.color == *dead_body_color)
-        {
-          None => {
-            // No such body
-            return Ok(None);
-          }
-          Some(body) => body,
-        };
-        let reporter = match self.state.players.get(&sender) {
-          None => {
-            // Reporter is a spectator lol
-            return Ok(None);
-          }
-          Some(player) => {
-            if player.dead {
-              // ... but nobody came
-              return Ok(None);
-            }
-            player
+      ClientToServerMessage::GetStats() => {
+        return Err("Stats are handled before messages get to the game".into());
+      }
+      ClientToServerMessage::ClientHello { .. } => {
+        return Err("Handshakes are handled before messages get to the game".into());
+      }
+      ClientToServerMessage::Mute { .. } => {
+        return Err("Muting is handled before messages get to the game".into());
+      }
+      ClientToServerMessage::KickPlayer { .. } | ClientToServerMessage::BanPlayer { .. } => {
+        return Err("Kicks are handled by the room, see GameServer::kick".into());
+      }
+      ClientToServerMessage::Chat { text } => {
+        let chat = self.state.note_chat(sender, text)?;
+        self.send_chat(chat)?;
+      }
+      ClientToServerMessage::Signal { to, signal } => {
+        self.state.check_voice_signal(sender, *to, signal)?;
+        self.send_to_player(
+          to,
+          &ServerToClientMessage::Signal {
+            from: sender,
+            signal: signal.clone(),
+          },
+        )?;
+      }
+      ClientToServerMessage::QuickChat(quick) => {
+        let chat = self.state.note_quick_chat(sender, *quick)?;
+        self.send_chat(chat)?;
+      }
+      ClientToServerMessage::Pong { id } => {
+        let latency = match prerecorded_decision {
+          LiveGame => {
+            let now = self.clock.now();
+            self.heartbeats.note_pong(connection, *id, now)
           }
+          Playback(Some(ServerDecision::Latency(latency))) => *latency,
+          invalid => return Err(format!("Expected Latency when handling a recorded ClientToServerMessage::Pong message, but got: {:?}", invalid).into()),
         };
-        let distance_between_reporter_and_body = reporter.position.distance(&body.position);
-        let slop_for_latency = 32.0;
-        if distance_between_reporter_and_body
-          > self.state.settings.report_distance + slop_for_latency
-        {
-          // body is too far away to report it
-          return Ok(None);
+        if let Some(player) = self.state.players.get_mut(&sender) {
+          player.latency = latency;
         }
-        // oh shit it's on
-        self.state.status = GameStatus::Playing(PlayState::Voting(VotingState {
-          votes: BTreeMap::new(),
-          time_remaining: self.state.settings.voting_time,
-        }));
+        // The lobby is where everyone's looking at how laggy they are.
+        if self.state.status == GameStatus::Lobby {
+          self.broadcast_snapshot()?;
+        }
+        return Ok(Some(ServerDecision::Latency(latency)));
+      }
+      ClientToServerMessage::Checksum { tick, checksum } => {
+        if let Err(divergence) = self.checksums.check(sender, *tick, *checksum) {
+          tracing::warn!(%divergence, "Desync");
+          let snapshot = self.snapshot_for(sender);
+          self.send_to_player(&sender, &ServerToClientMessage::Resync(snapshot))?;
+        }
+      }
+      ClientToServerMessage::ResumeGame() => {
+        self.state.note_force_resume(sender)?;
         self.broadcast_snapshot()?;
       }
-      ClientToServerMessage::FinishedTask(finished) => {
-        self.state.note_finished_task(sender, *finished)?;
+      ClientToServerMessage::Ready { ready } => {
+        self.state.note_ready(sender, *ready)?;
         self.broadcast_snapshot()?;
       }
-      ClientToServerMessage::Move(moved) => {
-        if let Some(player) = self.state.players.get_mut(&sender) {
-          player.velocity = moved.speed;
-          player.position = moved.position;
-        }
+      ClientToServerMessage::TransferHost { to } => {
+        self.state.note_host_transferred(sender, *to)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::Kill { victim, seen_at } => {
+        self.state.note_kill(sender, *victim, Some(*seen_at))?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::Shield { target } => {
+        self.state.note_shield(sender, *target)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::SheriffShot { target } => {
+        self.state.note_sheriff_shot(sender, *target)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::ReportBody {
+        dead_body_color,
+        seen_at,
+      } => {
+        self
+          .state
+          .note_body_reported(sender, *dead_body_color, Some(*seen_at))?;
         self.broadcast_snapshot()?;
       }
+      ClientToServerMessage::CallMeeting() => {
+        self.state.call_emergency_meeting(sender)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::Sabotage(kind) => {
+        self.state.note_sabotage(sender, *kind)?;
+        self
+          .broadcaster
+          .broadcast(&ServerToClientMessage::DisplayMessage(DisplayMessage {
+            message: Message::PlainString(format!("{} sabotaged!", kind.name())),
+            duration: Duration::from_secs(5),
+            delay_before_show: Duration::from_secs(0),
+          }))?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::FixSabotage() => {
+        self.state.note_sabotage_fixed(sender)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::EnterVent() => {
+        self.state.note_entered_vent(sender)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::ExitVent() => {
+        self.state.note_exited_vent(sender)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::DragBody { dead_body_color } => {
+        self.state.note_body_dragged(sender, *dead_body_color)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::ReleaseBody() => {
+        self.state.note_body_released(sender)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::DropDecoy() => {
+        self.state.note_decoy_dropped(sender)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::Shapeshift { target } => {
+        self.state.note_shapeshift(sender, *target)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::MoveVent { to } => {
+        self.state.note_moved_vent(sender, *to)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::BeginInteraction { task } => {
+        self.state.begin_interaction(sender, *task)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::SolveMinigame { solution } => {
+        self.state.solve_minigame(sender, solution)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::Input { sequence, movement } => {
+        // Picked up on the next tick.
+        self.inputs.push(sender, *sequence, *movement);
+      }
       ClientToServerMessage::Join {
         version,
         details: join,
@@ -289,38 +546,37 @@ impl GameServer {
           );
         }
         let mut decision = None;
+        let name: String = match join {
+          JoinRequest::JoinAsPlayer { name, .. } => match self.check_name(name) {
+            Ok(name) => name.into(),
+            Err(e) => {
+              let e = JoinError::BadName(e);
+              self.send_to_player(&sender, &ServerToClientMessage::JoinFailed(e))?;
+              return Err(e.into());
+            }
+          },
+          JoinRequest::JoinAsSpectator => "Spectator".to_string(),
+        };
         if self.state.status == GameStatus::Lobby {
           if let JoinRequest::JoinAsPlayer {
-            name,
             preferred_color,
+            cosmetics,
+            ..
           } = join
           {
-            if self.state.players.get(&sender).is_some() {
+            if self.state.players.contains_key(&sender) {
               return Ok(None); // we know about this player already
             }
-            // ok, it's a new player, and we have room for them. if their color is
-            // already taken, give them a new one.
-            let taken_colors: BTreeSet<Color> =
-              self.state.players.iter().map(|(_, p)| p.color).collect();
-            let add_player;
-            let mut color = preferred_color;
-            if taken_colors.contains(&color) {
-              match Color::all().iter().find(|c| !taken_colors.contains(c)) {
-                None => {
-                  add_player = false; // we can't add this player, all colors are taken!
-                }
-                Some(c) => {
-                  add_player = true;
-                  color = &*c;
-                }
-              }
-            } else {
-              // player's preferred color wasn't taken, they're good to go!
-              add_player = true;
+            if let Err(e) = self.state.check_room_for_player() {
+              self.send_to_player(&sender, &ServerToClientMessage::JoinFailed(e))?;
+              return Err(e.into());
             }
-            if add_player {
+            // ok, it's a new player, and we have room for them. if their color is
+            // already taken, give them a new one. If they're all gone, we
+            // can't add them.
+            if let Some(color) = self.state.assign_color(*preferred_color) {
               let position = match prerecorded_decision {
-                LiveGame => self.state.map.get_spawn_in_position(),
+                LiveGame => self.state.map.get_spawn_in_position(&mut self.state.rng),
                 Playback(Some(ServerDecision::NewPlayerPosition(pos))) => *pos,
                 _ => {
                   return Err(
@@ -334,14 +590,19 @@ impl GameServer {
               };
               decision = Some(ServerDecision::NewPlayerPosition(position));
               // Add the new player (possibly with a new color)
-              let player = Player::new(sender, name.to_string(), *color, position);
+              let mut player = Player::new(sender, name.to_string(), color, position);
+              player.cosmetics = *cosmetics;
               self.state.players.insert(sender, player);
+              self.state.claim_host_if_vacant(sender);
+              let token = UUID::random();
+              self.rejoin_tokens.insert(sender, token);
+              self.send_to_player(&sender, &ServerToClientMessage::RejoinToken { token })?;
               self
                 .broadcaster
                 .broadcast(&ServerToClientMessage::DisplayMessage(DisplayMessage {
                   message: Message::FormattingString(vec![
                     FormattedText {
-                      color: Some(*color),
+                      color: Some(color),
                       text: name.clone(),
                     },
                     FormattedText {
@@ -354,17 +615,34 @@ impl GameServer {
                 }))?;
             }
           }
-          // In all other cases, they're joining as a spectator.
+        }
+        // In all other cases, they're joining as a spectator.
+        if !self.state.players.contains_key(&sender) {
+          self.state.note_spectator_joined(sender, name.clone())?;
+          self
+            .broadcaster
+            .broadcast(&ServerToClientMessage::DisplayMessage(DisplayMessage {
+              message: Message::PlainString(format!("{} is watching", name)),
+              duration: Duration::from_secs(10),
+              delay_before_show: Duration::from_secs(0),
+            }))?;
         }
 
-        console_log!("Player joined? Sending welcome to player");
-        self.broadcaster.send_to_player(
+        tracing::debug!("Sending a welcome");
+        self.send_to_player(
           &sender,
           &ServerToClientMessage::Welcome {
             connection_id: sender,
           },
         )?;
-        self.broadcaster.send_to_player(
+        if self.state.map.id == MapId::Custom {
+          self.send_to_player(&sender, &ServerToClientMessage::Map(self.state.map.clone()))?;
+        }
+        self.send_to_player(
+          &sender,
+          &ServerToClientMessage::Settings(self.state.settings.clone()),
+        )?;
+        self.send_to_player(
           &sender,
           &&ServerToClientMessage::DisplayMessage(DisplayMessage {
             message: Message::PlainString("Welcome to airlock.chat!".to_string()),
@@ -381,35 +659,210 @@ impl GameServer {
         if !(eligable_to_vote(self.state.players.get(&sender)) && self.eligable_target(*target)) {
           return Ok(None);
         }
-        // If it's day, and the sender hasn't voted yet, record their vote.
-        if let GameStatus::Playing(PlayState::Voting(VotingState { votes, .. })) =
-          &mut self.state.status
-        {
-          if let Entry::Vacant(o) = votes.entry(sender) {
-            o.insert(*target);
+        self.state.note_vote(sender, *target)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::Rejoin { uuid, token } => {
+        // Recordings don't keep the tokens, so just trust them during playback.
+        if let LiveGame = prerecorded_decision {
+          if self.rejoin_tokens.get(uuid) != Some(token) {
+            return Err(format!("Bad rejoin token for {}", uuid).into());
           }
         }
+        self.state.note_rejoined(*uuid)?;
+        self.connections.insert(sender, *uuid);
+        // Tell the client who they are again.
+        self.send_to_player(
+          uuid,
+          &ServerToClientMessage::Welcome {
+            connection_id: *uuid,
+          },
+        )?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::AddBots { count } => {
+        // The bots' joining is in the recording already.
+        if let LiveGame = prerecorded_decision {
+          self.add_bots(sender, *count)?;
+        }
+      }
+      ClientToServerMessage::LockInVote() => {
+        self.state.note_vote_locked_in(sender)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::RequestColor(color) => {
+        self.state.note_color_requested(sender, *color)?;
+        self.broadcast_snapshot()?;
+      }
+      ClientToServerMessage::VoteKick { target } => {
+        self.note_kick_vote(sender, *target)?;
+      }
+      ClientToServerMessage::ReturnToLobby() => {
+        self.state.note_returned_to_lobby(sender)?;
+        // Anyone who was let go can't get back in as who they were.
+        let players = &self.state.players;
+        self
+          .rejoin_tokens
+          .retain(|uuid, _| players.contains_key(uuid));
+        self.lobby_votes = LobbyVotes::default();
+        // Everyone's been moved to a new spot in the lobby, which might be
+        // close enough to where they were that they'd keep their own idea
+        // of where they are.
+        self.broadcast_resync()?;
+        self
+          .broadcaster
+          .broadcast(&ServerToClientMessage::DisplayMessage(DisplayMessage {
+            message: Message::PlainString("Back to the lobby for another game!".to_string()),
+            duration: Duration::from_secs(10),
+            delay_before_show: Duration::from_secs(0),
+          }))?;
+      }
+      ClientToServerMessage::UpdateSettings(patch) => {
+        let settings = self.state.patched_settings(sender, patch)?;
+        let new_map = settings.map_id != self.state.map.id;
+        self.set_settings(settings)?;
+        if new_map {
+          // Wherever they were standing might be inside a wall now.
+          self
+            .state
+            .map
+            .place_players_at_game_start(&mut self.state.players);
+        }
+        self
+          .broadcaster
+          .broadcast(&ServerToClientMessage::Settings(
+            self.state.settings.clone(),
+          ))?;
         self.broadcast_snapshot()?;
       }
     };
     Ok(None)
   }
 
+  // Which player a connection is for. Usually they're one and the same, unless
+  // the player has rejoined on a new connection.
+  pub(crate) fn player_for_connection(&self, connection: UUID) -> UUID {
+    *self.connections.get(&connection).unwrap_or(&connection)
+  }
+
+  // And the other way around.
+  pub(crate) fn connection_for_player(&self, player: UUID) -> UUID {
+    self
+      .connections
+      .iter()
+      .find(|(_, p)| **p == player)
+      .map(|(connection, _)| *connection)
+      .unwrap_or(player)
+  }
+
+  fn send_to_player(
+    &self,
+    uuid: &UUID,
+    message: &ServerToClientMessage,
+  ) -> Result<(), Box<dyn Error>> {
+    if self.bots.contains(*uuid) {
+      return Ok(());
+    }
+    let connection = self.connection_for_player(*uuid);
+    self.broadcaster.send_to_player(&connection, message)
+  }
+
+  // Only whoever's listening on the channel gets to hear it.
+  fn send_chat(&self, chat: ChatMessage) -> Result<(), Box<dyn Error>> {
+    let recipients = self.state.chat_recipients(chat.channel);
+    let message = ServerToClientMessage::Chat(chat);
+    if !self.broadcaster.can_send_to_players() {
+      return self.broadcaster.broadcast(&message);
+    }
+    for uuid in recipients {
+      self.send_to_player(&uuid, &message)?;
+    }
+    Ok(())
+  }
+
+  // Passes on what's happened since last time, to whoever's allowed to know.
+  fn send_events(&mut self) -> Result<(), Box<dyn Error>> {
+    let events = self.state.drain_events();
+    if events.is_empty() {
+      return Ok(());
+    }
+    self.tally.note_events(&events, &self.state, &self.bots);
+    // Only once someone's won.
+    self.send_summary()?;
+    if !self.broadcaster.can_send_to_players() {
+      return self
+        .broadcaster
+        .broadcast(&ServerToClientMessage::Events(events));
+    }
+    let (private, public): (Vec<GameEvent>, Vec<GameEvent>) = events
+      .into_iter()
+      .partition(|e| e.private_to(&self.state).is_some());
+    if !public.is_empty() {
+      self
+        .broadcaster
+        .broadcast(&ServerToClientMessage::Events(public))?;
+    }
+    for event in private {
+      for uuid in event.private_to(&self.state).unwrap_or_default() {
+        self.send_to_player(&uuid, &ServerToClientMessage::Events(vec![event]))?;
+      }
+    }
+    Ok(())
+  }
+
   pub fn get_uuid_for_new_connection(&self) -> UUID {
     UUID::random()
   }
 
-  pub fn broadcast_snapshot(&self) -> Result<(), Box<dyn Error>> {
-    self
-      .broadcaster
-      .broadcast(&ServerToClientMessage::Snapshot(Snapshot {
-        status: self.state.status.clone(),
-        bodies: self.state.bodies.clone(),
-        players: self.state.players.iter().map(|(_, p)| p.clone()).collect(),
-      }))?;
+  pub fn broadcast_snapshot(&mut self) -> Result<(), Box<dyn Error>> {
+    self.broadcast_views(ServerToClientMessage::Snapshot)
+  }
+
+  // Like broadcast_snapshot, but everyone starts over from exactly what they
+  // were told, their own position included.
+  pub fn broadcast_resync(&mut self) -> Result<(), Box<dyn Error>> {
+    self.broadcast_views(ServerToClientMessage::Resync)
+  }
+
+  fn broadcast_views(
+    &mut self,
+    message: fn(Snapshot) -> ServerToClientMessage,
+  ) -> Result<(), Box<dyn Error>> {
+    if !self.broadcaster.can_send_to_players() {
+      // Played back recordings are of games that are already over.
+      let snapshot = Snapshot::new(&self.state, self.state.task_bar());
+      return self.broadcaster.broadcast(&message(snapshot));
+    }
+    // Everyone only gets told what they're allowed to know.
+    let uuids: Vec<UUID> = self
+      .state
+      .players
+      .keys()
+      .chain(self.state.spectators.keys())
+      .copied()
+      .filter(|uuid| !self.bots.contains(*uuid))
+      .collect();
+    for uuid in uuids {
+      let snapshot = self.snapshot_for(uuid);
+      self.send_to_player(&uuid, &message(snapshot))?;
+    }
     Ok(())
   }
 
+  // The game as the given player gets to see it. We remember what we told
+  // them, so that we can tell later on if they've drifted from it.
+  fn snapshot_for(&mut self, uuid: UUID) -> Snapshot {
+    let view = self.state.view_for(uuid);
+    self
+      .checksums
+      .note_sent(uuid, view.position_history.tick, view.checksum());
+    Snapshot {
+      input_ack: self.inputs.ack(uuid),
+      surveillance: self.state.surveillance(uuid),
+      ..Snapshot::new(&view, self.state.task_bar())
+    }
+  }
+
   fn eligable_target(&self, target: VoteTarget) -> bool {
     match target {
       VoteTarget::Skip => {
@@ -422,13 +875,43 @@ impl GameServer {
     }
   }
 
+  // The game so far, if we're recording it.
+  pub fn recording(&self) -> Option<&RecordedGame> {
+    self.recording.as_ref()
+  }
+
+  // Sets up the game the way the room wants it, before anyone's joined.
+  pub fn set_settings(&mut self, settings: Settings) -> Result<(), Box<dyn Error>> {
+    let map = MapRegistry::get(settings.map_id);
+    self.state.settings = settings;
+    match map {
+      Some(map) => self.set_map(map),
+      None => Ok(()),
+    }
+  }
+
+  // Custom maps get sent out to everyone, since they have no way of knowing
+  // what's on them.
+  pub fn set_map(&mut self, map: Map) -> Result<(), Box<dyn Error>> {
+    self.state.set_map(map);
+    if let Some(recording) = &mut self.recording {
+      recording.map = self.state.map.clone();
+    }
+    if self.state.map.id == MapId::Custom {
+      self
+        .broadcaster
+        .broadcast(&ServerToClientMessage::Map(self.state.map.clone()))?;
+    }
+    Ok(())
+  }
+
   fn record_event(&mut self, event: &RecordingEvent) {
     let recording = match &mut self.recording {
       None => return,
       Some(r) => r,
     };
-    recording.push(RecordingEntry {
-      since_start: self.start_time.elapsed(),
+    recording.entries.push(RecordingEntry {
+      tick: self.state.position_history.tick,
       event: event.clone(),
     });
   }
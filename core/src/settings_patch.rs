@@ -0,0 +1,357 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+// The settings the host can change from the lobby. Anything left out stays
+// as it is.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SettingsPatch {
+  pub speed: Option<f64>,
+  pub kill_distance: Option<f64>,
+  pub kill_cooldown: Option<Duration>,
+  pub discussion_time: Option<Duration>,
+  pub voting_time: Option<Duration>,
+  pub confirm_ejects: Option<bool>,
+  pub allow_vote_changes: Option<bool>,
+  pub anonymous_votes: Option<bool>,
+  pub tie_policy: Option<TiePolicy>,
+  pub impostor_count: Option<usize>,
+  pub short_tasks: Option<usize>,
+  pub long_tasks: Option<usize>,
+  pub common_tasks: Option<usize>,
+  pub emergency_meetings_per_player: Option<usize>,
+  pub vision_radius: Option<VisionRadius>,
+  pub map_id: Option<MapId>,
+  pub game_mode: Option<GameMode>,
+  // Zero for no limit.
+  pub game_time_limit: Option<Duration>,
+  pub time_limit_outcome: Option<TimeLimitOutcome>,
+  // Zero to have the impostor win straight away.
+  pub sudden_death_time: Option<Duration>,
+  // Zero to let people idle in the lobby for as long as they like.
+  pub afk_kick_time: Option<Duration>,
+  pub allow_body_drag: Option<bool>,
+  // Zero to keep decoys out of the game.
+  pub decoy_time: Option<Duration>,
+  // Zero to keep shapeshifting out of the game.
+  pub shapeshift_time: Option<Duration>,
+  pub shapeshift_cooldown: Option<Duration>,
+}
+
+// The settings that have limits, for saying which one was out of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Setting {
+  Speed,
+  KillDistance,
+  KillCooldown,
+  DiscussionTime,
+  VotingTime,
+  ImpostorCount,
+  // All the tasks a crewmate gets, of every kind.
+  TaskCount,
+  EmergencyMeetings,
+  VisionRadius,
+  GameTimeLimit,
+  SuddenDeathTime,
+  AfkKickTime,
+  DecoyTime,
+  ShapeshiftTime,
+  ShapeshiftCooldown,
+  // Custom maps have to be loaded by the server, they can't be picked.
+  Map,
+}
+
+impl Setting {
+  // The lowest and highest it can go, in seconds for anything that's a time.
+  pub fn bounds(&self) -> (f64, f64) {
+    match self {
+      // Much slower and it's a chore to get anywhere, much faster and the
+      // walls stop working.
+      Setting::Speed => (0.5, 5.0),
+      Setting::KillDistance => (16.0, 256.0),
+      Setting::KillCooldown => (5.0, 120.0),
+      Setting::DiscussionTime => (0.0, 120.0),
+      Setting::VotingTime => (15.0, 300.0),
+      Setting::ImpostorCount => (1.0, 3.0),
+      // With nothing to do, the crew would win before anyone could move.
+      Setting::TaskCount => (1.0, 15.0),
+      Setting::EmergencyMeetings => (0.0, 9.0),
+      Setting::VisionRadius => (32.0, 1024.0),
+      // Any shorter and there's barely time to get going.
+      Setting::GameTimeLimit => (60.0, 3600.0),
+      Setting::SuddenDeathTime => (15.0, 300.0),
+      // Long enough to go and get a drink.
+      Setting::AfkKickTime => (60.0, 1800.0),
+      Setting::DecoyTime => (3.0, 60.0),
+      Setting::ShapeshiftTime => (5.0, 60.0),
+      Setting::ShapeshiftCooldown => (10.0, 120.0),
+      Setting::Map => (0.0, 0.0),
+    }
+  }
+
+  pub fn is_time(&self) -> bool {
+    matches!(
+      self,
+      Setting::KillCooldown
+        | Setting::DiscussionTime
+        | Setting::VotingTime
+        | Setting::GameTimeLimit
+        | Setting::SuddenDeathTime
+        | Setting::AfkKickTime
+        | Setting::DecoyTime
+        | Setting::ShapeshiftTime
+        | Setting::ShapeshiftCooldown
+    )
+  }
+
+  fn check(self, value: f64) -> Result<(), GameError> {
+    let (min, max) = self.bounds();
+    if !(min..=max).contains(&value) {
+      return Err(GameError::BadSetting { setting: self });
+    }
+    Ok(())
+  }
+}
+
+impl Display for Setting {
+  fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+    let setting = match self {
+      Setting::Speed => "speed",
+      Setting::KillDistance => "kill distance",
+      Setting::KillCooldown => "kill cooldown",
+      Setting::DiscussionTime => "discussion time",
+      Setting::VotingTime => "voting time",
+      Setting::ImpostorCount => "number of impostors",
+      Setting::TaskCount => "number of tasks",
+      Setting::EmergencyMeetings => "number of emergency meetings",
+      Setting::VisionRadius => "vision radius",
+      Setting::GameTimeLimit => "time limit",
+      Setting::SuddenDeathTime => "sudden death time",
+      Setting::AfkKickTime => "idle time before being kicked",
+      Setting::DecoyTime => "decoy time",
+      Setting::ShapeshiftTime => "shapeshift time",
+      Setting::ShapeshiftCooldown => "shapeshift cooldown",
+      Setting::Map => "map",
+    };
+    write!(fmt, "{}", setting)
+  }
+}
+
+impl Settings {
+  // These settings with the patch's changes made, as long as every one of
+  // them is within bounds.
+  pub fn patched(&self, patch: &SettingsPatch) -> Result<Settings, GameError> {
+    let mut settings = self.clone();
+    if let Some(speed) = patch.speed {
+      Setting::Speed.check(speed)?;
+      settings.speed = speed;
+    }
+    if let Some(distance) = patch.kill_distance {
+      Setting::KillDistance.check(distance)?;
+      settings.kill_distance = distance;
+    }
+    if let Some(cooldown) = patch.kill_cooldown {
+      Setting::KillCooldown.check(cooldown.as_secs_f64())?;
+      settings.kill_cooldown = cooldown;
+    }
+    if let Some(time) = patch.discussion_time {
+      Setting::DiscussionTime.check(time.as_secs_f64())?;
+      settings.discussion_time = time;
+    }
+    if let Some(time) = patch.voting_time {
+      Setting::VotingTime.check(time.as_secs_f64())?;
+      settings.voting_time = time;
+    }
+    if let Some(count) = patch.impostor_count {
+      Setting::ImpostorCount.check(count as f64)?;
+      settings.impostor_count = count;
+    }
+    settings.short_tasks = patch.short_tasks.unwrap_or(settings.short_tasks);
+    settings.long_tasks = patch.long_tasks.unwrap_or(settings.long_tasks);
+    settings.common_tasks = patch.common_tasks.unwrap_or(settings.common_tasks);
+    if patch.short_tasks.is_some() || patch.long_tasks.is_some() || patch.common_tasks.is_some() {
+      let total = settings.short_tasks + settings.long_tasks + settings.common_tasks;
+      Setting::TaskCount.check(total as f64)?;
+    }
+    if let Some(meetings) = patch.emergency_meetings_per_player {
+      Setting::EmergencyMeetings.check(meetings as f64)?;
+      settings.emergency_meetings_per_player = meetings;
+    }
+    if let Some(vision) = patch.vision_radius {
+      Setting::VisionRadius.check(vision.crew)?;
+      Setting::VisionRadius.check(vision.impostors)?;
+      settings.vision_radius = vision;
+    }
+    if let Some(map_id) = patch.map_id {
+      if MapRegistry::get(map_id).is_none() {
+        return Err(GameError::BadSetting {
+          setting: Setting::Map,
+        });
+      }
+      settings.map_id = map_id;
+    }
+    settings.confirm_ejects = patch.confirm_ejects.unwrap_or(settings.confirm_ejects);
+    settings.allow_vote_changes = patch
+      .allow_vote_changes
+      .unwrap_or(settings.allow_vote_changes);
+    settings.anonymous_votes = patch.anonymous_votes.unwrap_or(settings.anonymous_votes);
+    settings.tie_policy = patch.tie_policy.unwrap_or(settings.tie_policy);
+    settings.game_mode = patch.game_mode.unwrap_or(settings.game_mode);
+    match patch.game_time_limit {
+      None => (),
+      Some(limit) if limit == Duration::from_secs(0) => settings.game_time_limit = None,
+      Some(limit) => {
+        Setting::GameTimeLimit.check(limit.as_secs_f64())?;
+        settings.game_time_limit = Some(limit);
+      }
+    }
+    settings.time_limit_outcome = patch
+      .time_limit_outcome
+      .unwrap_or(settings.time_limit_outcome);
+    match patch.sudden_death_time {
+      None => (),
+      Some(time) if time == Duration::from_secs(0) => settings.sudden_death_time = None,
+      Some(time) => {
+        Setting::SuddenDeathTime.check(time.as_secs_f64())?;
+        settings.sudden_death_time = Some(time);
+      }
+    }
+    settings.allow_body_drag = patch.allow_body_drag.unwrap_or(settings.allow_body_drag);
+    match patch.afk_kick_time {
+      None => (),
+      Some(time) if time == Duration::from_secs(0) => settings.afk_kick_time = None,
+      Some(time) => {
+        Setting::AfkKickTime.check(time.as_secs_f64())?;
+        settings.afk_kick_time = Some(time);
+      }
+    }
+    match patch.decoy_time {
+      None => (),
+      Some(time) if time == Duration::from_secs(0) => settings.decoy_time = None,
+      Some(time) => {
+        Setting::DecoyTime.check(time.as_secs_f64())?;
+        settings.decoy_time = Some(time);
+      }
+    }
+    match patch.shapeshift_time {
+      None => (),
+      Some(time) if time == Duration::from_secs(0) => settings.shapeshift_time = None,
+      Some(time) => {
+        Setting::ShapeshiftTime.check(time.as_secs_f64())?;
+        settings.shapeshift_time = Some(time);
+      }
+    }
+    if let Some(cooldown) = patch.shapeshift_cooldown {
+      Setting::ShapeshiftCooldown.check(cooldown.as_secs_f64())?;
+      settings.shapeshift_cooldown = cooldown;
+    }
+    Ok(settings)
+  }
+}
+
+impl GameState {
+  // Only the host gets to change the settings, and only in the lobby, so
+  // everyone knows what they're in for before the game starts.
+  pub fn patched_settings(
+    &self,
+    requester: UUID,
+    patch: &SettingsPatch,
+  ) -> Result<Settings, GameError> {
+    let action = Action::ChangeSettings;
+    if self.status != GameStatus::Lobby {
+      return Err(GameError::WrongStatus { action });
+    }
+    if !self.is_host(requester) {
+      return Err(GameError::NotHost { action });
+    }
+    self.settings.patched(patch)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_settings_only_change_within_bounds() {
+    let settings = Settings::default();
+    let patched = settings
+      .patched(&SettingsPatch {
+        speed: Some(3.0),
+        voting_time: Some(Duration::from_secs(60)),
+        anonymous_votes: Some(true),
+        ..SettingsPatch::default()
+      })
+      .unwrap();
+    assert_eq!(patched.speed, 3.0);
+    assert_eq!(patched.voting_time, Duration::from_secs(60));
+    assert!(patched.anonymous_votes);
+    // Everything else is left alone.
+    assert_eq!(patched.kill_cooldown, settings.kill_cooldown);
+    assert_eq!(
+      settings.patched(&SettingsPatch::default()),
+      Ok(settings.clone())
+    );
+
+    let too_fast = SettingsPatch {
+      speed: Some(50.0),
+      ..SettingsPatch::default()
+    };
+    assert_eq!(
+      settings.patched(&too_fast),
+      Err(GameError::BadSetting {
+        setting: Setting::Speed
+      })
+    );
+    let no_time_to_vote = SettingsPatch {
+      voting_time: Some(Duration::from_secs(1)),
+      ..SettingsPatch::default()
+    };
+    assert_eq!(
+      settings.patched(&no_time_to_vote),
+      Err(GameError::BadSetting {
+        setting: Setting::VotingTime
+      })
+    );
+    let nothing_to_do = SettingsPatch {
+      short_tasks: Some(0),
+      long_tasks: Some(0),
+      common_tasks: Some(0),
+      ..SettingsPatch::default()
+    };
+    assert_eq!(
+      settings.patched(&nothing_to_do),
+      Err(GameError::BadSetting {
+        setting: Setting::TaskCount
+      })
+    );
+    let one_second = SettingsPatch {
+      game_time_limit: Some(Duration::from_secs(1)),
+      ..SettingsPatch::default()
+    };
+    assert!(settings.patched(&one_second).is_err());
+    let no_limit = SettingsPatch {
+      game_time_limit: Some(Duration::from_secs(0)),
+      ..SettingsPatch::default()
+    };
+    let limited = Settings {
+      game_time_limit: Some(Duration::from_secs(600)),
+      ..Settings::default()
+    };
+    assert_eq!(limited.patched(&no_limit).unwrap().game_time_limit, None);
+    let custom = SettingsPatch {
+      map_id: Some(MapId::Custom),
+      ..SettingsPatch::default()
+    };
+    assert!(settings.patched(&custom).is_err());
+    assert_eq!(
+      GameError::BadSetting {
+        setting: Setting::VotingTime
+      }
+      .to_string(),
+      "The voting time has to be between 15 and 300 seconds"
+    );
+  }
+}
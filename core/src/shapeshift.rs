@@ -0,0 +1,196 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+// Who an impostor is passing themselves off as, and for how much longer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Disguise {
+  pub target: UUID,
+  pub time_remaining: Duration,
+}
+
+impl GameState {
+  // Lets an impostor take on another living player's looks for a while.
+  // Everyone but their fellow impostors is fooled.
+  pub fn note_shapeshift(&mut self, uuid: UUID, target: UUID) -> Result<(), GameError> {
+    let action = Action::Shapeshift;
+    let time = self
+      .settings
+      .shapeshift_time
+      .ok_or(GameError::TurnedOff { action })?;
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return Err(GameError::WrongStatus { action });
+    }
+    match self.players.get(&uuid) {
+      None => return Err(GameError::UnknownPlayer { uuid }),
+      Some(p) if p.dead => return Err(GameError::Dead { action }),
+      Some(p) if !p.impostor => return Err(GameError::NotAllowedForRole { action }),
+      Some(p) if p.in_vent.is_some() => return Err(GameError::InVent { action }),
+      Some(p) if p.in_kill_animation() => return Err(GameError::InKillAnimation { action }),
+      Some(p) if p.disguise.is_some() || p.shapeshift_cooldown > Duration::from_secs(0) => {
+        let remaining = p
+          .disguise
+          .map_or(p.shapeshift_cooldown, |d| d.time_remaining);
+        return Err(GameError::OnCooldown { action, remaining });
+      }
+      Some(_) => (),
+    }
+    if target == uuid || self.players.get(&target).is_none_or(|p| p.dead) {
+      return Err(GameError::InvalidTarget { action });
+    }
+    if let Some(player) = self.players.get_mut(&uuid) {
+      player.disguise = Some(Disguise {
+        target,
+        time_remaining: time,
+      });
+    }
+    Ok(())
+  }
+
+  pub(crate) fn progress_disguises(&mut self, elapsed: Duration) {
+    let cooldown = self.settings.shapeshift_cooldown;
+    let mut faded = vec![];
+    for player in self.players.values_mut() {
+      player.shapeshift_cooldown = player.shapeshift_cooldown.saturating_sub(elapsed);
+      let disguise = match &mut player.disguise {
+        None => continue,
+        Some(disguise) => disguise,
+      };
+      disguise.time_remaining = disguise.time_remaining.saturating_sub(elapsed);
+      if disguise.time_remaining == Duration::from_secs(0) {
+        player.disguise = None;
+        player.shapeshift_cooldown = cooldown;
+        faded.push(player.uuid);
+      }
+    }
+    for uuid in faded {
+      self.emit(GameEvent::DisguiseFaded { uuid });
+    }
+  }
+
+  // There's no hiding who's who around the table.
+  pub(crate) fn drop_disguises(&mut self) {
+    for player in self.players.values_mut() {
+      player.disguise = None;
+    }
+  }
+
+  pub fn disguised_count(&self) -> usize {
+    self
+      .players
+      .values()
+      .filter(|p| p.disguise.is_some())
+      .count()
+  }
+
+  // Whoever can see the player right now, and the player themselves.
+  pub(crate) fn witnesses(&self, uuid: UUID) -> Vec<UUID> {
+    self
+      .players
+      .keys()
+      .filter(|viewer| {
+        **viewer == uuid
+          || self
+            .visible_entities(**viewer)
+            .players
+            .iter()
+            .any(|p| p.uuid == uuid)
+      })
+      .copied()
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_shapeshifters_fool_everyone_but_their_friends() {
+    let at = |x| Position { x, y: 600.0 };
+    let (mut state, uuids) = game_at(&[
+      (Role::Impostor, at(200.0)),
+      (Role::Impostor, at(200.0)),
+      (Role::Crewmate, at(200.0)),
+      (Role::Crewmate, at(250.0)),
+      (Role::Crewmate, at(2900.0)),
+    ]);
+    state.settings.shapeshift_time = Some(Duration::from_secs(10));
+    let (impostor, partner, target) = (uuids[0], uuids[1], uuids[2]);
+    let (witness, faraway) = (uuids[3], uuids[4]);
+    state.players.get_mut(&target).unwrap().name = "Target".to_string();
+
+    let action = Action::Shapeshift;
+    assert_eq!(
+      state.note_shapeshift(witness, target),
+      Err(GameError::NotAllowedForRole { action })
+    );
+    assert_eq!(
+      state.note_shapeshift(impostor, impostor),
+      Err(GameError::InvalidTarget { action })
+    );
+    state.note_shapeshift(impostor, target).unwrap();
+
+    let view = state.view_for(witness);
+    assert_eq!(view.players[&impostor].color, Color::Blue);
+    assert_eq!(view.players[&impostor].name, "Target");
+    assert_eq!(view.players[&impostor].disguise, None);
+    let view = state.view_for(partner);
+    assert_eq!(view.players[&impostor].color, Color::Red);
+    let view = state.view_for(impostor);
+    assert_eq!(view.players[&impostor].color, Color::Red);
+    assert!(view.players[&impostor].disguise.is_some());
+
+    // When it wears off, anyone watching sees the change.
+    state.simulate(Duration::from_secs(11));
+    assert_eq!(state.disguised_count(), 0);
+    assert_eq!(
+      state.drain_events(),
+      vec![GameEvent::DisguiseFaded { uuid: impostor }]
+    );
+    let witnesses = state.witnesses(impostor);
+    assert!(witnesses.contains(&witness));
+    assert!(!witnesses.contains(&faraway));
+    assert!(matches!(
+      state.note_shapeshift(impostor, target),
+      Err(GameError::OnCooldown { .. })
+    ));
+  }
+
+  #[test]
+  fn test_meetings_put_shapeshifting_back_on_cooldown() {
+    let (mut state, uuids) = game_with(&[Role::Impostor, Role::Crewmate, Role::Crewmate]);
+    state.settings.shapeshift_time = Some(Duration::from_secs(10));
+    state.settings.shapeshift_cooldown = Duration::from_secs(20);
+    state.settings.discussion_time = Duration::from_secs(0);
+    let impostor = uuids[0];
+    state
+      .players
+      .get_mut(&impostor)
+      .unwrap()
+      .shapeshift_cooldown = Duration::from_secs(0);
+    // Leave the crew some work, so the meeting doesn't hand them the game.
+    let spot = state.players[&uuids[1]].position;
+    state.players.get_mut(&uuids[1]).unwrap().tasks = vec![Task::new(TaskKind::Short, spot)];
+
+    state.start_meeting(MeetingReason::EmergencyButton { caller: uuids[1] });
+    state.simulate_tick();
+    for voter in uuids.iter() {
+      state.note_vote(*voter, VoteTarget::Skip).unwrap();
+    }
+    for _ in 0..1000 {
+      if state.status == GameStatus::Playing(PlayState::Night) {
+        break;
+      }
+      state.simulate(Duration::from_millis(100));
+    }
+    assert_eq!(state.status, GameStatus::Playing(PlayState::Night));
+    assert!(state.players[&impostor].shapeshift_cooldown > Duration::from_secs(19));
+    assert!(matches!(
+      state.note_shapeshift(impostor, uuids[1]),
+      Err(GameError::OnCooldown { .. })
+    ));
+  }
+}
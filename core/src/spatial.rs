@@ -0,0 +1,126 @@
+use crate::*;
+
+// The map's furniture, sorted into buckets by where it is, so that working
+// out what someone might bump into or have their view blocked by only has to
+// look at what's nearby rather than everything on the map.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpatialGrid {
+  columns: usize,
+  rows: usize,
+  // Indexes into Map::static_geometry, for each cell, in order.
+  cells: Vec<Vec<usize>>,
+  // How many shapes there were when the grid was built. If the furniture's
+  // changed since, the grid can't be trusted.
+  shape_count: usize,
+}
+
+impl SpatialGrid {
+  // About the size of a room. Smaller cells mean fewer shapes to check, but
+  // more cells to look in.
+  const CELL_SIZE: f64 = 200.0;
+
+  pub fn new(map: &Map) -> Self {
+    let columns = ((map.width() / Self::CELL_SIZE).ceil() as usize).max(1);
+    let rows = ((map.height() / Self::CELL_SIZE).ceil() as usize).max(1);
+    let mut grid = SpatialGrid {
+      columns,
+      rows,
+      cells: vec![vec![]; columns * rows],
+      shape_count: map.static_geometry.len(),
+    };
+    for (index, shape) in map.static_geometry.iter().enumerate() {
+      let (min, max) = shape.bounds();
+      for cell in grid.cells_overlapping(min, max) {
+        grid.cells[cell].push(index);
+      }
+    }
+    grid
+  }
+
+  fn cells_overlapping(&self, min: Position, max: Position) -> Vec<usize> {
+    let column_of =
+      |x: f64| ((x / Self::CELL_SIZE).floor().max(0.0) as usize).min(self.columns - 1);
+    let row_of = |y: f64| ((y / Self::CELL_SIZE).floor().max(0.0) as usize).min(self.rows - 1);
+    let mut cells = vec![];
+    for row in row_of(min.y)..=row_of(max.y) {
+      for column in column_of(min.x)..=column_of(max.x) {
+        cells.push(row * self.columns + column);
+      }
+    }
+    cells
+  }
+
+  // The shapes in the map that could overlap the given circle, in the same
+  // order as they are in the map. When the grid's out of date, that's all of
+  // them.
+  pub fn query_circle(&self, map: &Map, center: Position, radius: f64) -> Vec<usize> {
+    if self.cells.is_empty() || self.shape_count != map.static_geometry.len() {
+      return (0..map.static_geometry.len()).collect();
+    }
+    let min = Position {
+      x: center.x - radius,
+      y: center.y - radius,
+    };
+    let max = Position {
+      x: center.x + radius,
+      y: center.y + radius,
+    };
+    let mut found: Vec<usize> = self
+      .cells_overlapping(min, max)
+      .into_iter()
+      .flat_map(|cell| self.cells[cell].iter().copied())
+      .collect();
+    found.sort_unstable();
+    found.dedup();
+    found
+  }
+}
+
+impl Map {
+  // Sorts the furniture into a grid. Needs doing again whenever the
+  // furniture changes.
+  pub fn reindex(&mut self) {
+    self.grid = SpatialGrid::new(self);
+  }
+
+  pub(crate) fn indexed(mut self) -> Self {
+    self.reindex();
+    self
+  }
+
+  // The furniture that could get in the way of a circle.
+  pub fn shapes_near(&self, center: Position, radius: f64) -> impl Iterator<Item = &Shape> {
+    self
+      .grid
+      .query_circle(self, center, radius)
+      .into_iter()
+      .map(move |index| &self.static_geometry[index])
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+
+  #[test]
+  fn test_only_nearby_furniture_gets_checked() {
+    let mut map = MapRegistry::get(MapId::Ship).unwrap();
+    let everything = map.static_geometry.len();
+    let table = map.static_geometry[0].center();
+    let near_table: Vec<&Shape> = map.shapes_near(table, 20.0).collect();
+    assert_eq!(near_table, vec![&map.static_geometry[0]]);
+    // Somewhere in the middle of nowhere.
+    let nowhere = Position { x: 700.0, y: 700.0 };
+    assert_eq!(map.shapes_near(nowhere, 20.0).count(), 0);
+    // A circle covering the whole map finds everything, once.
+    assert_eq!(map.shapes_near(table, 10000.0).count(), everything);
+
+    // Moving the furniture around without reindexing means checking it all.
+    let mut geometry = map.static_geometry.to_vec();
+    geometry.push(map.static_geometry[0].clone());
+    map.static_geometry = geometry.into();
+    assert_eq!(map.shapes_near(nowhere, 20.0).count(), everything + 1);
+    map.reindex();
+    assert_eq!(map.shapes_near(nowhere, 20.0).count(), 0);
+  }
+}
@@ -0,0 +1,57 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+// Someone watching the game rather than playing it. Anyone who connects while
+// a game is underway (or asks to spectate) ends up as one of these.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Spectator {
+  pub uuid: UUID,
+  pub name: String,
+}
+
+impl GameState {
+  pub fn note_spectator_joined(&mut self, uuid: UUID, name: String) -> Result<(), GameError> {
+    if self.players.contains_key(&uuid) {
+      return Err(GameError::AlreadyPlaying);
+    }
+    self.spectators.insert(uuid, Spectator { uuid, name });
+    Ok(())
+  }
+
+  pub fn is_spectator(&self, uuid: UUID) -> bool {
+    self.spectators.contains_key(&uuid)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_spectators_cant_see_impostors() {
+    let (mut state, uuids) = game_with(&[Role::Impostor, Role::Crewmate]);
+    let spectator = UUID::random();
+    state
+      .note_spectator_joined(spectator, "Watcher".to_string())
+      .unwrap();
+    assert!(state.is_spectator(spectator));
+    assert!(state
+      .note_spectator_joined(uuids[0], "Test Player".to_string())
+      .is_err());
+
+    // Spectators could be chatting with the players, so they don't get to
+    // know who the impostors are until the game is over.
+    let hidden = state.view_for(spectator);
+    assert!(hidden.players.values().all(|p| !p.impostor));
+
+    state.status = GameStatus::Won(Team::Impostors);
+    let revealed = state.view_for(spectator);
+    assert!(revealed.players.values().any(|p| p.impostor));
+    state.status = GameStatus::Playing(PlayState::Night);
+
+    state.handle_disconnection(spectator);
+    assert!(!state.is_spectator(spectator));
+    assert_eq!(state.players.len(), 2);
+  }
+}
@@ -0,0 +1,62 @@
+use crate::*;
+use std::error::Error;
+
+// The whole of a game's state, written out as it is right now. Unlike a
+// SavedGame, this doesn't need the recording that led up to it, so it's handy
+// for test fixtures and for attaching to bug reports. It's JSON so that it
+// can be read, and edited, by hand.
+impl GameState {
+  pub fn to_snapshot(&self) -> Result<String, Box<dyn Error>> {
+    Ok(serde_json::to_string_pretty(self)?)
+  }
+
+  pub fn from_snapshot(snapshot: &str) -> Result<Self, Box<dyn Error>> {
+    let mut state: GameState = serde_json::from_str(snapshot)?;
+    // The map's index of the furniture isn't saved, since it can be worked
+    // out again.
+    state.map.reindex();
+    Ok(state)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use core::time::Duration;
+  use rand::Rng;
+
+  #[test]
+  fn test_snapshots_pick_up_exactly_where_they_left_off() {
+    let mut state = GameState::new();
+    state.status = GameStatus::Lobby;
+    state.settings.kill_cooldown = Duration::from_secs(3);
+    for i in 0..5 {
+      let uuid = UUID::random();
+      let position = state.map.get_spawn_in_position(&mut state.rng);
+      let player = Player::new(uuid, "Test Player".to_string(), Color::all()[i], position);
+      state.players.insert(uuid, player);
+    }
+    let start_info = state.get_game_start_info(80);
+    state.note_game_started(&start_info).unwrap();
+    for player in state.players.values_mut() {
+      player.velocity = Velocity { dx: 1.0, dy: 0.5 };
+    }
+    state.simulate(Duration::from_millis(1234));
+
+    let snapshot = state.to_snapshot().unwrap();
+    let mut restored = GameState::from_snapshot(&snapshot).unwrap();
+    assert_eq!(restored, state);
+
+    // Including what the dice are going to say next.
+    for state in [&mut state, &mut restored].iter_mut() {
+      state.simulate(Duration::from_secs(2));
+      state.start_meeting(MeetingReason::EmergencyButton {
+        caller: start_info.assignments[0].0,
+      });
+    }
+    assert_eq!(restored, state);
+    assert_eq!(restored.rng.gen::<u64>(), state.rng.gen::<u64>());
+
+    assert!(GameState::from_snapshot("{\"status\": \"Lobby\"}").is_err());
+  }
+}
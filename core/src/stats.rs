@@ -0,0 +1,210 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Who someone is from one game to the next, which their connection's UUID
+// can't tell us. Made up by the client the first time it runs and kept
+// there, so it's only as good as their browser's memory, and means nothing
+// beyond "the same person as last time".
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PlayerAccountId(pub UUID);
+
+impl PlayerAccountId {
+  pub fn random() -> Self {
+    PlayerAccountId(UUID::random())
+  }
+}
+
+impl std::fmt::Display for PlayerAccountId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+// How someone's done, over every game they've finished, or just the one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerStats {
+  pub games_played: u64,
+  pub crew_wins: u64,
+  pub impostor_wins: u64,
+  pub tasks_done: u64,
+  pub kills: u64,
+}
+
+impl PlayerStats {
+  pub fn add(&mut self, other: &PlayerStats) {
+    self.games_played += other.games_played;
+    self.crew_wins += other.crew_wins;
+    self.impostor_wins += other.impostor_wins;
+    self.tasks_done += other.tasks_done;
+    self.kills += other.kills;
+  }
+}
+
+// Keeps count of what everyone's done in this game, from its events. What
+// they've done only counts once the game's been finished.
+#[derive(Default)]
+pub struct GameTally {
+  pub(crate) counts: BTreeMap<UUID, PlayerStats>,
+  finished: Option<BTreeMap<UUID, PlayerStats>>,
+  // The rest is only for the game's summary.
+  pub(crate) votes_cast: BTreeMap<UUID, u64>,
+  pub(crate) ejections: Vec<Ejection>,
+  pub(crate) started_at_tick: u64,
+  summary: Option<GameSummary>,
+}
+
+impl GameTally {
+  pub fn note_events(&mut self, events: &[GameEvent], state: &GameState, bots: &Bots) {
+    for event in events.iter() {
+      match event {
+        GameEvent::GameStarted => {
+          self.counts.clear();
+          self.votes_cast.clear();
+          self.ejections.clear();
+          self.started_at_tick = state.position_history.tick;
+        }
+        GameEvent::VoteCast { voter } => {
+          *self.votes_cast.entry(*voter).or_default() += 1;
+        }
+        GameEvent::PlayerEjected { uuid } => {
+          self.ejections.push(Ejection {
+            uuid: *uuid,
+            after: self.time_played(state),
+          });
+        }
+        // A sheriff who shot the wrong person doesn't get credit for it.
+        GameEvent::PlayerKilled { killer, victim } if killer != victim => {
+          self.counts.entry(*killer).or_default().kills += 1;
+        }
+        GameEvent::TaskCompleted { uuid, .. } => {
+          self.counts.entry(*uuid).or_default().tasks_done += 1;
+        }
+        GameEvent::GameWon { team } => self.note_finished(Some(*team), state, bots),
+        GameEvent::GameDrawn => self.note_finished(None, state, bots),
+        _ => (),
+      }
+    }
+  }
+
+  // A draw still counts as a game played, just not a win for anyone.
+  fn note_finished(&mut self, winner: Option<Team>, state: &GameState, bots: &Bots) {
+    self.summary = Some(GameSummary::new(winner, state, self));
+    let mut counts = std::mem::take(&mut self.counts);
+    for player in state.players.values() {
+      if bots.contains(player.uuid) {
+        continue;
+      }
+      let stats = counts.entry(player.uuid).or_default();
+      stats.games_played = 1;
+      match winner {
+        Some(Team::Impostors) if player.impostor => stats.impostor_wins = 1,
+        Some(Team::Crew) if !player.impostor => stats.crew_wins = 1,
+        _ => (),
+      }
+    }
+    counts.retain(|uuid, _| state.players.contains_key(uuid) && !bots.contains(*uuid));
+    self.finished = Some(counts);
+  }
+
+  // What everyone got out of the game that just finished, if there's one
+  // that hasn't been picked up yet, by player.
+  pub fn take_finished(&mut self) -> Option<BTreeMap<UUID, PlayerStats>> {
+    self.finished.take()
+  }
+
+  pub fn take_summary(&mut self) -> Option<GameSummary> {
+    self.summary.take()
+  }
+
+  pub(crate) fn time_played(&self, state: &GameState) -> Duration {
+    TICK
+      * state
+        .position_history
+        .tick
+        .saturating_sub(self.started_at_tick) as u32
+  }
+}
+
+impl GameServer {
+  // The stats from the game that just finished, by the connection of each
+  // player in it, for whoever's keeping track of who they were.
+  pub fn take_finished_stats(&mut self) -> Option<BTreeMap<UUID, PlayerStats>> {
+    let finished = self.tally.take_finished()?;
+    Some(
+      finished
+        .into_iter()
+        .map(|(player, stats)| (self.connection_for_player(player), stats))
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_stats_count_once_the_game_is_over() {
+    let (state, uuids) = game_with(&[Role::Crewmate, Role::Impostor]);
+    let (crew, impostor) = (uuids[0], uuids[1]);
+    let bots = Bots::default();
+
+    let mut tally = GameTally::default();
+    tally.note_events(
+      &[
+        GameEvent::GameStarted,
+        GameEvent::TaskCompleted {
+          uuid: crew,
+          index: 0,
+        },
+        GameEvent::TaskCompleted {
+          uuid: crew,
+          index: 1,
+        },
+        GameEvent::PlayerKilled {
+          killer: impostor,
+          victim: crew,
+        },
+      ],
+      &state,
+      &bots,
+    );
+    assert_eq!(tally.take_finished(), None);
+
+    tally.note_events(
+      &[GameEvent::GameWon {
+        team: Team::Impostors,
+      }],
+      &state,
+      &bots,
+    );
+    let finished = tally.take_finished().unwrap();
+    assert_eq!(
+      finished[&crew],
+      PlayerStats {
+        games_played: 1,
+        tasks_done: 2,
+        ..PlayerStats::default()
+      }
+    );
+    assert_eq!(
+      finished[&impostor],
+      PlayerStats {
+        games_played: 1,
+        impostor_wins: 1,
+        kills: 1,
+        ..PlayerStats::default()
+      }
+    );
+    // Only handed out the once.
+    assert_eq!(tally.take_finished(), None);
+
+    let mut total = finished[&impostor];
+    total.add(&finished[&crew]);
+    assert_eq!(total.games_played, 2);
+    assert_eq!(total.tasks_done, 2);
+  }
+}
@@ -0,0 +1,78 @@
+use crate::*;
+use core::time::Duration;
+
+impl GameState {
+  // Down to one impostor and one crewmate, the impostor would have an easy
+  // win, so if the room wants it, the crewmate gets a last chance to finish
+  // their tasks first. There's no calling a meeting to get out of it.
+  // Returns whether it's (still) on, instead of the impostor winning.
+  pub(crate) fn sudden_death_instead_of_win(&mut self, impostors: usize, crew: usize) -> bool {
+    let time = match self.settings.sudden_death_time {
+      Some(time) if impostors == 1 && crew == 1 => time,
+      _ => return false,
+    };
+    if self.sudden_death_remaining.is_none() {
+      self.sudden_death_remaining = Some(time);
+      self.emit(GameEvent::SuddenDeathStarted);
+    }
+    true
+  }
+
+  pub(crate) fn progress_sudden_death(&mut self, elapsed: Duration) {
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return;
+    }
+    let remaining = match self.sudden_death_remaining {
+      None => return,
+      Some(remaining) => remaining.saturating_sub(elapsed),
+    };
+    self.sudden_death_remaining = Some(remaining);
+    if remaining == Duration::from_secs(0) && !self.partial_view {
+      self.win(Team::Impostors);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_sudden_death_gives_the_last_crewmate_a_chance() {
+    let (mut state, uuids) = game_with(&[Role::Impostor, Role::Crewmate]);
+    state.settings.sudden_death_time = Some(Duration::from_secs(30));
+    let crew = uuids[1];
+    let position = state.players[&crew].position;
+    state.players.get_mut(&crew).unwrap().tasks = vec![Task::new(TaskKind::Short, position)];
+
+    state.check_for_victories();
+    assert_eq!(state.status, GameStatus::Playing(PlayState::Night));
+    assert_eq!(state.sudden_death_remaining, Some(Duration::from_secs(30)));
+    assert_eq!(
+      state.can_call_emergency_meeting(crew),
+      Err(GameError::InSuddenDeath {
+        action: Action::CallMeeting
+      })
+    );
+    // Run out the clock, and the impostor gets them after all.
+    state.simulate(Duration::from_secs(31));
+    assert_eq!(state.status, GameStatus::Won(Team::Impostors));
+
+    // The crewmate can still pull it off though.
+    state.status = GameStatus::Playing(PlayState::Night);
+    state.sudden_death_remaining = Some(Duration::from_secs(30));
+    state.players.get_mut(&crew).unwrap().tasks[0].finished = true;
+    state.check_for_victories();
+    assert_eq!(state.status, GameStatus::Won(Team::Crew));
+
+    // Without it, one on one is a win for the impostor.
+    state.settings.sudden_death_time = None;
+    state.sudden_death_remaining = None;
+    state.status = GameStatus::Playing(PlayState::Night);
+    state.players.get_mut(&crew).unwrap().tasks[0].finished = false;
+    state.check_for_victories();
+    assert_eq!(state.status, GameStatus::Won(Team::Impostors));
+  }
+}
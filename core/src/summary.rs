@@ -0,0 +1,154 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// How one player's game went, for the results screen.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSummary {
+  pub uuid: UUID,
+  pub name: String,
+  pub color: Color,
+  pub role: Role,
+  pub impostor: bool,
+  pub survived: bool,
+  pub won: bool,
+  pub kills: u64,
+  pub tasks_done: usize,
+  pub tasks_total: usize,
+  pub votes_cast: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ejection {
+  pub uuid: UUID,
+  // How far into the game it happened.
+  pub after: Duration,
+}
+
+// Everything there is to say about a game once it's over. Sent out when it's
+// won, so none of it needs to be kept secret any more.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameSummary {
+  // No one, if time ran out on a draw.
+  pub winner: Option<Team>,
+  pub duration: Duration,
+  // Winners first.
+  pub players: Vec<PlayerSummary>,
+  pub ejections: Vec<Ejection>,
+}
+
+impl GameSummary {
+  pub(crate) fn new(winner: Option<Team>, state: &GameState, tally: &GameTally) -> Self {
+    let mut players: Vec<PlayerSummary> = state
+      .players
+      .values()
+      .map(|player| {
+        let counts = tally.counts.get(&player.uuid).copied().unwrap_or_default();
+        PlayerSummary {
+          uuid: player.uuid,
+          name: player.name.clone(),
+          color: player.color,
+          role: player.role,
+          impostor: player.impostor,
+          survived: !player.dead,
+          won: winner.is_some_and(|team| player.impostor == (team == Team::Impostors)),
+          kills: counts.kills,
+          tasks_done: player.tasks.iter().filter(|t| t.finished).count(),
+          tasks_total: player.tasks.len(),
+          votes_cast: tally.votes_cast.get(&player.uuid).copied().unwrap_or(0),
+        }
+      })
+      .collect();
+    players.sort_by_key(|p| !p.won);
+    GameSummary {
+      winner,
+      duration: tally.time_played(state),
+      players,
+      ejections: tally.ejections.clone(),
+    }
+  }
+}
+
+impl GameServer {
+  // The results screen for the game that just finished, for everyone.
+  pub(crate) fn send_summary(&mut self) -> Result<(), Box<dyn Error>> {
+    match self.tally.take_summary() {
+      Some(summary) => self
+        .broadcaster
+        .broadcast(&ServerToClientMessage::GameSummary(summary)),
+      None => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_the_summary_says_how_everyone_did() {
+    let (mut state, uuids) = game_with(&[Role::Impostor, Role::Crewmate, Role::Crewmate]);
+    for player in state.players.values_mut() {
+      player.tasks = vec![Task::new(TaskKind::Short, player.position); 2];
+    }
+    let (impostor, crew, ejected) = (uuids[0], uuids[1], uuids[2]);
+    let bots = Bots::default();
+    let mut tally = GameTally::default();
+    tally.note_events(&[GameEvent::GameStarted], &state, &bots);
+
+    state.position_history.tick += 100;
+    state.players.get_mut(&crew).unwrap().tasks[0].finished = true;
+    state.players.get_mut(&ejected).unwrap().dead = true;
+    tally.note_events(
+      &[
+        GameEvent::TaskCompleted {
+          uuid: crew,
+          index: 0,
+        },
+        GameEvent::VoteCast { voter: crew },
+        GameEvent::VoteCast { voter: impostor },
+        GameEvent::PlayerEjected { uuid: ejected },
+      ],
+      &state,
+      &bots,
+    );
+    state.position_history.tick += 50;
+    state.players.get_mut(&crew).unwrap().dead = true;
+    tally.note_events(
+      &[
+        GameEvent::PlayerKilled {
+          killer: impostor,
+          victim: crew,
+        },
+        GameEvent::GameWon {
+          team: Team::Impostors,
+        },
+      ],
+      &state,
+      &bots,
+    );
+
+    let summary = tally.take_summary().unwrap();
+    assert_eq!(summary.winner, Some(Team::Impostors));
+    assert_eq!(summary.duration, TICK * 150);
+    assert_eq!(
+      summary.ejections,
+      vec![Ejection {
+        uuid: ejected,
+        after: TICK * 100
+      }]
+    );
+    // The impostor won, so they're first.
+    let winner = &summary.players[0];
+    assert_eq!(winner.uuid, impostor);
+    assert!(winner.won && winner.impostor && winner.survived);
+    assert_eq!((winner.kills, winner.votes_cast), (1, 1));
+    let crew = summary.players.iter().find(|p| p.uuid == crew).unwrap();
+    assert!(!crew.won && !crew.survived);
+    assert_eq!((crew.tasks_done, crew.tasks_total), (1, 2));
+    assert_eq!(crew.votes_cast, 1);
+    assert_eq!(tally.take_summary(), None);
+  }
+}
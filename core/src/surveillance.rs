@@ -0,0 +1,174 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+// Shows how many people are in each room, but not who they are. Only to
+// whoever's standing at it.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct AdminConsole {
+  pub position: Position,
+  // How close a player must be to use it.
+  pub reach: f64,
+}
+
+// Shows what all the cameras on the map can see, to whoever's standing at
+// it.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct SecurityConsole {
+  pub position: Position,
+  pub reach: f64,
+}
+
+// A camera on the wall, looking out over a cone of the map.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct Camera {
+  pub position: Position,
+  // Which way the middle of the cone points. Doesn't need to be normalized.
+  pub facing: Velocity,
+  // How wide the cone is, in radians.
+  pub spread: f64,
+  pub range: f64,
+}
+
+impl Camera {
+  fn covers(&self, position: Position) -> bool {
+    let offset = position.sub(self.position);
+    let distance = offset.magnitude();
+    if distance > self.range {
+      return false;
+    }
+    if distance == 0.0 {
+      return true;
+    }
+    let cos = offset.dot_product(&self.facing) / (distance * self.facing.magnitude());
+    cos >= (self.spread / 2.0).cos()
+  }
+}
+
+// What a player standing at a console gets to see. Anyone at a console is
+// stuck there while they watch, so it's information at the price of not
+// getting anything else done.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Surveillance {
+  // How many people are in each of Map::regions, in order.
+  Admin { headcounts: Vec<usize> },
+  // Everyone who's in front of a camera right now.
+  Security { spotted: Vec<(UUID, Position)> },
+}
+
+impl GameState {
+  // What the given player can see from the console they're standing at, if
+  // they're standing at one.
+  pub fn surveillance(&self, viewer: UUID) -> Option<Surveillance> {
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return None;
+    }
+    // The consoles go dark along with everything else.
+    if self.sabotage.is_active(Sabotage::Comms) {
+      return None;
+    }
+    let viewer = self.players.get(&viewer)?;
+    if viewer.in_vent.is_some() {
+      return None;
+    }
+    let at = |position: Position, reach: f64| position.distance(&viewer.position) <= reach;
+    // Anyone hiding in the vents isn't anywhere the consoles can tell.
+    let out_and_about = || {
+      self
+        .players
+        .values()
+        .filter(|p| p.in_vent.is_none() && p.is_visible_to(Some(viewer)))
+    };
+    if self
+      .map
+      .admin_consoles
+      .iter()
+      .any(|c| at(c.position, c.reach))
+    {
+      let mut headcounts = vec![0; self.map.regions.len()];
+      for player in out_and_about() {
+        if let Some(index) = self
+          .map
+          .regions
+          .iter()
+          .position(|r| r.contains(player.position))
+        {
+          headcounts[index] += 1;
+        }
+      }
+      return Some(Surveillance::Admin { headcounts });
+    }
+    if self
+      .map
+      .security_consoles
+      .iter()
+      .any(|c| at(c.position, c.reach))
+    {
+      let spotted = out_and_about()
+        .filter(|p| {
+          self
+            .map
+            .cameras
+            .iter()
+            .any(|c| c.covers(p.position) && self.line_is_clear(c.position, p.position))
+        })
+        .map(|p| (p.uuid, p.position))
+        .collect();
+      return Some(Surveillance::Security { spotted });
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use std::borrow::Cow;
+
+  #[test]
+  fn test_consoles_only_work_while_youre_at_them() {
+    let mut map = MapRegistry::get(MapId::Lobby).unwrap();
+    map.admin_consoles = Cow::Owned(vec![AdminConsole {
+      position: Position { x: 100.0, y: 300.0 },
+      reach: 30.0,
+    }]);
+    map.security_consoles = Cow::Owned(vec![SecurityConsole {
+      position: Position { x: 700.0, y: 300.0 },
+      reach: 30.0,
+    }]);
+    // Looking left along the top of the room.
+    map.cameras = Cow::Owned(vec![Camera {
+      position: Position { x: 790.0, y: 100.0 },
+      facing: Velocity { dx: -1.0, dy: 0.0 },
+      spread: 1.0,
+      range: 1000.0,
+    }]);
+    let (mut state, uuids) = game_at(&[
+      (Role::Crewmate, Position { x: 400.0, y: 500.0 }),
+      (Role::Crewmate, Position { x: 300.0, y: 100.0 }),
+      (Role::Crewmate, Position { x: 300.0, y: 500.0 }),
+    ]);
+    state.set_map(map);
+    let (watcher, in_view) = (uuids[0], uuids[1]);
+    assert_eq!(state.surveillance(watcher), None);
+
+    state.players.get_mut(&watcher).unwrap().position = Position { x: 110.0, y: 300.0 };
+    assert_eq!(
+      state.surveillance(watcher),
+      Some(Surveillance::Admin {
+        headcounts: vec![3]
+      })
+    );
+
+    state.players.get_mut(&watcher).unwrap().position = Position { x: 690.0, y: 300.0 };
+    assert_eq!(
+      state.surveillance(watcher),
+      Some(Surveillance::Security {
+        spotted: vec![(in_view, Position { x: 300.0, y: 100.0 })]
+      })
+    );
+
+    state.status = GameStatus::Lobby;
+    assert_eq!(state.surveillance(watcher), None);
+  }
+}
@@ -0,0 +1,230 @@
+use crate::*;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum TaskKind {
+  // Done in one go.
+  Short,
+  // Has to be worked on several times at the same spot.
+  Long { steps: usize },
+  // Has to be done at each of these spots in turn, like fixing the wires at
+  // several panels. The first stage is the task's starting position.
+  MultiStage { stages: Vec<Position> },
+  // Start it, then come back once it's had time to finish.
+  Timed { wait: Duration },
+}
+
+// How likely each kind of long task is when handing out tasks.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct TaskMix {
+  pub long: u32,
+  pub multi_stage: u32,
+  pub timed: u32,
+}
+
+impl Default for TaskMix {
+  fn default() -> Self {
+    TaskMix {
+      long: 1,
+      multi_stage: 1,
+      timed: 1,
+    }
+  }
+}
+
+// When the crew gets to see how far along they are with their tasks.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum TaskBarMode {
+  Always,
+  // The bar only catches up during meetings.
+  MeetingsOnly,
+  Never,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Task {
+  // Where the task (or its current stage) is done.
+  pub position: Position,
+  pub finished: bool,
+  pub kind: TaskKind,
+  // How many times this task has been worked on so far.
+  pub progress: usize,
+  // For timed tasks, how long until the task can be finished.
+  pub time_remaining: Duration,
+  // Impostors get fake tasks to pretend to work on. They can't be finished,
+  // and don't count towards the crew's progress.
+  pub fake: bool,
+  // The puzzle to solve each time the task's worked on, if any.
+  #[serde(default)]
+  pub minigame: Option<TaskMinigame>,
+}
+
+impl Task {
+  pub fn new(kind: TaskKind, position: Position) -> Self {
+    Task {
+      position,
+      finished: false,
+      kind,
+      progress: 0,
+      time_remaining: Duration::from_secs(0),
+      fake: false,
+      minigame: None,
+    }
+  }
+
+  pub fn into_fake(self) -> Self {
+    Task { fake: true, ..self }
+  }
+
+  // A timed task that's been started but isn't done cooking.
+  pub fn is_waiting(&self) -> bool {
+    self.time_remaining > Duration::from_secs(0)
+  }
+
+  // Whether there's any work that can be done on the task right now.
+  pub fn check_workable(&self) -> Result<(), GameError> {
+    if self.fake {
+      return Err(GameError::FakeTask);
+    }
+    if self.finished {
+      return Err(GameError::TaskAlreadyFinished);
+    }
+    if self.is_waiting() {
+      return Err(GameError::OnCooldown {
+        action: Action::FinishTask,
+        remaining: self.time_remaining,
+      });
+    }
+    Ok(())
+  }
+
+  // Works on the task once, finishing it if this was the last bit of work.
+  pub fn activate(&mut self) -> Result<(), GameError> {
+    self.check_workable()?;
+    self.progress += 1;
+    match &self.kind {
+      TaskKind::Short => self.finished = true,
+      TaskKind::Long { steps } => self.finished = self.progress >= *steps,
+      TaskKind::MultiStage { stages } => match stages.get(self.progress) {
+        Some(next) => self.position = *next,
+        None => self.finished = true,
+      },
+      TaskKind::Timed { wait } => {
+        if self.progress == 1 {
+          self.time_remaining = *wait;
+        } else {
+          self.finished = true;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  pub(crate) fn progress_time(&mut self, elapsed: Duration) {
+    self.time_remaining = self
+      .time_remaining
+      .checked_sub(elapsed)
+      .unwrap_or_else(|| Duration::from_secs(0));
+  }
+}
+
+impl GameState {
+  // The fraction of all crew tasks that have been finished, from 0 to 1.
+  pub fn task_progress(&self) -> f64 {
+    let crew_tasks = self
+      .players
+      .values()
+      .filter(|p| !p.impostor)
+      .flat_map(|p| p.tasks.iter())
+      .filter(|t| !t.fake);
+    let (finished, total) = crew_tasks.fold((0, 0), |(finished, total), t| {
+      (finished + t.finished as usize, total + 1)
+    });
+    if total == 0 {
+      return 0.0;
+    }
+    finished as f64 / total as f64
+  }
+
+  // The task progress to tell clients about right now, if any.
+  pub fn task_bar(&self) -> Option<f64> {
+    let in_meeting = match self.status {
+      GameStatus::Playing(PlayState::Night) => false,
+      GameStatus::Playing(_) => true,
+      _ => return None,
+    };
+    // Nobody's hearing how the others are getting on while comms are down.
+    if self.sabotage.is_active(Sabotage::Comms) {
+      return None;
+    }
+    match self.settings.task_bar_mode {
+      TaskBarMode::Always => Some(self.task_progress()),
+      TaskBarMode::MeetingsOnly if in_meeting => Some(self.task_progress()),
+      TaskBarMode::MeetingsOnly | TaskBarMode::Never => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_task_kind_completion_rules() {
+    let here = Position { x: 0.0, y: 0.0 };
+    let there = Position { x: 100.0, y: 0.0 };
+
+    let mut short = Task::new(TaskKind::Short, here);
+    short.activate().unwrap();
+    assert!(short.finished);
+    assert!(short.activate().is_err());
+
+    let mut long = Task::new(TaskKind::Long { steps: 3 }, here);
+    long.activate().unwrap();
+    long.activate().unwrap();
+    assert!(!long.finished);
+    long.activate().unwrap();
+    assert!(long.finished);
+
+    let stages = vec![here, there];
+    let mut wires = Task::new(TaskKind::MultiStage { stages }, here);
+    wires.activate().unwrap();
+    assert!(!wires.finished);
+    assert_eq!(wires.position, there);
+    wires.activate().unwrap();
+    assert!(wires.finished);
+
+    let wait = Duration::from_secs(10);
+    let mut timed = Task::new(TaskKind::Timed { wait }, here);
+    timed.activate().unwrap();
+    assert!(timed.activate().is_err());
+    timed.progress_time(wait);
+    timed.activate().unwrap();
+    assert!(timed.finished);
+  }
+
+  #[test]
+  fn test_task_bar_modes() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate]);
+    let uuid = uuids[0];
+    let player = state.players.get_mut(&uuid).unwrap();
+    let position = player.position;
+    player.tasks = vec![
+      Task::new(TaskKind::Short, position),
+      Task::new(TaskKind::Short, position),
+    ];
+    player.tasks[0].finished = true;
+    assert_eq!(state.task_bar(), Some(0.5));
+
+    state.settings.task_bar_mode = TaskBarMode::MeetingsOnly;
+    assert_eq!(state.task_bar(), None);
+    state.start_meeting(MeetingReason::EmergencyButton { caller: uuid });
+    assert_eq!(state.task_bar(), Some(0.5));
+
+    state.settings.task_bar_mode = TaskBarMode::Never;
+    assert_eq!(state.task_bar(), None);
+  }
+}
@@ -0,0 +1,27 @@
+#![cfg(test)]
+use crate::*;
+
+// A game partway through its first night, with a player for each of the given
+// roles, all standing in the same spot. Their uuids come back in the same
+// order, and they wear the colors from Color::all() in that order too.
+pub fn game_with(roles: &[Role]) -> (GameState, Vec<UUID>) {
+  let position = Position { x: 100.0, y: 100.0 };
+  let players: Vec<(Role, Position)> = roles.iter().map(|role| (*role, position)).collect();
+  game_at(&players)
+}
+
+// The same, but with everyone starting out where they're told.
+pub fn game_at(players: &[(Role, Position)]) -> (GameState, Vec<UUID>) {
+  let mut state = GameState::new();
+  let mut uuids = vec![];
+  for (i, (role, position)) in players.iter().enumerate() {
+    let uuid = UUID::random();
+    let mut player = Player::new(uuid, "Test Player".to_string(), Color::all()[i], *position);
+    player.role = *role;
+    player.impostor = *role == Role::Impostor;
+    state.players.insert(uuid, player);
+    uuids.push(uuid);
+  }
+  state.status = GameStatus::Playing(PlayState::Night);
+  (state, uuids)
+}
@@ -96,30 +96,30 @@ impl TestEnvironment {
           messages_local.clear();
         }
         for message in messages {
-          console_log!("Passing {} to server from {}", message.kind(), uuid);
+          tracing::debug!("Passing {} to server from {}", message.kind(), uuid);
           self.game_server.handle_message(*uuid, message)?;
         }
       }
       let mut queue = self.server_to_client_queue.lock().unwrap();
-      console_log!(
+      tracing::debug!(
         "Processing {} players in server_to_client_queue",
         queue.len()
       );
       for (uuid, messages) in queue.iter_mut() {
         if messages.is_empty() {
-          console_log!("Messages to {} are empty", uuid);
+          tracing::debug!("Messages to {} are empty", uuid);
           continue;
         }
         keep_going = true;
         let messages_local = messages.clone();
         for message in messages_local {
-          console_log!("Passing {} from server to {}", message.kind(), uuid);
+          tracing::debug!("Passing {} from server to {}", message.kind(), uuid);
           let player = self.players.get_mut(uuid).unwrap();
           player.handle_msg(message)?;
         }
         messages.clear();
       }
-      console_log!(
+      tracing::debug!(
         "Finished one loop of dispatch_messages. Keep going? {}",
         keep_going
       );
@@ -133,19 +133,19 @@ impl TestEnvironment {
   ) -> Result<GameState, Box<dyn Error>> {
     let server_state = self.game_server.state.clone();
     assert_eq!(self.players.len(), expected_num_players);
-    for (_, game_as_player) in self.players.iter() {
-      // Will eventually need to customize this more, because players will
-      // only know a subset.
-      assert_eq!(&server_state, &game_as_player.state);
+    for (uuid, game_as_player) in self.players.iter() {
+      // Players only know what they've been told.
+      assert_eq!(&server_state.view_for(*uuid), &game_as_player.state);
     }
     Ok(server_state)
   }
 
-  fn time_passes(&mut self, elapsed: Duration) {
-    self.game_server.state.simulate(elapsed);
+  fn time_passes(&mut self, elapsed: Duration) -> Result<(), Box<dyn Error>> {
+    self.game_server.simulate(elapsed)?;
     for (_, player) in self.players.iter_mut() {
       player.state.simulate(elapsed);
     }
+    Ok(())
   }
 
   fn create_and_connect_player(&mut self) -> Result<UUID, Box<dyn Error>> {
@@ -157,7 +157,8 @@ impl TestEnvironment {
         version: get_version_sha().to_string(),
         details: JoinRequest::JoinAsPlayer {
           name: "Test Player".to_string(),
-          preferred_color: Color::random(),
+          preferred_color: Color::random(&mut rand::thread_rng()),
+          cosmetics: Cosmetics::default(),
         },
       },
     )?;
@@ -172,13 +173,13 @@ struct TestBroadcaster {
 }
 impl Broadcaster for TestBroadcaster {
   fn broadcast(&self, message: &ServerToClientMessage) -> Result<(), Box<dyn Error>> {
-    console_log!("Broadcasting {} from server", message.kind());
+    tracing::debug!("Broadcasting {} from server", message.kind());
     let mut players = self.players.lock().unwrap();
     for (uuid, messages) in players.iter_mut() {
-      console_log!("Sending to {}", uuid);
+      tracing::debug!("Sending to {}", uuid);
       messages.push(message.clone());
     }
-    console_log!("Broadcast complete");
+    tracing::debug!("Broadcast complete");
     Ok(())
   }
   fn send_to_player(
@@ -186,7 +187,7 @@ impl Broadcaster for TestBroadcaster {
     uuid: &UUID,
     message: &ServerToClientMessage,
   ) -> Result<(), Box<dyn Error>> {
-    console_log!("Sending {} to player {} from server", message.kind(), uuid);
+    tracing::debug!("Sending {} to player {} from server", message.kind(), uuid);
     let mut players = self.players.lock().unwrap();
     if let Some(messages) = players.get_mut(uuid) {
       messages.push(message.clone());
@@ -212,7 +213,7 @@ fn test_connection_and_disconnection() -> Result<(), Box<dyn Error>> {
   env.expect_everyone_agrees_on_game_state(0)?;
 
   // P1 connects
-  env.create_and_connect_player()?;
+  let player1_id = env.create_and_connect_player()?;
 
   // P2 connects
   let player2_id = env.create_and_connect_player()?;
@@ -228,6 +229,14 @@ fn test_connection_and_disconnection() -> Result<(), Box<dyn Error>> {
   let game = env.expect_everyone_agrees_on_game_state(2)?;
   assert!(game.players.get(&player2_id).is_none());
 
+  // P1 was the host, so P3 takes over when they leave
+  assert_eq!(game.host, Some(player1_id));
+  env.remove_player(player1_id)?;
+  env.dispatch_messages()?;
+  let game = env.expect_everyone_agrees_on_game_state(1)?;
+  assert!(game.host.is_some());
+  assert_ne!(game.host, Some(player1_id));
+
   Ok(())
 }
 
@@ -267,9 +276,10 @@ fn test_movement() -> Result<(), Box<dyn Error>> {
   })?;
   let p3_position = p3_client.local_player().unwrap().position;
 
-  // Let four time ticks pass
+  // Let four time ticks pass, and hear from the server about where everyone went.
+  env.dispatch_messages()?;
+  env.time_passes(Duration::from_millis(64))?;
   env.dispatch_messages()?;
-  env.time_passes(Duration::from_millis(64));
 
   let player_positions: HashMap<UUID, Position> = env
     .game_server
@@ -295,3 +305,959 @@ fn test_movement() -> Result<(), Box<dyn Error>> {
 
   Ok(())
 }
+
+#[test]
+fn test_recorded_games_play_back_exactly() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let host = env.create_and_connect_player()?;
+  let others = [
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+  ];
+  for uuid in others.iter() {
+    env
+      .players
+      .get_mut(uuid)
+      .unwrap()
+      .socket
+      .send(&ClientToServerMessage::Ready { ready: true })?;
+  }
+  env.dispatch_messages()?;
+  env.time_passes(TICK * 3)?;
+  let host_client = env.players.get_mut(&host).unwrap();
+  host_client
+    .socket
+    .send(&ClientToServerMessage::StartGame())?;
+  env.dispatch_messages()?;
+
+  for player in env.players.values_mut() {
+    player.take_input(InputState {
+      up: true,
+      right: true,
+      ..InputState::default()
+    })?;
+  }
+  env.dispatch_messages()?;
+  env.time_passes(TICK * 6)?;
+  env.remove_player(others[1])?;
+  env.dispatch_messages()?;
+  env.time_passes(TICK * 2)?;
+
+  let recording = env.game_server.recording().unwrap();
+  let recording = RecordedGame::from_bytes(&recording.to_bytes()?)?;
+  let end = tick_time(env.game_server.state.position_history.tick);
+  let mut playback = PlaybackServer::new(recording);
+  let mut viewer = GameAsPlayer::new(UUID::random(), Box::new(PlaybackTx {}));
+  playback.skip_to(end, &mut viewer)?;
+  assert_eq!(playback.state(), &env.game_server.state);
+
+  // Scrubbing back to the start and playing forward again ends up in the
+  // same place.
+  playback.scrub_to(0.0, &mut viewer)?;
+  assert_eq!(playback.current_time(), Duration::from_secs(0));
+  playback.skip_to(end, &mut viewer)?;
+  assert_eq!(playback.state(), &env.game_server.state);
+  Ok(())
+}
+
+#[test]
+fn test_players_that_drift_get_resynced() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let player1_id = env.create_and_connect_player()?;
+  let player2_id = env.create_and_connect_player()?;
+
+  // Something's gone wrong on P1's end, and they tell the server as much.
+  let p1_client = env.players.get_mut(&player1_id).unwrap();
+  let tick = p1_client.state.position_history.tick;
+  p1_client
+    .state
+    .players
+    .get_mut(&player1_id)
+    .unwrap()
+    .position
+    .x += 100.0;
+  p1_client.state.players.get_mut(&player2_id).unwrap().ready = true;
+  let checksum = p1_client.state.checksum();
+  p1_client
+    .socket
+    .send(&ClientToServerMessage::Checksum { tick, checksum })?;
+  env.dispatch_messages()?;
+
+  env.expect_everyone_agrees_on_game_state(2)?;
+  Ok(())
+}
+
+#[test]
+fn test_quiet_players_get_disconnected() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let player1_id = env.create_and_connect_player()?;
+  let player2_id = env.create_and_connect_player()?;
+
+  // P1 gets pinged and answers.
+  env.time_passes(TICK)?;
+  env.dispatch_messages()?;
+  let game = env.expect_everyone_agrees_on_game_state(2)?;
+  assert!(game.players[&player1_id].latency.is_some());
+
+  // P2's connection silently drops, and the server never hears that it did.
+  env.player_queue.remove(&player2_id);
+  env
+    .server_to_client_queue
+    .lock()
+    .unwrap()
+    .remove(&player2_id);
+  env.players.remove(&player2_id);
+  let mut waited = Duration::from_secs(0);
+  while waited <= Heartbeats::IDLE_TIMEOUT {
+    env.time_passes(Heartbeats::PING_INTERVAL)?;
+    env.dispatch_messages()?;
+    waited += Heartbeats::PING_INTERVAL;
+  }
+  let game = env.expect_everyone_agrees_on_game_state(1)?;
+  assert!(game.players.contains_key(&player1_id));
+  assert!(!game.players.contains_key(&player2_id));
+  Ok(())
+}
+
+#[test]
+fn test_saved_games_pick_up_where_they_left_off() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let host = env.create_and_connect_player()?;
+  let others = [
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+  ];
+  for uuid in others.iter() {
+    env
+      .players
+      .get_mut(uuid)
+      .unwrap()
+      .socket
+      .send(&ClientToServerMessage::Ready { ready: true })?;
+  }
+  env.dispatch_messages()?;
+  let host_client = env.players.get_mut(&host).unwrap();
+  host_client
+    .socket
+    .send(&ClientToServerMessage::StartGame())?;
+  env.dispatch_messages()?;
+  for player in env.players.values_mut() {
+    player.take_input(InputState {
+      down: true,
+      left: true,
+      ..InputState::default()
+    })?;
+  }
+  env.dispatch_messages()?;
+  env.time_passes(TICK * 10)?;
+
+  // The server goes down for a deploy, taking everyone's connections with it.
+  let saved = env.game_server.save().unwrap();
+  let saved = SavedGame::from_bytes(&saved.to_bytes()?)?;
+  for uuid in [host, others[0], others[1], others[2]].iter() {
+    env.server_to_client_queue.lock().unwrap().remove(uuid);
+    env.game_server.disconnected(*uuid)?;
+  }
+  let expected = env.game_server.state.clone();
+  let restored = GameServer::restore(
+    saved,
+    Box::new(TestBroadcaster {
+      players: env.server_to_client_queue.clone(),
+    }),
+  )?;
+  assert_eq!(restored.state, expected);
+  assert!(restored.state.players.values().all(|p| p.disconnected));
+  env.game_server = restored;
+
+  // Everyone can get back in with the tokens they had before.
+  let token = env.players[&host].rejoin_token.unwrap();
+  let bad_rejoin = ClientToServerMessage::Rejoin {
+    uuid: others[0],
+    token,
+  };
+  assert!(env
+    .game_server
+    .handle_message(others[0], bad_rejoin)
+    .is_err());
+  let rejoin = ClientToServerMessage::Rejoin { uuid: host, token };
+  env.game_server.handle_message(host, rejoin)?;
+  assert!(!env.game_server.state.players[&host].disconnected);
+  Ok(())
+}
+
+#[test]
+fn test_players_are_told_why_they_cant_do_things() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let host = env.create_and_connect_player()?;
+  let guest = env.create_and_connect_player()?;
+
+  let start = ClientToServerMessage::StartGame();
+  let error = env.game_server.handle_message(guest, start).unwrap_err();
+  assert_eq!(
+    error.downcast_ref::<GameError>(),
+    Some(&GameError::NotHost {
+      action: Action::StartGame
+    })
+  );
+  let start = ClientToServerMessage::StartGame();
+  let error = env.game_server.handle_message(host, start).unwrap_err();
+  assert_eq!(
+    error.downcast_ref::<GameError>(),
+    Some(&GameError::NotEnoughPlayers { needed: 4, have: 2 })
+  );
+  env.dispatch_messages()?;
+  let told = &env.players[&guest].displayed_messages;
+  assert_eq!(
+    told.last().map(|m| &m.message),
+    Some(&Message::PlainString(
+      "Only the host can start the game".to_string()
+    ))
+  );
+  Ok(())
+}
+
+#[test]
+fn test_players_only_hear_about_what_they_should() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let host = env.create_and_connect_player()?;
+  let others = [
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+  ];
+  for uuid in others.iter() {
+    env
+      .players
+      .get_mut(uuid)
+      .unwrap()
+      .socket
+      .send(&ClientToServerMessage::Ready { ready: true })?;
+  }
+  env.dispatch_messages()?;
+  env
+    .players
+    .get_mut(&host)
+    .unwrap()
+    .socket
+    .send(&ClientToServerMessage::StartGame())?;
+  env.dispatch_messages()?;
+  for player in env.players.values_mut() {
+    assert_eq!(player.take_events(), vec![GameEvent::GameStarted]);
+  }
+
+  let state = &mut env.game_server.state;
+  let killer = *state
+    .players
+    .keys()
+    .find(|u| state.players[u].impostor)
+    .unwrap();
+  let victim = *state
+    .players
+    .keys()
+    .find(|u| !state.players[u].impostor)
+    .unwrap();
+  let seen_at = state.position_history.tick;
+  // Skip ahead to the impostor catching someone alone.
+  let killer_player = state.players.get_mut(&killer).unwrap();
+  killer_player.kill_cooldown = Duration::from_secs(0);
+  let position = killer_player.position;
+  state.players.get_mut(&victim).unwrap().position = position;
+  env
+    .game_server
+    .handle_message(killer, ClientToServerMessage::Kill { victim, seen_at })?;
+  env.dispatch_messages()?;
+  for (uuid, player) in env.players.iter_mut() {
+    let events = player.take_events();
+    if *uuid == killer || *uuid == victim {
+      assert_eq!(events, vec![GameEvent::PlayerKilled { killer, victim }]);
+    } else {
+      assert_eq!(events, vec![]);
+    }
+  }
+  Ok(())
+}
+
+#[test]
+fn test_ghosts_chat_amongst_themselves() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let alive = env.create_and_connect_player()?;
+  let ghost = env.create_and_connect_player()?;
+  let chat = |text: &str| ClientToServerMessage::Chat {
+    text: text.to_string(),
+  };
+  env.game_server.handle_message(alive, chat("hello"))?;
+  env.dispatch_messages()?;
+  for player in env.players.values() {
+    assert_eq!(player.chat.len(), 1);
+  }
+
+  env.game_server.state.status = GameStatus::Playing(PlayState::Night);
+  env.game_server.state.players.get_mut(&ghost).unwrap().dead = true;
+  assert!(env.game_server.handle_message(alive, chat("shh")).is_err());
+  env.game_server.handle_message(ghost, chat("boo"))?;
+  env.dispatch_messages()?;
+  assert_eq!(env.players[&alive].chat.len(), 1);
+  let heard = &env.players[&ghost].chat;
+  assert_eq!(heard.len(), 2);
+  assert_eq!(heard[1].channel, ChatChannel::Ghosts);
+  Ok(())
+}
+
+#[test]
+fn test_the_host_kicks_people_out() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let host = env.create_and_connect_player()?;
+  let guest = env.create_and_connect_player()?;
+  let troublemaker = env.create_and_connect_player()?;
+
+  assert!(env
+    .game_server
+    .kick(guest, troublemaker, KickReason::Kicked)
+    .is_err());
+  let connection = env
+    .game_server
+    .kick(host, troublemaker, KickReason::Banned)?;
+  assert_eq!(connection, troublemaker);
+  env.dispatch_messages()?;
+  assert_eq!(
+    env.players[&troublemaker]
+      .displayed_messages
+      .last()
+      .map(|m| &m.message),
+    Some(&Message::PlainString(KickReason::Banned.to_string()))
+  );
+  assert_eq!(
+    env.players[&guest]
+      .displayed_messages
+      .last()
+      .map(|m| &m.message),
+    Some(&Message::PlainString(
+      "Only the host can kick players out".to_string()
+    ))
+  );
+
+  // The room hangs up on them, which takes them out of the game.
+  env.remove_player(troublemaker)?;
+  env.dispatch_messages()?;
+  let game = env.expect_everyone_agrees_on_game_state(2)?;
+  assert!(!game.players.contains_key(&troublemaker));
+  Ok(())
+}
+
+#[test]
+fn test_the_lobby_votes_people_out() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let host = env.create_and_connect_player()?;
+  let voters = [
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+  ];
+  env.create_and_connect_player()?;
+  // It takes two of the other three to get rid of the host.
+  for voter in voters.iter() {
+    assert_eq!(env.players[&host].state.host, Some(host));
+    env.players.get_mut(voter).unwrap().vote_kick(host)?;
+    env.dispatch_messages()?;
+  }
+  let voted_out = Message::PlainString(KickReason::VotedOut.to_string());
+  assert!(env.players[&host]
+    .displayed_messages
+    .iter()
+    .any(|m| m.message == voted_out));
+  env.remove_player(host)?;
+  env.dispatch_messages()?;
+  let game = env.expect_everyone_agrees_on_game_state(3)?;
+  assert!(!game.players.contains_key(&host));
+  assert!(game.host.is_some());
+
+  // And it all happens again on playback.
+  env.time_passes(TICK)?;
+  let recording = env.game_server.recording().unwrap();
+  let end = tick_time(env.game_server.state.position_history.tick);
+  let mut playback = PlaybackServer::new(recording.clone());
+  let mut viewer = GameAsPlayer::new(UUID::random(), Box::new(PlaybackTx {}));
+  playback.skip_to(end, &mut viewer)?;
+  assert_eq!(playback.state().players, env.game_server.state.players);
+  Ok(())
+}
+
+#[test]
+fn test_players_cant_pass_for_each_other() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let broadcaster = TestBroadcaster {
+    players: env.server_to_client_queue.clone(),
+  };
+  env.game_server =
+    GameServer::new(Box::new(broadcaster), true).with_name_filter(|name| !name.contains("heck"));
+  let first = env.create_and_connect_player()?;
+  let second = env.create_and_connect_player()?;
+  let players = &env.game_server.state.players;
+  assert_eq!(players[&first].name, "Test Player");
+  assert_eq!(players[&second].name, "Test Player #2");
+
+  let rude = env.create_player();
+  let join = ClientToServerMessage::Join {
+    version: get_version_sha().to_string(),
+    details: JoinRequest::JoinAsPlayer {
+      name: "what the heck".to_string(),
+      preferred_color: Color::Red,
+      cosmetics: Cosmetics::default(),
+    },
+  };
+  assert!(env.game_server.handle_message(rude, join).is_err());
+  assert!(!env.game_server.state.players.contains_key(&rude));
+  env.dispatch_messages()?;
+  assert_eq!(
+    env.players[&rude]
+      .displayed_messages
+      .last()
+      .map(|m| &m.message),
+    Some(&Message::PlainString(
+      "Couldn't join: That name isn't allowed".to_string()
+    ))
+  );
+  Ok(())
+}
+
+#[test]
+fn test_players_pick_colors_no_one_else_has() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let first = env.create_and_connect_player()?;
+  let second = env.create_and_connect_player()?;
+  let taken = env.game_server.state.players[&first].color;
+  let player = env.players.get_mut(&second).unwrap();
+  assert!(player.request_color(taken).is_err());
+  let wanted = player.next_color().unwrap();
+  player.request_color(wanted)?;
+  env.dispatch_messages()?;
+  env.expect_everyone_agrees_on_game_state(2)?;
+  assert_eq!(env.game_server.state.players[&second].color, wanted);
+
+  // Someone else could have grabbed it since our last snapshot.
+  let wanted = env.players[&second].next_color().unwrap();
+  env.game_server.state.players.get_mut(&first).unwrap().color = wanted;
+  let request = ClientToServerMessage::RequestColor(wanted);
+  assert!(env.game_server.handle_message(second, request).is_err());
+  env.dispatch_messages()?;
+  assert_eq!(
+    env.players[&second]
+      .displayed_messages
+      .last()
+      .map(|m| &m.message),
+    Some(&Message::PlainString(
+      GameError::ColorTaken { color: wanted }.to_string()
+    ))
+  );
+  env.game_server.broadcast_snapshot()?;
+  env.dispatch_messages()?;
+  let game = env.expect_everyone_agrees_on_game_state(2)?;
+  assert_ne!(game.players[&second].color, wanted);
+  Ok(())
+}
+
+#[test]
+fn test_the_host_takes_everyone_back_to_the_lobby() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let host = env.create_and_connect_player()?;
+  let others = [
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+  ];
+  let get_ready = |env: &mut TestEnvironment| -> Result<(), Box<dyn Error>> {
+    for uuid in others.iter() {
+      env
+        .players
+        .get_mut(uuid)
+        .unwrap()
+        .socket
+        .send(&ClientToServerMessage::Ready { ready: true })?;
+    }
+    env.dispatch_messages()?;
+    env
+      .players
+      .get_mut(&host)
+      .unwrap()
+      .socket
+      .send(&ClientToServerMessage::StartGame())?;
+    env.dispatch_messages()
+  };
+  get_ready(&mut env)?;
+  let colors: Vec<Color> = env
+    .game_server
+    .state
+    .players
+    .values()
+    .map(|p| p.color)
+    .collect();
+
+  // The crew finish everything off.
+  for player in env.game_server.state.players.values_mut() {
+    for task in player.tasks.iter_mut() {
+      task.finished = true;
+    }
+  }
+  env.game_server.state.check_for_victories();
+  env.time_passes(TICK)?;
+  assert_eq!(env.game_server.state.status, GameStatus::Won(Team::Crew));
+  assert!(!env.game_server.simulate(TICK)?);
+  env.game_server.broadcast_snapshot()?;
+  env.dispatch_messages()?;
+
+  let back = ClientToServerMessage::ReturnToLobby();
+  assert!(env.game_server.handle_message(others[0], back).is_err());
+  env.players.get_mut(&host).unwrap().return_to_lobby()?;
+  env.dispatch_messages()?;
+  let game = env.expect_everyone_agrees_on_game_state(4)?;
+  assert_eq!(game.status, GameStatus::Lobby);
+  assert_eq!(
+    game.players.values().map(|p| p.color).collect::<Vec<_>>(),
+    colors
+  );
+  for player in game.players.values() {
+    assert!(player.tasks.is_empty() && !player.impostor && !player.dead && !player.ready);
+  }
+
+  // And off they go again.
+  get_ready(&mut env)?;
+  assert_eq!(
+    env.game_server.state.status,
+    GameStatus::Playing(PlayState::Night)
+  );
+  env.expect_everyone_agrees_on_game_state(4)?;
+  Ok(())
+}
+
+#[test]
+fn test_finished_rooms_close_if_no_one_goes_back() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  env.create_and_connect_player()?;
+  env.game_server.state.status = GameStatus::Won(Team::Crew);
+  // Keeping up with the pings as we go, so no one times out.
+  let mut waited = Duration::from_secs(0);
+  while waited < FINISHED_GAME_GRACE {
+    assert!(!env.game_server.simulate(Duration::from_secs(1))?);
+    env.dispatch_messages()?;
+    waited += Duration::from_secs(1);
+  }
+  assert_eq!(env.game_server.state.status, GameStatus::Won(Team::Crew));
+  assert!(env.game_server.simulate(TICK)?);
+  Ok(())
+}
+
+#[test]
+fn test_the_host_changes_the_settings_from_the_lobby() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let host = env.create_and_connect_player()?;
+  let guest = env.create_and_connect_player()?;
+  let patch = SettingsPatch {
+    speed: Some(3.0),
+    voting_time: Some(Duration::from_secs(30)),
+    map_id: Some(MapId::Station),
+    ..SettingsPatch::default()
+  };
+  assert!(env
+    .players
+    .get_mut(&guest)
+    .unwrap()
+    .update_settings(patch.clone())
+    .is_err());
+  env.players.get_mut(&host).unwrap().update_settings(patch)?;
+  env.dispatch_messages()?;
+  let game = env.expect_everyone_agrees_on_game_state(2)?;
+  assert_eq!(game.settings.speed, 3.0);
+  assert_eq!(game.settings.voting_time, Duration::from_secs(30));
+  assert_eq!(game.map.id, MapId::Station);
+
+  // Out of bounds, so nothing changes.
+  let too_fast = ClientToServerMessage::UpdateSettings(Box::new(SettingsPatch {
+    speed: Some(0.0),
+    ..SettingsPatch::default()
+  }));
+  assert!(env.game_server.handle_message(host, too_fast).is_err());
+  assert_eq!(env.game_server.state.settings.speed, 3.0);
+
+  // Late arrivals hear how it's set up too.
+  env.create_and_connect_player()?;
+  assert_eq!(env.game_server.state.settings.speed, 3.0);
+  Ok(())
+}
+
+#[test]
+fn test_hide_and_seek() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  env
+    .game_server
+    .set_settings(SettingsPreset::HideAndSeek.settings())?;
+  let host = env.create_and_connect_player()?;
+  let others = [
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+    env.create_and_connect_player()?,
+  ];
+  for uuid in others.iter() {
+    env
+      .players
+      .get_mut(uuid)
+      .unwrap()
+      .socket
+      .send(&ClientToServerMessage::Ready { ready: true })?;
+  }
+  env.dispatch_messages()?;
+  env
+    .players
+    .get_mut(&host)
+    .unwrap()
+    .socket
+    .send(&ClientToServerMessage::StartGame())?;
+  env.dispatch_messages()?;
+
+  // Everyone knows who's seeking.
+  let seeker = *env
+    .game_server
+    .state
+    .players
+    .values()
+    .find(|p| p.impostor)
+    .map(|p| &p.uuid)
+    .unwrap();
+  for player in env.players.values() {
+    assert!(player.state.players[&seeker].impostor);
+  }
+  let hider = *env.players.keys().find(|uuid| **uuid != seeker).unwrap();
+  assert_eq!(
+    env.game_server.state.call_emergency_meeting(hider),
+    Err(GameError::NotInGameMode {
+      action: Action::CallMeeting,
+      mode: GameMode::HideAndSeek
+    })
+  );
+
+  // Picking off one hider is no win, even with just two left.
+  let state = &mut env.game_server.state;
+  state.players.get_mut(&hider).unwrap().dead = true;
+  state.check_for_victories();
+  assert_eq!(state.status, GameStatus::Playing(PlayState::Night));
+
+  // But finishing every task is.
+  for player in state.players.values_mut() {
+    for task in player.tasks.iter_mut() {
+      task.finished = true;
+    }
+  }
+  state.check_for_victories();
+  assert_eq!(state.status, GameStatus::Won(Team::Crew));
+  Ok(())
+}
+
+#[test]
+fn test_players_find_out_which_map_they_are_on() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  env.game_server.set_settings(Settings {
+    map_id: MapId::Station,
+    ..Settings::default()
+  })?;
+  let player = env.create_and_connect_player()?;
+  env.dispatch_messages()?;
+  assert_eq!(env.players[&player].state.map.id, MapId::Station);
+  env.expect_everyone_agrees_on_game_state(1)?;
+
+  // Maps that aren't built in get sent to everyone.
+  let json = serde_json::to_string(&MapRegistry::get(MapId::Lobby).unwrap())?;
+  env
+    .game_server
+    .set_map(Map::from_reader(json.as_bytes())?)?;
+  let late = env.create_and_connect_player()?;
+  env.dispatch_messages()?;
+  assert_eq!(env.players[&late].state.map, env.game_server.state.map);
+  assert_eq!(env.players[&player].state.map, env.game_server.state.map);
+  env.expect_everyone_agrees_on_game_state(2)?;
+  Ok(())
+}
+
+#[test]
+fn test_bots_play_the_game() -> Result<(), Box<dyn Error>> {
+  let mut env = TestEnvironment::new();
+  let host = env.create_and_connect_player()?;
+  let guest = env.create_and_connect_player()?;
+  // Only the host gets to add bots.
+  env
+    .players
+    .get_mut(&guest)
+    .unwrap()
+    .add_bots(3)
+    .map_err(|e| e.to_string())?;
+  assert!(env.dispatch_messages().is_err());
+  env
+    .players
+    .get_mut(&host)
+    .unwrap()
+    .add_bots(3)
+    .map_err(|e| e.to_string())?;
+  env
+    .players
+    .get_mut(&guest)
+    .unwrap()
+    .socket
+    .send(&ClientToServerMessage::Ready { ready: true })?;
+  env.dispatch_messages()?;
+  assert_eq!(env.game_server.state.players.len(), 5);
+  env.expect_everyone_agrees_on_game_state(2)?;
+
+  env
+    .players
+    .get_mut(&host)
+    .unwrap()
+    .socket
+    .send(&ClientToServerMessage::StartGame())?;
+  env.dispatch_messages()?;
+  let bots: Vec<UUID> = env
+    .game_server
+    .state
+    .players
+    .keys()
+    .copied()
+    .filter(|uuid| *uuid != host && *uuid != guest)
+    .collect();
+  let starting_positions: Vec<Position> = bots
+    .iter()
+    .map(|uuid| env.game_server.state.players[uuid].position)
+    .collect();
+  for _ in 0..120 {
+    env.time_passes(TICK * 5)?;
+    env.dispatch_messages()?;
+  }
+  let moved = bots
+    .iter()
+    .zip(starting_positions)
+    .filter(|(uuid, start)| {
+      env
+        .game_server
+        .state
+        .players
+        .get(uuid)
+        .is_some_and(|p| p.position.distance(start) > 1.0)
+    })
+    .count();
+  assert!(moved > 0);
+
+  // They're in the recording like anyone else.
+  let recording = env.game_server.recording().unwrap();
+  let end = tick_time(env.game_server.state.position_history.tick);
+  let mut playback = PlaybackServer::new(recording.clone());
+  let mut viewer = GameAsPlayer::new(UUID::random(), Box::new(PlaybackTx {}));
+  playback.skip_to(end, &mut viewer)?;
+  assert_eq!(playback.state(), &env.game_server.state);
+  Ok(())
+}
+
+// Throws random streams of messages at the server from everyone in a game,
+// and checks that the rules hold no matter what order things happen in.
+#[test]
+fn test_random_games_keep_the_rules() {
+  // Make sure the games actually get somewhere, or there's nothing to check.
+  let (mut votes, mut wins) = (0, 0);
+  for seed in 0..64 {
+    let (game_votes, game_wins) = match std::panic::catch_unwind(|| play_random_game(seed)) {
+      Ok(Ok(counts)) => counts,
+      Ok(Err(e)) => panic!("seed {}: {}", seed, e),
+      Err(_) => panic!("seed {} broke the rules, see above", seed),
+    };
+    votes += game_votes;
+    wins += game_wins;
+  }
+  assert!(votes > 0);
+  assert!(wins > 0);
+}
+
+// Everything comes out of the seed, down to who everyone is, so a game that
+// breaks the rules goes the same way every time it's played.
+fn play_random_game(seed: u64) -> Result<(usize, usize), Box<dyn Error>> {
+  use rand::seq::SliceRandom;
+  use rand::Rng;
+
+  let (mut votes, mut wins) = (0, 0);
+  let mut rng = GameRng::new(seed);
+  // Time only passes when the game says so, so the rate limits don't
+  // depend on how fast the test runs.
+  let clock = VirtualClock::new();
+  // Hears everything the server tells everyone, to keep an eye on the events.
+  let observer = UUID::random_from(&mut rng);
+  let observed: Arc<Mutex<HashMap<UUID, Vec<ServerToClientMessage>>>> = Arc::default();
+  observed.lock().unwrap().insert(observer, vec![]);
+  let mut server = GameServer::new(
+    Box::new(TestBroadcaster {
+      players: observed.clone(),
+    }),
+    false,
+  )
+  .with_clock(clock.clone());
+  server.state.rng = GameRng::new(seed);
+  server.state.status = GameStatus::Lobby;
+  let uuids: Vec<UUID> = (0..6).map(|_| UUID::random_from(&mut rng)).collect();
+  for uuid in uuids.iter() {
+    server.handle_message(
+      *uuid,
+      ClientToServerMessage::Join {
+        version: get_version_sha().to_string(),
+        details: JoinRequest::JoinAsPlayer {
+          name: "Test Player".to_string(),
+          preferred_color: Color::random(&mut rng),
+          cosmetics: Cosmetics::default(),
+        },
+      },
+    )?;
+    server.handle_message(*uuid, ClientToServerMessage::Ready { ready: true })?;
+  }
+  server.handle_message(uuids[0], ClientToServerMessage::StartGame())?;
+
+  let mut log: Vec<String> = vec![];
+  for _ in 0..300 {
+    observed.lock().unwrap().insert(observer, vec![]);
+    let previous = server.state.clone();
+    let uuid = *uuids.choose(&mut rng).unwrap();
+    let target = *uuids.choose(&mut rng).unwrap();
+    let seen_at = server.state.position_history.tick;
+    let me = server.state.players.get(&uuid);
+    match rng.gen_range(0, 100) {
+      0 => {
+        log.push(format!("{} disconnected", uuid));
+        server.disconnected(uuid)?;
+      }
+      1..=15 => {
+        let elapsed = Duration::from_millis(rng.gen_range(0, 3000));
+        log.push(format!("{:?} passed", elapsed));
+        clock.advance(elapsed);
+        server.simulate(elapsed)?;
+      }
+      roll => {
+        let message = match roll {
+          16..=30 => ClientToServerMessage::Input {
+            sequence: rng.gen(),
+            movement: MovementInput {
+              up: rng.gen(),
+              down: rng.gen(),
+              left: rng.gen(),
+              right: rng.gen(),
+            },
+          },
+          31..=38 => ClientToServerMessage::Kill {
+            victim: target,
+            seen_at,
+          },
+          39..=46 => ClientToServerMessage::ReportBody {
+            dead_body_color: match server.state.bodies.choose(&mut rng) {
+              Some(body) => body.color,
+              None => Color::random(&mut rng),
+            },
+            seen_at,
+          },
+          47..=54 => ClientToServerMessage::CallMeeting(),
+          55..=74 => ClientToServerMessage::Vote {
+            target: if rng.gen() {
+              VoteTarget::Skip
+            } else {
+              VoteTarget::Player { uuid: target }
+            },
+          },
+          75..=82 => ClientToServerMessage::LockInVote(),
+          83..=91 => ClientToServerMessage::BeginInteraction {
+            task: rng.gen_range(0, 6),
+          },
+          _ => {
+            let minigame = me
+              .and_then(|p| Some(&p.tasks.get(p.interaction?.task)?.minigame))
+              .cloned()
+              .flatten();
+            match minigame {
+              Some(minigame) => ClientToServerMessage::SolveMinigame {
+                solution: minigame.solution(),
+              },
+              None => ClientToServerMessage::CallMeeting(),
+            }
+          }
+        };
+        log.push(format!("{} sent {:?}", uuid, message));
+        // Plenty of these won't be allowed, which is fine.
+        let _ = server.handle_message(uuid, message);
+      }
+    }
+    let events: Vec<GameEvent> = observed.lock().unwrap()[&observer]
+      .iter()
+      .flat_map(|message| match message {
+        ServerToClientMessage::Events(events) => events.clone(),
+        _ => vec![],
+      })
+      .collect();
+    check_rules(&previous, &server.state, &events, &log);
+    match server.state.status {
+      GameStatus::Playing(PlayState::Voting(_)) => votes += 1,
+      GameStatus::Won(_) => wins += 1,
+      _ => (),
+    }
+  }
+  Ok((votes, wins))
+}
+
+fn check_rules(before: &GameState, after: &GameState, events: &[GameEvent], log: &[String]) {
+  let recently = log[log.len().saturating_sub(10)..].join("\n");
+  // Everyone leaving afterwards is fine, but nothing else is.
+  if let GameStatus::Won(_) | GameStatus::Draw = before.status {
+    assert!(
+      after.status == before.status || after.status == GameStatus::Disconnected,
+      "the game went from {:?} to {:?}, after:\n{}",
+      before.status,
+      after.status,
+      recently
+    );
+  }
+  // Nor can anything carry on once the game's been called, even partway
+  // through a tick.
+  for event in events {
+    let over = match event {
+      GameEvent::GameWon { team } => GameStatus::Won(*team),
+      GameEvent::GameDrawn => GameStatus::Draw,
+      _ => continue,
+    };
+    assert!(
+      after.status == over || after.status == GameStatus::Disconnected,
+      "the game was {:?}, but went on to {:?}, after:\n{}",
+      over,
+      after.status,
+      recently
+    );
+  }
+  for (uuid, player) in before.players.iter() {
+    let now = match after.players.get(uuid) {
+      Some(now) => now,
+      None => continue,
+    };
+    for (index, task) in player.tasks.iter().enumerate() {
+      assert!(
+        !task.finished || now.tasks[index].finished,
+        "a finished task came undone, after:\n{}",
+        recently
+      );
+    }
+  }
+  if let GameStatus::Playing(PlayState::Voting(voting)) = &after.status {
+    for voter in voting.votes.keys() {
+      assert!(
+        matches!(after.players.get(voter), Some(p) if p.eligable_to_vote()),
+        "{} has a vote in but can't vote, after:\n{}",
+        voter,
+        recently
+      );
+    }
+    for voter in voting.locked_in.iter() {
+      assert!(
+        voting.votes.contains_key(voter),
+        "{} locked in without voting, after:\n{}",
+        voter,
+        recently
+      );
+    }
+  }
+}
@@ -0,0 +1,97 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+// A vent that impostors can duck into, and pop out of somewhere else.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Vent {
+  pub position: Position,
+  // Indexes into Map::vents of the vents this one leads to.
+  pub connections: Cow<'static, [usize]>,
+}
+
+impl GameState {
+  fn check_can_vent(&self, uuid: UUID) -> Result<&Player, GameError> {
+    let action = Action::Vent;
+    if self.status != GameStatus::Playing(PlayState::Night) {
+      return Err(GameError::WrongStatus { action });
+    }
+    match self.players.get(&uuid) {
+      None => Err(GameError::UnknownPlayer { uuid }),
+      Some(p) if p.dead => Err(GameError::Dead { action }),
+      Some(p) if p.in_kill_animation() => Err(GameError::InKillAnimation { action }),
+      Some(p) if p.impostor => Ok(p),
+      Some(p) if matches!(p.role, Role::Engineer { .. }) => Ok(p),
+      Some(_) => Err(GameError::NotAllowedForRole { action }),
+    }
+  }
+
+  // Returns the index of the closest vent that the given player could enter.
+  pub fn vent_near(&self, uuid: UUID) -> Option<usize> {
+    let player = self.check_can_vent(uuid).ok()?;
+    let vented_this_round = Role::Engineer {
+      vented_this_round: true,
+    };
+    if player.in_vent.is_some() || player.role == vented_this_round {
+      return None;
+    }
+    let mut closest_distance = self.settings.task_distance;
+    let mut closest = None;
+    for (index, vent) in self.map.vents.iter().enumerate() {
+      let distance = vent.position.distance(&player.position);
+      if distance < closest_distance {
+        closest_distance = distance;
+        closest = Some(index);
+      }
+    }
+    closest
+  }
+
+  pub fn note_entered_vent(&mut self, uuid: UUID) -> Result<(), GameError> {
+    let index = self.vent_near(uuid).ok_or(GameError::OutOfRange {
+      action: Action::Vent,
+    })?;
+    let position = self.map.vents[index].position;
+    // There's no fitting a body down there.
+    self.release_body(uuid);
+    if let Some(player) = self.players.get_mut(&uuid) {
+      player.in_vent = Some(index);
+      player.position = position;
+      player.velocity = Velocity::default();
+      if let Role::Engineer { .. } = player.role {
+        player.role = Role::Engineer {
+          vented_this_round: true,
+        };
+      }
+    }
+    Ok(())
+  }
+
+  pub fn note_moved_vent(&mut self, uuid: UUID, to: usize) -> Result<(), GameError> {
+    let from = self
+      .check_can_vent(uuid)?
+      .in_vent
+      .ok_or(GameError::NotInVent)?;
+    if !self.map.vents[from].connections.contains(&to) {
+      return Err(GameError::InvalidTarget {
+        action: Action::Vent,
+      });
+    }
+    let position = self.map.vents[to].position;
+    if let Some(player) = self.players.get_mut(&uuid) {
+      player.in_vent = Some(to);
+      player.position = position;
+    }
+    Ok(())
+  }
+
+  pub fn note_exited_vent(&mut self, uuid: UUID) -> Result<(), GameError> {
+    if self.check_can_vent(uuid)?.in_vent.is_none() {
+      return Err(GameError::NotInVent);
+    }
+    if let Some(player) = self.players.get_mut(&uuid) {
+      player.in_vent = None;
+    }
+    Ok(())
+  }
+}
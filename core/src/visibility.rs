@@ -0,0 +1,320 @@
+use crate::*;
+use core::time::Duration;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Where we tell players that everyone they can't see is. Far enough from
+// every map that it's never in sight.
+const OUT_OF_SIGHT: Position = Position {
+  x: -100_000.0,
+  y: -100_000.0,
+};
+
+// Everything that a given player should currently be able to see.
+#[derive(Debug, Default)]
+pub struct VisibleEntities<'a> {
+  pub players: Vec<&'a Player>,
+  pub bodies: Vec<&'a DeadBody>,
+  // Only the crew fall for decoys.
+  pub decoys: Vec<&'a Decoy>,
+  // The viewer's own unfinished tasks that are within sight.
+  pub tasks: Vec<&'a Task>,
+}
+
+impl GameState {
+  // Whether the given player can see the given position, taking both their
+  // vision radius and anything in the way into account.
+  pub fn can_see(&self, player: &Player, other: &Position) -> bool {
+    let vision = match self.vision(player) {
+      None => return true,
+      Some(vision) => vision,
+    };
+    if player.position.distance(other) > vision {
+      return false;
+    }
+    self.line_is_clear(player.position, *other)
+  }
+
+  // Whether there's nothing in the way between the two positions, however
+  // far apart they are, counting the doors that are shut right now.
+  pub fn line_is_clear(&self, from: Position, to: Position) -> bool {
+    self.map.line_of_sight(from, to)
+      && !self
+        .map
+        .closed_doors(&self.doors)
+        .any(|shape| shape.blocks_line_of_sight(from, to))
+  }
+
+  // Filters the players, bodies, and tasks down to what the given viewer can
+  // see. Viewers who aren't playing (i.e. spectators) see every player and body.
+  pub fn visible_entities(&self, viewer: UUID) -> VisibleEntities<'_> {
+    let viewer = match self.players.get(&viewer) {
+      Some(viewer) => viewer,
+      None => {
+        return VisibleEntities {
+          players: self.players.values().collect(),
+          bodies: self.bodies.iter().collect(),
+          decoys: vec![],
+          tasks: vec![],
+        }
+      }
+    };
+    // Only fellow impostors (and the dead) know who's lurking in the vents.
+    let sees_vents = viewer.dead || viewer.impostor;
+    let players = self
+      .players
+      .values()
+      .filter(|p| {
+        p.uuid == viewer.uuid
+          || (p.is_visible_to(Some(viewer))
+            && (p.in_vent.is_none() || sees_vents)
+            && self.can_see(viewer, &p.position))
+      })
+      .collect();
+    let bodies = self
+      .bodies
+      .iter()
+      .filter(|b| self.can_see(viewer, &b.position))
+      .collect();
+    let decoys = if viewer.impostor {
+      vec![]
+    } else {
+      self
+        .decoys
+        .iter()
+        .filter(|d| self.can_see(viewer, &d.position))
+        .collect()
+    };
+    let tasks = if self.comms_down_for(viewer) {
+      vec![]
+    } else {
+      viewer
+        .tasks
+        .iter()
+        .filter(|t| !t.finished && self.can_see(viewer, &t.position))
+        .collect()
+    };
+    VisibleEntities {
+      players,
+      bodies,
+      decoys,
+      tasks,
+    }
+  }
+
+  // The game as the given player (or spectator) is allowed to know it. Anything
+  // they could use to cheat is taken out: who the impostors are, everyone
+  // else's tasks, secret votes, where the players they can't see are, and
+  // what's coming out of the random number generator.
+  // Once the game is over, everyone gets to know everything.
+  pub fn view_for(&self, viewer: UUID) -> GameState {
+    let mut view = self.clone();
+    view.partial_view = true;
+    view.position_history = self.position_history.without_positions();
+    // Anyone who knew the seed could deal the game out again for themselves.
+    view.rng = GameRng::default();
+    // They'll hear about whatever they're allowed to from the server.
+    view.events.clear();
+    // Who's been up to what is the server's business.
+    view.activity = ActivityLog::default();
+    // Decoys only ever show up as the impostor who left them.
+    view.decoys.clear();
+    if let GameStatus::Won(_) | GameStatus::Draw = self.status {
+      return view;
+    }
+    // Impostors know who their friends are, and sometimes everyone does.
+    let knows_impostors = matches!(self.players.get(&viewer), Some(p) if p.impostor)
+      || self.settings.game_mode.reveals_impostors();
+    let entities = self.visible_entities(viewer);
+    let visible: BTreeSet<UUID> = entities.players.iter().map(|p| p.uuid).collect();
+    let bodies: BTreeSet<Color> = entities.bodies.iter().map(|b| b.color).collect();
+    // The medic remembers who they shielded, but no one else can tell.
+    let knows_shields = matches!(
+      self.players.get(&viewer).map(|p| p.role),
+      Some(Role::Medic { .. })
+    );
+    let decoys: BTreeMap<UUID, Position> = entities
+      .decoys
+      .iter()
+      .map(|d| (d.owner, d.position))
+      .collect();
+    for player in view.players.values_mut() {
+      if player.uuid == viewer {
+        // With comms down, they'll have to remember what they were up to.
+        if self.comms_down_for(player) {
+          player.tasks.clear();
+        }
+        continue;
+      }
+      if !(knows_impostors && player.impostor) {
+        player.impostor = false;
+        player.role = Role::Crewmate;
+        player.kill_cooldown = Duration::from_secs(0);
+      }
+      // Everyone but their fellow impostors sees who they're pretending to be.
+      if let Some(disguise) = player.disguise.take() {
+        let target = self.players.get(&disguise.target);
+        if let (Some(target), false) = (target, knows_impostors) {
+          player.name = target.name.clone();
+          player.color = target.color;
+          player.cosmetics = target.cosmetics;
+        }
+      }
+      if !knows_shields {
+        player.shielded = false;
+      }
+      player.tasks.clear();
+      player.interaction = None;
+      if !visible.contains(&player.uuid) {
+        // Unless there's a decoy of them in sight, which is as good as the
+        // real thing as far as the viewer can tell.
+        player.position = decoys.get(&player.uuid).copied().unwrap_or(OUT_OF_SIGHT);
+        player.velocity = Velocity::default();
+        player.in_vent = None;
+      }
+    }
+    // Bodies they can't see might as well not be there.
+    view.bodies.retain(|b| bodies.contains(&b.color));
+    // Who killed whom is for the server's records only.
+    for body in view.bodies.iter_mut() {
+      body.killer = None;
+      // Nor can they see who's dragging it, unless they can see them.
+      if !body
+        .dragged_by
+        .is_some_and(|uuid| uuid == viewer || visible.contains(&uuid))
+      {
+        body.dragged_by = None;
+      }
+    }
+    if let GameStatus::Playing(PlayState::Voting(voting)) = &mut view.status {
+      voting.context.killer = None;
+      if self.settings.anonymous_votes {
+        voting.votes.retain(|voter, _| *voter == viewer);
+      }
+    }
+    view
+  }
+}
+
+impl Map {
+  // Whether the furniture leaves a clear line between the two positions.
+  // Doors come and go, so they're up to the caller.
+  pub fn line_of_sight(&self, a: Position, b: Position) -> bool {
+    // Anything in the way has to be somewhere around the line between them.
+    let middle = Position {
+      x: (a.x + b.x) / 2.0,
+      y: (a.y + b.y) / 2.0,
+    };
+    let reach = a.distance(&b) / 2.0;
+    !self
+      .shapes_near(middle, reach)
+      .any(|shape| shape.blocks_line_of_sight(a, b))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use core::time::Duration;
+
+  #[test]
+  fn test_furniture_blocks_line_of_sight() {
+    let table = GameState::new().map.static_geometry[0].center();
+    let near_table = |dx, dy| Position {
+      x: table.x + dx,
+      y: table.y + dy,
+    };
+    let (state, uuids) = game_at(&[
+      (Role::Crewmate, near_table(-80.0, 0.0)),
+      (Role::Crewmate, near_table(80.0, 0.0)),
+      (Role::Crewmate, near_table(-80.0, 100.0)),
+    ]);
+    let (viewer, across, beside) = (uuids[0], uuids[1], uuids[2]);
+
+    let visible: Vec<UUID> = state
+      .visible_entities(viewer)
+      .players
+      .iter()
+      .map(|p| p.uuid)
+      .collect();
+    assert!(visible.contains(&viewer));
+    assert!(visible.contains(&beside));
+    // Close enough, but the table is in the way.
+    assert!(!visible.contains(&across));
+
+    // Spectators see everyone.
+    assert_eq!(state.visible_entities(UUID::random()).players.len(), 3);
+  }
+
+  #[test]
+  fn test_players_only_get_told_what_they_can_know() {
+    // Everyone's close together, except for the medic.
+    let (mut state, uuids) = game_at(&[
+      (Role::Impostor, Position { x: 100.0, y: 100.0 }),
+      (Role::Impostor, Position { x: 100.0, y: 110.0 }),
+      (Role::Crewmate, Position { x: 100.0, y: 120.0 }),
+      (
+        Role::Medic { shield_used: true },
+        Position {
+          x: 1100.0,
+          y: 130.0,
+        },
+      ),
+    ]);
+    state.settings.anonymous_votes = true;
+    for player in state.players.values_mut() {
+      let task = Task::new(TaskKind::Short, player.position);
+      player.tasks = vec![if player.impostor {
+        task.into_fake()
+      } else {
+        task
+      }];
+    }
+    state.players.get_mut(&uuids[1]).unwrap().shielded = true;
+    let nearby = DeadBody::new(Color::Black, Position { x: 150.0, y: 120.0 });
+    let far_away = DeadBody::new(
+      Color::White,
+      Position {
+        x: 1100.0,
+        y: 400.0,
+      },
+    );
+    state.bodies = vec![nearby, far_away];
+
+    let crew_view = state.view_for(uuids[2]);
+    assert!(crew_view.partial_view);
+    assert!(crew_view.players.values().all(|p| !p.impostor));
+    assert_eq!(crew_view.players[&uuids[2]].tasks.len(), 1);
+    assert!(crew_view.players[&uuids[0]].tasks.is_empty());
+    assert_eq!(
+      crew_view.players[&uuids[1]].position,
+      state.players[&uuids[1]].position
+    );
+    assert_ne!(
+      crew_view.players[&uuids[3]].position,
+      state.players[&uuids[3]].position
+    );
+    assert_eq!(crew_view.bodies, vec![nearby]);
+    // Only the medic knows who's shielded.
+    assert!(!crew_view.players[&uuids[1]].shielded);
+    assert!(state.view_for(uuids[3]).players[&uuids[1]].shielded);
+
+    let impostor_view = state.view_for(uuids[0]);
+    assert!(impostor_view.players[&uuids[1]].impostor);
+
+    state.settings.discussion_time = Duration::from_secs(0);
+    state.start_meeting(MeetingReason::EmergencyButton { caller: uuids[2] });
+    state.simulate_tick();
+    for voter in uuids.iter() {
+      state.note_vote(*voter, VoteTarget::Skip).unwrap();
+    }
+    let votes = match state.view_for(uuids[2]).status {
+      GameStatus::Playing(PlayState::Voting(voting)) => voting.votes,
+      status => panic!("Expected to be voting, but was {:?}", status),
+    };
+    assert_eq!(votes.keys().collect::<Vec<_>>(), vec![&uuids[2]]);
+
+    state.status = GameStatus::Won(Team::Impostors);
+    assert_eq!(state.view_for(uuids[2]).players, state.players);
+  }
+}
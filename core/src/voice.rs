@@ -0,0 +1,145 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+// Plenty for a session description, which is the biggest of these.
+pub const MAX_SIGNAL_LENGTH: usize = 16 * 1024;
+
+// What two clients need to tell each other to set up a WebRTC voice
+// connection. The server just passes these along without looking inside.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VoiceSignal {
+  Offer {
+    sdp: String,
+  },
+  Answer {
+    sdp: String,
+  },
+  IceCandidate {
+    candidate: String,
+    sdp_mid: Option<String>,
+    sdp_m_line_index: Option<u16>,
+  },
+}
+
+impl VoiceSignal {
+  fn len(&self) -> usize {
+    match self {
+      VoiceSignal::Offer { sdp } | VoiceSignal::Answer { sdp } => sdp.len(),
+      VoiceSignal::IceCandidate {
+        candidate, sdp_mid, ..
+      } => candidate.len() + sdp_mid.as_ref().map_or(0, |m| m.len()),
+    }
+  }
+}
+
+impl GameState {
+  // Whether a signal from one player to another should be passed on.
+  pub fn check_voice_signal(
+    &self,
+    from: UUID,
+    to: UUID,
+    signal: &VoiceSignal,
+  ) -> Result<(), GameError> {
+    let in_game = |uuid| self.players.contains_key(&uuid) || self.is_spectator(uuid);
+    if !in_game(from) {
+      return Err(GameError::UnknownPlayer { uuid: from });
+    }
+    if from == to || !in_game(to) {
+      return Err(GameError::InvalidTarget {
+        action: Action::Voice,
+      });
+    }
+    if signal.len() > MAX_SIGNAL_LENGTH {
+      return Err(GameError::MessageTooLong {
+        max: MAX_SIGNAL_LENGTH,
+      });
+    }
+    Ok(())
+  }
+
+  // Every pair of players who can hear each other right now, with the lower
+  // UUID first. At night, the living only hear those within max_distance of
+  // them, and not through the walls, while everyone alive hears everyone else
+  // at the table during a meeting. The dead can always hear each other,
+  // wherever they are, but never anyone living.
+  pub fn audible_pairs(&self, max_distance: f64) -> Vec<(UUID, UUID)> {
+    let mut pairs = vec![];
+    for (a, first) in self.players.iter() {
+      for (b, second) in self.players.range(*a..).skip(1) {
+        if self.volume_between(first, second, max_distance) > 0.0 {
+          pairs.push((*a, *b));
+        }
+      }
+    }
+    pairs
+  }
+
+  // How loud one player is to another, from 0 (can't hear them at all) to 1.
+  // At night, voices fade out with distance, until they're gone at
+  // max_distance.
+  pub fn volume_between(&self, first: &Player, second: &Player, max_distance: f64) -> f64 {
+    let everyone_gathered = !matches!(self.status, GameStatus::Playing(PlayState::Night));
+    match (first.dead, second.dead) {
+      (true, true) => 1.0,
+      (false, false) if everyone_gathered => 1.0,
+      (false, false) => {
+        let distance = first.position.distance(&second.position);
+        if distance > max_distance || !self.map.line_of_sight(first.position, second.position) {
+          return 0.0;
+        }
+        // Right at the edge is quiet, but not silent.
+        (1.0 - distance / max_distance).max(0.05)
+      }
+      _ => 0.0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+  use std::borrow::Cow;
+
+  #[test]
+  fn test_only_nearby_players_can_hear_each_other() {
+    let (mut state, mut uuids) = game_with(&[Role::Crewmate; 4]);
+    uuids.sort();
+    let positions = [0.0, 50.0, 500.0, 1000.0];
+    for (uuid, x) in uuids.iter().zip(positions.iter()) {
+      state.players.get_mut(uuid).unwrap().position = Position { x: *x, y: 0.0 };
+    }
+    assert_eq!(state.audible_pairs(100.0), vec![(uuids[0], uuids[1])]);
+
+    // Ghosts have their own channel, however far apart they are.
+    state.players.get_mut(&uuids[2]).unwrap().dead = true;
+    state.players.get_mut(&uuids[3]).unwrap().dead = true;
+    assert_eq!(
+      state.audible_pairs(100.0),
+      vec![(uuids[0], uuids[1]), (uuids[2], uuids[3])]
+    );
+    let (first, second) = (&state.players[&uuids[0]], &state.players[&uuids[1]]);
+    assert_eq!(state.volume_between(first, second, 100.0), 0.5);
+    assert_eq!(state.volume_between(first, second, 25.0), 0.0);
+
+    // No one hears anything through the walls.
+    state.map.static_geometry.to_mut().push(Shape::Wall {
+      start: Position { x: 25.0, y: -50.0 },
+      end: Position { x: 25.0, y: 50.0 },
+      thickness: 10.0,
+      color: Cow::Borrowed("#000"),
+    });
+    state.map.reindex();
+    assert_eq!(state.audible_pairs(100.0), vec![(uuids[2], uuids[3])]);
+
+    let signal = VoiceSignal::Offer {
+      sdp: "v=0".to_string(),
+    };
+    assert!(state
+      .check_voice_signal(uuids[0], uuids[1], &signal)
+      .is_ok());
+    assert!(state
+      .check_voice_signal(uuids[0], UUID::random(), &signal)
+      .is_err());
+  }
+}
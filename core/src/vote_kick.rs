@@ -0,0 +1,147 @@
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+// Votes to throw someone out of the lobby, for public rooms where the host
+// has wandered off, or is the one causing trouble.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LobbyVotes {
+  // Who's been voted against, and by whom.
+  kick_votes: BTreeMap<UUID, BTreeSet<UUID>>,
+}
+
+impl LobbyVotes {
+  // It always takes at least this many, so that no one can get rid of
+  // someone all on their own.
+  const MIN_VOTES: usize = 2;
+
+  pub fn votes_against(&self, target: UUID) -> usize {
+    self
+      .kick_votes
+      .get(&target)
+      .map_or(0, |voters| voters.len())
+  }
+
+  // How many votes it takes to kick someone out, when this many others
+  // could have voted.
+  pub fn votes_needed(settings: &Settings, eligible: usize) -> usize {
+    let needed = (settings.vote_kick_fraction * eligible as f64).ceil() as usize;
+    needed.max(Self::MIN_VOTES)
+  }
+
+  // Counts the vote, and says whether there's now enough to kick them.
+  pub fn note_kick_vote(
+    &mut self,
+    state: &GameState,
+    voter: UUID,
+    target: UUID,
+    eligible: usize,
+  ) -> Result<bool, GameError> {
+    let action = Action::VoteKick;
+    if state.status != GameStatus::Lobby {
+      return Err(GameError::WrongStatus { action });
+    }
+    if !state.players.contains_key(&voter) {
+      return Err(GameError::UnknownPlayer { uuid: voter });
+    }
+    if !state.players.contains_key(&target) {
+      return Err(GameError::UnknownPlayer { uuid: target });
+    }
+    if voter == target {
+      return Err(GameError::InvalidTarget { action });
+    }
+    self.kick_votes.entry(target).or_default().insert(voter);
+    Ok(self.votes_against(target) >= Self::votes_needed(&state.settings, eligible))
+  }
+
+  // Forgets about someone who's left, both their votes and any against them.
+  pub fn remove(&mut self, uuid: UUID) {
+    self.kick_votes.remove(&uuid);
+    for voters in self.kick_votes.values_mut() {
+      voters.remove(&uuid);
+    }
+  }
+}
+
+impl GameServer {
+  pub(crate) fn note_kick_vote(&mut self, voter: UUID, target: UUID) -> Result<(), Box<dyn Error>> {
+    // There's no connection to hang up on for a bot, and bots don't vote,
+    // so they don't count either.
+    if self.bots.contains(target) {
+      return Err(
+        GameError::InvalidTarget {
+          action: Action::VoteKick,
+        }
+        .into(),
+      );
+    }
+    let eligible = self
+      .state
+      .players
+      .keys()
+      .filter(|uuid| **uuid != target && !self.bots.contains(**uuid))
+      .count();
+    let decided = self
+      .lobby_votes
+      .note_kick_vote(&self.state, voter, target, eligible)?;
+    if !decided {
+      return Ok(());
+    }
+    tracing::info!(%target, "Voted out of the lobby");
+    self.rejoin_tokens.remove(&target);
+    let connection = self.connection_for_player(target);
+    self.broadcaster.send_to_player(
+      &connection,
+      &ServerToClientMessage::Kicked {
+        reason: KickReason::VotedOut,
+      },
+    )?;
+    self.broadcaster.disconnect(&connection);
+    // The vote is in the recording, so this happens again on playback, and
+    // there's no need to record the disconnection as well.
+    self.heartbeats.remove(connection);
+    self.remove_connection(connection)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_util::*;
+  use crate::*;
+
+  #[test]
+  fn test_it_takes_a_supermajority_to_vote_someone_out() {
+    let (mut state, uuids) = game_with(&[Role::Crewmate; 5]);
+    state.status = GameStatus::Lobby;
+    let target = uuids[0];
+    let mut votes = LobbyVotes::default();
+    assert_eq!(
+      votes.note_kick_vote(&state, target, target, 4),
+      Err(GameError::InvalidTarget {
+        action: Action::VoteKick
+      })
+    );
+    // Two thirds of the other four rounds up to three.
+    assert_eq!(LobbyVotes::votes_needed(&state.settings, 4), 3);
+    assert_eq!(votes.note_kick_vote(&state, uuids[1], target, 4), Ok(false));
+    // Voting twice doesn't count twice.
+    assert_eq!(votes.note_kick_vote(&state, uuids[1], target, 4), Ok(false));
+    assert_eq!(votes.note_kick_vote(&state, uuids[2], target, 4), Ok(false));
+    // Anyone who leaves takes their vote with them.
+    votes.remove(uuids[2]);
+    assert_eq!(votes.votes_against(target), 1);
+    assert_eq!(votes.note_kick_vote(&state, uuids[2], target, 4), Ok(false));
+    assert_eq!(votes.note_kick_vote(&state, uuids[3], target, 4), Ok(true));
+
+    // Never fewer than two, even in a tiny lobby.
+    assert_eq!(LobbyVotes::votes_needed(&state.settings, 1), 2);
+
+    state.status = GameStatus::Playing(PlayState::Night);
+    assert_eq!(
+      votes.note_kick_vote(&state, uuids[1], uuids[4], 4),
+      Err(GameError::WrongStatus {
+        action: Action::VoteKick
+      })
+    );
+  }
+}
@@ -20,7 +20,9 @@ if [[ "$OSTYPE" == "linux-gnu"* ]]; then
   sudo setcap CAP_NET_BIND_SERVICE=+eip target/release/prod
 fi
 
-# kill the previous server, if any
-kill `ps aux | grep -v grep | grep target/release/prod | tr -s ' ' | cut -d ' ' -f 2`
+# stop the previous server, if any, and wait for it to save its games
+previous=`ps aux | grep -v grep | grep target/release/prod | tr -s ' ' | cut -d ' ' -f 2`
+kill $previous
+while kill -0 $previous 2>/dev/null; do sleep 0.1; done
 nohup ./target/release/prod >./nohup.out &
 tail -f nohup.out
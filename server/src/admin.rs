@@ -0,0 +1,82 @@
+use crate::metrics::METRICS;
+use crate::server::WebsocketServer;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use warp::http::StatusCode;
+use warp::{Filter, Reply};
+
+// Only reachable from the machine the server's running on, since there's no
+// sign in. Get at it over ssh, e.g.
+//   curl localhost:3013/admin/rooms
+//   curl localhost:3013/admin/rooms/ABCDE/snapshot
+//   curl -X POST localhost:3013/admin/rooms/ABCDE/terminate
+// Prometheus can scrape localhost:3013/metrics.
+pub const ADMIN_ADDR: ([u8; 4], u16) = ([127, 0, 0, 1], 3013);
+
+fn not_found(code: &str) -> Box<dyn Reply> {
+  Box::new(warp::reply::with_status(
+    format!("No room {}\n", code),
+    StatusCode::NOT_FOUND,
+  ))
+}
+
+pub async fn serve_admin(ws_server: Arc<Mutex<WebsocketServer>>) {
+  let ws_server = warp::any().map(move || ws_server.clone());
+
+  let rooms = warp::path!("admin" / "rooms")
+    .and(warp::get())
+    .and(ws_server.clone())
+    .map(|ws_server: Arc<Mutex<WebsocketServer>>| {
+      let rooms = ws_server.lock().unwrap().room_stats();
+      warp::reply::json(&rooms)
+    });
+
+  let snapshot = warp::path!("admin" / "rooms" / String / "snapshot")
+    .and(warp::get())
+    .and(ws_server.clone())
+    .map(|code: String, ws_server: Arc<Mutex<WebsocketServer>>| {
+      let snapshot = ws_server.lock().unwrap().snapshot_room(&code);
+      match snapshot {
+        None => not_found(&code),
+        Some(Ok(snapshot)) => Box::new(warp::reply::with_header(
+          snapshot,
+          "content-type",
+          "application/json",
+        )),
+        Some(Err(e)) => Box::new(warp::reply::with_status(
+          format!("Unable to snapshot room {}: {}\n", code, e),
+          StatusCode::INTERNAL_SERVER_ERROR,
+        )),
+      }
+    });
+
+  let terminate = warp::path!("admin" / "rooms" / String / "terminate")
+    .and(warp::post())
+    .and(ws_server.clone())
+    .map(|code: String, ws_server: Arc<Mutex<WebsocketServer>>| {
+      if ws_server.lock().unwrap().terminate_room(&code) {
+        Box::new(format!("Terminated room {}\n", code)) as Box<dyn Reply>
+      } else {
+        not_found(&code)
+      }
+    });
+
+  let metrics = warp::path!("metrics")
+    .and(warp::get())
+    .and(ws_server.clone())
+    .map(|ws_server: Arc<Mutex<WebsocketServer>>| {
+      let rooms = ws_server.lock().unwrap().rooms_by_status();
+      warp::reply::with_header(
+        METRICS.render(&rooms),
+        "content-type",
+        "text/plain; version=0.0.4",
+      )
+    });
+
+  let addr: SocketAddr = ADMIN_ADDR.into();
+  tracing::info!("Admin interface listening on: {}", addr);
+  warp::serve(rooms.or(snapshot).or(terminate).or(metrics))
+    .run(addr)
+    .await;
+}
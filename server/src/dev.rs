@@ -1,7 +1,14 @@
 #![warn(rust_2018_idioms)]
 
+mod admin;
+mod metrics;
 mod server;
-use crate::server::{client_connected, WebsocketServer};
+mod storage;
+use crate::admin::serve_admin;
+use crate::server::trusted_proxies;
+use crate::server::WebsocketServer;
+use crate::server::{client_connected, restore_rooms, set_up_logging, shut_down_on_sigterm};
+use crate::storage::open_stores;
 use std::error::Error;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -11,16 +18,27 @@ use warp::Filter;
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
   let addr: SocketAddr = ([0, 0, 0, 0], 3012).into();
-  let gameserver: Arc<Mutex<WebsocketServer>> = Arc::default();
+  set_up_logging();
+  let (store, stats) = open_stores()?;
+  let gameserver = Arc::new(Mutex::new(
+    WebsocketServer::new(store)
+      .with_stats_store(stats)
+      .with_trusted_proxies(trusted_proxies()?),
+  ));
+  restore_rooms(&gameserver);
+  tokio::spawn(shut_down_on_sigterm(gameserver.clone()));
+  tokio::spawn(serve_admin(gameserver.clone()));
   let gameserver = warp::any().map(move || gameserver.clone());
   let websocket_server = warp::ws()
+    .and(warp::addr::remote())
+    .and(warp::header::optional::<String>("x-forwarded-for"))
     .and(gameserver)
-    .map(|ws: warp::ws::Ws, gameserver| {
-      ws.on_upgrade(move |socket| client_connected(socket, gameserver))
+    .map(|ws: warp::ws::Ws, remote, forwarded_for, gameserver| {
+      ws.on_upgrade(move |socket| client_connected(socket, remote, forwarded_for, gameserver))
     });
 
   let server = warp::serve(websocket_server);
-  println!("Listening on: {}", addr);
+  tracing::info!("Listening on: {}", addr);
   server.run(addr).await;
 
   Ok(())
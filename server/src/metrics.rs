@@ -0,0 +1,199 @@
+use rust_us_core::{GameEvent, GameStatus, ServerToClientMessage, UUID};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+// Counts of what the server's been up to, for Prometheus to scrape. Rates,
+// like messages per second or kills per minute, are left to Prometheus to
+// work out from the totals.
+pub struct Metrics {
+  connected_clients: AtomicI64,
+  messages_in: AtomicU64,
+  messages_out: AtomicU64,
+  kills: AtomicU64,
+  meetings: AtomicU64,
+  ticks: Histogram,
+}
+
+pub static METRICS: Metrics = Metrics {
+  connected_clients: AtomicI64::new(0),
+  messages_in: AtomicU64::new(0),
+  messages_out: AtomicU64::new(0),
+  kills: AtomicU64::new(0),
+  meetings: AtomicU64::new(0),
+  ticks: Histogram::new(),
+};
+
+// In seconds. A tick's meant to fit in 16ms, with room to spare.
+const TICK_BUCKETS: [f64; 9] = [
+  0.000_1, 0.000_25, 0.000_5, 0.001, 0.002_5, 0.005, 0.01, 0.016, 0.05,
+];
+
+struct Histogram {
+  // How many observations fell in each bucket, not counting the ones below,
+  // with the last one for everything bigger.
+  counts: [AtomicU64; TICK_BUCKETS.len() + 1],
+  sum_micros: AtomicU64,
+}
+
+impl Histogram {
+  const fn new() -> Self {
+    Histogram {
+      counts: [const { AtomicU64::new(0) }; TICK_BUCKETS.len() + 1],
+      sum_micros: AtomicU64::new(0),
+    }
+  }
+
+  fn observe(&self, took: Duration) {
+    let seconds = took.as_secs_f64();
+    let bucket = TICK_BUCKETS
+      .iter()
+      .position(|bound| seconds <= *bound)
+      .unwrap_or(TICK_BUCKETS.len());
+    self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+    self
+      .sum_micros
+      .fetch_add(took.as_micros() as u64, Ordering::Relaxed);
+  }
+
+  fn render(&self, name: &str, out: &mut String) {
+    // Prometheus wants the buckets to count everything below them, not just
+    // what's in them.
+    let mut count = 0;
+    for (bound, bucket) in TICK_BUCKETS.iter().zip(self.counts.iter()) {
+      count += bucket.load(Ordering::Relaxed);
+      let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+    }
+    count += self.counts[TICK_BUCKETS.len()].load(Ordering::Relaxed);
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+    let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+    let _ = writeln!(out, "{}_sum {}", name, sum);
+    let _ = writeln!(out, "{}_count {}", name, count);
+  }
+}
+
+impl Metrics {
+  pub fn client_connected(&self) {
+    self.connected_clients.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn client_disconnected(&self) {
+    self.connected_clients.fetch_sub(1, Ordering::Relaxed);
+  }
+
+  pub fn message_in(&self) {
+    self.messages_in.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn messages_out(&self, count: u64) {
+    self.messages_out.fetch_add(count, Ordering::Relaxed);
+  }
+
+  // Picks out what's happened in the game from what's being said about it.
+  // Kills are only told to the killer and the victim, so they're counted
+  // once, when the killer hears about it.
+  pub fn note_events(&self, message: &ServerToClientMessage, to: Option<&UUID>) {
+    let events = match message {
+      ServerToClientMessage::Events(events) => events,
+      _ => return,
+    };
+    for event in events.iter() {
+      match event {
+        GameEvent::PlayerKilled { killer, .. } if Some(killer) == to => {
+          self.kills.fetch_add(1, Ordering::Relaxed);
+        }
+        GameEvent::MeetingCalled { .. } => {
+          self.meetings.fetch_add(1, Ordering::Relaxed);
+        }
+        _ => (),
+      }
+    }
+  }
+
+  pub fn tick(&self, took: Duration) {
+    self.ticks.observe(took);
+  }
+
+  // Everything, in Prometheus's text format. The rooms are counted by
+  // whoever's asking, since they're not ours to keep track of.
+  pub fn render(&self, rooms_by_status: &BTreeMap<&'static str, usize>) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+      let _ = writeln!(out, "# HELP {} {}", name, help);
+      let _ = writeln!(out, "# TYPE {} {}", name, kind);
+      let _ = writeln!(out, "{} {}", name, value);
+    };
+    metric(
+      "airlock_connected_clients",
+      "gauge",
+      "Websocket connections that are currently open.",
+      self.connected_clients.load(Ordering::Relaxed).to_string(),
+    );
+    metric(
+      "airlock_messages_received_total",
+      "counter",
+      "Messages received from clients.",
+      self.messages_in.load(Ordering::Relaxed).to_string(),
+    );
+    metric(
+      "airlock_messages_sent_total",
+      "counter",
+      "Messages sent to clients.",
+      self.messages_out.load(Ordering::Relaxed).to_string(),
+    );
+    metric(
+      "airlock_kills_total",
+      "counter",
+      "Players killed, by impostors or sheriffs.",
+      self.kills.load(Ordering::Relaxed).to_string(),
+    );
+    metric(
+      "airlock_meetings_total",
+      "counter",
+      "Meetings called, by reporting a body or pressing the button.",
+      self.meetings.load(Ordering::Relaxed).to_string(),
+    );
+
+    let _ = writeln!(out, "# HELP airlock_rooms Rooms open on this server.");
+    let _ = writeln!(out, "# TYPE airlock_rooms gauge");
+    for status in STATUSES.iter() {
+      let count = rooms_by_status.get(status).copied().unwrap_or(0);
+      let _ = writeln!(out, "airlock_rooms{{status=\"{}\"}} {}", status, count);
+    }
+
+    let name = "airlock_tick_duration_seconds";
+    let _ = writeln!(
+      out,
+      "# HELP {} How long it takes to simulate a room's game, each tick.",
+      name
+    );
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    self.ticks.render(name, &mut out);
+    out
+  }
+}
+
+// Every status gets a line, even when there's no rooms in it, so that graphs
+// drop to zero rather than stopping.
+const STATUSES: [&str; 7] = [
+  "connecting",
+  "lobby",
+  "playing",
+  "paused",
+  "won",
+  "draw",
+  "disconnected",
+];
+
+pub fn status_label(status: &GameStatus) -> &'static str {
+  match status {
+    GameStatus::Connecting => STATUSES[0],
+    GameStatus::Lobby => STATUSES[1],
+    GameStatus::Playing(_) => STATUSES[2],
+    GameStatus::Paused { .. } => STATUSES[3],
+    GameStatus::Won(_) => STATUSES[4],
+    GameStatus::Draw => STATUSES[5],
+    GameStatus::Disconnected => STATUSES[6],
+  }
+}
@@ -1,8 +1,15 @@
 #![warn(rust_2018_idioms)]
 
+mod admin;
+mod metrics;
 mod server;
+mod storage;
 
-use crate::server::{client_connected, WebsocketServer};
+use crate::admin::serve_admin;
+use crate::server::trusted_proxies;
+use crate::server::WebsocketServer;
+use crate::server::{client_connected, restore_rooms, set_up_logging, shut_down_on_sigterm};
+use crate::storage::open_stores;
 use futures::join;
 use std::error::Error;
 use std::path::Path;
@@ -28,12 +35,23 @@ Also this server must be run from the server directory."
   let fileserver = warp::fs::dir(path).with(warp::compression::gzip());
 
   // Define the websocket server
-  let gameserver: Arc<Mutex<WebsocketServer>> = Arc::default();
+  set_up_logging();
+  let (store, stats) = open_stores()?;
+  let gameserver = Arc::new(Mutex::new(
+    WebsocketServer::new(store)
+      .with_stats_store(stats)
+      .with_trusted_proxies(trusted_proxies()?),
+  ));
+  restore_rooms(&gameserver);
+  tokio::spawn(shut_down_on_sigterm(gameserver.clone()));
+  tokio::spawn(serve_admin(gameserver.clone()));
   let gameserver = warp::any().map(move || gameserver.clone());
   let websocket_server = warp::ws()
+    .and(warp::addr::remote())
+    .and(warp::header::optional::<String>("x-forwarded-for"))
     .and(gameserver)
-    .map(|ws: warp::ws::Ws, gameserver| {
-      ws.on_upgrade(move |socket| client_connected(socket, gameserver))
+    .map(|ws: warp::ws::Ws, remote, forwarded_for, gameserver| {
+      ws.on_upgrade(move |socket| client_connected(socket, remote, forwarded_for, gameserver))
     });
 
   // If an incoming request looks like a websockets request, serve it as one,
@@ -57,11 +75,11 @@ Also this server must be run from the server directory."
       )
     }))
     .run(([0, 0, 0, 0], 80));
-    println!("Listening for static file traffic on https://airlock.chat/");
+    tracing::info!("Listening for static file traffic on https://airlock.chat/");
     join!(tls_server, http_server);
   } else {
     let server = warp::serve(server).run(([0, 0, 0, 0], 80));
-    println!("Listening for static file traffic on http://localhost");
+    tracing::info!("Listening for static file traffic on http://localhost");
     server.await;
   }
 
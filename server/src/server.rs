@@ -1,9 +1,21 @@
 use futures_channel::mpsc::{unbounded, UnboundedSender};
 use futures_util::{future, pin_mut, stream::TryStreamExt, StreamExt};
-use rust_us_core::ServerToClientMessage;
-use rust_us_core::{Broadcaster, ClientToServerMessage, GameServer, GameStatus, UUID};
-use std::collections::HashMap;
+use rand::Rng;
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use rust_us_core::{get_version_sha, ServerToClientMessage};
+use rust_us_core::{Broadcaster, ClientToServerMessage, GameServer, GameStatus, Settings, UUID};
+use rust_us_core::{DisplayMessage, Message as GameMessage, SavedGame};
+use rust_us_core::{EncodedMessage, Encoding, IncompatibleVersion, PROTOCOL_VERSION};
+use rust_us_core::{JoinError, KickReason, Map, RoomSummary, RoomVisibility};
+use rust_us_core::{PlayerAccountId, PlayerStats};
+use rust_us_core::{RateLimitVerdict, RateLimiter, SystemClock, Ticker};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
@@ -12,38 +24,660 @@ use tokio::time::delay_for;
 use warp::ws::Message;
 use warp::ws::WebSocket;
 
+use crate::metrics::{status_label, METRICS};
+use crate::storage::{MemoryStore, RoomStore, StatsStore};
+use rust_us_core::{init_logging, LogFormat};
+
 type Tx = UnboundedSender<Message>;
-type Room = Arc<Mutex<HashMap<UUID, Tx>>>;
+type Peers = Arc<Mutex<HashMap<UUID, Peer>>>;
+
+// Someone connected to a room, and how they'd like their messages.
+struct Peer {
+  tx: Tx,
+  encoding: Encoding,
+  // Players whose chat messages they don't want to see.
+  muted: HashSet<UUID>,
+  // Where they're connecting from, which bans go by along with their account,
+  // since a new connection gets a new UUID.
+  identity: Option<IpAddr>,
+  // Who they say they are, for keeping their stats.
+  account: Option<PlayerAccountId>,
+}
+
+impl Peer {
+  fn wants(&self, message: &ServerToClientMessage) -> bool {
+    match message {
+      ServerToClientMessage::Chat(chat) => !self.muted.contains(&chat.sender),
+      _ => true,
+    }
+  }
+}
+
+// Join codes are made from these. There's no 0/O or 1/I, so that they're easy
+// to read out to your friends.
+const JOIN_CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const JOIN_CODE_LENGTH: usize = 5;
 
+// Where games that are still going get saved when the server shuts down.
+pub const SAVED_ROOMS_DIR: &str = "saved_rooms";
+// Where everyone's stats are kept.
+pub const PLAYER_STATS_DIR: &str = "player_stats";
+
+// We only ever keep room passwords salted and hashed.
+#[derive(Clone, Serialize, Deserialize)]
+struct PasswordHash {
+  salt: [u8; 16],
+  hash: [u8; 32],
+}
+
+impl PasswordHash {
+  const ITERATIONS: u32 = 100_000;
+
+  fn new(password: &str) -> Result<Self, Box<dyn Error>> {
+    let mut salt = [0u8; 16];
+    SystemRandom::new()
+      .fill(&mut salt)
+      .map_err(|_| "Unable to generate a salt")?;
+    let mut hash = [0u8; 32];
+    pbkdf2::derive(
+      pbkdf2::PBKDF2_HMAC_SHA256,
+      Self::iterations(),
+      &salt,
+      password.as_bytes(),
+      &mut hash,
+    );
+    Ok(PasswordHash { salt, hash })
+  }
+
+  fn iterations() -> NonZeroU32 {
+    NonZeroU32::new(Self::ITERATIONS).unwrap()
+  }
+
+  fn matches(&self, password: &str) -> bool {
+    pbkdf2::verify(
+      pbkdf2::PBKDF2_HMAC_SHA256,
+      Self::iterations(),
+      &self.salt,
+      password.as_bytes(),
+      &self.hash,
+    )
+    .is_ok()
+  }
+}
+
+// How long the simulation loop is taking to run a room's game, for keeping an
+// eye on how loaded the server is.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct TickTimes {
+  pub ticks: u64,
+  pub last: Duration,
+  // Weighted towards the last second or so.
+  pub average: Duration,
+}
+
+impl TickTimes {
+  fn record(&mut self, took: Duration) {
+    self.average = if self.ticks == 0 {
+      took
+    } else {
+      (self.average * 63 + took) / 64
+    };
+    self.last = took;
+    self.ticks += 1;
+  }
+}
+
+// What the admin interface shows about a room. Unlike RoomSummary, this is
+// for every room, public or not.
+#[derive(Clone, Debug, Serialize)]
+pub struct RoomStats {
+  pub code: String,
+  pub visibility: RoomVisibility,
+  pub status: String,
+  pub players: usize,
+  pub spectators: usize,
+  pub connections: usize,
+  pub tick_times: TickTimes,
+}
+
+// A single game, and everyone connected to it.
 #[derive(Clone)]
-pub struct WebsocketServer {
-  room: Room,
+pub struct Room {
+  pub code: String,
+  pub visibility: RoomVisibility,
+  password: Option<PasswordHash>,
+  peers: Peers,
   game_server: Arc<Mutex<GameServer>>,
+  // Whether there's a loop running the game yet.
+  simulating: Arc<AtomicBool>,
+  tick_times: Arc<Mutex<TickTimes>>,
+  // Who the host has banned. These don't survive a restart.
+  banned: Arc<Mutex<Bans>>,
+}
+
+// Everything we know a banned player by, so that a new connection doesn't get
+// them back in.
+#[derive(Default)]
+struct Bans {
+  addresses: HashSet<IpAddr>,
+  accounts: HashSet<PlayerAccountId>,
+  // Who they were in the game, so they can't rejoin as that player.
+  players: HashSet<UUID>,
+}
+
+impl Bans {
+  fn covers(&self, identity: Option<IpAddr>, account: Option<PlayerAccountId>) -> bool {
+    identity.is_some_and(|identity| self.addresses.contains(&identity))
+      || account.is_some_and(|account| self.accounts.contains(&account))
+  }
 }
 
+impl Room {
+  fn new(
+    code: String,
+    settings: Settings,
+    visibility: RoomVisibility,
+    password: Option<PasswordHash>,
+  ) -> Self {
+    let peers = Peers::default();
+    let mut game_server = GameServer::new(
+      Box::new(BroadCastServer {
+        peers: peers.clone(),
+      }),
+      true,
+    );
+    if let Err(e) = game_server.set_settings(settings) {
+      tracing::warn!(room = %code, error = %e, "Unable to set up the room");
+    }
+    Room {
+      code,
+      visibility,
+      password,
+      peers,
+      game_server: Arc::new(Mutex::new(game_server)),
+      simulating: Arc::default(),
+      tick_times: Arc::default(),
+      banned: Arc::default(),
+    }
+  }
+
+  fn finished(&self) -> bool {
+    self.game_server.lock().unwrap().state.status.finished()
+  }
+
+  fn summary(&self) -> RoomSummary {
+    let game_server = self.game_server.lock().unwrap();
+    let state = &game_server.state;
+    RoomSummary {
+      code: self.code.clone(),
+      players: state.players.len(),
+      map_name: state.map.name.to_string(),
+      in_progress: matches!(
+        state.status,
+        GameStatus::Playing(_) | GameStatus::Paused { .. }
+      ),
+      has_password: self.password.is_some(),
+    }
+  }
+
+  fn stats(&self) -> RoomStats {
+    let game_server = self.game_server.lock().unwrap();
+    let state = &game_server.state;
+    RoomStats {
+      code: self.code.clone(),
+      visibility: self.visibility,
+      status: format!("{:?}", state.status),
+      players: state.players.len(),
+      spectators: state.spectators.len(),
+      connections: self.peers.lock().unwrap().len(),
+      tick_times: *self.tick_times.lock().unwrap(),
+    }
+  }
+
+  // Ends the game, without saving it. Everyone gets hung up on once the
+  // simulation loop notices.
+  fn terminate(&self) {
+    let mut game_server = self.game_server.lock().unwrap();
+    let notice = ServerToClientMessage::DisplayMessage(DisplayMessage {
+      message: GameMessage::PlainString("This game has been shut down by the server.".to_string()),
+      duration: Duration::from_secs(10),
+      delay_before_show: Duration::from_secs(0),
+    });
+    if let Err(e) = broadcast(self.peers.clone(), &notice) {
+      tracing::warn!(room = %self.code, error = %e, "Unable to tell the room it's over");
+    }
+    game_server.state.status = GameStatus::Disconnected;
+  }
+
+  fn check_banned(
+    &self,
+    identity: Option<IpAddr>,
+    account: Option<PlayerAccountId>,
+  ) -> Result<(), JoinError> {
+    if self.banned.lock().unwrap().covers(identity, account) {
+      return Err(JoinError::Banned);
+    }
+    Ok(())
+  }
+
+  fn check_rejoin(&self, player: UUID) -> Result<(), JoinError> {
+    if self.banned.lock().unwrap().players.contains(&player) {
+      return Err(JoinError::Banned);
+    }
+    Ok(())
+  }
+
+  // Throws someone out at the host's request, and for a ban, keeps them out
+  // whether they come back from the same place, as the same account, or as
+  // the same player.
+  fn kick(&self, host_connection: UUID, target: UUID, reason: KickReason) {
+    let mut game_server = self.game_server.lock().unwrap();
+    let connection = match game_server.kick(host_connection, target, reason) {
+      Ok(connection) => connection,
+      Err(e) => {
+        return tracing::info!(room = %self.code, uuid = %target, error = %e, "Unable to kick them")
+      }
+    };
+    {
+      let peers = self.peers.lock().unwrap();
+      if let Some(peer) = peers.get(&connection) {
+        tracing::info!(
+          room = %self.code,
+          uuid = %target,
+          peer = peer.identity.map(tracing::field::display),
+          ?reason,
+          "Kicked out of the room"
+        );
+        if reason == KickReason::Banned {
+          let mut banned = self.banned.lock().unwrap();
+          banned.addresses.extend(peer.identity);
+          banned.accounts.extend(peer.account);
+          banned.players.insert(target);
+        }
+        if let Err(e) = peer.tx.unbounded_send(Message::close()) {
+          tracing::warn!(uuid = %target, error = %e, "Error with unbounded send");
+        }
+      }
+    }
+    if let Err(e) = game_server.disconnected(connection) {
+      tracing::warn!(uuid = %target, error = %e, "Error handling disconnection");
+    }
+  }
+
+  fn check_password(&self, password: &Option<String>) -> Result<(), JoinError> {
+    match (&self.password, password) {
+      (None, _) => Ok(()),
+      (Some(hash), Some(password)) if hash.matches(password) => Ok(()),
+      (Some(_), _) => Err(JoinError::WrongPassword),
+    }
+  }
+
+  // Lets everyone know we're going away, and saves the game for the next
+  // server to pick up. Nothing happens in the room after this.
+  fn shut_down(&self) -> Option<SavedRoom> {
+    let mut game_server = self.game_server.lock().unwrap();
+    if game_server.state.status.finished() {
+      return None;
+    }
+    let notice = ServerToClientMessage::DisplayMessage(DisplayMessage {
+      message: GameMessage::PlainString(
+        "The server is restarting, your game will be right back.".to_string(),
+      ),
+      duration: Duration::from_secs(10),
+      delay_before_show: Duration::from_secs(0),
+    });
+    if let Err(e) = broadcast(self.peers.clone(), &notice) {
+      tracing::warn!(room = %self.code, error = %e, "Unable to tell the room we're restarting");
+    }
+    let game = game_server.save();
+    let quick_chat_only = game_server.state.settings.quick_chat_only;
+    // The simulation loop hangs up on everyone once it sees this.
+    game_server.state.status = GameStatus::Disconnected;
+    Some(SavedRoom {
+      code: self.code.clone(),
+      visibility: self.visibility,
+      password: self.password.clone(),
+      quick_chat_only,
+      game: game?,
+    })
+  }
+
+  fn restore(saved: SavedRoom) -> Result<Self, Box<dyn Error>> {
+    let peers = Peers::default();
+    let mut game_server = GameServer::restore(
+      saved.game,
+      Box::new(BroadCastServer {
+        peers: peers.clone(),
+      }),
+    )?;
+    // Recordings don't keep the settings, and free text that the room
+    // wouldn't have allowed never made it into the recording anyway.
+    game_server.state.settings.quick_chat_only = saved.quick_chat_only;
+    Ok(Room {
+      code: saved.code,
+      visibility: saved.visibility,
+      password: saved.password,
+      peers,
+      game_server: Arc::new(Mutex::new(game_server)),
+      simulating: Arc::default(),
+      tick_times: Arc::default(),
+      banned: Arc::default(),
+    })
+  }
+}
+
+// A room as it was when the last server shut down.
+#[derive(Serialize, Deserialize)]
+struct SavedRoom {
+  code: String,
+  visibility: RoomVisibility,
+  password: Option<PasswordHash>,
+  quick_chat_only: bool,
+  game: SavedGame,
+}
+
+impl SavedRoom {
+  fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(bincode::serialize(self)?)
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+    Ok(bincode::deserialize(bytes)?)
+  }
+}
+
+// Runs any number of games side by side, each in its own room.
+pub struct WebsocketServer {
+  rooms: HashMap<String, Room>,
+  // Where players who don't ask for a particular room end up.
+  default_room: Option<String>,
+  // Rooms that aren't running anywhere right now, but could be picked back up.
+  store: Box<dyn RoomStore>,
+  stats: Box<dyn StatsStore>,
+  // Reverse proxies in front of us, whose word we take for where someone's
+  // connecting from.
+  trusted_proxies: Vec<IpAddr>,
+}
+
+// Without anywhere else to put them, rooms are gone when the server is.
 impl Default for WebsocketServer {
   fn default() -> Self {
-    let room = Room::default();
-    let game_server = Arc::new(Mutex::new(GameServer::new(
-      Box::new(BroadCastServer { room: room.clone() }),
-      true,
-    )));
-    WebsocketServer { room, game_server }
+    WebsocketServer::new(Box::new(MemoryStore::default()))
   }
 }
 
-impl WebsocketServer {}
+impl WebsocketServer {
+  pub fn new(store: Box<dyn RoomStore>) -> Self {
+    WebsocketServer {
+      rooms: HashMap::new(),
+      default_room: None,
+      store,
+      stats: Box::new(MemoryStore::default()),
+      trusted_proxies: vec![],
+    }
+  }
+
+  // By default, stats are forgotten along with the server.
+  pub fn with_stats_store(mut self, stats: Box<dyn StatsStore>) -> Self {
+    self.stats = stats;
+    self
+  }
+
+  // By default, everyone is connecting from wherever their socket says.
+  pub fn with_trusted_proxies(mut self, proxies: Vec<IpAddr>) -> Self {
+    self.trusted_proxies = proxies;
+    self
+  }
+
+  fn stats_for(&self, account: Option<PlayerAccountId>) -> PlayerStats {
+    let account = match account {
+      Some(account) => account,
+      None => return PlayerStats::default(),
+    };
+    self.stats.get(&account).unwrap_or_else(|e| {
+      tracing::warn!(%account, error = %e, "Unable to look up their stats");
+      PlayerStats::default()
+    })
+  }
+
+  fn record_stats(&mut self, finished: Vec<(PlayerAccountId, PlayerStats)>) {
+    for (account, game) in finished.iter() {
+      if let Err(e) = self.stats.record(account, game) {
+        tracing::warn!(%account, error = %e, "Unable to save their stats");
+      }
+    }
+  }
+
+  pub fn create_room(
+    &mut self,
+    settings: Settings,
+    visibility: RoomVisibility,
+    password: Option<&str>,
+  ) -> Result<Room, Box<dyn Error>> {
+    let code = loop {
+      let code = gen_join_code();
+      if !self.code_taken(&code) {
+        break code;
+      }
+    };
+    let password = match password {
+      Some(password) => Some(PasswordHash::new(password)?),
+      None => None,
+    };
+    let room = Room::new(code.clone(), settings, visibility, password);
+    self.rooms.insert(code, room.clone());
+    tracing::info!(room = %room.code, open_rooms = ?self.room_codes(), "Opened room");
+    Ok(room)
+  }
+
+  pub fn find_room(&self, code: &str) -> Option<Room> {
+    self.rooms.get(&normalize_code(code)?).cloned()
+  }
+
+  // Picks up a room from the store, if no one else has already.
+  fn claim_room(&mut self, code: &str) -> Option<Room> {
+    let code = normalize_code(code)?;
+    let claimed = match self.store.claim(&code) {
+      Ok(claimed) => claimed?,
+      Err(e) => {
+        tracing::warn!(room = %code, error = %e, "Unable to look for the room in the store");
+        return None;
+      }
+    };
+    let room = match SavedRoom::from_bytes(&claimed).and_then(Room::restore) {
+      Ok(room) => room,
+      Err(e) => {
+        tracing::warn!(room = %code, error = %e, "Unable to restore the room");
+        // Leave it be, rather than throw away someone's game.
+        if let Err(e) = self.store.release(&code) {
+          tracing::warn!(room = %code, error = %e, "Unable to put the room back in the store");
+        }
+        return None;
+      }
+    };
+    if let Err(e) = self.store.remove(&code) {
+      tracing::warn!(room = %code, error = %e, "Unable to clear the room out of the store");
+    }
+    tracing::info!(room = %room.code, "Restored room");
+    self.rooms.insert(room.code.clone(), room.clone());
+    Some(room)
+  }
+
+  fn code_taken(&self, code: &str) -> bool {
+    if self.rooms.contains_key(code) {
+      return true;
+    }
+    match self.store.codes() {
+      Ok(codes) => codes.iter().any(|c| c == code),
+      Err(e) => {
+        tracing::warn!(error = %e, "Unable to list the rooms in the store");
+        false
+      }
+    }
+  }
+
+  pub fn room_codes(&self) -> Vec<String> {
+    let mut codes: Vec<String> = self.rooms.keys().cloned().collect();
+    codes.sort();
+    codes
+  }
+
+  // The rooms that anyone can find and join, for the server browser.
+  pub fn list_rooms(&self) -> Vec<RoomSummary> {
+    let mut rooms: Vec<RoomSummary> = self
+      .rooms
+      .values()
+      .filter(|room| room.visibility == RoomVisibility::Public && !room.finished())
+      .map(|room| room.summary())
+      .collect();
+    rooms.sort_by(|a, b| a.code.cmp(&b.code));
+    rooms
+  }
+
+  // Every room, for the admin interface.
+  pub fn room_stats(&self) -> Vec<RoomStats> {
+    let mut rooms: Vec<RoomStats> = self.rooms.values().map(|room| room.stats()).collect();
+    rooms.sort_by(|a, b| a.code.cmp(&b.code));
+    rooms
+  }
+
+  pub fn rooms_by_status(&self) -> BTreeMap<&'static str, usize> {
+    let mut counts = BTreeMap::new();
+    for room in self.rooms.values() {
+      let game_server = room.game_server.lock().unwrap();
+      *counts
+        .entry(status_label(&game_server.state.status))
+        .or_insert(0) += 1;
+    }
+    counts
+  }
+
+  // The whole state of a room's game, as JSON, or None if there's no such
+  // room.
+  pub fn snapshot_room(&self, code: &str) -> Option<Result<String, Box<dyn Error>>> {
+    let room = self.find_room(code)?;
+    let game_server = room.game_server.lock().unwrap();
+    Some(game_server.state.to_snapshot())
+  }
+
+  // Returns whether there was a room to terminate.
+  pub fn terminate_room(&mut self, code: &str) -> bool {
+    let room = match self.find_room(code) {
+      Some(room) => room,
+      None => return false,
+    };
+    room.terminate();
+    // Otherwise the simulation loop takes care of it.
+    if !room.simulating.load(Ordering::SeqCst) {
+      self.rooms.remove(&room.code);
+    }
+    tracing::info!(room = %room.code, "Terminated room");
+    true
+  }
+
+  // Puts every game that's still going in the store, for the next server to
+  // pick up.
+  fn shut_down(&mut self) {
+    let store = &mut self.store;
+    for room in self.rooms.values() {
+      let saved = match room.shut_down() {
+        Some(saved) => saved,
+        None => continue,
+      };
+      match saved
+        .to_bytes()
+        .and_then(|bytes| store.put(&room.code, bytes))
+      {
+        Ok(()) => tracing::info!(room = %room.code, "Saved room"),
+        Err(e) => tracing::warn!(room = %room.code, error = %e, "Unable to save the room"),
+      }
+    }
+  }
+
+  // Once the last game is over, players without a code get a fresh one.
+  fn default_room(&mut self) -> Result<Room, Box<dyn Error>> {
+    let current = self
+      .default_room
+      .as_ref()
+      .and_then(|code| self.rooms.get(code));
+    if let Some(room) = current {
+      if !room.finished() {
+        return Ok(room.clone());
+      }
+    }
+    let room = self.create_room(Settings::default(), RoomVisibility::Public, None)?;
+    self.default_room = Some(room.code.clone());
+    Ok(room)
+  }
+
+  // Works out which room a new connection belongs in, based on the first thing it says.
+  fn route(&mut self, message: &ClientToServerMessage) -> Result<Room, Box<dyn Error>> {
+    match message {
+      ClientToServerMessage::CreateRoom {
+        visibility,
+        password,
+        quick_chat_only,
+        map_id,
+        custom_map,
+      } => {
+        // Check the map before making a room for it.
+        let custom_map = match custom_map {
+          Some(json) => Some(Map::from_upload(json).map_err(JoinError::BadMap)?),
+          None => None,
+        };
+        let settings = Settings {
+          quick_chat_only: *quick_chat_only,
+          map_id: *map_id,
+          ..Settings::default()
+        };
+        let room = self.create_room(settings, *visibility, password.as_deref())?;
+        if let Some(map) = custom_map {
+          room.game_server.lock().unwrap().set_map(map)?;
+        }
+        Ok(room)
+      }
+      ClientToServerMessage::JoinRoom { code, password } => {
+        let room = self
+          .find_room(code)
+          .or_else(|| self.claim_room(code))
+          .ok_or(JoinError::RoomNotFound)?;
+        room.check_password(password)?;
+        Ok(room)
+      }
+      _ => self.default_room(),
+    }
+  }
+}
+
+// The code as we'd have handed it out, or None if it couldn't be one of ours.
+// The stores use codes as file names and keys, so anything else mustn't get
+// anywhere near them.
+fn normalize_code(code: &str) -> Option<String> {
+  let code = code.trim().to_uppercase();
+  let valid = code.len() == JOIN_CODE_LENGTH && code.bytes().all(|c| JOIN_CODE_CHARS.contains(&c));
+  if valid {
+    Some(code)
+  } else {
+    None
+  }
+}
+
+fn gen_join_code() -> String {
+  let mut rng = rand::thread_rng();
+  (0..JOIN_CODE_LENGTH)
+    .map(|_| JOIN_CODE_CHARS[rng.gen_range(0, JOIN_CODE_CHARS.len())] as char)
+    .collect()
+}
 
 struct BroadCastServer {
-  room: Room,
+  peers: Peers,
 }
 
 impl Broadcaster for BroadCastServer {
   fn broadcast(&self, message: &ServerToClientMessage) -> Result<(), Box<dyn Error>> {
-    println!("Broadcasting {:?}", message);
-    let message = Message::text(serde_json::to_string(message)?);
-    broadcast(self.room.clone(), &message);
-    Ok(())
+    tracing::debug!(?message, "Broadcasting");
+    METRICS.note_events(message, None);
+    broadcast(self.peers.clone(), message)
   }
 
   fn send_to_player(
@@ -51,155 +685,558 @@ impl Broadcaster for BroadCastServer {
     uuid: &UUID,
     message: &ServerToClientMessage,
   ) -> Result<(), Box<dyn Error>> {
-    let room = self.room.clone();
-    let peers = room.lock().unwrap();
+    METRICS.note_events(message, Some(uuid));
+    let peers = self.peers.lock().unwrap();
     let player_connection = match peers.get(uuid) {
       None => {
         // This could be a race condition with a player disconnecting.
         // Not a fatal error.
-        println!("No player connection with UUID {:?}", uuid);
+        tracing::warn!(%uuid, "No player connection with that UUID");
         return Ok(());
       }
       Some(p) => p,
     };
-    println!("Sending {:?} to {:?}", message, uuid);
-    let encoded = Message::text(serde_json::to_string(message)?);
-    if let Err(e) = player_connection.unbounded_send(encoded) {
-      println!("Error with unbounded send: {}", e);
+    if !player_connection.wants(message) {
+      return Ok(());
     }
+    tracing::debug!(%uuid, ?message, "Sending");
+    send(&player_connection.tx, player_connection.encoding, message);
     Ok(())
   }
+
+  fn disconnect(&self, connection: &UUID) {
+    if let Some(peer) = self.peers.lock().unwrap().get_mut(connection) {
+      // Their connection has its own handle on the channel, so it takes a
+      // close frame to actually hang up. Anything already queued goes first.
+      if let Err(e) = peer.tx.unbounded_send(Message::close()) {
+        tracing::warn!(uuid = %connection, error = %e, "Error with unbounded send");
+      }
+      peer.tx.disconnect();
+    }
+  }
 }
 
-pub async fn client_connected(ws: WebSocket, ws_server: Arc<Mutex<WebsocketServer>>) {
-  let game_server;
-  let room;
-  {
-    let mut ws_server = ws_server.lock().unwrap();
-    let prev_game_finished;
-    {
-      let game_server = ws_server.game_server.lock().unwrap();
-      prev_game_finished = game_server.state.status.finished();
+// One JSON object per line, for something else to collect and search
+// through. AIRLOCK_LOG=debug to see every message going back and forth.
+pub fn set_up_logging() {
+  let level = std::env::var("AIRLOCK_LOG")
+    .ok()
+    .and_then(|level| level.parse().ok())
+    .unwrap_or(tracing::Level::INFO);
+  init_logging(LogFormat::Json, level, |line| println!("{}", line));
+}
+
+// The addresses of any reverse proxies in front of us, from
+// AIRLOCK_TRUSTED_PROXIES, separated by commas.
+pub fn trusted_proxies() -> Result<Vec<IpAddr>, Box<dyn Error>> {
+  let proxies = std::env::var("AIRLOCK_TRUSTED_PROXIES").unwrap_or_default();
+  let mut addresses = vec![];
+  for proxy in proxies.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+    match proxy.parse() {
+      Ok(address) => addresses.push(address),
+      Err(e) => {
+        return Err(format!("Bad address {} in AIRLOCK_TRUSTED_PROXIES: {}", proxy, e).into())
+      }
+    }
+  }
+  Ok(addresses)
+}
+
+// Where someone's really connecting from. Behind a proxy, that's whatever it
+// says in X-Forwarded-For, where each proxy adds who it heard from onto the
+// end. Anyone can write anything before that, so it's the last address that
+// isn't one of our own proxies. If a proxy didn't tell us, we don't know,
+// rather than lump everyone behind it together.
+fn client_address(
+  remote: Option<SocketAddr>,
+  forwarded_for: Option<&str>,
+  trusted: &[IpAddr],
+) -> Option<IpAddr> {
+  let remote = remote?.ip();
+  if !trusted.contains(&remote) {
+    return Some(remote);
+  }
+  for address in forwarded_for?.rsplit(',') {
+    match address.trim().parse() {
+      Ok(address) if trusted.contains(&address) => continue,
+      Ok(address) => return Some(address),
+      Err(_) => return None,
     }
-    if prev_game_finished {
-      // The previous game is finished. Create a new game and direct future players to it.
-      let room = Room::default();
-      let broadcast_server = BroadCastServer { room: room.clone() };
-      ws_server.room = room;
-      ws_server.game_server = Arc::new(Mutex::new(GameServer::new(
-        Box::new(broadcast_server),
-        true,
-      )));
-      println!("Starting a new game for the new client!");
+  }
+  None
+}
+
+pub async fn client_connected(
+  ws: WebSocket,
+  remote: Option<SocketAddr>,
+  forwarded_for: Option<String>,
+  ws_server: Arc<Mutex<WebsocketServer>>,
+) {
+  let identity = {
+    let trusted = &ws_server.lock().unwrap().trusted_proxies;
+    client_address(remote, forwarded_for.as_deref(), trusted)
+  };
+  tokio::spawn(handle_connection(ws_server, ws, identity));
+}
+
+// Makes sure that someone is simulating the game in this room.
+fn start_simulating(ws_server: &Arc<Mutex<WebsocketServer>>, room: &Room) {
+  if room.simulating.swap(true, Ordering::SeqCst) {
+    return;
+  }
+  let mut game_server = room.game_server.lock().unwrap();
+  if game_server.state.status == GameStatus::Connecting {
+    game_server.state.status = GameStatus::Lobby;
+  }
+  tokio::spawn(simulation_loop(ws_server.clone(), room.clone()));
+}
+
+// Picks up every game in the store, for when they were left there by the last
+// server to run.
+pub fn restore_rooms(ws_server: &Arc<Mutex<WebsocketServer>>) {
+  let codes = match ws_server.lock().unwrap().store.codes() {
+    Ok(codes) => codes,
+    Err(e) => return tracing::warn!(error = %e, "Unable to list the rooms in the store"),
+  };
+  for code in codes {
+    let room = ws_server.lock().unwrap().claim_room(&code);
+    if let Some(room) = room {
+      start_simulating(ws_server, &room);
     }
-    game_server = ws_server.game_server.clone();
-    room = ws_server.room.clone();
   }
-  tokio::spawn(handle_connection(game_server, room, ws));
 }
 
-async fn simulation_loop(game_server: Arc<Mutex<GameServer>>, room: Room) {
-  let mut prev = Instant::now();
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn note_sigterm(_: libc::c_int) {
+  SHUTTING_DOWN.store(true, Ordering::SeqCst);
+}
+
+// When we're asked to stop, say for a deploy, saves every game that's still
+// going before exiting, so that they survive the restart.
+pub async fn shut_down_on_sigterm(ws_server: Arc<Mutex<WebsocketServer>>) {
+  // Signal handlers can't do much safely, so this one just raises a flag.
+  unsafe {
+    libc::signal(
+      libc::SIGTERM,
+      note_sigterm as extern "C" fn(libc::c_int) as libc::sighandler_t,
+    );
+  }
+  while !SHUTTING_DOWN.load(Ordering::SeqCst) {
+    delay_for(Duration::from_millis(100)).await;
+  }
+  tracing::info!("Shutting down, saving rooms");
+  ws_server.lock().unwrap().shut_down();
+  // Give the simulation loops a moment to hang up on everyone.
+  delay_for(Duration::from_secs(1)).await;
+  std::process::exit(0);
+}
+
+async fn simulation_loop(ws_server: Arc<Mutex<WebsocketServer>>, room: Room) {
+  let clock = SystemClock;
+  let mut ticker = Ticker::new(Duration::from_millis(16), &clock);
   loop {
-    delay_for(Duration::from_millis(16)).await;
-    let now = Instant::now();
-    let elapsed = now - prev;
-    prev = now;
-    let mut game_server = game_server.lock().unwrap();
+    delay_for(ticker.until_next(&clock)).await;
+    let elapsed = ticker.tick(&clock);
+    let _room = tracing::info_span!("room", room = %room.code).entered();
+    let mut game_server = room.game_server.lock().unwrap();
+    let started = Instant::now();
     // The server wants to disconnect the players (e.g. timeout),
     // so close all the connections.
     let finished = match game_server.simulate(elapsed) {
       Err(e) => {
-        println!("Error handling simulation: {}", e);
+        tracing::warn!(error = %e, "Error handling simulation");
         true
       }
       Ok(b) => b,
     };
+    let took = started.elapsed();
+    room.tick_times.lock().unwrap().record(took);
+    METRICS.tick(took);
+    let finished_stats = game_server.take_finished_stats();
+    drop(game_server);
+    if let Some(finished) = finished_stats {
+      // Only players who told us who they are get anything out of it.
+      let finished: Vec<_> = {
+        let peers = room.peers.lock().unwrap();
+        finished
+          .into_iter()
+          .filter_map(|(uuid, stats)| Some((peers.get(&uuid)?.account?, stats)))
+          .collect()
+      };
+      ws_server.lock().unwrap().record_stats(finished);
+    }
     if finished {
-      println!("Game finished, done simulating it on the server.");
-      let mut room = room.lock().unwrap();
-      for (_, player) in room.iter_mut() {
-        player.disconnect();
+      tracing::info!(
+        room = %room.code,
+        "Game finished, done simulating it on the server"
+      );
+      let mut peers = room.peers.lock().unwrap();
+      for (_, peer) in peers.iter_mut() {
+        peer.tx.disconnect();
       }
       break;
     }
   }
+  ws_server.lock().unwrap().rooms.remove(&room.code);
 }
 
-async fn handle_connection(game_server: Arc<Mutex<GameServer>>, room: Room, socket: WebSocket) {
-  // Ensure we're simulating this game.
-  {
-    let mut game_server_unlocked = game_server.lock().unwrap();
-    if game_server_unlocked.state.status == GameStatus::Connecting {
-      game_server_unlocked.state.status = GameStatus::Lobby;
-      tokio::spawn(simulation_loop(game_server.clone(), room.clone()));
-    }
+fn to_websocket_message(encoded: EncodedMessage) -> Message {
+  match encoded {
+    EncodedMessage::Text(text) => Message::text(text),
+    EncodedMessage::Binary(bytes) => Message::binary(bytes),
+  }
+}
+
+fn send(tx: &Tx, encoding: Encoding, message: &ServerToClientMessage) {
+  let encoded = match encoding.encode(message) {
+    Ok(encoded) => to_websocket_message(encoded),
+    Err(e) => return tracing::warn!(?message, error = %e, "Unable to encode"),
+  };
+  match tx.unbounded_send(encoded) {
+    Ok(()) => METRICS.messages_out(1),
+    Err(e) => tracing::warn!(error = %e, "Error with unbounded send"),
   }
+}
 
-  // Insert the write part of this peer to the peer map.
+async fn handle_connection(
+  ws_server: Arc<Mutex<WebsocketServer>>,
+  socket: WebSocket,
+  identity: Option<IpAddr>,
+) {
   let (tx, rx) = unbounded();
   let (outgoing, incoming) = socket.split();
 
   // Generate a connection id for our user.
-  let uuid = game_server.lock().unwrap().get_uuid_for_new_connection();
-
-  // Hook up the ability to send messages to this client.
-  room.lock().unwrap().insert(uuid, tx);
+  let uuid = UUID::random();
+  // We don't know which room they're in until they tell us.
+  let mut room: Option<Room> = None;
+  let mut asked_for_room = false;
+  // Until they tell us otherwise.
+  let mut encoding = Encoding::Json;
+  let mut account = None;
+  // Clients we can't understand, or that won't stop flooding us, get hung up
+  // on and then ignored.
+  let mut hung_up = false;
+  let mut rate_limiter = RateLimiter::new(Instant::now());
+  // Where they're connecting from, when we know.
+  let peer = identity.map(tracing::field::display);
+  tracing::info!(%uuid, peer, "Connected");
+  METRICS.client_connected();
 
-  // Buffer and forward messages.
-  let receive_from_others = rx.map(Ok).forward(outgoing);
+  {
+    // Buffer and forward messages.
+    let receive_from_others = rx.map(Ok).forward(outgoing);
 
-  // Handle incoming messages from the client.
-  let broadcast_incoming = incoming.try_for_each(|msg| {
-    let message_text = match msg.to_str() {
-      Ok(s) => s,
-      Err(_) => return future::ok(()), // other kind of message, ignore
-    };
-    println!("Received a message from {}: {:?}", uuid, message_text);
-    let message: ClientToServerMessage = match serde_json::from_str(&message_text) {
-      Ok(m) => m,
-      Err(e) => {
-        println!("Unable to deserialize {:?} – {:?}", message_text, e);
+    // Handle incoming messages from the client.
+    let broadcast_incoming = incoming.try_for_each(|msg| {
+      if hung_up {
         return future::ok(());
       }
-    };
-    let mut game_server = game_server.lock().unwrap();
-    match game_server.handle_message(uuid, message) {
-      Ok(_) => (),
-      Err(e) => {
-        println!("Failed to handle message from {:?}: {}", uuid, e);
+      let _connection = tracing::info_span!("connection", uuid = %uuid, peer).entered();
+      let encoded = if let Ok(text) = msg.to_str() {
+        EncodedMessage::Text(text.to_string())
+      } else if msg.is_binary() {
+        EncodedMessage::Binary(msg.as_bytes().to_vec())
+      } else {
+        return future::ok(()); // other kind of message, ignore
+      };
+      METRICS.message_in();
+      let decoded = encoded.decode::<ClientToServerMessage>();
+      // Garbage costs them just the same, so they can't flood us with it.
+      let verdict = match &decoded {
+        Ok(message) => rate_limiter.check(message, Instant::now()),
+        Err(_) => rate_limiter.check_undecodable(Instant::now()),
+      };
+      match verdict {
+        RateLimitVerdict::Allow => (),
+        RateLimitVerdict::Drop => {
+          tracing::info!(
+            %uuid,
+            kind = decoded.as_ref().map_or("undecodable", |m| m.kind()),
+            "Dropping a message, they're sending too much"
+          );
+          return future::ok(());
+        }
+        RateLimitVerdict::Disconnect => {
+          tracing::warn!(%uuid, peer, "Won't stop flooding us, hanging up");
+          if let Err(e) = tx.unbounded_send(Message::close()) {
+            tracing::warn!(%uuid, error = %e, "Error with unbounded send");
+          }
+          hung_up = true;
+          return future::ok(());
+        }
+      }
+      let message = match decoded {
+        Ok(message) => message,
+        Err(e) => {
+          // Who knows what's in it, so just how big it was. The error can
+          // quote bits of it back at us, so not too much of that either.
+          let size = match &encoded {
+            EncodedMessage::Text(text) => text.len(),
+            EncodedMessage::Binary(bytes) => bytes.len(),
+          };
+          let error: String = e.to_string().chars().take(100).collect();
+          tracing::warn!(%uuid, size, %error, "Unable to deserialize a message");
+          return future::ok(());
+        }
+      };
+      tracing::debug!(%uuid, ?message, "Received a message");
+      if let ClientToServerMessage::ClientHello {
+        protocol_version,
+        client_build,
+        encoding: requested,
+        account: claimed,
+      } = &message
+      {
+        if let Err(e) = IncompatibleVersion::check(*protocol_version) {
+          tracing::info!(
+            %uuid,
+            %client_build,
+            error = %e,
+            "Running a build we can't talk to"
+          );
+          // Always in JSON, since that's the only thing they might understand.
+          send(
+            &tx,
+            Encoding::Json,
+            &ServerToClientMessage::IncompatibleVersion(e),
+          );
+          if let Err(e) = tx.unbounded_send(Message::close()) {
+            tracing::warn!(%uuid, error = %e, "Error with unbounded send");
+          }
+          hung_up = true;
+          return future::ok(());
+        }
+        encoding = *requested;
+        account = *claimed;
+        if let Some(room) = &room {
+          if let Err(e) = room.check_banned(identity, account) {
+            tracing::info!(room = %room.code, %uuid, peer, "Banned account, hanging up");
+            send(&tx, encoding, &ServerToClientMessage::JoinFailed(e));
+            if let Err(e) = tx.unbounded_send(Message::close()) {
+              tracing::warn!(%uuid, error = %e, "Error with unbounded send");
+            }
+            hung_up = true;
+            return future::ok(());
+          }
+          if let Some(peer) = room.peers.lock().unwrap().get_mut(&uuid) {
+            peer.encoding = encoding;
+            peer.account = account;
+          }
+        }
+        send(
+          &tx,
+          encoding,
+          &ServerToClientMessage::ServerHello {
+            protocol_version: PROTOCOL_VERSION,
+            server_build: get_version_sha().to_string(),
+            encoding,
+          },
+        );
         return future::ok(());
       }
-    }
-    future::ok(())
-  });
+      if let ClientToServerMessage::ListRooms() = message {
+        let rooms = ws_server.lock().unwrap().list_rooms();
+        send(&tx, encoding, &ServerToClientMessage::RoomList { rooms });
+        return future::ok(());
+      }
+      if let ClientToServerMessage::GetStats() = message {
+        let stats = ws_server.lock().unwrap().stats_for(account);
+        send(&tx, encoding, &ServerToClientMessage::Stats(stats));
+        return future::ok(());
+      }
+      let picking_room = matches!(
+        message,
+        ClientToServerMessage::CreateRoom { .. } | ClientToServerMessage::JoinRoom { .. }
+      );
+      let current_room = match &room {
+        Some(room) => room.clone(),
+        None => {
+          if !picking_room && asked_for_room {
+            // Their room didn't work out, don't drop them into some other game.
+            return future::ok(());
+          }
+          asked_for_room |= picking_room;
+          let routed = ws_server.lock().unwrap().route(&message).and_then(|room| {
+            room.check_banned(identity, account)?;
+            Ok(room)
+          });
+          let new_room = match routed {
+            Ok(r) => r,
+            Err(e) => {
+              match e.downcast_ref::<JoinError>() {
+                Some(e) => {
+                  tracing::info!(%uuid, peer, reason = ?e, "Turned away from a room");
+                  send(&tx, encoding, &ServerToClientMessage::JoinFailed(*e))
+                }
+                None => tracing::warn!(%uuid, error = %e, "Unable to find a room for them"),
+              }
+              return future::ok(());
+            }
+          };
+          // Hook up the ability to send messages to this client.
+          new_room.peers.lock().unwrap().insert(
+            uuid,
+            Peer {
+              tx: tx.clone(),
+              encoding,
+              muted: HashSet::new(),
+              identity,
+              account,
+            },
+          );
+          tracing::info!(room = %new_room.code, %uuid, peer, "Joined room");
+          start_simulating(&ws_server, &new_room);
+          send(
+            &tx,
+            encoding,
+            &ServerToClientMessage::JoinedRoom {
+              code: new_room.code.clone(),
+            },
+          );
+          room = Some(new_room.clone());
+          new_room
+        }
+      };
+      if picking_room {
+        // Either that's how they got in here, or they're already in a room.
+        return future::ok(());
+      }
+      let _room = tracing::info_span!("room", room = %current_room.code).entered();
+      if let ClientToServerMessage::Mute {
+        uuid: muted_player,
+        muted,
+      } = message
+      {
+        if let Some(peer) = current_room.peers.lock().unwrap().get_mut(&uuid) {
+          if muted {
+            peer.muted.insert(muted_player);
+          } else {
+            peer.muted.remove(&muted_player);
+          }
+        }
+        return future::ok(());
+      }
+      let kick = match message {
+        ClientToServerMessage::KickPlayer { uuid: target } => Some((target, KickReason::Kicked)),
+        ClientToServerMessage::BanPlayer { uuid: target } => Some((target, KickReason::Banned)),
+        _ => None,
+      };
+      if let Some((target, reason)) = kick {
+        current_room.kick(uuid, target, reason);
+        return future::ok(());
+      }
+      if let ClientToServerMessage::Rejoin { uuid: player, .. } = message {
+        if let Err(e) = current_room.check_rejoin(player) {
+          tracing::info!(%uuid, %player, peer, "Tried to rejoin as a banned player");
+          send(&tx, encoding, &ServerToClientMessage::JoinFailed(e));
+          return future::ok(());
+        }
+      }
+      let mut game_server = current_room.game_server.lock().unwrap();
+      match game_server.handle_message(uuid, message) {
+        Ok(_) => (),
+        Err(e) => {
+          tracing::warn!(%uuid, error = %e, "Failed to handle message");
+          return future::ok(());
+        }
+      }
+      future::ok(())
+    });
 
-  // Some magic incantation for futures to work.
-  pin_mut!(broadcast_incoming, receive_from_others);
+    // Some magic incantation for futures to work.
+    pin_mut!(broadcast_incoming, receive_from_others);
 
-  // Wait for either side to close the connection
-  future::select(broadcast_incoming, receive_from_others).await;
+    // Wait for either side to close the connection
+    future::select(broadcast_incoming, receive_from_others).await;
+  }
 
-  println!("{} disconnected", uuid);
-  room.lock().unwrap().remove(&uuid);
+  tracing::info!(
+    room = room.as_ref().map(|room| tracing::field::display(&room.code)),
+    %uuid,
+    peer,
+    "Disconnected"
+  );
+  METRICS.client_disconnected();
+  let room = match room {
+    Some(room) => room,
+    // Never made it into a game, nothing to clean up.
+    None => return,
+  };
+  room.peers.lock().unwrap().remove(&uuid);
 
-  let mut game_server = game_server.lock().unwrap();
-  if game_server.state.status.finished() {
+  let mut game_server = room.game_server.lock().unwrap();
+  if game_server.state.status == GameStatus::Disconnected {
     // The game is done, and the simulation loop will clean up, just return.
   } else {
     // Disconnect this connection from the game server.
     match game_server.disconnected(uuid) {
       Ok(()) => (),
-      Err(e) => println!("Error handling disconnection: {}", e),
+      Err(e) => tracing::warn!(%uuid, error = %e, "Error handling disconnection"),
     }
   }
 }
 
-fn broadcast(room: Room, msg: &Message) {
-  let peers = room.lock().unwrap();
-  for (_, recp) in peers.iter() {
-    if let Err(e) = recp.unbounded_send(msg.clone()) {
-      println!("Error with unbounded send: {}", e);
+// Encodes the message at most once for each encoding that someone wants.
+fn broadcast(peers: Peers, message: &ServerToClientMessage) -> Result<(), Box<dyn Error>> {
+  let peers = peers.lock().unwrap();
+  let mut encoded: HashMap<Encoding, Message> = HashMap::new();
+  for (_, peer) in peers.iter() {
+    if !peer.wants(message) {
+      continue;
+    }
+    let msg = match encoded.get(&peer.encoding) {
+      Some(msg) => msg.clone(),
+      None => {
+        let msg = to_websocket_message(peer.encoding.encode(message)?);
+        encoded.insert(peer.encoding, msg.clone());
+        msg
+      }
+    };
+    match peer.tx.unbounded_send(msg) {
+      Ok(()) => METRICS.messages_out(1),
+      Err(e) => tracing::warn!(error = %e, "Error with unbounded send"),
     }
   }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::server::*;
+
+  #[test]
+  fn test_only_trusted_proxies_say_where_someone_is() {
+    let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+    let from_proxy = Some(SocketAddr::new(proxy, 4000));
+    let stranger = Some(SocketAddr::new("203.0.113.7".parse().unwrap(), 4000));
+    let trusted = [proxy];
+    let client: IpAddr = "198.51.100.4".parse().unwrap();
+
+    let forwarded = Some("1.2.3.4, 198.51.100.4");
+    assert_eq!(
+      client_address(from_proxy, forwarded, &trusted),
+      Some(client)
+    );
+    // Anyone else saying so is just making it up.
+    assert_eq!(
+      client_address(stranger, forwarded, &trusted),
+      stranger.map(|addr| addr.ip())
+    );
+    assert_eq!(client_address(from_proxy, forwarded, &[]), Some(proxy));
+    // Without a word from the proxy, we can't tell who's behind it.
+    assert_eq!(client_address(from_proxy, None, &trusted), None);
+    assert_eq!(client_address(from_proxy, Some("nonsense"), &trusted), None);
+  }
+
+  #[test]
+  fn test_bans_follow_accounts_around() {
+    let account = PlayerAccountId(UUID::random());
+    let mut bans = Bans::default();
+    bans.accounts.insert(account);
+    let elsewhere = Some("198.51.100.4".parse().unwrap());
+    assert!(bans.covers(elsewhere, Some(account)));
+    assert!(!bans.covers(elsewhere, None));
+  }
 }
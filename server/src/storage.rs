@@ -0,0 +1,542 @@
+use crate::server::{PLAYER_STATS_DIR, SAVED_ROOMS_DIR};
+use rusqlite::OptionalExtension;
+use rust_us_core::{PlayerAccountId, PlayerStats};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub type Stores = (Box<dyn RoomStore>, Box<dyn StatsStore>);
+
+// Picking a room up only takes a moment, so a room that's been claimed for
+// longer than this was left behind by a server that died partway through.
+pub const STALE_CLAIM_AGE: Duration = Duration::from_secs(60);
+
+// Where to keep rooms and stats, from AIRLOCK_STORE: sqlite:<path> for a
+// database file, a redis:// URL to share them between machines, or unset
+// for a directory each next to the server.
+pub fn open_stores() -> Result<Stores, Box<dyn Error>> {
+  let store = std::env::var("AIRLOCK_STORE").unwrap_or_default();
+  if let Some(path) = store.strip_prefix("sqlite:") {
+    return Ok((
+      Box::new(SqliteStore::open(path)?),
+      Box::new(SqliteStore::open(path)?),
+    ));
+  }
+  if store.starts_with("redis://") || store.starts_with("rediss://") {
+    return Ok((
+      Box::new(RedisStore::open(&store)?),
+      Box::new(RedisStore::open(&store)?),
+    ));
+  }
+  if !store.is_empty() {
+    return Err(format!("Don't know how to keep things in AIRLOCK_STORE={}", store).into());
+  }
+  let mut rooms = DirectoryStore::new(SAVED_ROOMS_DIR);
+  rooms.release_stale_claims(STALE_CLAIM_AGE)?;
+  Ok((
+    Box::new(rooms),
+    Box::new(DirectoryStore::new(PLAYER_STATS_DIR)),
+  ))
+}
+
+// Somewhere to keep rooms that no server is running right now, keyed by their
+// join code. When it's shared between servers, whichever one someone asks
+// for a room first picks it up.
+pub trait RoomStore: Send {
+  fn put(&mut self, code: &str, room: Vec<u8>) -> Result<(), Box<dyn Error>>;
+  // Sets the room aside as it's handed over, so that no two servers can end
+  // up running the same game. It stays set aside until it's either removed,
+  // once the game is going again, or released, if it couldn't be.
+  fn claim(&mut self, code: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>>;
+  fn remove(&mut self, code: &str) -> Result<(), Box<dyn Error>>;
+  fn release(&mut self, code: &str) -> Result<(), Box<dyn Error>>;
+  fn codes(&self) -> Result<Vec<String>, Box<dyn Error>>;
+}
+
+// How everyone's done over all their games, by the account their client
+// says they are.
+pub trait StatsStore: Send {
+  // Someone we've never heard of hasn't done anything yet.
+  fn get(&self, account: &PlayerAccountId) -> Result<PlayerStats, Box<dyn Error>>;
+  fn put(&mut self, account: &PlayerAccountId, stats: &PlayerStats) -> Result<(), Box<dyn Error>>;
+
+  // Counts a finished game on top of what they'd done before.
+  fn record(
+    &mut self,
+    account: &PlayerAccountId,
+    game: &PlayerStats,
+  ) -> Result<(), Box<dyn Error>> {
+    let mut stats = self.get(account)?;
+    stats.add(game);
+    self.put(account, &stats)
+  }
+}
+
+// Only lasts as long as the server does, for when there's nowhere better.
+#[derive(Default)]
+pub struct MemoryStore {
+  rooms: HashMap<String, Vec<u8>>,
+  claimed: HashMap<String, Vec<u8>>,
+  stats: HashMap<PlayerAccountId, PlayerStats>,
+}
+
+impl RoomStore for MemoryStore {
+  fn put(&mut self, code: &str, room: Vec<u8>) -> Result<(), Box<dyn Error>> {
+    self.rooms.insert(code.to_string(), room);
+    Ok(())
+  }
+
+  fn claim(&mut self, code: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let room = match self.rooms.remove(code) {
+      Some(room) => room,
+      None => return Ok(None),
+    };
+    self.claimed.insert(code.to_string(), room.clone());
+    Ok(Some(room))
+  }
+
+  fn remove(&mut self, code: &str) -> Result<(), Box<dyn Error>> {
+    self.claimed.remove(code);
+    Ok(())
+  }
+
+  fn release(&mut self, code: &str) -> Result<(), Box<dyn Error>> {
+    if let Some(room) = self.claimed.remove(code) {
+      self.rooms.insert(code.to_string(), room);
+    }
+    Ok(())
+  }
+
+  fn codes(&self) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(self.rooms.keys().cloned().collect())
+  }
+}
+
+impl StatsStore for MemoryStore {
+  fn get(&self, account: &PlayerAccountId) -> Result<PlayerStats, Box<dyn Error>> {
+    Ok(self.stats.get(account).copied().unwrap_or_default())
+  }
+
+  fn put(&mut self, account: &PlayerAccountId, stats: &PlayerStats) -> Result<(), Box<dyn Error>> {
+    self.stats.insert(*account, *stats);
+    Ok(())
+  }
+}
+
+// A file per room, in a directory that survives restarts, and that servers on
+// the same machine can share.
+pub struct DirectoryStore {
+  dir: PathBuf,
+}
+
+impl DirectoryStore {
+  const EXTENSION: &'static str = "room";
+  const CLAIMED_EXTENSION: &'static str = "claimed";
+  const STATS_EXTENSION: &'static str = "stats";
+
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    DirectoryStore { dir: dir.into() }
+  }
+
+  fn path(&self, code: &str) -> PathBuf {
+    self.dir.join(code).with_extension(Self::EXTENSION)
+  }
+
+  fn claimed_path(&self, code: &str) -> PathBuf {
+    self.path(code).with_extension(Self::CLAIMED_EXTENSION)
+  }
+
+  // Puts back any rooms that have been claimed for longer than the given
+  // age, so that a server dying partway through picking one up doesn't
+  // take the game down with it.
+  pub fn release_stale_claims(&mut self, age: Duration) -> Result<(), Box<dyn Error>> {
+    let entries = match std::fs::read_dir(&self.dir) {
+      Ok(entries) => entries,
+      Err(_) => return Ok(()),
+    };
+    for entry in entries {
+      let path = entry?.path();
+      if path
+        .extension()
+        .is_none_or(|e| e != Self::CLAIMED_EXTENSION)
+      {
+        continue;
+      }
+      let claimed_at = path.metadata()?.modified()?;
+      if claimed_at
+        .elapsed()
+        .is_ok_and(|claimed_for| claimed_for >= age)
+      {
+        if let Some(code) = path.file_stem().and_then(|s| s.to_str()) {
+          self.release(code)?;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn stats_path(&self, account: &PlayerAccountId) -> PathBuf {
+    self
+      .dir
+      .join(account.to_string())
+      .with_extension(Self::STATS_EXTENSION)
+  }
+}
+
+impl RoomStore for DirectoryStore {
+  fn put(&mut self, code: &str, room: Vec<u8>) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(&self.dir)?;
+    // Write it somewhere else first, so no one can pick up half a room.
+    let partial = self.path(code).with_extension("partial");
+    std::fs::write(&partial, room)?;
+    std::fs::rename(partial, self.path(code))?;
+    Ok(())
+  }
+
+  fn claim(&mut self, code: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    // Renaming it out of the way first means only one server gets it.
+    let claimed = self.claimed_path(code);
+    if std::fs::rename(self.path(code), &claimed).is_err() {
+      return Ok(None);
+    }
+    // Renaming keeps the time it was saved, but what matters for noticing a
+    // stale claim is when it was claimed.
+    let file = std::fs::OpenOptions::new().write(true).open(&claimed)?;
+    file.set_modified(SystemTime::now())?;
+    Ok(Some(std::fs::read(&claimed)?))
+  }
+
+  fn remove(&mut self, code: &str) -> Result<(), Box<dyn Error>> {
+    std::fs::remove_file(self.claimed_path(code))?;
+    Ok(())
+  }
+
+  fn release(&mut self, code: &str) -> Result<(), Box<dyn Error>> {
+    std::fs::rename(self.claimed_path(code), self.path(code))?;
+    Ok(())
+  }
+
+  fn codes(&self) -> Result<Vec<String>, Box<dyn Error>> {
+    let entries = match std::fs::read_dir(&self.dir) {
+      Ok(entries) => entries,
+      // Nothing's been saved yet.
+      Err(_) => return Ok(vec![]),
+    };
+    let mut codes = vec![];
+    for entry in entries {
+      let path = entry?.path();
+      if path.extension().is_none_or(|e| e != Self::EXTENSION) {
+        continue;
+      }
+      if let Some(code) = path.file_stem().and_then(|s| s.to_str()) {
+        codes.push(code.to_string());
+      }
+    }
+    Ok(codes)
+  }
+}
+
+// A little JSON file per account, so they're easy to look at or clear out.
+impl StatsStore for DirectoryStore {
+  fn get(&self, account: &PlayerAccountId) -> Result<PlayerStats, Box<dyn Error>> {
+    match std::fs::read(self.stats_path(account)) {
+      Ok(json) => Ok(serde_json::from_slice(&json)?),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(PlayerStats::default()),
+      Err(e) => Err(e.into()),
+    }
+  }
+
+  fn put(&mut self, account: &PlayerAccountId, stats: &PlayerStats) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(&self.dir)?;
+    let partial = self.stats_path(account).with_extension("partial");
+    std::fs::write(&partial, serde_json::to_vec(stats)?)?;
+    std::fs::rename(partial, self.stats_path(account))?;
+    Ok(())
+  }
+}
+
+// One database file for everything, which any number of servers on the same
+// machine can share.
+pub struct SqliteStore {
+  db: rusqlite::Connection,
+}
+
+impl SqliteStore {
+  pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+    let db = rusqlite::Connection::open(path)?;
+    // Another server might be in the middle of something, so wait our turn.
+    db.busy_timeout(Duration::from_secs(5))?;
+    db.execute_batch(
+      "CREATE TABLE IF NOT EXISTS rooms (
+        code TEXT PRIMARY KEY,
+        room BLOB NOT NULL,
+        claimed INTEGER NOT NULL DEFAULT 0
+      );
+      CREATE TABLE IF NOT EXISTS stats (
+        account TEXT PRIMARY KEY,
+        stats TEXT NOT NULL
+      );",
+    )?;
+    Ok(SqliteStore { db })
+  }
+}
+
+impl RoomStore for SqliteStore {
+  fn put(&mut self, code: &str, room: Vec<u8>) -> Result<(), Box<dyn Error>> {
+    self.db.execute(
+      "INSERT OR REPLACE INTO rooms (code, room, claimed) VALUES (?1, ?2, 0)",
+      rusqlite::params![code, room],
+    )?;
+    Ok(())
+  }
+
+  fn claim(&mut self, code: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    // Only one server gets to flip it over to claimed.
+    let claimed = self.db.execute(
+      "UPDATE rooms SET claimed = 1 WHERE code = ?1 AND claimed = 0",
+      [code],
+    )?;
+    if claimed == 0 {
+      return Ok(None);
+    }
+    let room = self
+      .db
+      .query_row("SELECT room FROM rooms WHERE code = ?1", [code], |row| {
+        row.get(0)
+      })?;
+    Ok(Some(room))
+  }
+
+  fn remove(&mut self, code: &str) -> Result<(), Box<dyn Error>> {
+    self
+      .db
+      .execute("DELETE FROM rooms WHERE code = ?1 AND claimed = 1", [code])?;
+    Ok(())
+  }
+
+  fn release(&mut self, code: &str) -> Result<(), Box<dyn Error>> {
+    self
+      .db
+      .execute("UPDATE rooms SET claimed = 0 WHERE code = ?1", [code])?;
+    Ok(())
+  }
+
+  fn codes(&self) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut query = self
+      .db
+      .prepare("SELECT code FROM rooms WHERE claimed = 0")?;
+    let codes = query
+      .query_map([], |row| row.get(0))?
+      .collect::<Result<Vec<String>, _>>()?;
+    Ok(codes)
+  }
+}
+
+impl StatsStore for SqliteStore {
+  fn get(&self, account: &PlayerAccountId) -> Result<PlayerStats, Box<dyn Error>> {
+    let json: Option<String> = self
+      .db
+      .query_row(
+        "SELECT stats FROM stats WHERE account = ?1",
+        [account.to_string()],
+        |row| row.get(0),
+      )
+      .optional()?;
+    match json {
+      Some(json) => Ok(serde_json::from_str(&json)?),
+      None => Ok(PlayerStats::default()),
+    }
+  }
+
+  fn put(&mut self, account: &PlayerAccountId, stats: &PlayerStats) -> Result<(), Box<dyn Error>> {
+    self.db.execute(
+      "INSERT OR REPLACE INTO stats (account, stats) VALUES (?1, ?2)",
+      rusqlite::params![account.to_string(), serde_json::to_string(stats)?],
+    )?;
+    Ok(())
+  }
+}
+
+// For servers on different machines, so a room saved on one can be picked
+// up on any other.
+pub struct RedisStore {
+  // Even just looking takes a connection we can send commands down.
+  redis: RefCell<redis::Connection>,
+}
+
+impl RedisStore {
+  const ROOM_PREFIX: &'static str = "airlock:room:";
+  const CLAIMED_PREFIX: &'static str = "airlock:claimed:";
+  const STATS_PREFIX: &'static str = "airlock:stats:";
+
+  pub fn open(url: &str) -> Result<Self, Box<dyn Error>> {
+    let redis = redis::Client::open(url)?.get_connection()?;
+    Ok(RedisStore {
+      redis: RefCell::new(redis),
+    })
+  }
+
+  fn room_key(code: &str) -> String {
+    format!("{}{}", Self::ROOM_PREFIX, code)
+  }
+
+  fn claimed_key(code: &str) -> String {
+    format!("{}{}", Self::CLAIMED_PREFIX, code)
+  }
+
+  fn stats_key(account: &PlayerAccountId) -> String {
+    format!("{}{}", Self::STATS_PREFIX, account)
+  }
+}
+
+impl RoomStore for RedisStore {
+  fn put(&mut self, code: &str, room: Vec<u8>) -> Result<(), Box<dyn Error>> {
+    redis::cmd("SET")
+      .arg(Self::room_key(code))
+      .arg(room)
+      .query::<()>(self.redis.get_mut())?;
+    Ok(())
+  }
+
+  fn claim(&mut self, code: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    // Moved over to its claimed key in one go, so only one server gets it.
+    let room = redis::cmd("EVAL")
+      .arg(
+        "if redis.call('EXISTS', KEYS[1]) == 0 then return false end
+        redis.call('RENAME', KEYS[1], KEYS[2])
+        return redis.call('GET', KEYS[2])",
+      )
+      .arg(2)
+      .arg(Self::room_key(code))
+      .arg(Self::claimed_key(code))
+      .query(self.redis.get_mut())?;
+    Ok(room)
+  }
+
+  fn remove(&mut self, code: &str) -> Result<(), Box<dyn Error>> {
+    redis::cmd("DEL")
+      .arg(Self::claimed_key(code))
+      .query::<()>(self.redis.get_mut())?;
+    Ok(())
+  }
+
+  fn release(&mut self, code: &str) -> Result<(), Box<dyn Error>> {
+    redis::cmd("RENAME")
+      .arg(Self::claimed_key(code))
+      .arg(Self::room_key(code))
+      .query::<()>(self.redis.get_mut())?;
+    Ok(())
+  }
+
+  fn codes(&self) -> Result<Vec<String>, Box<dyn Error>> {
+    // A bit at a time, since KEYS would hold up everyone else using redis
+    // while it went through every key there is.
+    let mut redis = self.redis.borrow_mut();
+    let mut codes = vec![];
+    let mut cursor: u64 = 0;
+    loop {
+      let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+        .arg(cursor)
+        .arg("MATCH")
+        .arg(format!("{}*", Self::ROOM_PREFIX))
+        .arg("COUNT")
+        .arg(100)
+        .query(&mut *redis)?;
+      codes.extend(
+        keys
+          .iter()
+          .filter_map(|key| key.strip_prefix(Self::ROOM_PREFIX))
+          .map(|code| code.to_string()),
+      );
+      if next == 0 {
+        break;
+      }
+      cursor = next;
+    }
+    // SCAN can turn up the same key more than once.
+    codes.sort();
+    codes.dedup();
+    Ok(codes)
+  }
+}
+
+impl StatsStore for RedisStore {
+  fn get(&self, account: &PlayerAccountId) -> Result<PlayerStats, Box<dyn Error>> {
+    let json: Option<Vec<u8>> = redis::cmd("GET")
+      .arg(Self::stats_key(account))
+      .query(&mut *self.redis.borrow_mut())?;
+    match json {
+      Some(json) => Ok(serde_json::from_slice(&json)?),
+      None => Ok(PlayerStats::default()),
+    }
+  }
+
+  fn put(&mut self, account: &PlayerAccountId, stats: &PlayerStats) -> Result<(), Box<dyn Error>> {
+    redis::cmd("SET")
+      .arg(Self::stats_key(account))
+      .arg(serde_json::to_vec(stats)?)
+      .query::<()>(self.redis.get_mut())?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::storage::*;
+
+  fn store_round_trips(store: &mut dyn RoomStore) {
+    store.put("ABCD", vec![1, 2, 3]).unwrap();
+    assert_eq!(store.codes().unwrap(), vec!["ABCD".to_string()]);
+    assert_eq!(store.claim("ABCD").unwrap(), Some(vec![1, 2, 3]));
+    // Only one server gets it.
+    assert_eq!(store.claim("ABCD").unwrap(), None);
+    assert!(store.codes().unwrap().is_empty());
+
+    // If it couldn't be restored, it goes back for someone else to try.
+    store.release("ABCD").unwrap();
+    assert_eq!(store.codes().unwrap(), vec!["ABCD".to_string()]);
+    assert_eq!(store.claim("ABCD").unwrap(), Some(vec![1, 2, 3]));
+    // Once it's going again, it's gone for good, and there's nothing left
+    // to put back.
+    store.remove("ABCD").unwrap();
+    store.release("ABCD").ok();
+    assert_eq!(store.claim("ABCD").unwrap(), None);
+    assert!(store.codes().unwrap().is_empty());
+    assert_eq!(store.claim("WXYZ").unwrap(), None);
+  }
+
+  fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("airlock-{}-{}", name, std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    dir
+  }
+
+  #[test]
+  fn test_memory_store_hands_rooms_out_once() {
+    store_round_trips(&mut MemoryStore::default());
+  }
+
+  #[test]
+  fn test_directory_store_hands_rooms_out_once() {
+    let dir = scratch_dir("rooms");
+    store_round_trips(&mut DirectoryStore::new(&dir));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_directory_store_takes_back_abandoned_claims() {
+    let dir = scratch_dir("abandoned");
+    let mut store = DirectoryStore::new(&dir);
+    RoomStore::put(&mut store, "ABCD", vec![1, 2, 3]).unwrap();
+    store.claim("ABCD").unwrap();
+    // Whoever has it might still be restoring it.
+    store.release_stale_claims(STALE_CLAIM_AGE).unwrap();
+    assert_eq!(store.claim("ABCD").unwrap(), None);
+    // But not after all this time.
+    store.release_stale_claims(Duration::from_secs(0)).unwrap();
+    assert_eq!(store.claim("ABCD").unwrap(), Some(vec![1, 2, 3]));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
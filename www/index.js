@@ -54,6 +54,10 @@ async function init() {
     const elapsed = timestamp - previousFrameTime;
     previousFrameTime = timestamp;
     const finished = game.simulate();
+    // Let anything that wants to play sounds or keep score know what happened.
+    for (const event of JSON.parse(game.take_events())) {
+      document.dispatchEvent(new CustomEvent('gameevent', {detail: event}));
+    }
     const afterSim = performance.now();
     const simTime = afterSim - timestamp;
     game.draw();
@@ -101,7 +105,7 @@ async function init() {
   const knownButtons = new Set([
     'w', 'a', 's', 'd', 'q', 'e', 'r', ' ', 'p',
     'arrowup', 'arrowdown', 'arrowleft', 'arrowright',
-    'j', 'k', 'l', 'f11'
+    'j', 'k', 'l', 'f11', '1', '2', '3', '4', 'f', 'c'
   ]);
   const heldButtons = {};
   for (const button of knownButtons) {
@@ -119,22 +123,85 @@ async function init() {
     const skip_back = heldButtons['j'];
     const skip_forward = heldButtons['l'];
     const pause_playback = heldButtons['k'];
+    const sabotage_lights = heldButtons['1'];
+    const sabotage_reactor = heldButtons['2'];
+    const sabotage_doors = heldButtons['3'];
+    const sabotage_comms = heldButtons['4'];
+    const drag = heldButtons['f'];
+    const decoy = heldButtons['c'];
     game.set_inputs(
       up, down, left, right, kill, report,
-      activate, play, skip_back, skip_forward, pause_playback);
+      activate, play, skip_back, skip_forward, pause_playback,
+      sabotage_lights, sabotage_reactor, sabotage_doors, sabotage_comms, drag,
+      decoy);
     if (!running) {
       running = true;
       requestAnimationFrame(drawOneFrame);
     }
   }
+  // Enter opens the chat box, and Enter again sends what's in it.
+  const chatInput = document.createElement('input');
+  chatInput.maxLength = 200;
+  chatInput.style.display = 'none';
+  textOutput.appendChild(chatInput);
+  chatInput.addEventListener('keydown', (ev) => {
+    ev.stopPropagation();
+    if (ev.key === 'Enter' || ev.key === 'Escape') {
+      if (ev.key === 'Enter' && chatInput.value.trim() !== '') {
+        game.send_chat(chatInput.value);
+      }
+      chatInput.value = '';
+      chatInput.style.display = 'none';
+      chatInput.blur();
+      ev.preventDefault();
+    }
+  });
+  chatInput.addEventListener('keyup', (ev) => ev.stopPropagation());
+
   document.addEventListener('keydown', (ev) => {
     const key = ev.key.toLowerCase();
+    if (key === 'enter' && !game.is_playback()) {
+      chatInput.style.display = '';
+      chatInput.focus();
+      ev.preventDefault();
+      return;
+    }
     if (key == '/') {
       displayPerf = !displayPerf;
       window.localStorage.displayPerf = displayPerf;
       ev.preventDefault();
       return;
     }
+    // Flip through the colors no one else is wearing, in the lobby.
+    if (key === 'c' && !game.is_playback()) {
+      try {
+        game.cycle_color();
+      } catch (e) {
+        console.log(e);
+      }
+      ev.preventDefault();
+      return;
+    }
+    // Once the game's over, the host can take everyone back to the lobby.
+    if (key === 'b' && !game.is_playback()) {
+      try {
+        game.return_to_lobby();
+      } catch (e) {
+        console.log(e);
+      }
+      ev.preventDefault();
+      return;
+    }
+    // When watching a recording, the number keys jump through it in tenths.
+    if (game.is_playback() && /^[0-9]$/.test(key)) {
+      game.scrub_playback(Number(key) / 10);
+      if (!running) {
+        running = true;
+        requestAnimationFrame(drawOneFrame);
+      }
+      ev.preventDefault();
+      return;
+    }
     if (!knownButtons.has(key)) {
       return;
     }